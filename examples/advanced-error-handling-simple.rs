@@ -145,7 +145,7 @@ struct Pagination {
 // Utility function to validate email format
 fn validate_email(email: &str) -> Result<()> {
     if !email.contains('@') || !email.contains('.') {
-        return Err(Error::Validation("Invalid email format".to_string()));
+        return Err(Error::Validation("Invalid email format".into()));
     }
     Ok(())
 }
@@ -227,7 +227,7 @@ async fn create_user(
 ) -> Result<Response> {
     // Validate input
     if payload.name.trim().is_empty() {
-        return Err(Error::Validation("Name cannot be empty".to_string()));
+        return Err(Error::Validation("Name cannot be empty".into()));
     }
     
     validate_email(&payload.email)?;
@@ -340,7 +340,7 @@ async fn validation_test(_req: Request) -> Result<Response> {
     
     if let Some(name) = params.get("name") {
         if name.trim().is_empty() {
-            return Err(Error::Validation("Name cannot be empty".to_string()));
+            return Err(Error::Validation("Name cannot be empty".into()));
         }
     }
     
@@ -175,7 +175,7 @@ struct Pagination {
 // Utility function to validate email format
 fn validate_email(email: &str) -> Result<()> {
     if !email.contains('@') || !email.contains('.') {
-        return Err(Error::Validation("Invalid email format".to_string()));
+        return Err(Error::Validation("Invalid email format".into()));
     }
     Ok(())
 }
@@ -273,7 +273,7 @@ async fn create_user(
     
     // Validate input
     if payload.name.trim().is_empty() {
-        return Err(Error::Validation("Name cannot be empty".to_string()));
+        return Err(Error::Validation("Name cannot be empty".into()));
     }
     
     validate_email(&payload.email)?;
@@ -425,7 +425,7 @@ async fn validation_test(mut req: Request) -> Result<Response> {
     
     if let Some(name) = params.get("name") {
         if name.trim().is_empty() {
-            return Err(Error::Validation("Name cannot be empty".to_string()));
+            return Err(Error::Validation("Name cannot be empty".into()));
         }
     }
     
@@ -29,7 +29,7 @@ async fn main() -> Result<()> {
     
     // Basic health check
     router.get("/health", |_: Request| async {
-        Ok(Response::json(serde_json::json!({ "status": "ok" })))
+        Response::json(serde_json::json!({ "status": "ok" }))
     });
     
     // Start server
@@ -13,6 +13,6 @@ pub async fn init_queue() -> Result<Arc<Queue>> {
 
 pub fn queue_routes(router: &mut Router) {
     router.post("/queue/job", |_req: Request| async {
-        Ok(Response::json(serde_json::json!({ "status": "job_enqueued" })))
+        Response::json(serde_json::json!({ "status": "job_enqueued" }))
     });
 }
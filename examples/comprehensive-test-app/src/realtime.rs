@@ -14,6 +14,6 @@ pub async fn init_realtime() -> Result<Arc<WebSocketManager>> {
 pub fn realtime_routes(router: &mut Router) {
     router.get("/ws", |_req: Request| async {
         // In a real app, upgrade to WebSocket
-        Ok(Response::text("WebSocket endpoint"))
+        Response::text("WebSocket endpoint")
     });
 }
@@ -154,7 +154,7 @@ async fn error_example(Query(params): Query<serde_json::Value>) -> Result<Respon
             "unauthorized" => Err(Error::Unauthorized("Unauthorized access".to_string())),
             "forbidden" => Err(Error::Forbidden("Access forbidden".to_string())),
             "conflict" => Err(Error::Conflict("Resource conflict".to_string())),
-            "validation" => Err(Error::Validation("Validation failed".to_string())),
+            "validation" => Err(Error::Validation("Validation failed".into())),
             "rate_limited" => Err(Error::RateLimited("Rate limit exceeded".to_string())),
             "service_unavailable" => Err(Error::ServiceUnavailable("Service temporarily unavailable".to_string())),
             _ => Ok(Response::json(serde_json::json!({ "status": "unknown_error_type" })))
@@ -1,4 +1,4 @@
-use oxidite_core::{Request, Response, Error, json, RequestExt, State, FromRequest};
+use oxidite_core::{Request, Response, Error, json, RequestExt, State};
 use serde::{Deserialize, Serialize};
 use crate::AppState;
 use std::sync::Arc;
@@ -13,7 +13,7 @@ pub struct User {
 
 /// List all users
 pub async fn list_users(mut req: Request) -> Result<Response, Error> {
-    let State(state): State<Arc<AppState>> = State::from_request(&mut req).await?;
+    let State(state): State<Arc<AppState>> = req.extract_parts().await?;
     
     let rows = state.db.query("SELECT id, email, name FROM users").await
         .map_err(|e| Error::Server(format!("DB error: {}", e)))?;
@@ -35,7 +35,7 @@ pub async fn list_users(mut req: Request) -> Result<Response, Error> {
 
 /// Create a new user
 pub async fn create_user(mut req: Request) -> Result<Response, Error> {
-    let State(state): State<Arc<AppState>> = State::from_request(&mut req).await?;
+    let State(state): State<Arc<AppState>> = req.extract_parts().await?;
     let body = req.body_string().await?;
     
     #[derive(Deserialize)]
@@ -1,4 +1,4 @@
-use oxidite_core::{Request, Response, Error, State, FromRequest, RequestExt, json};
+use oxidite_core::{Request, Response, Error, State, RequestExt, json};
 use serde::Deserialize;
 use crate::AppState;
 use std::sync::Arc;
@@ -33,7 +33,7 @@ pub async fn register(mut req: Request) -> Result<Response, Error> {
 
 /// Login and receive JWT token
 pub async fn login(mut req: Request) -> Result<Response, Error> {
-    let State(state): State<Arc<AppState>> = State::from_request(&mut req).await?;
+    let State(state): State<Arc<AppState>> = req.extract_parts().await?;
     let body = req.body_string().await?;
     let login_req: LoginRequest = serde_json::from_str(&body)
         .map_err(|e| Error::BadRequest(format!("Invalid JSON: {}", e)))?;
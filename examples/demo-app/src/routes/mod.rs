@@ -6,7 +6,7 @@ pub mod web;
 pub mod posts;
 pub mod user_posts;
 
-use oxidite_core::{Request, Response, Error, html, State, FromRequest};
+use oxidite_core::{Request, Response, Error, html, State, RequestExt};
 use oxidite_template::Context;
 use serde_json::json;
 use crate::AppState;
@@ -14,7 +14,7 @@ use std::sync::Arc;
 
 /// Home page
 pub async fn index(mut req: Request) -> Result<Response, Error> {
-    let State(state): State<Arc<AppState>> = State::from_request(&mut req).await?;
+    let State(state): State<Arc<AppState>> = req.extract_parts().await?;
     
     let context = Context::from_json(json!({}));
     let rendered = state.templates.render("home.html", &context)
@@ -25,7 +25,7 @@ pub async fn index(mut req: Request) -> Result<Response, Error> {
 
 /// API Documentation page
 pub async fn api_docs(mut req: Request) -> Result<Response, Error> {
-    let State(state): State<Arc<AppState>> = State::from_request(&mut req).await?;
+    let State(state): State<Arc<AppState>> = req.extract_parts().await?;
     
     // We pass the spec URL to the template
     let context = Context::from_json(json!({
@@ -1,4 +1,4 @@
-use oxidite_core::{Request, Response, Error, State, FromRequest, html, json, RequestExt};
+use oxidite_core::{Request, Response, Error, State, html, json, RequestExt};
 use crate::{AppState, models::{User, Post}};
 use std::sync::Arc;
 use oxidite_db::{Database, sqlx::Row};
@@ -8,7 +8,7 @@ use serde::Deserialize;
 
 /// List all posts (web page)
 pub async fn list_posts(mut req: Request) -> Result<Response, Error> {
-    let State(state): State<Arc<AppState>> = State::from_request(&mut req).await?;
+    let State(state): State<Arc<AppState>> = req.extract_parts().await?;
     
     let rows = state.db.query("SELECT id, user_id, title, content, created_at FROM posts ORDER BY created_at DESC").await
         .map_err(|e| Error::Server(format!("DB error: {}", e)))?;
@@ -36,7 +36,7 @@ pub async fn list_posts(mut req: Request) -> Result<Response, Error> {
 
 /// Show a single post
 pub async fn show_post(mut req: Request) -> Result<Response, Error> {
-    let State(state): State<Arc<AppState>> = State::from_request(&mut req).await?;
+    let State(state): State<Arc<AppState>> = req.extract_parts().await?;
     
     // Extract post ID from path
     let path = req.uri().path();
@@ -72,7 +72,7 @@ pub async fn show_post(mut req: Request) -> Result<Response, Error> {
 
 /// Show new post form
 pub async fn new_post_form(mut req: Request) -> Result<Response, Error> {
-    let State(state): State<Arc<AppState>> = State::from_request(&mut req).await?;
+    let State(state): State<Arc<AppState>> = req.extract_parts().await?;
     
     let context = Context::from_json(json!({}));
     let rendered = state.templates.render("posts/new.html", &context)
@@ -83,7 +83,7 @@ pub async fn new_post_form(mut req: Request) -> Result<Response, Error> {
 
 /// List all posts (API)
 pub async fn api_list_posts(mut req: Request) -> Result<Response, Error> {
-    let State(state): State<Arc<AppState>> = State::from_request(&mut req).await?;
+    let State(state): State<Arc<AppState>> = req.extract_parts().await?;
     
     let rows = state.db.query("SELECT id, user_id, title, content, created_at FROM posts ORDER BY created_at DESC").await
         .map_err(|e| Error::Server(format!("DB error: {}", e)))?;
@@ -107,7 +107,7 @@ pub async fn api_list_posts(mut req: Request) -> Result<Response, Error> {
 
 /// Create a new post (API)
 pub async fn api_create_post(mut req: Request) -> Result<Response, Error> {
-    let State(state): State<Arc<AppState>> = State::from_request(&mut req).await?;
+    let State(state): State<Arc<AppState>> = req.extract_parts().await?;
     let body = req.body_string().await?;
     
     #[derive(Deserialize)]
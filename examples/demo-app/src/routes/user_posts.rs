@@ -1,4 +1,4 @@
-use oxidite_core::{Request, Response, Error, State, FromRequest, html};
+use oxidite_core::{Request, Response, Error, State, RequestExt, html};
 use crate::{AppState, models::{User, Post}};
 use std::sync::Arc;
 use oxidite_db::{Database,sqlx::Row};
@@ -7,7 +7,7 @@ use serde_json::json;
 
 /// Show posts by a specific user
 pub async fn user_posts(mut req: Request) -> Result<Response, Error> {
-    let State(state): State<Arc<AppState>> = State::from_request(&mut req).await?;
+    let State(state): State<Arc<AppState>> = req.extract_parts().await?;
     
     // Extract user_id from path (you would parse this from the request path)
     let path = req.uri().path();
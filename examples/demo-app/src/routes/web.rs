@@ -1,4 +1,4 @@
-use oxidite_core::{Request, Response, Error, State, FromRequest, html};
+use oxidite_core::{Request, Response, Error, State, RequestExt, html};
 use crate::{AppState, models::User};
 use std::sync::Arc;
 use oxidite_db::{Database, sqlx::Row};
@@ -7,7 +7,7 @@ use oxidite_template::Context;
 
 /// List all users (web page)
 pub async fn list_users(mut req: Request) -> Result<Response, Error> {
-    let State(state): State<Arc<AppState>> = State::from_request(&mut req).await?;
+    let State(state): State<Arc<AppState>> = req.extract_parts().await?;
     
     // Fetch users from database
     let rows = state.db.query("SELECT id, email, name, created_at FROM users ORDER BY created_at DESC").await
@@ -36,7 +36,7 @@ pub async fn list_users(mut req: Request) -> Result<Response, Error> {
 
 /// Show new user form
 pub async fn new_user_form(mut req: Request) -> Result<Response, Error> {
-    let State(state): State<Arc<AppState>> = State::from_request(&mut req).await?;
+    let State(state): State<Arc<AppState>> = req.extract_parts().await?;
     
     let context = Context::from_json(json!({}));
     let rendered = state.templates.render("users/new.html", &context)
@@ -84,6 +84,24 @@ pub struct ServerConfig {
     pub port: u16,
     #[serde(default)]
     pub workers: usize,
+    /// Maximum concurrent connections; further clients wait in the accept backlog
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_connections: Option<usize>,
+    /// Seconds a client has to send the request headers
+    #[serde(default = "default_header_read_timeout")]
+    pub header_read_timeout: u64,
+    /// Seconds an idle keep-alive connection is kept open
+    #[serde(default = "default_keep_alive_timeout")]
+    pub keep_alive_timeout: u64,
+    /// Maximum size of the request headers in bytes
+    #[serde(default = "default_max_header_size")]
+    pub max_header_size: usize,
+    /// Maximum size of a buffered request body in bytes
+    #[serde(default = "default_max_body_size")]
+    pub max_body_size: usize,
+    /// Proxy addresses or CIDR ranges whose forwarding headers are believed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub trusted_proxies: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -126,6 +144,12 @@ pub struct SecurityConfig {
     pub cors_origins: Vec<String>,
     #[serde(default)]
     pub rate_limit: u32,
+    /// Secret of at least 32 bytes used to sign and encrypt cookies
+    #[serde(default)]
+    pub cookie_secret: String,
+    /// Retired cookie secrets, still accepted when reading cookies
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub previous_cookie_secrets: Vec<String>,
 }
 
 // Default functions
@@ -141,6 +165,22 @@ fn default_port() -> u16 {
     3000
 }
 
+fn default_header_read_timeout() -> u64 {
+    30
+}
+
+fn default_keep_alive_timeout() -> u64 {
+    75
+}
+
+fn default_max_header_size() -> usize {
+    64 * 1024
+}
+
+fn default_max_body_size() -> usize {
+    2 * 1024 * 1024
+}
+
 fn default_pool_size() -> u32 {
     10
 }
@@ -174,6 +214,12 @@ impl Default for ServerConfig {
             host: default_host(),
             port: default_port(),
             workers: num_cpus::get(),
+            max_connections: None,
+            header_read_timeout: default_header_read_timeout(),
+            keep_alive_timeout: default_keep_alive_timeout(),
+            max_header_size: default_max_header_size(),
+            max_body_size: default_max_body_size(),
+            trusted_proxies: Vec::new(),
         }
     }
 }
@@ -215,6 +261,8 @@ impl Default for SecurityConfig {
             jwt_expiry: default_jwt_expiry(),
             cors_origins: vec![],
             rate_limit: 0,
+            cookie_secret: String::new(),
+            previous_cookie_secrets: Vec::new(),
         }
     }
 }
@@ -259,6 +307,9 @@ impl Config {
         if let Ok(val) = env::var("JWT_SECRET") {
             self.security.jwt_secret = val;
         }
+        if let Ok(val) = env::var("COOKIE_SECRET") {
+            self.security.cookie_secret = val;
+        }
         Ok(())
     }
 
@@ -0,0 +1,177 @@
+//! Request body size limits for buffering extractors.
+
+use crate::extract::BodyLimit;
+use std::task::{Context, Poll};
+use tower::Layer;
+use tower_service::Service;
+
+/// Body size used when neither the server nor a [`DefaultBodyLimit`] sets one
+pub const DEFAULT_BODY_LIMIT: usize = 2 * 1024 * 1024;
+
+/// Layer that sets the body size limit for `Json`, `Form`, `Body` and
+/// `Multipart`, answering larger bodies with 413 Payload Too Large
+///
+/// The innermost layer wins, so a limit on a route overrides one around the
+/// whole router, which overrides the server's `max_body_size`.
+///
+/// # Example
+/// ```ignore
+/// router.post("/upload", upload);
+/// router.route_layer(DefaultBodyLimit::max(50 * 1024 * 1024));
+///
+/// // Every route, e.g. when serving without `Server::limits`
+/// let app = ServiceBuilder::new().layer(DefaultBodyLimit::max(64 * 1024)).service(router);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct DefaultBodyLimit {
+    limit: Option<usize>,
+}
+
+impl DefaultBodyLimit {
+    /// Limit bodies to `bytes`
+    pub fn max(bytes: usize) -> Self {
+        Self { limit: Some(bytes) }
+    }
+
+    /// Remove the limit; only do this for trusted clients or streaming handlers
+    pub fn disable() -> Self {
+        Self { limit: None }
+    }
+}
+
+impl<S> Layer<S> for DefaultBodyLimit {
+    type Service = DefaultBodyLimitService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        DefaultBodyLimitService {
+            inner,
+            limit: self.limit,
+        }
+    }
+}
+
+/// Service created by [`DefaultBodyLimit`]
+#[derive(Debug, Clone)]
+pub struct DefaultBodyLimitService<S> {
+    inner: S,
+    limit: Option<usize>,
+}
+
+impl<S, B> Service<http::Request<B>> for DefaultBodyLimitService<S>
+where
+    S: Service<http::Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: http::Request<B>) -> Self::Future {
+        req.extensions_mut().insert(BodyLimit(self.limit));
+        self.inner.call(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use crate::extract::{Body, Form, Json};
+    use crate::router::Router;
+    use crate::types::{OxiditeBody, OxiditeRequest};
+    use bytes::Bytes;
+    use http_body_util::BodyExt;
+
+    /// A request whose body arrives in several frames
+    fn chunked(uri: &str, content_type: &str, chunks: &[&'static [u8]]) -> OxiditeRequest {
+        let frames = chunks
+            .iter()
+            .map(|chunk| Ok::<_, std::convert::Infallible>(hyper::body::Frame::data(Bytes::from_static(chunk))))
+            .collect::<Vec<_>>();
+        let body: OxiditeBody = http_body_util::StreamBody::new(futures_util::stream::iter(frames))
+            .map_err(|e| match e {})
+            .boxed();
+        http::Request::post(uri).header("content-type", content_type).body(body).unwrap()
+    }
+
+    async fn text(res: crate::types::OxiditeResponse) -> String {
+        let bytes = res.into_inner().into_body().collect().await.unwrap().to_bytes();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_route_limit_overrides_global_limit() {
+        let mut router = Router::new();
+        router.post("/big", |Body(body): Body<String>| async move { body.len().to_string() });
+        router.route_layer(DefaultBodyLimit::max(8));
+        router.post("/small", |Body(body): Body<String>| async move { body.len().to_string() });
+        let mut app = DefaultBodyLimit::max(4).layer(router.clone());
+
+        let res = app.call(chunked("/big", "text/plain", &[b"1234", b"5678"])).await.expect("big");
+        assert_eq!(text(res).await, "8");
+        let err = app.call(chunked("/big", "text/plain", &[b"1234", b"5678", b"9"])).await.err().expect("too big");
+        assert!(matches!(err, Error::PayloadTooLarge(_)));
+        let err = app.call(chunked("/small", "text/plain", &[b"12", b"345"])).await.err().expect("too big");
+        assert!(matches!(err, Error::PayloadTooLarge(_)));
+
+        let mut unlimited = DefaultBodyLimit::disable().layer(router);
+        let res = unlimited.call(chunked("/small", "text/plain", &[b"12", b"345"])).await.expect("unlimited");
+        assert_eq!(text(res).await, "5");
+    }
+
+    #[tokio::test]
+    async fn test_default_limit_without_server() {
+        let mut router = Router::new();
+        router.post("/", |Body(body): Body<Vec<u8>>| async move { body.len().to_string() });
+
+        let big: &'static [u8] = vec![b'x'; DEFAULT_BODY_LIMIT + 1].leak();
+        let err = router.handle(chunked("/", "application/octet-stream", &[big])).await.err().expect("too big");
+        assert!(matches!(err, Error::PayloadTooLarge(_)));
+    }
+
+    #[tokio::test]
+    async fn test_multi_chunk_bodies() {
+        let mut router = Router::new();
+        router.post("/json", |Json(value): Json<serde_json::Value>| async move { value["name"].as_str().unwrap_or_default().to_string() });
+        router.post("/form", |Form(form): Form<std::collections::HashMap<String, String>>| async move { form["name"].clone() });
+        router.post("/text", |Body(body): Body<String>| async move { body });
+
+        // "Jürgen", with the two bytes of `ü` split across frames
+        let res = router.handle(chunked("/json", "application/json", &[b"{\"name\":\"J\xc3", b"\xbcrgen\"}"])).await.expect("json");
+        assert_eq!(text(res).await, "Jürgen");
+        let res = router.handle(chunked("/form", "application/x-www-form-urlencoded; charset=utf-8", &[b"name=J%C3", b"%BCrgen&x=1"])).await.expect("form");
+        assert_eq!(text(res).await, "Jürgen");
+        let res = router.handle(chunked("/text", "text/plain", &[b"J\xc3", b"\xbcrgen"])).await.expect("text");
+        assert_eq!(text(res).await, "Jürgen");
+
+        let err = router.handle(chunked("/text", "text/plain", &[b"\xff"])).await.err().expect("invalid utf-8");
+        assert!(matches!(err, Error::BadRequest(_)));
+    }
+
+    #[tokio::test]
+    async fn test_json_content_types() {
+        let mut router = Router::new();
+        router.post("/", |Json(value): Json<serde_json::Value>| async move { value.to_string() });
+        router.post("/form", |Form(_): Form<std::collections::HashMap<String, String>>| async { "ok" });
+
+        for content_type in ["application/json", "application/json; charset=UTF-8", "application/problem+json", "application/vnd.api+json; charset=utf-8"] {
+            let res = router.handle(chunked("/", content_type, &[b"[1]"])).await.expect(content_type);
+            assert_eq!(text(res).await, "[1]");
+        }
+
+        for content_type in ["text/plain", "application/jsonp", "application/json; charset=latin1", "not a mime"] {
+            let err = router.handle(chunked("/", content_type, &[b"[1]"])).await.err().expect(content_type);
+            assert!(matches!(err, Error::UnsupportedMediaType(_)), "{content_type}");
+            assert_eq!(err.status_code(), http::StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        }
+        let req = http::Request::post("/").body(OxiditeBody::default()).unwrap();
+        let err = router.handle(req).await.err().expect("missing content type");
+        assert!(matches!(err, Error::UnsupportedMediaType(_)));
+
+        let err = router.handle(chunked("/form", "application/json", &[b"a=1"])).await.err().expect("form");
+        assert!(matches!(err, Error::UnsupportedMediaType(_)));
+    }
+}
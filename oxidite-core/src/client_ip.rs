@@ -0,0 +1,175 @@
+//! The originating client address behind reverse proxies.
+
+use crate::error::{Error, Result};
+use crate::extract::{FromRequestParts, RemoteAddr};
+use crate::headers::{forwarded_for_chain, x_forwarded_for_chain};
+use http::request::Parts;
+use ipnet::IpNet;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+/// Proxies whose `Forwarded`/`X-Forwarded-For` entries are believed
+///
+/// Set with `Server::trusted_proxies` or `trusted_proxies` in the `[server]`
+/// config.
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies {
+    nets: Arc<[IpNet]>,
+    unix: bool,
+}
+
+impl TrustedProxies {
+    /// Parse addresses (`10.0.0.1`) and CIDR ranges (`10.0.0.0/8`, `fd00::/8`)
+    ///
+    /// The entry `unix:` trusts every peer connected over a Unix socket.
+    pub fn new<'a>(proxies: impl IntoIterator<Item = &'a str>) -> Result<Self> {
+        let mut unix = false;
+        let nets = proxies.into_iter()
+            .map(str::trim)
+            .filter(|proxy| {
+                let is_unix = *proxy == "unix:";
+                unix |= is_unix;
+                !is_unix
+            })
+            .map(|proxy| {
+                proxy.parse::<IpNet>()
+                    .or_else(|_| proxy.parse::<IpAddr>().map(IpNet::from))
+                    .map_err(|_| Error::InternalServerError(format!("Invalid trusted proxy '{}'", proxy)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { nets: nets.into(), unix })
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        self.nets.iter().any(|net| net.contains(&ip))
+    }
+
+    /// Whether Unix socket peers are trusted (the `unix:` entry)
+    pub fn trusts_unix_peers(&self) -> bool {
+        self.unix
+    }
+}
+
+/// IP address of the client that sent the request
+///
+/// Without [`TrustedProxies`] this is the peer address and forwarding headers
+/// are ignored. When the peer is a trusted proxy, the `Forwarded` header (or
+/// `X-Forwarded-For` if absent) is walked from the nearest hop back, and the
+/// first address that is not a trusted proxy is the client. Entries that are
+/// not IP addresses stop the walk at the last address seen. Unix socket peers
+/// have no address, so their headers are only used when `unix:` is trusted.
+///
+/// # Example
+/// ```ignore
+/// async fn login(ClientIp(ip): ClientIp, Json(form): Json<Login>) -> Result<Response> {
+///     limiter.check(ip)?;
+///     // ...
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+impl FromRequestParts for ClientIp {
+    async fn from_request_parts(parts: &mut Parts) -> Result<Self> {
+        let peer = parts.extensions.get::<RemoteAddr>().map(|addr| addr.0.ip().to_canonical());
+        let unavailable = || Error::InternalServerError("Client address not available for this connection".to_string());
+
+        let trusted = match (peer, parts.extensions.get::<TrustedProxies>()) {
+            (Some(peer), Some(trusted)) if trusted.contains(peer) => trusted,
+            (None, Some(trusted)) if trusted.trusts_unix_peers() => trusted,
+            (Some(peer), _) => return Ok(ClientIp(peer)),
+            (None, _) => return Err(unavailable()),
+        };
+
+        let chain = if parts.headers.contains_key(http::header::FORWARDED) {
+            forwarded_for_chain(&parts.headers)
+        } else {
+            x_forwarded_for_chain(&parts.headers)
+        };
+        let mut client = peer;
+        for hop in chain.into_iter().rev() {
+            let Some(hop) = hop.map(|ip| ip.to_canonical()) else {
+                break;
+            };
+            client = Some(hop);
+            if !trusted.contains(hop) {
+                break;
+            }
+        }
+        client.map(ClientIp).ok_or_else(unavailable)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parts(peer: Option<&str>, trusted: Option<&[&str]>, headers: &[(&str, &str)]) -> Parts {
+        let mut req = http::Request::builder();
+        for (name, value) in headers {
+            req = req.header(*name, *value);
+        }
+        let (mut parts, _) = req.body(()).unwrap().into_parts();
+        if let Some(peer) = peer {
+            parts.extensions.insert(RemoteAddr(peer.parse().unwrap()));
+        }
+        if let Some(trusted) = trusted {
+            parts.extensions.insert(TrustedProxies::new(trusted.iter().copied()).unwrap());
+        }
+        parts
+    }
+
+    async fn client_ip(mut parts: Parts) -> String {
+        ClientIp::from_request_parts(&mut parts).await.unwrap().0.to_string()
+    }
+
+    #[tokio::test]
+    async fn test_headers_ignored_without_trusted_proxies() {
+        let parts = parts(Some("198.51.100.9:5000"), None, &[("x-forwarded-for", "1.2.3.4")]);
+        assert_eq!(client_ip(parts).await, "198.51.100.9");
+
+        let parts = self::parts(Some("198.51.100.9:5000"), Some(&["10.0.0.0/8"]), &[("x-forwarded-for", "1.2.3.4")]);
+        assert_eq!(client_ip(parts).await, "198.51.100.9");
+    }
+
+    #[tokio::test]
+    async fn test_walks_past_trusted_hops() {
+        let trusted: &[&str] = &["10.0.0.0/8", "192.168.1.1"];
+        // The client spoofs a leading entry; only what the proxies appended counts
+        let headers = [("x-forwarded-for", "6.6.6.6, 203.0.113.7, 192.168.1.1")];
+        let parts = parts(Some("[::ffff:10.1.2.3]:443"), Some(trusted), &headers);
+        assert_eq!(client_ip(parts).await, "203.0.113.7");
+
+        let headers = [("x-forwarded-for", "garbage, 192.168.1.1")];
+        let parts = self::parts(Some("10.1.2.3:443"), Some(trusted), &headers);
+        assert_eq!(client_ip(parts).await, "192.168.1.1");
+
+        let headers = [("forwarded", "for=203.0.113.7, for=\"[2001:db8::1]:80\""), ("x-forwarded-for", "1.2.3.4")];
+        let parts = self::parts(Some("10.1.2.3:443"), Some(trusted), &headers);
+        assert_eq!(client_ip(parts).await, "2001:db8::1");
+    }
+
+    #[tokio::test]
+    async fn test_unix_socket_peer() {
+        let parts = parts(None, Some(&["127.0.0.1", "unix:"]), &[("x-forwarded-for", "203.0.113.7")]);
+        assert_eq!(client_ip(parts).await, "203.0.113.7");
+
+        let mut parts = self::parts(None, Some(&["127.0.0.1"]), &[("x-forwarded-for", "203.0.113.7")]);
+        assert!(ClientIp::from_request_parts(&mut parts).await.is_err());
+
+        let mut parts = self::parts(None, None, &[("x-forwarded-for", "203.0.113.7")]);
+        assert!(ClientIp::from_request_parts(&mut parts).await.is_err());
+    }
+
+    #[test]
+    fn test_trusted_proxies_parse() {
+        let trusted = TrustedProxies::new(["10.0.0.0/8", "::1"]).unwrap();
+        assert!(trusted.contains("10.20.30.40".parse().unwrap()));
+        assert!(trusted.contains("::1".parse().unwrap()));
+        assert!(!trusted.contains("11.0.0.1".parse().unwrap()));
+        assert!(!trusted.trusts_unix_peers());
+        assert!(TrustedProxies::new(["unix:"]).unwrap().trusts_unix_peers());
+        assert!(TrustedProxies::new(["10.0.0.0/33"]).is_err());
+    }
+}
@@ -1,6 +1,392 @@
-//! Backwards-compatible cookie/form module.
+//! Cookie jars for reading cookies and emitting `Set-Cookie`.
 //!
-//! Canonical extractor implementations live in `crate::extract`.
-//! This module re-exports them to preserve existing import paths.
+//! The plain `Cookies`/`Form` extractors live in `crate::extract` and are
+//! re-exported here to preserve existing import paths.
+//!
+//! # Example
+//! ```ignore
+//! let mut router = Router::new();
+//! router.cookie_keys(CookieKeys::from_config(&config.security)?);
+//!
+//! async fn login(jar: SignedCookieJar, Form(form): Form<Login>) -> Result<(SignedCookieJar, Redirect)> {
+//!     let user = authenticate(&form).await?;
+//!     let session = Cookie::build(("session", user.id.to_string()))
+//!         .http_only(true)
+//!         .secure(true)
+//!         .same_site(SameSite::Lax)
+//!         .max_age(Duration::days(7));
+//!     Ok((jar.add(session), Redirect::to("/")))
+//! }
+//! ```
 
 pub use crate::extract::{Cookies, Form};
+pub use ::cookie::time::Duration;
+pub use ::cookie::{Cookie, Key, SameSite};
+
+use crate::error::{Error, Result};
+use crate::extract::{FromRequestParts, State};
+use crate::response::{IntoResponse, IntoResponseHeaders};
+use crate::state::StateType;
+use crate::types::OxiditeResponse;
+use http::header::{HeaderMap, HeaderValue, COOKIE, SET_COOKIE};
+use http::request::Parts;
+use std::sync::Arc;
+
+/// Keys for [`SignedCookieJar`] and [`PrivateCookieJar`]
+///
+/// New cookies are always signed or encrypted with the current key; cookies
+/// made with a previous key are still accepted, so secrets can be rotated
+/// without logging everyone out. Install with `Router::cookie_keys`.
+#[derive(Clone)]
+pub struct CookieKeys(Arc<[Key]>);
+
+impl CookieKeys {
+    pub fn new(current: Key) -> Self {
+        Self(Arc::new([current]))
+    }
+
+    /// Also accept cookies made with a retired key
+    pub fn previous(self, key: Key) -> Self {
+        let mut keys = self.0.to_vec();
+        keys.push(key);
+        Self(keys.into())
+    }
+
+    /// Derive keys from `cookie_secret` and `previous_cookie_secrets`
+    ///
+    /// Each secret must be at least 32 bytes.
+    pub fn from_config(config: &oxidite_config::SecurityConfig) -> Result<Self> {
+        let derive = |secret: &str| {
+            if secret.len() < 32 {
+                return Err(Error::InternalServerError("Cookie secrets must be at least 32 bytes".to_string()));
+            }
+            Ok(Key::derive_from(secret.as_bytes()))
+        };
+        let mut keys = CookieKeys::new(derive(&config.cookie_secret)?);
+        for secret in &config.previous_cookie_secrets {
+            keys = keys.previous(derive(secret)?);
+        }
+        Ok(keys)
+    }
+
+    fn current(&self) -> &Key {
+        &self.0[0]
+    }
+}
+
+impl std::fmt::Debug for CookieKeys {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CookieKeys").field("keys", &self.0.len()).finish_non_exhaustive()
+    }
+}
+
+impl FromRequestParts for CookieKeys {
+    async fn from_request_parts(parts: &mut Parts) -> Result<Self> {
+        State::<Self>::from_request_parts(parts).await.map(|State(keys)| keys).map_err(|_| {
+            Error::InternalServerError("Cookie keys not configured; see Router::cookie_keys".to_string())
+        })
+    }
+
+    fn required_state() -> Vec<StateType> {
+        State::<Self>::required_state()
+    }
+}
+
+/// Request cookies, and the changes to send back as `Set-Cookie`
+///
+/// Return the jar from the handler, alone or as `(jar, response)`, for the
+/// cookies added or removed to be sent.
+#[derive(Debug, Clone, Default)]
+pub struct CookieJar {
+    jar: ::cookie::CookieJar,
+}
+
+impl CookieJar {
+    /// Parse every `Cookie` header; malformed pairs are skipped
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let mut jar = ::cookie::CookieJar::new();
+        for value in headers.get_all(COOKIE) {
+            let Ok(value) = value.to_str() else {
+                continue;
+            };
+            for cookie in Cookie::split_parse_encoded(value.to_string()).flatten() {
+                jar.add_original(cookie);
+            }
+        }
+        Self { jar }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Cookie<'static>> {
+        self.jar.get(name)
+    }
+
+    /// Set a cookie in the response
+    #[allow(clippy::should_implement_trait)]
+    pub fn add(mut self, cookie: impl Into<Cookie<'static>>) -> Self {
+        self.jar.add(cookie);
+        self
+    }
+
+    /// Expire a cookie; its path and domain must match the ones it was set with
+    pub fn remove(mut self, cookie: impl Into<Cookie<'static>>) -> Self {
+        self.jar.remove(cookie);
+        self
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Cookie<'static>> {
+        self.jar.iter()
+    }
+}
+
+impl FromRequestParts for CookieJar {
+    async fn from_request_parts(parts: &mut Parts) -> Result<Self> {
+        Ok(Self::from_headers(&parts.headers))
+    }
+}
+
+/// Cookies signed with [`CookieKeys`], so clients can read but not alter them
+///
+/// Cookies with a missing or invalid signature are ignored by
+/// [`get`](Self::get) and [`iter`](Self::iter).
+#[derive(Debug, Clone)]
+pub struct SignedCookieJar {
+    jar: ::cookie::CookieJar,
+    keys: CookieKeys,
+}
+
+impl SignedCookieJar {
+    pub fn from_headers(headers: &HeaderMap, keys: CookieKeys) -> Self {
+        Self { jar: CookieJar::from_headers(headers).jar, keys }
+    }
+
+    /// The verified cookie, with its signature removed
+    pub fn get(&self, name: &str) -> Option<Cookie<'static>> {
+        self.keys.0.iter().find_map(|key| self.jar.signed(key).get(name))
+    }
+
+    /// Sign a cookie with the current key and set it in the response
+    #[allow(clippy::should_implement_trait)]
+    pub fn add(mut self, cookie: impl Into<Cookie<'static>>) -> Self {
+        self.jar.signed_mut(self.keys.current()).add(cookie);
+        self
+    }
+
+    /// Expire a cookie; its path and domain must match the ones it was set with
+    pub fn remove(mut self, cookie: impl Into<Cookie<'static>>) -> Self {
+        self.jar.remove(cookie);
+        self
+    }
+
+    /// Every cookie with a valid signature
+    pub fn iter(&self) -> impl Iterator<Item = Cookie<'static>> + '_ {
+        self.jar.iter().filter_map(|cookie| self.get(cookie.name()))
+    }
+}
+
+impl FromRequestParts for SignedCookieJar {
+    async fn from_request_parts(parts: &mut Parts) -> Result<Self> {
+        let keys = CookieKeys::from_request_parts(parts).await?;
+        Ok(Self::from_headers(&parts.headers, keys))
+    }
+
+    fn required_state() -> Vec<StateType> {
+        CookieKeys::required_state()
+    }
+}
+
+/// Cookies encrypted with [`CookieKeys`], so clients can neither read nor
+/// alter them
+///
+/// Cookies that fail to decrypt are ignored by [`get`](Self::get) and
+/// [`iter`](Self::iter).
+#[derive(Debug, Clone)]
+pub struct PrivateCookieJar {
+    jar: ::cookie::CookieJar,
+    keys: CookieKeys,
+}
+
+impl PrivateCookieJar {
+    pub fn from_headers(headers: &HeaderMap, keys: CookieKeys) -> Self {
+        Self { jar: CookieJar::from_headers(headers).jar, keys }
+    }
+
+    /// The decrypted cookie
+    pub fn get(&self, name: &str) -> Option<Cookie<'static>> {
+        self.keys.0.iter().find_map(|key| self.jar.private(key).get(name))
+    }
+
+    /// Encrypt a cookie with the current key and set it in the response
+    #[allow(clippy::should_implement_trait)]
+    pub fn add(mut self, cookie: impl Into<Cookie<'static>>) -> Self {
+        self.jar.private_mut(self.keys.current()).add(cookie);
+        self
+    }
+
+    /// Expire a cookie; its path and domain must match the ones it was set with
+    pub fn remove(mut self, cookie: impl Into<Cookie<'static>>) -> Self {
+        self.jar.remove(cookie);
+        self
+    }
+
+    /// Every cookie that decrypts
+    pub fn iter(&self) -> impl Iterator<Item = Cookie<'static>> + '_ {
+        self.jar.iter().filter_map(|cookie| self.get(cookie.name()))
+    }
+}
+
+impl FromRequestParts for PrivateCookieJar {
+    async fn from_request_parts(parts: &mut Parts) -> Result<Self> {
+        let keys = CookieKeys::from_request_parts(parts).await?;
+        Ok(Self::from_headers(&parts.headers, keys))
+    }
+
+    fn required_state() -> Vec<StateType> {
+        CookieKeys::required_state()
+    }
+}
+
+/// Append a `Set-Cookie` header for every added or removed cookie
+fn append_delta(jar: &::cookie::CookieJar, headers: &mut HeaderMap) {
+    for cookie in jar.delta() {
+        if let Ok(value) = HeaderValue::from_str(&cookie.encoded().to_string()) {
+            headers.append(SET_COOKIE, value);
+        }
+    }
+}
+
+macro_rules! impl_jar_response {
+    ($($jar:ty),*) => {
+        $(
+            impl IntoResponseHeaders for $jar {
+                fn apply(self, headers: &mut HeaderMap) {
+                    append_delta(&self.jar, headers);
+                }
+            }
+
+            impl IntoResponse for $jar {
+                fn into_response(self) -> OxiditeResponse {
+                    (self, ()).into_response()
+                }
+            }
+        )*
+    };
+}
+
+impl_jar_response!(CookieJar, SignedCookieJar, PrivateCookieJar);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(cookie: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(COOKIE, HeaderValue::from_str(cookie).unwrap());
+        headers
+    }
+
+    fn set_cookies(res: &OxiditeResponse) -> Vec<String> {
+        res.headers().get_all(SET_COOKIE).iter().map(|v| v.to_str().unwrap().to_string()).collect()
+    }
+
+    /// Turn the `Set-Cookie` headers of a response into a `Cookie` request header
+    fn echo(res: &OxiditeResponse) -> HeaderMap {
+        let pairs: Vec<String> = set_cookies(res)
+            .iter()
+            .map(|c| c.split(';').next().unwrap().to_string())
+            .collect();
+        headers(&pairs.join("; "))
+    }
+
+    fn key(byte: u8) -> Key {
+        Key::derive_from(&[byte; 32])
+    }
+
+    #[test]
+    fn test_jar_reads_and_emits_changes() {
+        let jar = CookieJar::from_headers(&headers("theme=dark; name=J%C3%BCrgen; broken"));
+        assert_eq!(jar.get("theme").unwrap().value(), "dark");
+        assert_eq!(jar.get("name").unwrap().value(), "Jürgen");
+
+        let res = jar
+            .add(Cookie::build(("lang", "sw")).http_only(true).secure(true).same_site(SameSite::Strict).max_age(Duration::hours(1)))
+            .remove(Cookie::from("theme"))
+            .into_response();
+        let mut cookies = set_cookies(&res);
+        cookies.sort();
+        assert_eq!(cookies[0], "lang=sw; HttpOnly; SameSite=Strict; Secure; Max-Age=3600");
+        assert!(cookies[1].starts_with("theme=; Max-Age=0; Expires="), "{}", cookies[1]);
+    }
+
+    #[test]
+    fn test_signed_jar_rejects_tampering() {
+        let keys = CookieKeys::new(key(1));
+        let res = SignedCookieJar::from_headers(&HeaderMap::new(), keys.clone())
+            .add(("user", "42"))
+            .into_response();
+        let jar = SignedCookieJar::from_headers(&echo(&res), keys.clone());
+        assert_eq!(jar.get("user").unwrap().value(), "42");
+        assert_eq!(jar.iter().count(), 1);
+
+        let forged = set_cookies(&res)[0].split(';').next().unwrap().replace("42", "1");
+        let jar = SignedCookieJar::from_headers(&headers(&forged), keys);
+        assert!(jar.get("user").is_none());
+    }
+
+    #[test]
+    fn test_private_jar_hides_values_and_rotates_keys() {
+        let old = CookieKeys::new(key(1));
+        let res = PrivateCookieJar::from_headers(&HeaderMap::new(), old).add(("cart", "7 items")).into_response();
+        assert!(!set_cookies(&res)[0].contains("7 items"));
+
+        let rotated = CookieKeys::new(key(2)).previous(key(1));
+        let jar = PrivateCookieJar::from_headers(&echo(&res), rotated.clone());
+        assert_eq!(jar.get("cart").unwrap().value(), "7 items");
+
+        // Re-set cookies are encrypted with the new key only
+        let res = jar.add(("cart", "8 items")).into_response();
+        let jar = PrivateCookieJar::from_headers(&echo(&res), CookieKeys::new(key(2)));
+        assert_eq!(jar.get("cart").unwrap().value(), "8 items");
+        let jar = PrivateCookieJar::from_headers(&echo(&res), CookieKeys::new(key(1)));
+        assert!(jar.get("cart").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_router_provides_keys() {
+        use crate::router::Router;
+        use crate::types::OxiditeBody;
+
+        let mut router = Router::new();
+        router.get("/login", |jar: SignedCookieJar| async move {
+            Ok::<_, Error>((jar.add(("user", "42")), OxiditeResponse::text("ok")))
+        });
+        router.get("/me", |jar: SignedCookieJar| async move {
+            let user = jar.get("user").map(|c| c.value().to_string()).unwrap_or_default();
+            Ok::<_, Error>(OxiditeResponse::text(user))
+        });
+
+        let get = |uri: &str, headers: HeaderMap| {
+            let mut req = http::Request::get(uri).body(OxiditeBody::default()).unwrap();
+            *req.headers_mut() = headers;
+            req
+        };
+        let err = router.handle(get("/login", HeaderMap::new())).await.err().expect("no keys");
+        assert!(matches!(err, Error::InternalServerError(_)));
+
+        router.cookie_keys(CookieKeys::new(key(3)));
+        let res = router.handle(get("/login", HeaderMap::new())).await.expect("login");
+        let res = router.handle(get("/me", echo(&res))).await.expect("me");
+        use http_body_util::BodyExt;
+        let body = res.into_inner().into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(&body[..], b"42");
+    }
+
+    #[test]
+    fn test_keys_from_config() {
+        let mut config = oxidite_config::SecurityConfig::default();
+        assert!(CookieKeys::from_config(&config).is_err());
+
+        config.cookie_secret = "a".repeat(32);
+        config.previous_cookie_secrets = vec!["b".repeat(40)];
+        assert_eq!(CookieKeys::from_config(&config).unwrap().0.len(), 2);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::problem::{FieldErrors, ProblemDetails};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -15,13 +16,19 @@ pub enum Error {
     #[error("Resource conflict: {0}")]
     Conflict(String),
     #[error("Validation failed: {0}")]
-    Validation(String),
+    Validation(FieldErrors),
     #[error("Rate limit exceeded: {0}")]
     RateLimited(String),
     #[error("Service temporarily unavailable: {0}")]
     ServiceUnavailable(String),
     #[error("Method not allowed: {0}")]
     MethodNotAllowed(String),
+    #[error("Payload too large: {0}")]
+    PayloadTooLarge(String),
+    #[error("Unsupported media type: {0}")]
+    UnsupportedMediaType(String),
+    #[error("{0}")]
+    Problem(Box<ProblemDetails>),
     #[error(transparent)]
     Hyper(#[from] hyper::Error),
     #[error(transparent)]
@@ -52,10 +59,58 @@ impl Error {
             Error::RateLimited(_) => hyper::StatusCode::TOO_MANY_REQUESTS,
             Error::ServiceUnavailable(_) => hyper::StatusCode::SERVICE_UNAVAILABLE,
             Error::MethodNotAllowed(_) => hyper::StatusCode::METHOD_NOT_ALLOWED,
+            Error::PayloadTooLarge(_) => hyper::StatusCode::PAYLOAD_TOO_LARGE,
+            Error::UnsupportedMediaType(_) => hyper::StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            Error::Problem(problem) => problem.status_code(),
             Error::InternalServerError(_) | Error::Hyper(_) | Error::Io(_) | Error::Http(_) => hyper::StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
 
+    /// Machine-readable error code, sent as the `code` member of problem responses
+    pub fn code(&self) -> &str {
+        match self {
+            Error::NotFound(_) => "not_found",
+            Error::BadRequest(_) | Error::Utf8(_) => "bad_request",
+            Error::SerdeJson(_) => "invalid_json",
+            Error::SerdeUrlEncoded(_) => "invalid_urlencoded",
+            Error::Unauthorized(_) => "unauthorized",
+            Error::Forbidden(_) => "forbidden",
+            Error::Conflict(_) => "conflict",
+            Error::Validation(_) => "validation_failed",
+            Error::RateLimited(_) => "rate_limited",
+            Error::ServiceUnavailable(_) => "service_unavailable",
+            Error::MethodNotAllowed(_) => "method_not_allowed",
+            Error::PayloadTooLarge(_) => "payload_too_large",
+            Error::UnsupportedMediaType(_) => "unsupported_media_type",
+            Error::Problem(problem) => problem
+                .extensions
+                .get("code")
+                .and_then(|code| code.as_str())
+                .unwrap_or("error"),
+            Error::InternalServerError(_) | Error::Hyper(_) | Error::Io(_) | Error::Http(_) => "internal_error",
+        }
+    }
+
+    /// The message without the variant's prefix, used as the problem `detail`
+    pub fn detail(&self) -> String {
+        match self {
+            Error::InternalServerError(msg)
+            | Error::NotFound(msg)
+            | Error::BadRequest(msg)
+            | Error::Unauthorized(msg)
+            | Error::Forbidden(msg)
+            | Error::Conflict(msg)
+            | Error::RateLimited(msg)
+            | Error::ServiceUnavailable(msg)
+            | Error::MethodNotAllowed(msg)
+            | Error::PayloadTooLarge(msg)
+            | Error::UnsupportedMediaType(msg) => msg.clone(),
+            Error::Validation(errors) => errors.to_string(),
+            Error::Problem(problem) => problem.detail.clone().unwrap_or_default(),
+            other => other.to_string(),
+        }
+    }
+
     /// Check if this is a client error (4xx status code)
     /// These errors are expected and should be logged at debug/trace level
     pub fn is_client_error(&self) -> bool {
@@ -1,5 +1,9 @@
+use crate::body_limit::DEFAULT_BODY_LIMIT;
 use crate::error::{Error, Result};
+use crate::headers::{ContentType, HeaderMapExt};
+use crate::state::{StateMap, StateType};
 use crate::types::OxiditeRequest;
+use http::request::Parts;
 use serde::de::DeserializeOwned;
 
 /// Extract typed path parameters from the request
@@ -52,15 +56,103 @@ pub struct Query<T>(pub T);
 /// ```
 pub struct Json<T>(pub T);
 
+/// Extractor that only needs the request head (method, URI, headers, extensions)
+///
+/// Any number of these can appear in a handler's argument list. Types that
+/// need the body implement [`FromRequest`] instead and must be the last argument;
+/// putting one anywhere else is a compile error:
+///
+/// ```compile_fail
+/// use oxidite_core::{Json, OxiditeResponse, Path, Result, Router};
+///
+/// async fn update(Json(body): Json<serde_json::Value>, Path(id): Path<u64>) -> Result<OxiditeResponse> {
+///     Ok(OxiditeResponse::json(body))
+/// }
+///
+/// let mut router = Router::new();
+/// router.put("/items/:id", update);
+/// ```
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` cannot be extracted from the request head",
+    note = "extractors that consume the request body (`Json`, `Form`, `Body`, `Request`) must be the last handler argument"
+)]
+pub trait FromRequestParts: Sized {
+    fn from_request_parts(parts: &mut Parts) -> impl std::future::Future<Output = Result<Self>> + Send;
+
+    /// State this extractor cannot work without, checked when the server starts
+    fn required_state() -> Vec<StateType> {
+        Vec::new()
+    }
+}
+
 /// Extractor trait - allows types to be extracted from requests
-pub trait FromRequest: Sized {
-    fn from_request(req: &mut OxiditeRequest) -> impl std::future::Future<Output = Result<Self>> + Send;
+///
+/// The request is passed by value, so at most one extractor per handler can
+/// consume the body. Every [`FromRequestParts`] type is also a `FromRequest`
+/// through the `ViaParts` marker.
+pub trait FromRequest<M = private::ViaRequest>: Sized {
+    fn from_request(req: OxiditeRequest) -> impl std::future::Future<Output = Result<Self>> + Send;
+
+    /// State this extractor cannot work without, checked when the server starts
+    fn required_state() -> Vec<StateType> {
+        Vec::new()
+    }
+}
+
+pub(crate) mod private {
+    #[derive(Debug, Clone, Copy)]
+    pub enum ViaParts {}
+
+    #[derive(Debug, Clone, Copy)]
+    pub enum ViaRequest {}
+}
+
+impl<T: FromRequestParts> FromRequest<private::ViaParts> for T {
+    async fn from_request(req: OxiditeRequest) -> Result<Self> {
+        let (mut parts, _) = req.into_parts();
+        T::from_request_parts(&mut parts).await
+    }
+
+    fn required_state() -> Vec<StateType> {
+        <T as FromRequestParts>::required_state()
+    }
 }
 
-impl<T: DeserializeOwned + Send> FromRequest for Path<T> {
-    async fn from_request(req: &mut OxiditeRequest) -> Result<Self> {
+impl FromRequest for OxiditeRequest {
+    async fn from_request(req: OxiditeRequest) -> Result<Self> {
+        Ok(req)
+    }
+}
+
+impl FromRequestParts for http::Method {
+    async fn from_request_parts(parts: &mut Parts) -> Result<Self> {
+        Ok(parts.method.clone())
+    }
+}
+
+impl FromRequestParts for http::Uri {
+    async fn from_request_parts(parts: &mut Parts) -> Result<Self> {
+        Ok(parts.uri.clone())
+    }
+}
+
+impl FromRequestParts for http::HeaderMap {
+    async fn from_request_parts(parts: &mut Parts) -> Result<Self> {
+        Ok(parts.headers.clone())
+    }
+}
+
+/// Optional extraction: `None` wherever `T` would have been rejected
+impl<T: FromRequestParts> FromRequestParts for Option<T> {
+    async fn from_request_parts(parts: &mut Parts) -> Result<Self> {
+        Ok(T::from_request_parts(parts).await.ok())
+    }
+}
+
+impl<T: DeserializeOwned + Send> FromRequestParts for Path<T> {
+    async fn from_request_parts(parts: &mut Parts) -> Result<Self> {
         // Path params are stored in request extensions after routing
-        req.extensions()
+        parts.extensions
             .get::<PathParams>()
             .ok_or_else(|| Error::BadRequest("No path parameters found".to_string()))
             .and_then(|params| {
@@ -71,9 +163,9 @@ impl<T: DeserializeOwned + Send> FromRequest for Path<T> {
     }
 }
 
-impl<T: DeserializeOwned + Send> FromRequest for Query<T> {
-    async fn from_request(req: &mut OxiditeRequest) -> Result<Self> {
-        let query = req.uri().query().unwrap_or("");
+impl<T: DeserializeOwned + Send> FromRequestParts for Query<T> {
+    async fn from_request_parts(parts: &mut Parts) -> Result<Self> {
+        let query = parts.uri.query().unwrap_or("");
         serde_urlencoded::from_str(query)
             .map(Query)
             .map_err(|e| Error::BadRequest(format!("Invalid query parameters: {}", e)))
@@ -81,51 +173,126 @@ impl<T: DeserializeOwned + Send> FromRequest for Query<T> {
 }
 
 impl<T: DeserializeOwned + Send> FromRequest for Json<T> {
-    async fn from_request(req: &mut OxiditeRequest) -> Result<Self> {
-        use http_body_util::BodyExt;
-        use bytes::Buf;
+    async fn from_request(mut req: OxiditeRequest) -> Result<Self> {
+        let content_type = req.headers().typed_get::<ContentType>().filter(ContentType::is_json);
+        let Some(content_type) = content_type else {
+            return Err(Error::UnsupportedMediaType(
+                "Expected application/json or a +json content type".to_string()
+            ));
+        };
+        // JSON is always UTF-8 (RFC 8259)
+        if let Some(charset) = content_type.charset().filter(|c| !c.eq_ignore_ascii_case("utf-8")) {
+            return Err(Error::UnsupportedMediaType(format!("Unsupported JSON charset {}", charset)));
+        }
 
-        let body = req.body_mut();
-        let bytes = body.collect().await
-            .map_err(|e| Error::InternalServerError(format!("Failed to read body: {}", e)))?
-            .aggregate();
+        let bytes = read_body(&mut req).await?;
 
-        serde_json::from_reader(bytes.reader())
+        serde_json::from_slice(&bytes)
             .map(Json)
             .map_err(|e| Error::BadRequest(format!("Invalid JSON: {}", e)))
     }
 }
 
-// Storage for path parameters extracted during routing
-#[derive(Clone)]
-pub struct PathParams(pub serde_json::Value);
+/// Address of the connected peer
+///
+/// This is the proxy's address when running behind one; use
+/// [`ClientIp`](crate::ClientIp) for the originating client. Unavailable on
+/// Unix socket and HTTP/3 connections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RemoteAddr(pub std::net::SocketAddr);
 
-// Helper to serialize responses as JSON
-impl<T: serde::Serialize> Json<T> {
-    pub fn into_response(self) -> Result<http_body_util::Full<bytes::Bytes>> {
-        let body = serde_json::to_vec(&self.0)
-            .map_err(|e| Error::InternalServerError(format!("Failed to serialize JSON: {}", e)))?;
-        Ok(http_body_util::Full::new(bytes::Bytes::from(body)))
+impl FromRequestParts for RemoteAddr {
+    async fn from_request_parts(parts: &mut Parts) -> Result<Self> {
+        parts.extensions
+            .get::<Self>()
+            .copied()
+            .ok_or_else(|| Error::InternalServerError("Remote address not available for this connection".to_string()))
     }
 }
 
-/// Extract application state from request extensions
+/// Maximum request body size, set by the server or a
+/// [`DefaultBodyLimit`](crate::DefaultBodyLimit) and read by body
+/// extractors; `None` means unlimited
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct BodyLimit(pub(crate) Option<usize>);
+
+impl BodyLimit {
+    /// The limit for a request, [`DEFAULT_BODY_LIMIT`] if none was set
+    pub(crate) fn of(extensions: &http::Extensions) -> Option<usize> {
+        extensions.get::<BodyLimit>().map_or(Some(DEFAULT_BODY_LIMIT), |limit| limit.0)
+    }
+}
+
+/// Buffer the request body, stopping with `PayloadTooLarge` once it exceeds
+/// the request's [`BodyLimit`]
+pub(crate) async fn read_body(req: &mut OxiditeRequest) -> Result<bytes::Bytes> {
+    use http_body_util::{BodyExt, LengthLimitError, Limited};
+
+    let body = std::mem::take(req.body_mut());
+    let Some(limit) = BodyLimit::of(req.extensions()) else {
+        return body.collect().await
+            .map(|collected| collected.to_bytes())
+            .map_err(|e| Error::InternalServerError(format!("Failed to read body: {}", e)));
+    };
+
+    let too_large = || Error::PayloadTooLarge(format!("Request body exceeds {} bytes", limit));
+    let declared = req.headers()
+        .get(http::header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    if declared.is_some_and(|len| len > limit as u64) {
+        return Err(too_large());
+    }
+
+    Limited::new(body, limit).collect().await
+        .map(|collected| collected.to_bytes())
+        .map_err(|e| {
+            if e.downcast_ref::<LengthLimitError>().is_some() {
+                too_large()
+            } else {
+                Error::InternalServerError(format!("Failed to read body: {}", e))
+            }
+        })
+}
+
+// Storage for path parameters extracted during routing
+#[derive(Clone)]
+pub struct PathParams(pub serde_json::Value);
+
+/// Extract application state registered with `Router::state` or `Server::state`
+///
+/// Serving a router whose handlers need state that was never registered
+/// fails at startup. Values inserted into the request extensions by
+/// middleware are also found, but are not known to that check.
 ///
 /// # Example
 /// ```ignore
+/// let mut router = Router::new();
+/// router.get("/", handler);
+/// router.state(Arc::new(AppState { .. }));
+///
 /// async fn handler(State(state): State<Arc<AppState>>) -> Result<Response> {
 ///     // use state
 /// }
 /// ```
 pub struct State<T>(pub T);
 
-impl<T: Clone + Send + Sync + 'static> FromRequest for State<T> {
-    async fn from_request(req: &mut OxiditeRequest) -> Result<Self> {
-        req.extensions()
-            .get::<T>()
+impl<T: Clone + Send + Sync + 'static> FromRequestParts for State<T> {
+    async fn from_request_parts(parts: &mut Parts) -> Result<Self> {
+        parts.extensions
+            .get::<StateMap>()
+            .and_then(|state| state.get::<T>())
+            .or_else(|| parts.extensions.get::<T>())
             .cloned()
             .map(State)
-            .ok_or_else(|| Error::InternalServerError("Application state not found in request extensions".to_string()))
+            .ok_or_else(|| Error::InternalServerError(format!(
+                "State `{}` is not registered",
+                std::any::type_name::<T>()
+            )))
+    }
+
+    fn required_state() -> Vec<StateType> {
+        vec![StateType::of::<T>()]
     }
 }
 
@@ -146,28 +313,19 @@ impl<T: Clone + Send + Sync + 'static> FromRequest for State<T> {
 pub struct Form<T>(pub T);
 
 impl<T: DeserializeOwned + Send> FromRequest for Form<T> {
-    async fn from_request(req: &mut OxiditeRequest) -> Result<Self> {
-        use http_body_util::BodyExt;
-        use bytes::Buf;
-        
-        // Check content type
-        let content_type = req.headers()
-            .get("content-type")
-            .and_then(|ct| ct.to_str().ok())
-            .unwrap_or("");
-            
-        if !content_type.starts_with("application/x-www-form-urlencoded") {
-            return Err(Error::BadRequest(
+    async fn from_request(mut req: OxiditeRequest) -> Result<Self> {
+        let is_form = req.headers()
+            .typed_get::<ContentType>()
+            .is_some_and(|ct| ct.mime().essence_str() == mime::APPLICATION_WWW_FORM_URLENCODED.essence_str());
+        if !is_form {
+            return Err(Error::UnsupportedMediaType(
                 "Expected application/x-www-form-urlencoded content type".to_string()
             ));
         }
+
+        let bytes = read_body(&mut req).await?;
         
-        let body = req.body_mut();
-        let bytes = body.collect().await
-            .map_err(|e| Error::InternalServerError(format!("Failed to read body: {}", e)))?
-            .aggregate();
-        
-        let body_str = std::str::from_utf8(bytes.chunk())
+        let body_str = std::str::from_utf8(&bytes)
             .map_err(|e| Error::BadRequest(format!("Invalid UTF-8 in form data: {}", e)))?;
         
         serde_urlencoded::from_str(body_str)
@@ -205,11 +363,11 @@ impl Cookies {
     }
 }
 
-impl FromRequest for Cookies {
-    async fn from_request(req: &mut OxiditeRequest) -> Result<Self> {
+impl FromRequestParts for Cookies {
+    async fn from_request_parts(parts: &mut Parts) -> Result<Self> {
         let mut cookies_map = std::collections::HashMap::new();
         
-        if let Some(cookie_header) = req.headers().get(http::header::COOKIE) {
+        if let Some(cookie_header) = parts.headers.get(http::header::COOKIE) {
             if let Ok(cookie_str) = cookie_header.to_str() {
                 for cookie_pair in cookie_str.split(';') {
                     let trimmed = cookie_pair.trim();
@@ -236,17 +394,11 @@ impl FromRequest for Cookies {
 pub struct Body<T>(pub T);
 
 impl FromRequest for Body<String> {
-    async fn from_request(req: &mut OxiditeRequest) -> Result<Self> {
-        use http_body_util::BodyExt;
-        use bytes::Buf;
-        
-        let body = req.body_mut();
-        let bytes = body.collect().await
-            .map_err(|e| Error::InternalServerError(format!("Failed to read body: {}", e)))?
-            .aggregate();
+    async fn from_request(mut req: OxiditeRequest) -> Result<Self> {
+        let bytes = read_body(&mut req).await?;
         
-        let body_str = std::str::from_utf8(bytes.chunk())
-            .map_err(|e| Error::InternalServerError(format!("Invalid UTF-8 in body: {}", e)))?
+        let body_str = std::str::from_utf8(&bytes)
+            .map_err(|e| Error::BadRequest(format!("Invalid UTF-8 in body: {}", e)))?
             .to_string();
         
         Ok(Body(body_str))
@@ -254,14 +406,52 @@ impl FromRequest for Body<String> {
 }
 
 impl FromRequest for Body<Vec<u8>> {
-    async fn from_request(req: &mut OxiditeRequest) -> Result<Self> {
-        use http_body_util::BodyExt;
-        
-        let body = req.body_mut();
-        let bytes = body.collect().await
-            .map_err(|e| Error::InternalServerError(format!("Failed to read body: {}", e)))?
-            .to_bytes();
+    async fn from_request(mut req: OxiditeRequest) -> Result<Self> {
+        let bytes = read_body(&mut req).await?;
         
         Ok(Body(bytes.to_vec()))
     }
 }
+
+/// Stream the request body chunk by chunk instead of buffering it
+///
+/// Unlike the buffering extractors, the stream is not capped by the server's
+/// `max_body_size` or a `DefaultBodyLimit`; count bytes as they arrive if
+/// you need a limit.
+///
+/// # Example
+/// ```ignore
+/// use futures_util::StreamExt;
+///
+/// async fn ingest(mut body: BodyStream) -> Result<Response> {
+///     let mut total = 0;
+///     while let Some(chunk) = body.next().await {
+///         total += chunk?.len();
+///     }
+///     Ok(Response::text(format!("{} bytes", total)))
+/// }
+/// ```
+pub struct BodyStream {
+    inner: http_body_util::BodyDataStream<crate::types::OxiditeBody>,
+}
+
+impl futures_util::Stream for BodyStream {
+    type Item = Result<bytes::Bytes>;
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        std::pin::Pin::new(&mut self.inner)
+            .poll_next(cx)
+            .map_err(|e| Error::InternalServerError(format!("Failed to read body: {}", e)))
+    }
+}
+
+impl FromRequest for BodyStream {
+    async fn from_request(req: OxiditeRequest) -> Result<Self> {
+        Ok(BodyStream {
+            inner: http_body_util::BodyDataStream::new(req.into_body()),
+        })
+    }
+}
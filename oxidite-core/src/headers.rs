@@ -0,0 +1,254 @@
+//! Typed request and response headers.
+
+use crate::error::{Error, Result};
+use crate::extract::FromRequestParts;
+use http::header::{self, HeaderMap, HeaderName, HeaderValue};
+use http::request::Parts;
+use mime::Mime;
+
+mod accept;
+mod authorization;
+mod conditional;
+mod forwarded;
+mod range;
+
+pub use accept::{Accept, QualityItem};
+pub use authorization::Authorization;
+pub use conditional::{EntityTag, IfModifiedSince, IfNoneMatch};
+pub use forwarded::{Forwarded, ForwardedElement, XForwardedFor};
+pub use range::{ByteRange, Range};
+
+pub(crate) use forwarded::{forwarded_for_chain, x_forwarded_for_chain};
+
+/// A header that can be parsed from and written to a `HeaderMap`
+pub trait Header: Sized {
+    const NAME: HeaderName;
+
+    /// Parse every value of the header, in order; there is always at least one
+    fn decode<'a, I: Iterator<Item = &'a HeaderValue>>(values: I) -> Result<Self>;
+
+    fn encode(&self) -> HeaderValue;
+
+    /// Rejection when the header is absent
+    fn missing() -> Error {
+        Error::BadRequest(format!("Missing {} header", Self::NAME))
+    }
+}
+
+/// Extract a header parsed into `T`
+///
+/// Fails with 400 (401 for `Authorization`) when the header is missing or
+/// malformed; use `Option<TypedHeader<T>>` when it is optional.
+///
+/// # Example
+/// ```ignore
+/// async fn profile(
+///     TypedHeader(auth): TypedHeader<Authorization>,
+///     agent: Option<TypedHeader<UserAgent>>,
+/// ) -> Result<Response> {
+///     let token = auth.token().ok_or_else(|| Error::Unauthorized("Bearer token required".into()))?;
+///     // ...
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypedHeader<T>(pub T);
+
+impl<T: Header + Send> FromRequestParts for TypedHeader<T> {
+    async fn from_request_parts(parts: &mut Parts) -> Result<Self> {
+        parts.headers.typed_try_get::<T>()?.map(TypedHeader).ok_or_else(T::missing)
+    }
+}
+
+/// Typed access to a `HeaderMap`
+pub trait HeaderMapExt {
+    /// Parse a header, `Ok(None)` if it is absent
+    fn typed_try_get<H: Header>(&self) -> Result<Option<H>>;
+
+    /// Parse a header, `None` if it is absent or malformed
+    fn typed_get<H: Header>(&self) -> Option<H> {
+        self.typed_try_get().ok().flatten()
+    }
+
+    /// Replace any existing values of the header
+    fn typed_insert<H: Header>(&mut self, header: H);
+}
+
+impl HeaderMapExt for HeaderMap {
+    fn typed_try_get<H: Header>(&self) -> Result<Option<H>> {
+        let mut values = self.get_all(H::NAME).iter().peekable();
+        if values.peek().is_none() {
+            return Ok(None);
+        }
+        H::decode(values).map(Some)
+    }
+
+    fn typed_insert<H: Header>(&mut self, header: H) {
+        self.insert(H::NAME, header.encode());
+    }
+}
+
+/// `Content-Type`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContentType(pub Mime);
+
+impl ContentType {
+    pub fn json() -> Self {
+        Self(mime::APPLICATION_JSON)
+    }
+
+    pub fn html() -> Self {
+        Self(mime::TEXT_HTML_UTF_8)
+    }
+
+    pub fn text() -> Self {
+        Self(mime::TEXT_PLAIN_UTF_8)
+    }
+
+    pub fn form_url_encoded() -> Self {
+        Self(mime::APPLICATION_WWW_FORM_URLENCODED)
+    }
+
+    pub fn mime(&self) -> &Mime {
+        &self.0
+    }
+
+    /// The `charset` parameter, if any
+    pub fn charset(&self) -> Option<&str> {
+        self.0.get_param(mime::CHARSET).map(|charset| charset.as_str())
+    }
+
+    /// `application/json` or any `+json` type such as `application/problem+json`
+    pub fn is_json(&self) -> bool {
+        self.0.type_() == mime::APPLICATION
+            && (self.0.subtype() == mime::JSON || self.0.suffix() == Some(mime::JSON))
+    }
+}
+
+impl From<Mime> for ContentType {
+    fn from(mime: Mime) -> Self {
+        Self(mime)
+    }
+}
+
+impl Header for ContentType {
+    const NAME: HeaderName = header::CONTENT_TYPE;
+
+    fn decode<'a, I: Iterator<Item = &'a HeaderValue>>(mut values: I) -> Result<Self> {
+        values.next()
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok())
+            .map(ContentType)
+            .ok_or_else(invalid::<Self>)
+    }
+
+    fn encode(&self) -> HeaderValue {
+        HeaderValue::from_str(self.0.as_ref()).expect("a parsed mime type is a valid header value")
+    }
+}
+
+/// `User-Agent`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserAgent(pub String);
+
+impl UserAgent {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Header for UserAgent {
+    const NAME: HeaderName = header::USER_AGENT;
+
+    fn decode<'a, I: Iterator<Item = &'a HeaderValue>>(mut values: I) -> Result<Self> {
+        values.next()
+            .and_then(|value| value.to_str().ok())
+            .map(|value| UserAgent(value.to_string()))
+            .ok_or_else(invalid::<Self>)
+    }
+
+    fn encode(&self) -> HeaderValue {
+        HeaderValue::from_str(&self.0).unwrap_or_else(|_| HeaderValue::from_static(""))
+    }
+}
+
+/// Rejection for a header that is present but cannot be parsed
+pub(crate) fn invalid<H: Header>() -> Error {
+    Error::BadRequest(format!("Invalid {} header", H::NAME))
+}
+
+/// Every comma-separated item across the header's values, trimmed, skipping
+/// empty items and not splitting inside quoted strings
+pub(crate) fn list_items<'a>(values: impl Iterator<Item = &'a HeaderValue>) -> Option<Vec<&'a str>> {
+    let mut items = Vec::new();
+    for value in values {
+        items.extend(split_quoted(value.to_str().ok()?, ','));
+    }
+    Some(items.into_iter().map(str::trim).filter(|item| !item.is_empty()).collect())
+}
+
+/// Split on `separator` outside of double-quoted strings
+pub(crate) fn split_quoted(s: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            c if c == separator && !quoted => {
+                parts.push(&s[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parts(headers: &[(&str, &str)]) -> Parts {
+        let mut req = http::Request::builder();
+        for (name, value) in headers {
+            req = req.header(*name, *value);
+        }
+        req.body(()).unwrap().into_parts().0
+    }
+
+    #[tokio::test]
+    async fn test_typed_header_extraction() {
+        let mut parts = parts(&[("content-type", "application/vnd.api+json; charset=utf-8")]);
+        let TypedHeader(content_type) = TypedHeader::<ContentType>::from_request_parts(&mut parts).await.unwrap();
+        assert!(content_type.is_json());
+        assert_eq!(content_type.charset(), Some("utf-8"));
+
+        let err = TypedHeader::<UserAgent>::from_request_parts(&mut parts).await.unwrap_err();
+        assert!(matches!(err, Error::BadRequest(_)));
+        let agent = <Option<TypedHeader<UserAgent>>>::from_request_parts(&mut parts).await.unwrap();
+        assert!(agent.is_none());
+
+        let mut parts = self::parts(&[("content-type", "not a mime")]);
+        let err = TypedHeader::<ContentType>::from_request_parts(&mut parts).await.unwrap_err();
+        assert!(matches!(err, Error::BadRequest(msg) if msg == "Invalid content-type header"));
+    }
+
+    #[test]
+    fn test_header_map_roundtrip() {
+        let mut headers = HeaderMap::new();
+        headers.typed_insert(ContentType::json());
+        assert_eq!(headers["content-type"], "application/json");
+        assert_eq!(headers.typed_get::<ContentType>(), Some(ContentType::json()));
+        assert_eq!(headers.typed_get::<UserAgent>(), None);
+    }
+
+    #[test]
+    fn test_list_items_respect_quotes() {
+        let values = [HeaderValue::from_static("\"a,b\", W/\"c\""), HeaderValue::from_static(" ,d")];
+        assert_eq!(list_items(values.iter()).unwrap(), ["\"a,b\"", "W/\"c\"", "d"]);
+    }
+}
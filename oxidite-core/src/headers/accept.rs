@@ -0,0 +1,129 @@
+use super::{invalid, list_items, Header};
+use crate::error::Result;
+use http::header::{self, HeaderName, HeaderValue};
+use mime::Mime;
+
+/// `Accept`, ordered from most to least preferred
+///
+/// # Example
+/// ```ignore
+/// async fn report(accept: Option<TypedHeader<Accept>>) -> Result<Response> {
+///     let preferred = accept.and_then(|TypedHeader(a)| a.negotiate(&["application/json", "text/csv"]));
+///     match preferred.unwrap_or("application/json") { /* ... */ }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Accept(Vec<QualityItem>);
+
+/// A media range with its `q` weight
+#[derive(Debug, Clone, PartialEq)]
+pub struct QualityItem {
+    pub media_type: Mime,
+    pub quality: f32,
+}
+
+impl Accept {
+    /// Media ranges sorted by quality; equal weights keep the client's order
+    pub fn items(&self) -> &[QualityItem] {
+        &self.0
+    }
+
+    /// Pick the best of the `available` media types
+    ///
+    /// Each candidate takes the weight of the most specific range matching it
+    /// (`text/html` over `text/*` over `*/*`). Ties go to the earlier entry in
+    /// `available`; `None` means nothing is acceptable.
+    pub fn negotiate<'a>(&self, available: &[&'a str]) -> Option<&'a str> {
+        let mut best: Option<(&'a str, f32)> = None;
+        for &candidate in available {
+            let Ok(mime) = candidate.parse::<Mime>() else {
+                continue;
+            };
+            let quality = self.quality(&mime);
+            if quality > 0.0 && best.is_none_or(|(_, q)| quality > q) {
+                best = Some((candidate, quality));
+            }
+        }
+        best.map(|(candidate, _)| candidate)
+    }
+
+    /// Weight of the most specific range matching `mime`, 0 if none does
+    fn quality(&self, mime: &Mime) -> f32 {
+        self.0.iter()
+            .filter_map(|item| {
+                let range = &item.media_type;
+                let specificity = if range.type_() == mime::STAR {
+                    0
+                } else if range.type_() != mime.type_() {
+                    return None;
+                } else if range.subtype() == mime::STAR {
+                    1
+                } else if range.subtype() == mime.subtype() {
+                    2
+                } else {
+                    return None;
+                };
+                Some((specificity, item.quality))
+            })
+            .max_by_key(|&(specificity, _)| specificity)
+            .map_or(0.0, |(_, quality)| quality)
+    }
+}
+
+impl Header for Accept {
+    const NAME: HeaderName = header::ACCEPT;
+
+    /// Unparseable ranges are skipped rather than failing the whole header
+    fn decode<'a, I: Iterator<Item = &'a HeaderValue>>(values: I) -> Result<Self> {
+        let mut items: Vec<QualityItem> = list_items(values)
+            .ok_or_else(invalid::<Self>)?
+            .into_iter()
+            .filter_map(|item| {
+                let media_type: Mime = item.parse().ok()?;
+                let quality = match media_type.get_param("q") {
+                    Some(q) => q.as_str().parse::<f32>().ok().filter(|q| (0.0..=1.0).contains(q))?,
+                    None => 1.0,
+                };
+                Some(QualityItem { media_type, quality })
+            })
+            .collect();
+        items.sort_by(|a, b| b.quality.total_cmp(&a.quality));
+        Ok(Accept(items))
+    }
+
+    fn encode(&self) -> HeaderValue {
+        let value = self.0.iter()
+            .map(|item| item.media_type.as_ref())
+            .collect::<Vec<_>>()
+            .join(", ");
+        HeaderValue::from_str(&value).unwrap_or_else(|_| HeaderValue::from_static("*/*"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accept(value: &'static str) -> Accept {
+        Accept::decode([HeaderValue::from_static(value)].iter()).unwrap()
+    }
+
+    #[test]
+    fn test_items_sorted_by_quality() {
+        let accept = accept("text/html;q=0.5, application/json, bogus, text/*;q=0.8");
+        let types: Vec<_> = accept.items().iter().map(|item| item.media_type.essence_str().to_string()).collect();
+        assert_eq!(types, ["application/json", "text/*", "text/html"]);
+    }
+
+    #[test]
+    fn test_negotiate() {
+        let browser = accept("text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8");
+        assert_eq!(browser.negotiate(&["application/json", "text/html"]), Some("text/html"));
+        assert_eq!(browser.negotiate(&["application/json", "text/csv"]), Some("application/json"));
+
+        // The most specific range decides, even if a broader one weighs more
+        let picky = accept("text/*, text/csv;q=0");
+        assert_eq!(picky.negotiate(&["text/csv", "text/plain"]), Some("text/plain"));
+        assert_eq!(picky.negotiate(&["text/csv", "application/json"]), None);
+    }
+}
@@ -0,0 +1,103 @@
+use super::Header;
+use crate::error::{Error, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use http::header::{self, HeaderName, HeaderValue};
+
+/// `Authorization` with the `Bearer` or `Basic` scheme
+///
+/// Missing, malformed or other-scheme credentials are rejected with 401.
+#[derive(Clone, PartialEq, Eq)]
+pub enum Authorization {
+    Bearer(String),
+    Basic { username: String, password: String },
+}
+
+impl Authorization {
+    pub fn bearer(token: impl Into<String>) -> Self {
+        Self::Bearer(token.into())
+    }
+
+    pub fn basic(username: impl Into<String>, password: impl Into<String>) -> Self {
+        Self::Basic { username: username.into(), password: password.into() }
+    }
+
+    /// The bearer token, if this is `Bearer` authorization
+    pub fn token(&self) -> Option<&str> {
+        match self {
+            Self::Bearer(token) => Some(token),
+            Self::Basic { .. } => None,
+        }
+    }
+}
+
+/// Credentials are left out so they do not end up in logs
+impl std::fmt::Debug for Authorization {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Bearer(_) => f.write_str("Bearer(..)"),
+            Self::Basic { username, .. } => f.debug_struct("Basic").field("username", username).finish_non_exhaustive(),
+        }
+    }
+}
+
+impl Header for Authorization {
+    const NAME: HeaderName = header::AUTHORIZATION;
+
+    fn decode<'a, I: Iterator<Item = &'a HeaderValue>>(mut values: I) -> Result<Self> {
+        let invalid = || Error::Unauthorized("Invalid Authorization header".to_string());
+        let value = values.next().and_then(|value| value.to_str().ok()).ok_or_else(invalid)?;
+        let (scheme, credentials) = value.trim().split_once(' ').ok_or_else(invalid)?;
+        let credentials = credentials.trim();
+        if credentials.is_empty() {
+            return Err(invalid());
+        }
+
+        if scheme.eq_ignore_ascii_case("bearer") {
+            Ok(Self::Bearer(credentials.to_string()))
+        } else if scheme.eq_ignore_ascii_case("basic") {
+            let decoded = STANDARD.decode(credentials).map_err(|_| invalid())?;
+            let decoded = String::from_utf8(decoded).map_err(|_| invalid())?;
+            let (username, password) = decoded.split_once(':').ok_or_else(invalid)?;
+            Ok(Self::basic(username, password))
+        } else {
+            Err(Error::Unauthorized(format!("Unsupported authorization scheme '{}'", scheme)))
+        }
+    }
+
+    fn encode(&self) -> HeaderValue {
+        let value = match self {
+            Self::Bearer(token) => format!("Bearer {}", token),
+            Self::Basic { username, password } => {
+                format!("Basic {}", STANDARD.encode(format!("{}:{}", username, password)))
+            }
+        };
+        let mut value = HeaderValue::from_str(&value).unwrap_or_else(|_| HeaderValue::from_static(""));
+        value.set_sensitive(true);
+        value
+    }
+
+    fn missing() -> Error {
+        Error::Unauthorized("Missing Authorization header".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(value: &'static str) -> Result<Authorization> {
+        Authorization::decode([HeaderValue::from_static(value)].iter())
+    }
+
+    #[test]
+    fn test_schemes() {
+        assert_eq!(decode("Bearer abc.def").unwrap().token(), Some("abc.def"));
+        assert_eq!(decode("basic YWxhZGRpbjpvcGVuOnNlc2FtZQ==").unwrap(), Authorization::basic("aladdin", "open:sesame"));
+        assert_eq!(Authorization::basic("aladdin", "open:sesame").encode(), "Basic YWxhZGRpbjpvcGVuOnNlc2FtZQ==");
+
+        for value in ["Bearer", "Bearer  ", "Basic !!!", "Basic bm9jb2xvbg==", "Digest abc"] {
+            assert!(matches!(decode(value), Err(Error::Unauthorized(_))), "{value}");
+        }
+    }
+}
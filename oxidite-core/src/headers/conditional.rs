@@ -0,0 +1,164 @@
+use super::{invalid, list_items, Header};
+use crate::error::Result;
+use http::header::{self, HeaderName, HeaderValue};
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// An entity tag such as `"v1"` or `W/"v1"`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EntityTag {
+    pub weak: bool,
+    /// The opaque tag, without quotes
+    pub tag: String,
+}
+
+impl EntityTag {
+    pub fn strong(tag: impl Into<String>) -> Self {
+        Self { weak: false, tag: tag.into() }
+    }
+
+    pub fn weak(tag: impl Into<String>) -> Self {
+        Self { weak: true, tag: tag.into() }
+    }
+
+    /// Parse a quoted tag with an optional `W/` prefix
+    pub fn parse(s: &str) -> Option<Self> {
+        let (weak, quoted) = match s.strip_prefix("W/") {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+        let tag = quoted.strip_prefix('"')?.strip_suffix('"')?;
+        if tag.contains('"') {
+            return None;
+        }
+        Some(Self { weak, tag: tag.to_string() })
+    }
+
+    /// Weak comparison: tags are equal ignoring the `W/` prefix
+    pub fn weak_eq(&self, other: &EntityTag) -> bool {
+        self.tag == other.tag
+    }
+
+    /// Strong comparison: both tags are strong and equal
+    pub fn strong_eq(&self, other: &EntityTag) -> bool {
+        !self.weak && !other.weak && self.tag == other.tag
+    }
+}
+
+impl fmt::Display for EntityTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.weak {
+            f.write_str("W/")?;
+        }
+        write!(f, "\"{}\"", self.tag)
+    }
+}
+
+/// `If-None-Match`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IfNoneMatch {
+    /// `*`: matches any current representation
+    Any,
+    Tags(Vec<EntityTag>),
+}
+
+impl IfNoneMatch {
+    /// Whether a representation tagged `etag` matches, i.e. a `GET` can be
+    /// answered with 304 Not Modified
+    pub fn matches(&self, etag: &EntityTag) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Tags(tags) => tags.iter().any(|tag| tag.weak_eq(etag)),
+        }
+    }
+}
+
+impl Header for IfNoneMatch {
+    const NAME: HeaderName = header::IF_NONE_MATCH;
+
+    fn decode<'a, I: Iterator<Item = &'a HeaderValue>>(values: I) -> Result<Self> {
+        let items = list_items(values).ok_or_else(invalid::<Self>)?;
+        if items == ["*"] {
+            return Ok(Self::Any);
+        }
+        items.into_iter()
+            .map(EntityTag::parse)
+            .collect::<Option<Vec<_>>>()
+            .map(Self::Tags)
+            .ok_or_else(invalid::<Self>)
+    }
+
+    fn encode(&self) -> HeaderValue {
+        let value = match self {
+            Self::Any => "*".to_string(),
+            Self::Tags(tags) => tags.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "),
+        };
+        HeaderValue::from_str(&value).unwrap_or_else(|_| HeaderValue::from_static("*"))
+    }
+}
+
+/// `If-Modified-Since`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IfModifiedSince(pub SystemTime);
+
+impl IfModifiedSince {
+    /// Whether `last_modified` is later than this date, compared at the
+    /// one-second resolution of HTTP dates
+    pub fn is_modified(&self, last_modified: SystemTime) -> bool {
+        truncate_to_secs(last_modified) > self.0
+    }
+}
+
+fn truncate_to_secs(time: SystemTime) -> SystemTime {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+impl Header for IfModifiedSince {
+    const NAME: HeaderName = header::IF_MODIFIED_SINCE;
+
+    fn decode<'a, I: Iterator<Item = &'a HeaderValue>>(mut values: I) -> Result<Self> {
+        values.next()
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| httpdate::parse_http_date(value).ok())
+            .map(IfModifiedSince)
+            .ok_or_else(invalid::<Self>)
+    }
+
+    fn encode(&self) -> HeaderValue {
+        HeaderValue::from_str(&httpdate::fmt_http_date(self.0)).expect("HTTP dates are valid header values")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode<H: Header>(value: &'static str) -> Result<H> {
+        H::decode([HeaderValue::from_static(value)].iter())
+    }
+
+    #[test]
+    fn test_if_none_match() {
+        let header: IfNoneMatch = decode("\"a\", W/\"b\"").unwrap();
+        assert!(header.matches(&EntityTag::strong("b")));
+        assert!(header.matches(&EntityTag::weak("a")));
+        assert!(!header.matches(&EntityTag::strong("c")));
+        assert_eq!(header.encode(), "\"a\", W/\"b\"");
+
+        assert_eq!(decode::<IfNoneMatch>("*").unwrap(), IfNoneMatch::Any);
+        assert!(decode::<IfNoneMatch>("unquoted").is_err());
+        assert!(!EntityTag::weak("a").strong_eq(&EntityTag::strong("a")));
+    }
+
+    #[test]
+    fn test_if_modified_since() {
+        let header: IfModifiedSince = decode("Sun, 06 Nov 1994 08:49:37 GMT").unwrap();
+        let date = UNIX_EPOCH + Duration::from_secs(784111777);
+        assert_eq!(header.0, date);
+        assert!(!header.is_modified(date + Duration::from_millis(500)));
+        assert!(header.is_modified(date + Duration::from_secs(1)));
+        assert_eq!(header.encode(), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert!(decode::<IfModifiedSince>("yesterday").is_err());
+    }
+}
@@ -0,0 +1,185 @@
+use super::{invalid, list_items, split_quoted, Header};
+use crate::error::Result;
+use http::header::{self, HeaderMap, HeaderName, HeaderValue};
+use std::net::{IpAddr, SocketAddr};
+
+/// `X-Forwarded-For`: the client first, then every proxy but the last
+///
+/// Anyone can send this header. Use [`ClientIp`](crate::ClientIp) to only
+/// believe the entries added by trusted proxies.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XForwardedFor(pub Vec<IpAddr>);
+
+impl Header for XForwardedFor {
+    const NAME: HeaderName = HeaderName::from_static("x-forwarded-for");
+
+    fn decode<'a, I: Iterator<Item = &'a HeaderValue>>(values: I) -> Result<Self> {
+        list_items(values)
+            .and_then(|items| items.into_iter().map(parse_node).collect::<Option<Vec<_>>>())
+            .filter(|ips| !ips.is_empty())
+            .map(XForwardedFor)
+            .ok_or_else(invalid::<Self>)
+    }
+
+    fn encode(&self) -> HeaderValue {
+        let value = self.0.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ");
+        HeaderValue::from_str(&value).expect("IP addresses are valid header values")
+    }
+}
+
+/// `Forwarded` (RFC 7239), one element per proxy hop
+///
+/// Like `X-Forwarded-For`, this is client-controlled unless a trusted proxy
+/// rewrites it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Forwarded(pub Vec<ForwardedElement>);
+
+/// One hop of a `Forwarded` header; values are unquoted
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ForwardedElement {
+    /// The `for` node, e.g. `192.0.2.60`, `"[2001:db8::1]:4711"` or `unknown`
+    pub forwarded_for: Option<String>,
+    pub by: Option<String>,
+    pub host: Option<String>,
+    pub proto: Option<String>,
+}
+
+impl ForwardedElement {
+    /// IP address of the `for` node, if it is not obfuscated or `unknown`
+    pub fn for_ip(&self) -> Option<IpAddr> {
+        self.forwarded_for.as_deref().and_then(parse_node)
+    }
+}
+
+impl Header for Forwarded {
+    const NAME: HeaderName = header::FORWARDED;
+
+    fn decode<'a, I: Iterator<Item = &'a HeaderValue>>(values: I) -> Result<Self> {
+        list_items(values)
+            .and_then(|items| items.into_iter().map(parse_element).collect::<Option<Vec<_>>>())
+            .filter(|elements| !elements.is_empty())
+            .map(Forwarded)
+            .ok_or_else(invalid::<Self>)
+    }
+
+    fn encode(&self) -> HeaderValue {
+        let elements: Vec<String> = self.0.iter()
+            .map(|element| {
+                [
+                    ("for", &element.forwarded_for),
+                    ("by", &element.by),
+                    ("host", &element.host),
+                    ("proto", &element.proto),
+                ]
+                .into_iter()
+                .filter_map(|(key, value)| value.as_deref().map(|value| format!("{}={}", key, quote(value))))
+                .collect::<Vec<_>>()
+                .join(";")
+            })
+            .collect();
+        HeaderValue::from_str(&elements.join(", ")).unwrap_or_else(|_| HeaderValue::from_static(""))
+    }
+}
+
+fn parse_element(element: &str) -> Option<ForwardedElement> {
+    let mut parsed = ForwardedElement::default();
+    for pair in split_quoted(element, ';') {
+        let pair = pair.trim();
+        if pair.is_empty() {
+            continue;
+        }
+        let (key, value) = pair.split_once('=')?;
+        let value = unquote(value.trim())?;
+        match key.trim().to_ascii_lowercase().as_str() {
+            "for" => parsed.forwarded_for = Some(value),
+            "by" => parsed.by = Some(value),
+            "host" => parsed.host = Some(value),
+            "proto" => parsed.proto = Some(value),
+            _ => {}
+        }
+    }
+    Some(parsed)
+}
+
+fn unquote(value: &str) -> Option<String> {
+    match value.strip_prefix('"') {
+        Some(quoted) => Some(quoted.strip_suffix('"')?.replace("\\\"", "\"").replace("\\\\", "\\")),
+        None => Some(value.to_string()),
+    }
+}
+
+fn quote(value: &str) -> String {
+    let is_token = !value.is_empty()
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c));
+    if is_token {
+        value.to_string()
+    } else {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+/// An IP with an optional port: `192.0.2.1`, `192.0.2.1:80`, `[::1]:80`, `::1`
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim();
+    if let Ok(ip) = node.parse::<IpAddr>() {
+        return Some(ip);
+    }
+    if let Ok(addr) = node.parse::<SocketAddr>() {
+        return Some(addr.ip());
+    }
+    node.strip_prefix('[')?.strip_suffix(']')?.parse().ok()
+}
+
+/// Hops listed in `X-Forwarded-For`, client first; `None` for entries that
+/// are not IP addresses
+pub(crate) fn x_forwarded_for_chain(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    list_items(headers.get_all(XForwardedFor::NAME).iter())
+        .unwrap_or_default()
+        .into_iter()
+        .map(parse_node)
+        .collect()
+}
+
+/// `for` nodes listed in `Forwarded`, client first; `None` for hops without
+/// an IP address
+pub(crate) fn forwarded_for_chain(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    list_items(headers.get_all(header::FORWARDED).iter())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|element| parse_element(element).and_then(|element| element.for_ip()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode<H: Header>(value: &'static str) -> Result<H> {
+        H::decode([HeaderValue::from_static(value)].iter())
+    }
+
+    #[test]
+    fn test_x_forwarded_for() {
+        let header: XForwardedFor = decode("203.0.113.7, [2001:db8::1]:443, 10.0.0.1:8080").unwrap();
+        let expected: Vec<IpAddr> = vec!["203.0.113.7".parse().unwrap(), "2001:db8::1".parse().unwrap(), "10.0.0.1".parse().unwrap()];
+        assert_eq!(header.0, expected);
+        assert!(decode::<XForwardedFor>("203.0.113.7, unknown").is_err());
+    }
+
+    #[test]
+    fn test_forwarded() {
+        let header: Forwarded = decode("for=192.0.2.60;proto=http;by=203.0.113.43, For=\"[2001:db8:cafe::17]:4711\"").unwrap();
+        assert_eq!(header.0.len(), 2);
+        assert_eq!(header.0[0].proto.as_deref(), Some("http"));
+        assert_eq!(header.0[0].for_ip(), Some("192.0.2.60".parse().unwrap()));
+        assert_eq!(header.0[1].for_ip(), Some("2001:db8:cafe::17".parse().unwrap()));
+        assert_eq!(
+            header.encode(),
+            "for=192.0.2.60;by=203.0.113.43;proto=http, for=\"[2001:db8:cafe::17]:4711\""
+        );
+
+        let hidden: Forwarded = decode("for=_hidden").unwrap();
+        assert_eq!(hidden.0[0].for_ip(), None);
+        assert!(decode::<Forwarded>("for").is_err());
+    }
+}
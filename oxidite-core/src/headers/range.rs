@@ -0,0 +1,108 @@
+use super::{invalid, Header};
+use crate::error::Result;
+use http::header::{self, HeaderName, HeaderValue};
+use std::ops::RangeInclusive;
+
+/// `Range` in bytes, e.g. `bytes=0-499, -500`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Range(pub Vec<ByteRange>);
+
+/// One range of a `Range` header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRange {
+    /// `first-last`, both inclusive
+    FromTo(u64, u64),
+    /// `first-`: from `first` to the end
+    From(u64),
+    /// `-n`: the last `n` bytes
+    Last(u64),
+}
+
+impl Range {
+    /// Resolve against a representation of `len` bytes, dropping ranges that
+    /// do not overlap it; empty means the request is unsatisfiable (416)
+    pub fn satisfiable(&self, len: u64) -> Vec<RangeInclusive<u64>> {
+        self.0.iter()
+            .filter_map(|range| match *range {
+                ByteRange::FromTo(first, last) if first < len => Some(first..=last.min(len - 1)),
+                ByteRange::From(first) if first < len => Some(first..=len - 1),
+                ByteRange::Last(n) if n > 0 && len > 0 => Some(len - n.min(len)..=len - 1),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl Header for Range {
+    const NAME: HeaderName = header::RANGE;
+
+    fn decode<'a, I: Iterator<Item = &'a HeaderValue>>(mut values: I) -> Result<Self> {
+        let value = values.next().and_then(|value| value.to_str().ok()).ok_or_else(invalid::<Self>)?;
+        let (unit, ranges) = value.split_once('=').ok_or_else(invalid::<Self>)?;
+        if !unit.trim().eq_ignore_ascii_case("bytes") {
+            return Err(invalid::<Self>());
+        }
+        ranges.split(',')
+            .map(str::trim)
+            .filter(|range| !range.is_empty())
+            .map(parse_byte_range)
+            .collect::<Option<Vec<_>>>()
+            .filter(|ranges| !ranges.is_empty())
+            .map(Range)
+            .ok_or_else(invalid::<Self>)
+    }
+
+    fn encode(&self) -> HeaderValue {
+        let ranges: Vec<String> = self.0.iter()
+            .map(|range| match range {
+                ByteRange::FromTo(first, last) => format!("{}-{}", first, last),
+                ByteRange::From(first) => format!("{}-", first),
+                ByteRange::Last(n) => format!("-{}", n),
+            })
+            .collect();
+        HeaderValue::from_str(&format!("bytes={}", ranges.join(", "))).expect("byte ranges are valid header values")
+    }
+}
+
+fn parse_byte_range(range: &str) -> Option<ByteRange> {
+    let (first, last) = range.split_once('-')?;
+    let number = |s: &str| s.trim().parse::<u64>().ok();
+    match (first.trim().is_empty(), last.trim().is_empty()) {
+        (true, false) => number(last).map(ByteRange::Last),
+        (false, true) => number(first).map(ByteRange::From),
+        (false, false) => {
+            let (first, last) = (number(first)?, number(last)?);
+            (first <= last).then_some(ByteRange::FromTo(first, last))
+        }
+        (true, true) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(value: &'static str) -> Result<Range> {
+        Range::decode([HeaderValue::from_static(value)].iter())
+    }
+
+    #[test]
+    fn test_parse() {
+        let range = decode("bytes=0-499, 500-, -200").unwrap();
+        assert_eq!(range.0, [ByteRange::FromTo(0, 499), ByteRange::From(500), ByteRange::Last(200)]);
+        assert_eq!(range.encode(), "bytes=0-499, 500-, -200");
+
+        for value in ["items=0-1", "bytes=", "bytes=5-1", "bytes=-", "bytes=a-b"] {
+            assert!(decode(value).is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn test_satisfiable() {
+        let range = decode("bytes=0-99, 950-2000, -50, 1000-").unwrap();
+        assert_eq!(range.satisfiable(1000), [0..=99, 950..=999, 950..=999]);
+        assert_eq!(decode("bytes=-5000").unwrap().satisfiable(10), [0..=9]);
+        assert!(decode("bytes=10-").unwrap().satisfiable(10).is_empty());
+        assert!(decode("bytes=-1").unwrap().satisfiable(0).is_empty());
+    }
+}
@@ -1,21 +1,36 @@
+pub mod body_limit;
+pub mod client_ip;
 pub mod error;
 pub mod extract;
+pub mod headers;
+pub mod multipart;
+pub mod problem;
 pub mod request;
 pub mod response;
 pub mod router;
 pub mod server;
+pub mod state;
 pub mod tls;
 pub mod types;
 pub mod versioning;
 pub mod cookie;
 
+pub use body_limit::DefaultBodyLimit;
+pub use client_ip::{ClientIp, TrustedProxies};
+pub use cookie::{CookieJar, CookieKeys, PrivateCookieJar, SignedCookieJar};
 pub use error::{Error, Result};
-pub use extract::{FromRequest, Json, Path, Query, State, Form, Cookies, Body};
+pub use extract::{FromRequest, FromRequestParts, Json, Path, Query, State, Form, Cookies, Body, BodyStream, RemoteAddr};
+pub use headers::{HeaderMapExt, TypedHeader};
+pub use multipart::{Multipart, MultipartLimits};
 
 
-pub use router::{Handler, Router};
-pub use server::Server;
+pub use problem::{FieldErrors, ProblemDetails};
+pub use response::{Html, IntoResponse, IntoResponseHeaders, Redirect};
+pub use router::{Handler, RouteError, RouteRef, Router, UrlFor};
+pub use request::RequestExt;
+pub use server::{Http2Settings, HttpVersion, Server, ServerLimits};
+pub use state::StateType;
 pub use types::{OxiditeRequest, OxiditeResponse};
 pub use types::OxiditeResponse as Response;
 pub use types::OxiditeRequest as Request;
-pub use versioning::{ApiVersion, VersionedRouter};
+pub use versioning::{ApiVersion, Deprecation, VersionedRouter};
@@ -0,0 +1,308 @@
+//! Streaming `multipart/form-data` extraction.
+
+use crate::error::{Error, Result};
+use crate::extract::{BodyLimit, FromRequest};
+use crate::types::OxiditeRequest;
+use bytes::{Bytes, BytesMut};
+use futures_util::{Stream, StreamExt};
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Size limits for a [`Multipart`] body; `None` disables a limit
+#[derive(Debug, Clone, Default)]
+pub struct MultipartLimits {
+    /// Maximum size of a single part's content
+    pub max_field_size: Option<u64>,
+    /// Maximum size of the whole body, boundaries and part headers included
+    pub max_total_size: Option<u64>,
+}
+
+/// Extract a `multipart/form-data` body one part at a time
+///
+/// Parts are read straight off the connection, so uploads never have to be
+/// held in memory. The total size defaults to the server's `max_body_size`;
+/// use [`with_limits`](Self::with_limits) to raise it for upload routes.
+///
+/// # Example
+/// ```ignore
+/// async fn upload(multipart: Multipart) -> Result<Response> {
+///     let mut multipart = multipart.with_limits(MultipartLimits {
+///         max_field_size: Some(10 * 1024 * 1024),
+///         max_total_size: Some(50 * 1024 * 1024),
+///     });
+///     while let Some(mut field) = multipart.next_field().await? {
+///         println!("{:?} ({:?})", field.file_name(), field.content_type());
+///         while let Some(chunk) = field.chunk().await? {
+///             // write chunk somewhere
+///         }
+///     }
+///     Ok(Response::text("Uploaded"))
+/// }
+/// ```
+pub struct Multipart {
+    inner: multer::Multipart<'static>,
+    max_field_size: Option<u64>,
+    /// Shared with the body stream so the limit can change after extraction
+    max_total_size: Arc<AtomicU64>,
+}
+
+impl Multipart {
+    /// Replace the size limits, including the default total limit
+    pub fn with_limits(mut self, limits: MultipartLimits) -> Self {
+        self.max_field_size = limits.max_field_size;
+        self.max_total_size
+            .store(limits.max_total_size.unwrap_or(u64::MAX), Ordering::Relaxed);
+        self
+    }
+
+    /// Advance to the next part, skipping whatever is left of the current one
+    pub async fn next_field(&mut self) -> Result<Option<Field<'_>>> {
+        let field = self.inner.next_field().await.map_err(into_error)?;
+        Ok(field.map(|inner| Field {
+            inner,
+            max_size: self.max_field_size,
+            received: 0,
+            _multipart: PhantomData,
+        }))
+    }
+}
+
+impl FromRequest for Multipart {
+    async fn from_request(req: OxiditeRequest) -> Result<Self> {
+        let boundary = req.headers()
+            .get(http::header::CONTENT_TYPE)
+            .and_then(|ct| ct.to_str().ok())
+            .and_then(|ct| multer::parse_boundary(ct).ok())
+            .ok_or_else(|| Error::BadRequest("Expected multipart/form-data content type".to_string()))?;
+
+        let limit = BodyLimit::of(req.extensions()).map_or(u64::MAX, |limit| limit as u64);
+        let max_total_size = Arc::new(AtomicU64::new(limit));
+        let budget = max_total_size.clone();
+        let mut received = 0u64;
+        let body = http_body_util::BodyDataStream::new(req.into_body()).map(move |chunk| {
+            let chunk = chunk.map_err(BoxError::from)?;
+            received += chunk.len() as u64;
+            let limit = budget.load(Ordering::Relaxed);
+            if received > limit {
+                return Err(Box::new(TotalSizeExceeded(limit)) as BoxError);
+            }
+            Ok(chunk)
+        });
+
+        Ok(Multipart {
+            inner: multer::Multipart::new(body, boundary),
+            max_field_size: None,
+            max_total_size,
+        })
+    }
+}
+
+/// A single part of a [`Multipart`] body
+///
+/// Read it with [`chunk`](Self::chunk), [`bytes`](Self::bytes) or
+/// [`text`](Self::text), or use it as a `Stream` of chunks.
+pub struct Field<'a> {
+    inner: multer::Field<'static>,
+    max_size: Option<u64>,
+    received: u64,
+    _multipart: PhantomData<&'a mut Multipart>,
+}
+
+impl Field<'_> {
+    /// Form field name from `Content-Disposition`
+    pub fn name(&self) -> Option<&str> {
+        self.inner.name()
+    }
+
+    /// Client-supplied file name; treat it as untrusted input
+    pub fn file_name(&self) -> Option<&str> {
+        self.inner.file_name()
+    }
+
+    /// Content type declared for this part
+    pub fn content_type(&self) -> Option<&str> {
+        self.inner.content_type().map(|mime| mime.as_ref())
+    }
+
+    /// All headers of this part
+    pub fn headers(&self) -> &http::HeaderMap {
+        self.inner.headers()
+    }
+
+    /// Read the next chunk of the part, or `None` once it is exhausted
+    pub async fn chunk(&mut self) -> Result<Option<Bytes>> {
+        self.next().await.transpose()
+    }
+
+    /// Buffer the rest of the part
+    pub async fn bytes(mut self) -> Result<Bytes> {
+        let mut buf = BytesMut::new();
+        while let Some(chunk) = self.chunk().await? {
+            buf.extend_from_slice(&chunk);
+        }
+        Ok(buf.freeze())
+    }
+
+    /// Buffer the rest of the part as UTF-8 text
+    pub async fn text(self) -> Result<String> {
+        let bytes = self.bytes().await?;
+        String::from_utf8(bytes.to_vec())
+            .map_err(|e| Error::BadRequest(format!("Invalid UTF-8 in multipart field: {}", e)))
+    }
+}
+
+impl Stream for Field<'_> {
+    type Item = Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let chunk = match ready!(Pin::new(&mut self.inner).poll_next(cx)) {
+            Some(Ok(chunk)) => chunk,
+            Some(Err(err)) => return Poll::Ready(Some(Err(into_error(err)))),
+            None => return Poll::Ready(None),
+        };
+        self.received += chunk.len() as u64;
+        if let Some(max) = self.max_size.filter(|&max| self.received > max) {
+            return Poll::Ready(Some(Err(Error::PayloadTooLarge(format!(
+                "Multipart field '{}' exceeds {} bytes",
+                self.name().unwrap_or_default(),
+                max
+            )))));
+        }
+        Poll::Ready(Some(Ok(chunk)))
+    }
+}
+
+/// Keeps the `Multipart` borrowed until the field is dropped, since the parser
+/// cannot move on to the next part while this one is alive
+impl Drop for Field<'_> {
+    fn drop(&mut self) {}
+}
+
+/// Raised by the body stream once the total limit is crossed
+#[derive(Debug)]
+struct TotalSizeExceeded(u64);
+
+impl std::fmt::Display for TotalSizeExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Multipart body exceeds {} bytes", self.0)
+    }
+}
+
+impl std::error::Error for TotalSizeExceeded {}
+
+fn into_error(err: multer::Error) -> Error {
+    if let multer::Error::StreamReadFailed(source) = &err {
+        if let Some(exceeded) = source.downcast_ref::<TotalSizeExceeded>() {
+            return Error::PayloadTooLarge(exceeded.to_string());
+        }
+    }
+    Error::BadRequest(format!("Invalid multipart data: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_body_util::{BodyExt, StreamBody};
+
+    const BODY: &str = "--XYZ\r\n\
+        Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+        Holiday\r\n\
+        --XYZ\r\n\
+        Content-Disposition: form-data; name=\"photo\"; filename=\"beach.png\"\r\n\
+        Content-Type: image/png\r\n\r\n\
+        0123456789abcdef\r\n\
+        --XYZ--\r\n";
+
+    /// Build a multipart request whose body arrives in small chunks
+    fn request(body: &'static str, limit: Option<usize>) -> OxiditeRequest {
+        let chunks = body
+            .as_bytes()
+            .chunks(7)
+            .map(|chunk| Ok(hyper::body::Frame::data(Bytes::from_static(chunk))));
+        let body = BodyExt::boxed(StreamBody::new(futures_util::stream::iter(chunks)));
+        let mut req = http::Request::builder()
+            .header("content-type", "multipart/form-data; boundary=XYZ")
+            .body(body)
+            .unwrap();
+        if let Some(limit) = limit {
+            req.extensions_mut().insert(BodyLimit(Some(limit)));
+        }
+        req
+    }
+
+    #[tokio::test]
+    async fn test_fields_stream_in_order() {
+        let mut multipart = Multipart::from_request(request(BODY, None)).await.unwrap();
+
+        let field = multipart.next_field().await.unwrap().unwrap();
+        assert_eq!(field.name(), Some("title"));
+        assert_eq!(field.file_name(), None);
+        assert_eq!(field.text().await.unwrap(), "Holiday");
+
+        let mut field = multipart.next_field().await.unwrap().unwrap();
+        assert_eq!(field.file_name(), Some("beach.png"));
+        assert_eq!(field.content_type(), Some("image/png"));
+        let mut content = Vec::new();
+        while let Some(chunk) = field.chunk().await.unwrap() {
+            content.extend_from_slice(&chunk);
+        }
+        assert_eq!(content, b"0123456789abcdef");
+        drop(field);
+
+        assert!(multipart.next_field().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_field_limit() {
+        let multipart = Multipart::from_request(request(BODY, None)).await.unwrap();
+        let mut multipart = multipart.with_limits(MultipartLimits {
+            max_field_size: Some(8),
+            max_total_size: None,
+        });
+
+        let title = multipart.next_field().await.unwrap().unwrap();
+        assert!(title.bytes().await.is_ok());
+        let photo = multipart.next_field().await.unwrap().unwrap();
+        let err = photo.bytes().await.unwrap_err();
+        assert!(matches!(err, Error::PayloadTooLarge(_)), "{err:?}");
+    }
+
+    #[tokio::test]
+    async fn test_total_limit_defaults_to_body_limit() {
+        let mut multipart = Multipart::from_request(request(BODY, Some(64))).await.unwrap();
+        let mut result = Ok(());
+        while result.is_ok() {
+            result = match multipart.next_field().await {
+                Ok(Some(field)) => field.bytes().await.map(drop),
+                Ok(None) => break,
+                Err(err) => Err(err),
+            };
+        }
+        assert!(matches!(result, Err(Error::PayloadTooLarge(_))), "{result:?}");
+
+        // Raising the limit after extraction lets the same body through
+        let multipart = Multipart::from_request(request(BODY, Some(64))).await.unwrap();
+        let mut multipart = multipart.with_limits(MultipartLimits {
+            max_field_size: None,
+            max_total_size: Some(1024),
+        });
+        while let Some(field) = multipart.next_field().await.unwrap() {
+            field.bytes().await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_requires_multipart_content_type() {
+        let req = http::Request::builder()
+            .header("content-type", "application/json")
+            .body(crate::types::OxiditeBody::default())
+            .unwrap();
+        let err = Multipart::from_request(req).await.err().unwrap();
+        assert!(matches!(err, Error::BadRequest(_)));
+    }
+}
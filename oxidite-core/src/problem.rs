@@ -0,0 +1,440 @@
+//! RFC 7807 problem details for error responses.
+
+use crate::error::{Error, Result};
+use crate::response::{IntoResponse, SERVER_HEADER_VALUE};
+use crate::types::OxiditeResponse;
+use bytes::Bytes;
+use http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+use http_body_util::{BodyExt, Full};
+use hyper::header::{CONTENT_TYPE, SERVER};
+use hyper::Response;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Request extension marking requests served with 5xx details exposed
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ExposeDetails;
+
+/// Render an error that escaped the handlers, as the server does
+///
+/// Server errors are logged; the problem gets the request path as its
+/// `instance` and is negotiated against the request's `Accept` header.
+pub(crate) fn render_error(
+    error: &Error,
+    expose_server_details: bool,
+    instance: Option<String>,
+    accept: Option<&HeaderValue>,
+) -> OxiditeResponse {
+    if error.is_server_error() {
+        eprintln!("Server error: {}", error);
+    }
+    let mut problem = ProblemDetails::from_error_with(error, expose_server_details);
+    if let Some(instance) = instance {
+        problem.instance.get_or_insert(instance);
+    }
+    problem.negotiate(accept)
+}
+
+/// An `application/problem+json` body as described by RFC 7807
+///
+/// # Example
+/// ```ignore
+/// let problem = ProblemDetails::new(StatusCode::PAYMENT_REQUIRED)
+///     .with_type("https://example.com/problems/out-of-credit")
+///     .with_detail("Your balance is 30, but that costs 50")
+///     .with_extension("balance", 30);
+/// return Err(problem.into());
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProblemDetails {
+    /// URI reference identifying the problem type
+    #[serde(default = "about_blank")]
+    pub r#type: String,
+    /// Short summary of the problem type
+    pub title: String,
+    /// HTTP status code
+    pub status: u16,
+    /// Explanation specific to this occurrence
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// URI reference identifying this occurrence, usually the request path
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    /// Additional members such as `code` or `errors`
+    #[serde(flatten)]
+    pub extensions: Map<String, Value>,
+    /// Response headers sent with the problem, such as `Retry-After`
+    #[serde(skip)]
+    pub headers: HeaderMap,
+}
+
+fn about_blank() -> String {
+    "about:blank".to_string()
+}
+
+impl ProblemDetails {
+    /// Create a problem titled with the status' canonical reason
+    pub fn new(status: StatusCode) -> Self {
+        Self {
+            r#type: about_blank(),
+            title: status.canonical_reason().unwrap_or("Error").to_string(),
+            status: status.as_u16(),
+            detail: None,
+            instance: None,
+            extensions: Map::new(),
+            headers: HeaderMap::new(),
+        }
+    }
+
+    /// Build the problem for an error, hiding 5xx details
+    ///
+    /// Errors returned from handlers are rendered by the server instead, which
+    /// exposes 5xx details when its [`debug`](crate::server::Server::debug) flag is set.
+    pub fn from_error(error: &Error) -> Self {
+        Self::from_error_with(error, false)
+    }
+
+    /// Build the problem for an error, choosing whether 5xx details are exposed
+    pub fn from_error_with(error: &Error, expose_server_details: bool) -> Self {
+        if let Error::Problem(problem) = error {
+            return (**problem).clone();
+        }
+
+        let mut problem = Self::new(error.status_code())
+            .with_extension("code", error.code());
+        if error.is_client_error() || expose_server_details {
+            problem.detail = Some(error.detail());
+        }
+        if let Error::Validation(errors) = error {
+            if !errors.fields.is_empty() {
+                problem = problem.with_extension("errors", &errors.fields);
+            }
+        }
+        problem
+    }
+
+    pub fn with_type(mut self, uri: impl Into<String>) -> Self {
+        self.r#type = uri.into();
+        self
+    }
+
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    pub fn with_detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    pub fn with_instance(mut self, instance: impl Into<String>) -> Self {
+        self.instance = Some(instance.into());
+        self
+    }
+
+    /// Add a header to the response carrying this problem
+    pub fn with_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    /// Add an extension member; values that fail to serialize are skipped
+    pub fn with_extension(mut self, key: impl Into<String>, value: impl Serialize) -> Self {
+        if let Ok(value) = serde_json::to_value(value) {
+            self.extensions.insert(key.into(), value);
+        }
+        self
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+
+    /// Render as a minimal HTML error page
+    pub fn to_html(&self) -> String {
+        let heading = format!("{} {}", self.status, escape_html(&self.title));
+        let detail = self
+            .detail
+            .as_deref()
+            .map(|d| format!("<p>{}</p>", escape_html(d)))
+            .unwrap_or_default();
+        let errors = match self.extensions.get("errors").and_then(Value::as_object) {
+            Some(fields) => {
+                let items: String = fields
+                    .iter()
+                    .flat_map(|(field, messages)| {
+                        let messages = messages.as_array().cloned().unwrap_or_default();
+                        messages.into_iter().map(move |m| {
+                            format!(
+                                "<li><strong>{}</strong>: {}</li>",
+                                escape_html(field),
+                                escape_html(m.as_str().unwrap_or_default())
+                            )
+                        })
+                    })
+                    .collect();
+                format!("<ul>{}</ul>", items)
+            }
+            None => String::new(),
+        };
+        format!(
+            "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{heading}</title></head>\n<body>\n<h1>{heading}</h1>\n{detail}{errors}\n</body>\n</html>\n"
+        )
+    }
+
+    /// Render as HTML when `accept` prefers `text/html`, otherwise as problem+json
+    pub fn negotiate(self, accept: Option<&HeaderValue>) -> OxiditeResponse {
+        if accept.and_then(|v| v.to_str().ok()).is_some_and(prefers_html) {
+            let mut res = OxiditeResponse::html(self.to_html());
+            *res.status_mut() = self.status_code();
+            res.headers_mut().extend(self.headers);
+            res
+        } else {
+            self.into_response()
+        }
+    }
+}
+
+impl fmt::Display for ProblemDetails {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.detail {
+            Some(detail) => write!(f, "{}: {}", self.title, detail),
+            None => f.write_str(&self.title),
+        }
+    }
+}
+
+impl From<ProblemDetails> for Error {
+    fn from(problem: ProblemDetails) -> Self {
+        Error::Problem(Box::new(problem))
+    }
+}
+
+impl IntoResponse for ProblemDetails {
+    fn into_response(self) -> OxiditeResponse {
+        let body = serde_json::to_vec(&self).unwrap_or_else(|_| {
+            format!(r#"{{"title":"Internal Server Error","status":{}}}"#, self.status).into_bytes()
+        });
+        let mut res = Response::builder()
+            .status(self.status_code())
+            .header(CONTENT_TYPE, HeaderValue::from_static("application/problem+json"))
+            .header(SERVER, HeaderValue::from_static(SERVER_HEADER_VALUE))
+            .body(Full::new(Bytes::from(body)).map_err(|e| match e {}).boxed())
+            .unwrap();
+        res.headers_mut().extend(self.headers);
+        OxiditeResponse(res)
+    }
+}
+
+/// The payload of `Error::Validation`: an overall message and/or field-level
+/// messages, the latter rendered as the problem's `errors` member
+///
+/// # Example
+/// ```ignore
+/// let mut errors = FieldErrors::new();
+/// if input.name.is_empty() {
+///     errors.add("name", "is required");
+/// }
+/// errors.into_result()?;
+///
+/// return Err(Error::Validation("Invalid email format".into()));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FieldErrors {
+    message: Option<String>,
+    fields: BTreeMap<String, Vec<String>>,
+}
+
+impl FieldErrors {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a message for a field
+    pub fn add(&mut self, field: impl Into<String>, message: impl Into<String>) -> &mut Self {
+        self.fields.entry(field.into()).or_default().push(message.into());
+        self
+    }
+
+    /// Builder form of [`add`](Self::add)
+    pub fn with(mut self, field: impl Into<String>, message: impl Into<String>) -> Self {
+        self.add(field, message);
+        self
+    }
+
+    /// The overall message, if any
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+
+    pub fn get(&self, field: &str) -> Option<&[String]> {
+        self.fields.get(field).map(Vec::as_slice)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.message.is_none() && self.fields.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &[String])> {
+        self.fields.iter().map(|(k, v)| (k.as_str(), v.as_slice()))
+    }
+
+    /// `Ok(())` when empty, otherwise `Error::Validation`
+    pub fn into_result(self) -> Result<()> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Validation(self))
+        }
+    }
+}
+
+impl From<String> for FieldErrors {
+    fn from(message: String) -> Self {
+        Self {
+            message: Some(message),
+            fields: BTreeMap::new(),
+        }
+    }
+}
+
+impl From<&str> for FieldErrors {
+    fn from(message: &str) -> Self {
+        message.to_string().into()
+    }
+}
+
+impl fmt::Display for FieldErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut first = true;
+        if let Some(message) = &self.message {
+            f.write_str(message)?;
+            first = false;
+        }
+        for (field, messages) in &self.fields {
+            for message in messages {
+                if !first {
+                    f.write_str("; ")?;
+                }
+                write!(f, "{}: {}", field, message)?;
+                first = false;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether an `Accept` header ranks `text/html` above JSON
+fn prefers_html(accept: &str) -> bool {
+    let mut html = 0.0f32;
+    let mut json = 0.0f32;
+    for range in accept.split(',') {
+        let mut parts = range.split(';');
+        let media = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+        let q = parts
+            .filter_map(|p| p.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+        match media.as_str() {
+            "text/html" => html = html.max(q),
+            "application/json" | "application/problem+json" | "application/*" | "*/*" => json = json.max(q),
+            _ => {}
+        }
+    }
+    html > json
+}
+
+fn escape_html(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#x27;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_errors_are_redacted() {
+        let error = Error::InternalServerError("db password=hunter2".to_string());
+
+        let redacted = ProblemDetails::from_error_with(&error, false);
+        assert_eq!(redacted.status, 500);
+        assert_eq!(redacted.detail, None);
+        assert_eq!(redacted.extensions["code"], "internal_error");
+
+        let exposed = ProblemDetails::from_error_with(&error, true);
+        assert_eq!(exposed.detail.as_deref(), Some("db password=hunter2"));
+
+        let client = ProblemDetails::from_error_with(&Error::NotFound("no user 7".to_string()), false);
+        assert_eq!(client.detail.as_deref(), Some("no user 7"));
+    }
+
+    #[test]
+    fn test_field_errors_serialize_as_extension() {
+        let error = FieldErrors::new()
+            .with("email", "is invalid")
+            .with("name", "is required")
+            .into_result()
+            .unwrap_err();
+        let problem = ProblemDetails::from_error(&error).with_instance("/users");
+        let json = serde_json::to_value(&problem).unwrap();
+
+        assert_eq!(json["type"], "about:blank");
+        assert_eq!(json["status"], 422);
+        assert_eq!(json["instance"], "/users");
+        assert_eq!(json["code"], "validation_failed");
+        assert_eq!(json["errors"]["name"][0], "is required");
+        assert!(problem.to_html().contains("<strong>email</strong>: is invalid"));
+    }
+
+    #[test]
+    fn test_validation_message_has_no_errors_member() {
+        let error = Error::Validation("name cannot be empty".into());
+        assert_eq!(error.to_string(), "Validation failed: name cannot be empty");
+
+        let problem = ProblemDetails::from_error(&error);
+        assert_eq!(problem.status, 422);
+        assert_eq!(problem.detail.as_deref(), Some("name cannot be empty"));
+        assert!(!problem.extensions.contains_key("errors"));
+    }
+
+    #[test]
+    fn test_accept_negotiation() {
+        assert!(prefers_html("text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8"));
+        assert!(!prefers_html("*/*"));
+        assert!(!prefers_html("application/json, text/html;q=0.5"));
+        assert!(!prefers_html("text/html;q=0.5, application/problem+json"));
+
+        let html = HeaderValue::from_static("text/html");
+        let res = ProblemDetails::new(StatusCode::NOT_FOUND).negotiate(Some(&html));
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        assert_eq!(res.headers()[CONTENT_TYPE], "text/html");
+
+        let res = ProblemDetails::new(StatusCode::NOT_FOUND).negotiate(None);
+        assert_eq!(res.headers()[CONTENT_TYPE], "application/problem+json");
+    }
+
+    #[test]
+    fn test_custom_problem_round_trips_through_error() {
+        let error: Error = ProblemDetails::new(StatusCode::PAYMENT_REQUIRED)
+            .with_detail("balance too low")
+            .with_extension("code", "out_of_credit")
+            .into();
+        assert_eq!(error.status_code(), StatusCode::PAYMENT_REQUIRED);
+        assert_eq!(error.code(), "out_of_credit");
+        assert_eq!(ProblemDetails::from_error(&error).detail.as_deref(), Some("balance too low"));
+    }
+}
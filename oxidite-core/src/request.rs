@@ -1,6 +1,6 @@
 use crate::error::{Error, Result};
+use crate::extract::FromRequestParts;
 use crate::types::OxiditeRequest;
-use http_body_util::BodyExt;
 
 /// Extension trait for Request to provide helper methods
 pub trait RequestExt {
@@ -9,6 +9,10 @@ pub trait RequestExt {
     
     /// Read the entire body as Bytes
     fn body_bytes(&mut self) -> impl std::future::Future<Output = Result<bytes::Bytes>> + Send;
+
+    /// Run a head-only extractor without consuming the request,
+    /// e.g. `let State(state): State<Arc<AppState>> = req.extract_parts().await?;`
+    fn extract_parts<T: FromRequestParts>(&mut self) -> impl std::future::Future<Output = Result<T>> + Send;
 }
 
 impl RequestExt for OxiditeRequest {
@@ -19,9 +23,13 @@ impl RequestExt for OxiditeRequest {
     }
 
     async fn body_bytes(&mut self) -> Result<bytes::Bytes> {
-        let body = self.body_mut();
-        let collected = body.collect().await
-            .map_err(|e| Error::InternalServerError(format!("Failed to read body: {}", e)))?;
-        Ok(collected.to_bytes())
+        crate::extract::read_body(self).await
+    }
+
+    async fn extract_parts<T: FromRequestParts>(&mut self) -> Result<T> {
+        let (mut parts, body) = std::mem::take(self).into_parts();
+        let result = T::from_request_parts(&mut parts).await;
+        *self = OxiditeRequest::from_parts(parts, body);
+        result
     }
 }
@@ -1,12 +1,18 @@
-use crate::types::OxiditeResponse;
-use crate::error::Error;
+use crate::types::{BoxBody, BoxError, OxiditeResponse};
+use crate::error::{Error, Result};
+use crate::extract::Json;
+use crate::problem::ProblemDetails;
 use http_body_util::{Full, BodyExt};
 use bytes::Bytes;
+use futures_util::Stream;
 use hyper::Response;
-use hyper::header::{HeaderValue, CONTENT_TYPE, SERVER};
-use http::StatusCode;
+use hyper::body::Frame;
+use hyper::header::{HeaderValue, CONTENT_LENGTH, CONTENT_TYPE, LOCATION, SERVER};
+use http::{HeaderMap, StatusCode};
+use std::pin::Pin;
+use std::task::{Context, Poll};
 
-const SERVER_HEADER_VALUE: &str = concat!("Oxidite/", env!("CARGO_PKG_VERSION"));
+pub(crate) const SERVER_HEADER_VALUE: &str = concat!("Oxidite/", env!("CARGO_PKG_VERSION"));
 
 impl OxiditeResponse {
     /// Create a JSON response
@@ -71,24 +77,406 @@ impl OxiditeResponse {
             .unwrap();
         Self(res)
     }
+
+    /// Create a response whose body is sent chunk by chunk as `stream` yields
+    ///
+    /// The body is sent with chunked encoding; an error from the stream aborts
+    /// the response mid-body.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let chunks = futures_util::stream::iter(["a", "b"].map(|s| Ok::<_, Error>(Bytes::from(s))));
+    /// Response::stream(chunks)
+    /// ```
+    pub fn stream<S, E>(stream: S) -> Self
+    where
+        S: Stream<Item = std::result::Result<Bytes, E>> + Send + 'static,
+        E: Into<BoxError>,
+    {
+        let res = Response::builder()
+            .header(CONTENT_TYPE, HeaderValue::from_static("application/octet-stream"))
+            .header(SERVER, HeaderValue::from_static(SERVER_HEADER_VALUE))
+            .body(BodyExt::boxed(StreamingBody(std::sync::Mutex::new(Box::pin(stream)))))
+            .unwrap();
+        Self(res)
+    }
+
+    /// Stream a file from disk, with `Content-Type` guessed from its extension
+    ///
+    /// Missing files and directories are reported as `Error::NotFound`.
+    ///
+    /// # Example
+    /// ```ignore
+    /// async fn report() -> Result<Response> {
+    ///     Response::file("reports/latest.pdf").await
+    /// }
+    /// ```
+    pub async fn file(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let not_found = || Error::NotFound(format!("File not found: {}", path.display()));
+        let file = tokio::fs::File::open(path).await.map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => not_found(),
+            _ => Error::InternalServerError(format!("Failed to open {}: {}", path.display(), e)),
+        })?;
+        let metadata = file.metadata().await
+            .map_err(|e| Error::InternalServerError(format!("Failed to read {}: {}", path.display(), e)))?;
+        if !metadata.is_file() {
+            return Err(not_found());
+        }
+
+        let mime = mime_guess::from_path(path).first_or_octet_stream();
+        let mut res = Self::stream(tokio_util::io::ReaderStream::new(file));
+        let headers = res.headers_mut();
+        if let Ok(content_type) = HeaderValue::from_str(mime.as_ref()) {
+            headers.insert(CONTENT_TYPE, content_type);
+        }
+        headers.insert(CONTENT_LENGTH, HeaderValue::from(metadata.len()));
+        Ok(res)
+    }
+}
+
+/// Body backed by a stream of chunks
+///
+/// `BoxBody` must be `Sync`; the mutex is never contended (polling takes
+/// `&mut self`) and only lets streams that are not `Sync` be boxed.
+struct StreamingBody<S>(std::sync::Mutex<Pin<Box<S>>>);
+
+impl<S, E> hyper::body::Body for StreamingBody<S>
+where
+    S: Stream<Item = std::result::Result<Bytes, E>>,
+    E: Into<BoxError>,
+{
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<std::result::Result<Frame<Bytes>, BoxError>>> {
+        let stream = self
+            .get_mut()
+            .0
+            .get_mut()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        stream
+            .as_mut()
+            .poll_next(cx)
+            .map(|chunk| chunk.map(|chunk| chunk.map(Frame::data).map_err(Into::into)))
+    }
 }
 
+/// Renders the error as `application/problem+json`; the server picks HTML
+/// instead when the request's `Accept` header prefers it
 impl From<Error> for OxiditeResponse {
     fn from(error: Error) -> Self {
-        let status = error.status_code();
-        let body = serde_json::json!({
-            "error": error.to_string()
-        });
+        ProblemDetails::from_error(&error).into_response()
+    }
+}
 
-        let fallback = format!(r#"{{"error":"{}"}}"#, status);
-        let json_bytes = serde_json::to_vec(&body).unwrap_or_else(|_| fallback.into_bytes());
+/// Conversion of handler return values into responses
+///
+/// Handlers may return any type implementing this trait, e.g. `Json<T>`,
+/// `String`, `(StatusCode, T)` or `Result<T, Error>`.
+///
+/// # Example
+/// ```ignore
+/// async fn create(Json(input): Json<NewUser>) -> Result<(StatusCode, Json<User>)> {
+///     let user = User::create(input).await?;
+///     Ok((StatusCode::CREATED, Json(user)))
+/// }
+/// ```
+pub trait IntoResponse {
+    fn into_response(self) -> OxiditeResponse;
 
-        let res = Response::builder()
-            .status(status)
-            .header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
-            .header(SERVER, HeaderValue::from_static(SERVER_HEADER_VALUE))
-            .body(Full::new(Bytes::from(json_bytes)).map_err(|e| match e {}).boxed())
-            .unwrap();
-        Self(res)
+    /// Convert into the router's `Result`, keeping `Error` values as `Err` so
+    /// middleware can still observe them
+    #[doc(hidden)]
+    fn into_result(self) -> Result<OxiditeResponse>
+    where
+        Self: Sized,
+    {
+        Ok(self.into_response())
+    }
+}
+
+/// HTML response body
+///
+/// # Example
+/// ```ignore
+/// async fn home() -> Html<&'static str> {
+///     Html("<h1>Welcome</h1>")
+/// }
+/// ```
+pub struct Html<T>(pub T);
+
+/// Redirect response with a `Location` header
+///
+/// # Example
+/// ```ignore
+/// async fn legacy() -> Redirect {
+///     Redirect::permanent("/new-home")
+/// }
+/// ```
+pub struct Redirect {
+    status: StatusCode,
+    location: String,
+}
+
+impl Redirect {
+    /// 303 See Other, the usual answer to a successful form submission
+    pub fn to(location: impl Into<String>) -> Self {
+        Self { status: StatusCode::SEE_OTHER, location: location.into() }
+    }
+
+    /// 307 Temporary Redirect, preserving the request method and body
+    pub fn temporary(location: impl Into<String>) -> Self {
+        Self { status: StatusCode::TEMPORARY_REDIRECT, location: location.into() }
+    }
+
+    /// 308 Permanent Redirect, preserving the request method and body
+    pub fn permanent(location: impl Into<String>) -> Self {
+        Self { status: StatusCode::PERMANENT_REDIRECT, location: location.into() }
+    }
+}
+
+fn with_body(content_type: &'static str, body: Bytes) -> OxiditeResponse {
+    let res = Response::builder()
+        .header(CONTENT_TYPE, HeaderValue::from_static(content_type))
+        .header(SERVER, HeaderValue::from_static(SERVER_HEADER_VALUE))
+        .body(Full::new(body).map_err(|e| match e {}).boxed())
+        .unwrap();
+    OxiditeResponse(res)
+}
+
+impl IntoResponse for OxiditeResponse {
+    fn into_response(self) -> OxiditeResponse {
+        self
+    }
+}
+
+impl IntoResponse for Response<BoxBody> {
+    fn into_response(self) -> OxiditeResponse {
+        OxiditeResponse(self)
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> OxiditeResponse {
+        OxiditeResponse::from(self)
+    }
+
+    fn into_result(self) -> Result<OxiditeResponse> {
+        Err(self)
+    }
+}
+
+impl<T, E> IntoResponse for std::result::Result<T, E>
+where
+    T: IntoResponse,
+    E: IntoResponse,
+{
+    fn into_response(self) -> OxiditeResponse {
+        match self {
+            Ok(value) => value.into_response(),
+            Err(err) => err.into_response(),
+        }
+    }
+
+    fn into_result(self) -> Result<OxiditeResponse> {
+        match self {
+            Ok(value) => value.into_result(),
+            Err(err) => err.into_result(),
+        }
+    }
+}
+
+/// `None` becomes a 404 Not Found
+impl<T: IntoResponse> IntoResponse for Option<T> {
+    fn into_response(self) -> OxiditeResponse {
+        self.into_result().unwrap_or_else(OxiditeResponse::from)
+    }
+
+    fn into_result(self) -> Result<OxiditeResponse> {
+        match self {
+            Some(value) => value.into_result(),
+            None => Err(Error::NotFound("Resource not found".to_string())),
+        }
+    }
+}
+
+impl IntoResponse for () {
+    fn into_response(self) -> OxiditeResponse {
+        OxiditeResponse::ok()
+    }
+}
+
+impl IntoResponse for StatusCode {
+    fn into_response(self) -> OxiditeResponse {
+        let mut res = OxiditeResponse::ok();
+        *res.status_mut() = self;
+        res
+    }
+}
+
+impl IntoResponse for String {
+    fn into_response(self) -> OxiditeResponse {
+        OxiditeResponse::text(self)
+    }
+}
+
+impl IntoResponse for &'static str {
+    fn into_response(self) -> OxiditeResponse {
+        OxiditeResponse::text(self)
+    }
+}
+
+impl IntoResponse for Bytes {
+    fn into_response(self) -> OxiditeResponse {
+        with_body("application/octet-stream", self)
+    }
+}
+
+impl IntoResponse for Vec<u8> {
+    fn into_response(self) -> OxiditeResponse {
+        Bytes::from(self).into_response()
+    }
+}
+
+impl<T: Into<String>> IntoResponse for Html<T> {
+    fn into_response(self) -> OxiditeResponse {
+        OxiditeResponse::html(self.0)
+    }
+}
+
+impl<T: serde::Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> OxiditeResponse {
+        OxiditeResponse::json(self.0)
+    }
+}
+
+impl IntoResponse for Redirect {
+    fn into_response(self) -> OxiditeResponse {
+        match HeaderValue::try_from(self.location) {
+            Ok(location) => {
+                let mut res = self.status.into_response();
+                res.headers_mut().insert(LOCATION, location);
+                res
+            }
+            Err(e) => Error::InternalServerError(format!("Invalid redirect location: {}", e)).into_response(),
+        }
+    }
+}
+
+impl<T: IntoResponse> IntoResponse for (StatusCode, T) {
+    fn into_response(self) -> OxiditeResponse {
+        let (status, value) = self;
+        let mut res = value.into_response();
+        *res.status_mut() = status;
+        res
+    }
+}
+
+/// Headers that can be returned next to a response, as in `(headers, body)`
+/// or `(status, headers, body)`
+///
+/// Implemented for `HeaderMap` (replacing same-named headers) and for the
+/// cookie jars (appending `Set-Cookie`).
+pub trait IntoResponseHeaders {
+    fn apply(self, headers: &mut HeaderMap);
+}
+
+impl IntoResponseHeaders for HeaderMap {
+    fn apply(self, headers: &mut HeaderMap) {
+        headers.extend(self);
+    }
+}
+
+impl<H: IntoResponseHeaders, T: IntoResponse> IntoResponse for (H, T) {
+    fn into_response(self) -> OxiditeResponse {
+        let (headers, value) = self;
+        let mut res = value.into_response();
+        headers.apply(res.headers_mut());
+        res
+    }
+}
+
+impl<H: IntoResponseHeaders, T: IntoResponse> IntoResponse for (StatusCode, H, T) {
+    fn into_response(self) -> OxiditeResponse {
+        let (status, headers, value) = self;
+        (status, (headers, value)).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn body_text(res: OxiditeResponse) -> String {
+        let bytes = res.into_inner().into_body().collect().await.expect("body").to_bytes();
+        String::from_utf8(bytes.to_vec()).expect("utf8")
+    }
+
+    #[tokio::test]
+    async fn test_tuple_responses_set_status_and_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-id", HeaderValue::from_static("7"));
+        let res = (StatusCode::CREATED, headers, Json(serde_json::json!({ "id": 7 }))).into_response();
+
+        assert_eq!(res.status(), StatusCode::CREATED);
+        assert_eq!(res.headers()["x-id"], "7");
+        assert_eq!(res.headers()[CONTENT_TYPE], "application/json");
+        assert_eq!(body_text(res).await, r#"{"id":7}"#);
+    }
+
+    #[tokio::test]
+    async fn test_stream_response_sends_chunks_and_aborts_on_error() {
+        let chunks = futures_util::stream::iter(vec![
+            Ok::<_, Error>(Bytes::from_static(b"hello ")),
+            Ok(Bytes::from_static(b"world")),
+        ]);
+        assert_eq!(body_text(OxiditeResponse::stream(chunks)).await, "hello world");
+
+        let failing = futures_util::stream::iter(vec![
+            Ok(Bytes::from_static(b"partial")),
+            Err(Error::InternalServerError("disk gone".to_string())),
+        ]);
+        let body = OxiditeResponse::stream(failing).into_inner().into_body();
+        assert!(body.collect().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_file_response_streams_from_disk() {
+        let dir = std::env::temp_dir().join(format!("oxidite-file-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("hello.txt");
+        std::fs::write(&path, "from disk").unwrap();
+
+        let res = OxiditeResponse::file(&path).await.unwrap();
+        assert_eq!(res.headers()[CONTENT_TYPE], "text/plain");
+        assert_eq!(res.headers()[CONTENT_LENGTH], "9");
+        assert_eq!(body_text(res).await, "from disk");
+
+        assert!(matches!(OxiditeResponse::file(dir.join("missing.txt")).await, Err(Error::NotFound(_))));
+        assert!(matches!(OxiditeResponse::file(&dir).await, Err(Error::NotFound(_))));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_redirect_sets_location() {
+        let res = Redirect::permanent("/new").into_response();
+        assert_eq!(res.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(res.headers()[LOCATION], "/new");
+    }
+
+    #[test]
+    fn test_errors_stay_errors_in_results() {
+        let ok: Result<&'static str> = Ok("fine");
+        assert!(ok.into_result().is_ok());
+
+        let err: Result<&'static str> = Err(Error::Forbidden("nope".to_string()));
+        assert!(matches!(err.into_result(), Err(Error::Forbidden(_))));
+
+        assert!(matches!(None::<String>.into_result(), Err(Error::NotFound(_))));
+        assert_eq!(None::<String>.into_response().status(), StatusCode::NOT_FOUND);
     }
 }
@@ -1,14 +1,25 @@
 use crate::error::{Error, Result};
+use crate::cookie::CookieKeys;
+use crate::state::{StateMap, StateType};
 use crate::types::{OxiditeRequest, OxiditeResponse};
-use crate::extract::FromRequest;
+use crate::extract::{FromRequest, FromRequestParts};
+use crate::response::IntoResponse;
 use hyper::Method;
+use percent_encoding::percent_decode_str;
 use std::collections::HashMap;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
+use tower::{Layer, ServiceExt};
 use tower_service::Service;
-use regex::Regex;
+
+mod tree;
+mod url_for;
+
+pub use tree::RouteError;
+pub use url_for::UrlFor;
+use tree::{parse_pattern, PathTree, Segment};
 
 /// Trait for type-erased handlers stored in the router
 pub trait Endpoint: Send + Sync + 'static {
@@ -16,247 +27,597 @@ pub trait Endpoint: Send + Sync + 'static {
 }
 
 /// Trait for async functions that can be used as handlers
+///
+/// Implemented for async functions taking up to 12 extractors and returning
+/// any [`IntoResponse`] type. Every argument except the last must implement
+/// [`FromRequestParts`]; the last may be any [`FromRequest`], including body
+/// extractors such as `Json` or `Form`.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not a valid request handler",
+    note = "handlers take up to 12 extractors; only the last one may consume the body (`Json`, `Form`, `Body`, `Request`), the others must implement `FromRequestParts`"
+)]
 pub trait Handler<Args>: Clone + Send + Sync + 'static {
     fn call(&self, req: OxiditeRequest) -> Pin<Box<dyn Future<Output = Result<OxiditeResponse>> + Send>>;
+
+    /// State required by the handler's extractors; see [`Router::check_state`]
+    fn required_state() -> Vec<StateType> {
+        Vec::new()
+    }
 }
 
 // Wrapper to convert Handler<Args> into Endpoint
 struct HandlerService<H, Args> {
     handler: H,
-    _marker: std::marker::PhantomData<Args>,
+    _marker: std::marker::PhantomData<fn() -> Args>,
 }
 
 impl<H, Args> Endpoint for HandlerService<H, Args>
 where
     H: Handler<Args>,
-    Args: Send + Sync + 'static,
+    Args: 'static,
 {
     fn call(&self, req: OxiditeRequest) -> Pin<Box<dyn Future<Output = Result<OxiditeResponse>> + Send>> {
         self.handler.call(req)
     }
 }
 
-// Implement Handler for Fn(OxiditeRequest) -> Fut
-impl<F, Fut> Handler<OxiditeRequest> for F
-where
-    F: Fn(OxiditeRequest) -> Fut + Clone + Send + Sync + 'static,
-    Fut: Future<Output = Result<OxiditeResponse>> + Send + 'static,
-{
-    fn call(&self, req: OxiditeRequest) -> Pin<Box<dyn Future<Output = Result<OxiditeResponse>> + Send>> {
-        let fut = self(req);
-        Box::pin(async move { fut.await })
-    }
-}
-
 // Implement Handler for Fn() -> Fut
 impl<F, Fut> Handler<()> for F
 where
     F: Fn() -> Fut + Clone + Send + Sync + 'static,
-    Fut: Future<Output = Result<OxiditeResponse>> + Send + 'static,
+    Fut: Future + Send + 'static,
+    Fut::Output: IntoResponse,
 {
     fn call(&self, _req: OxiditeRequest) -> Pin<Box<dyn Future<Output = Result<OxiditeResponse>> + Send>> {
         let fut = self();
-        Box::pin(async move { fut.await })
+        Box::pin(async move { fut.await.into_result() })
     }
 }
 
-// Implement Handler for Fn(T1) -> Fut
-impl<F, Fut, T1> Handler<(T1,)> for F
-where
-    F: Fn(T1) -> Fut + Clone + Send + Sync + 'static,
-    Fut: Future<Output = Result<OxiditeResponse>> + Send + 'static,
-    T1: FromRequest + Send + 'static,
-{
-    fn call(&self, mut req: OxiditeRequest) -> Pin<Box<dyn Future<Output = Result<OxiditeResponse>> + Send>> {
-        let handler = self.clone();
-        Box::pin(async move {
-            let t1 = T1::from_request(&mut req).await?;
-            handler(t1).await
-        })
-    }
+// Implement Handler for Fn(T1, ..., Tn) -> Fut, where every argument but the
+// last is extracted from the request head and the last may consume the body.
+macro_rules! impl_handler {
+    ([$($ty:ident),*], $last:ident) => {
+        #[allow(non_snake_case)]
+        impl<F, Fut, M, $($ty,)* $last> Handler<(M, $($ty,)* $last,)> for F
+        where
+            F: Fn($($ty,)* $last) -> Fut + Clone + Send + Sync + 'static,
+            Fut: Future + Send + 'static,
+            Fut::Output: IntoResponse,
+            M: 'static,
+            $( $ty: FromRequestParts + Send + 'static, )*
+            $last: FromRequest<M> + Send + 'static,
+        {
+            fn call(&self, req: OxiditeRequest) -> Pin<Box<dyn Future<Output = Result<OxiditeResponse>> + Send>> {
+                let handler = self.clone();
+                Box::pin(async move {
+                    #[allow(unused_mut)]
+                    let (mut parts, body) = req.into_parts();
+                    $( let $ty = $ty::from_request_parts(&mut parts).await?; )*
+                    let req = OxiditeRequest::from_parts(parts, body);
+                    let $last = $last::from_request(req).await?;
+                    handler($($ty,)* $last).await.into_result()
+                })
+            }
+
+            fn required_state() -> Vec<StateType> {
+                let mut state = Vec::new();
+                $( state.extend(<$ty as FromRequestParts>::required_state()); )*
+                state.extend(<$last as FromRequest<M>>::required_state());
+                state
+            }
+        }
+    };
 }
 
-// Implement Handler for Fn(T1, T2) -> Fut
-impl<F, Fut, T1, T2> Handler<(T1, T2)> for F
-where
-    F: Fn(T1, T2) -> Fut + Clone + Send + Sync + 'static,
-    Fut: Future<Output = Result<OxiditeResponse>> + Send + 'static,
-    T1: FromRequest + Send + 'static,
-    T2: FromRequest + Send + 'static,
-{
-    fn call(&self, mut req: OxiditeRequest) -> Pin<Box<dyn Future<Output = Result<OxiditeResponse>> + Send>> {
-        let handler = self.clone();
-        Box::pin(async move {
-            let t1 = T1::from_request(&mut req).await?;
-            let t2 = T2::from_request(&mut req).await?;
-            handler(t1, t2).await
-        })
+impl_handler!([], T1);
+impl_handler!([T1], T2);
+impl_handler!([T1, T2], T3);
+impl_handler!([T1, T2, T3], T4);
+impl_handler!([T1, T2, T3, T4], T5);
+impl_handler!([T1, T2, T3, T4, T5], T6);
+impl_handler!([T1, T2, T3, T4, T5, T6], T7);
+impl_handler!([T1, T2, T3, T4, T5, T6, T7], T8);
+impl_handler!([T1, T2, T3, T4, T5, T6, T7, T8], T9);
+impl_handler!([T1, T2, T3, T4, T5, T6, T7, T8, T9], T10);
+impl_handler!([T1, T2, T3, T4, T5, T6, T7, T8, T9, T10], T11);
+impl_handler!([T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11], T12);
+
+/// Tower service wrapping a single route's handler, passed to layers given
+/// to [`Router::route_layer`]
+#[derive(Clone)]
+pub struct RouteService {
+    endpoint: Arc<dyn Endpoint>,
+}
+
+impl Service<OxiditeRequest> for RouteService {
+    type Response = OxiditeResponse;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<std::result::Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
     }
+
+    fn call(&mut self, req: OxiditeRequest) -> Self::Future {
+        self.endpoint.call(req)
+    }
+}
+
+// Endpoint backed by a layered RouteService
+struct LayeredEndpoint<S> {
+    service: S,
 }
 
-// Implement Handler for Fn(T1, T2, T3) -> Fut
-impl<F, Fut, T1, T2, T3> Handler<(T1, T2, T3)> for F
+impl<S> Endpoint for LayeredEndpoint<S>
 where
-    F: Fn(T1, T2, T3) -> Fut + Clone + Send + Sync + 'static,
-    Fut: Future<Output = Result<OxiditeResponse>> + Send + 'static,
-    T1: FromRequest + Send + 'static,
-    T2: FromRequest + Send + 'static,
-    T3: FromRequest + Send + 'static,
+    S: Service<OxiditeRequest, Response = OxiditeResponse, Error = Error> + Clone + Send + Sync + 'static,
+    S::Future: Send + 'static,
 {
-    fn call(&self, mut req: OxiditeRequest) -> Pin<Box<dyn Future<Output = Result<OxiditeResponse>> + Send>> {
-        let handler = self.clone();
-        Box::pin(async move {
-            let t1 = T1::from_request(&mut req).await?;
-            let t2 = T2::from_request(&mut req).await?;
-            let t3 = T3::from_request(&mut req).await?;
-            handler(t1, t2, t3).await
-        })
+    fn call(&self, req: OxiditeRequest) -> Pin<Box<dyn Future<Output = Result<OxiditeResponse>> + Send>> {
+        Box::pin(self.service.clone().oneshot(req))
     }
 }
 
 struct Route {
-    pattern: Regex,
-    param_names: Vec<String>,
+    param_names: Vec<Option<String>>,
     handler: Arc<dyn Endpoint>,
+    state: Arc<[StateType]>,
+    /// State scoped to this route, e.g. by nesting; wins over the router's
+    values: StateMap,
 }
 
 #[derive(Clone)]
 pub struct Router {
-    routes: HashMap<Method, Vec<Arc<Route>>>,
+    routes: Arc<HashMap<Method, PathTree<Arc<Route>>>>,
+    names: Arc<HashMap<String, String>>,
+    state: StateMap,
+}
+
+/// Handle to a freshly registered route, used to attach a name to it
+///
+/// # Example
+/// ```ignore
+/// router.get("/users/:id", show_user).name("users.show");
+/// ```
+pub struct RouteRef<'a> {
+    router: &'a mut Router,
+    path: String,
+}
+
+impl RouteRef<'_> {
+    /// Name the route so URLs can be generated for it with [`Router::url_for`]
+    ///
+    /// Panics if the name is already taken; see [`RouteRef::try_name`].
+    pub fn name(self, name: &str) -> Self {
+        match self.try_name(name) {
+            Ok(route) => route,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Name the route, returning an error if the name is already taken
+    pub fn try_name(self, name: &str) -> std::result::Result<Self, RouteError> {
+        self.router.insert_name(name, &self.path)?;
+        Ok(self)
+    }
 }
 
 impl Router {
     pub fn new() -> Self {
         Self {
-            routes: HashMap::new(),
+            routes: Arc::new(HashMap::new()),
+            names: Arc::new(HashMap::new()),
+            state: StateMap::default(),
         }
     }
 
-    pub fn get<H, Args>(&mut self, path: &str, handler: H)
+    pub fn get<H, Args>(&mut self, path: &str, handler: H) -> RouteRef<'_>
     where
         H: Handler<Args>,
-        Args: Send + Sync + 'static,
+        Args: 'static,
     {
-        self.add_route(Method::GET, path, handler);
+        self.add_route(Method::GET, path, handler)
     }
     
-    pub fn post<H, Args>(&mut self, path: &str, handler: H)
+    pub fn post<H, Args>(&mut self, path: &str, handler: H) -> RouteRef<'_>
     where
         H: Handler<Args>,
-        Args: Send + Sync + 'static,
+        Args: 'static,
     {
-        self.add_route(Method::POST, path, handler);
+        self.add_route(Method::POST, path, handler)
     }
 
-    pub fn put<H, Args>(&mut self, path: &str, handler: H)
+    pub fn put<H, Args>(&mut self, path: &str, handler: H) -> RouteRef<'_>
     where
         H: Handler<Args>,
-        Args: Send + Sync + 'static,
+        Args: 'static,
     {
-        self.add_route(Method::PUT, path, handler);
+        self.add_route(Method::PUT, path, handler)
     }
 
-    pub fn delete<H, Args>(&mut self, path: &str, handler: H)
+    pub fn delete<H, Args>(&mut self, path: &str, handler: H) -> RouteRef<'_>
     where
         H: Handler<Args>,
-        Args: Send + Sync + 'static,
+        Args: 'static,
     {
-        self.add_route(Method::DELETE, path, handler);
+        self.add_route(Method::DELETE, path, handler)
     }
 
-    pub fn patch<H, Args>(&mut self, path: &str, handler: H)
+    pub fn patch<H, Args>(&mut self, path: &str, handler: H) -> RouteRef<'_>
     where
         H: Handler<Args>,
-        Args: Send + Sync + 'static,
+        Args: 'static,
     {
-        self.add_route(Method::PATCH, path, handler);
+        self.add_route(Method::PATCH, path, handler)
     }
 
-    fn add_route<H, Args>(&mut self, method: Method, path: &str, handler: H)
+    /// Register a route, returning an error instead of panicking when the
+    /// pattern is invalid or conflicts with an existing route
+    ///
+    /// Patterns support `:name` parameters, constrained parameters such as
+    /// `:id<int>`, `:id<uuid>` or `:code<[A-Z]{3}>`, and a trailing catch-all
+    /// `*rest`. Static segments take precedence over parameters, which take
+    /// precedence over catch-alls, regardless of registration order.
+    pub fn try_route<H, Args>(&mut self, method: Method, path: &str, handler: H) -> std::result::Result<(), RouteError>
     where
         H: Handler<Args>,
-        Args: Send + Sync + 'static,
+        Args: 'static,
     {
-        let (pattern, param_names) = compile_path(path);
         let endpoint = HandlerService {
             handler,
             _marker: std::marker::PhantomData,
         };
-        
+        self.insert_endpoint(method, path, Arc::new(endpoint), H::required_state().into(), StateMap::default())
+    }
+
+    fn add_route<H, Args>(&mut self, method: Method, path: &str, handler: H) -> RouteRef<'_>
+    where
+        H: Handler<Args>,
+        Args: 'static,
+    {
+        if let Err(err) = self.try_route(method, path, handler) {
+            panic!("{}", err);
+        }
+        RouteRef {
+            router: self,
+            path: path.to_string(),
+        }
+    }
+
+    fn insert_name(&mut self, name: &str, path: &str) -> std::result::Result<(), RouteError> {
+        let names = Arc::make_mut(&mut self.names);
+        if names.contains_key(name) {
+            return Err(RouteError::DuplicateName(name.to_string()));
+        }
+        names.insert(name.to_string(), path.to_string());
+        Ok(())
+    }
+
+    /// Build the path of a named route, percent-encoding each parameter
+    ///
+    /// Parameters not used by the pattern are appended as a query string.
+    ///
+    /// # Example
+    /// ```ignore
+    /// router.get("/users/:id", show_user).name("users.show");
+    /// assert_eq!(router.url_for("users.show", [("id", "42")])?, "/users/42");
+    /// ```
+    pub fn url_for<I, K, V>(&self, name: &str, params: I) -> std::result::Result<String, RouteError>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: ToString,
+    {
+        self.url_generator().url_for(name, params)
+    }
+
+    /// Snapshot of the named routes registered so far, e.g. for template engines
+    pub fn url_generator(&self) -> UrlFor {
+        UrlFor::new(self.names.clone())
+    }
+
+    /// Keys used by `SignedCookieJar` and `PrivateCookieJar`
+    ///
+    /// # Example
+    /// ```ignore
+    /// router.cookie_keys(CookieKeys::from_config(&config.security)?);
+    /// ```
+    pub fn cookie_keys(&mut self, keys: CookieKeys) {
+        self.state(keys);
+    }
+
+    /// Make `value` available to every handler as `State<T>`
+    ///
+    /// Registering a second value of the same type replaces the first.
+    ///
+    /// # Example
+    /// ```ignore
+    /// router.state(Arc::new(AppState { db, mailer }));
+    /// router.state(cache as Arc<dyn Cache>);
+    /// ```
+    pub fn state<T: Clone + Send + Sync + 'static>(&mut self, value: T) {
+        self.state.insert(value);
+    }
+
+    /// Register state derived from an already registered parent state, so
+    /// handlers can ask for just the part they need
+    ///
+    /// Fails if the parent type has not been registered.
+    ///
+    /// # Example
+    /// ```ignore
+    /// router.state(Arc::new(AppState { db, mailer }));
+    /// router.substate(|app: &Arc<AppState>| app.db.clone())?; // State<DbPool>
+    /// ```
+    pub fn substate<P, T>(&mut self, derive: impl FnOnce(&P) -> T) -> std::result::Result<(), RouteError>
+    where
+        P: 'static,
+        T: Clone + Send + Sync + 'static,
+    {
+        self.state.derive(derive)
+    }
+
+    /// Check that the state every handler requires has been registered
+    ///
+    /// Servers run this check on startup when given a `Router` or
+    /// `VersionedRouter` directly; call it yourself before wrapping the
+    /// router in other services.
+    pub fn check_state(&self) -> std::result::Result<(), RouteError> {
+        self.check_state_with(&StateMap::default())
+    }
+
+    /// Like [`Router::check_state`], also accepting state registered on the server
+    pub(crate) fn check_state_with(&self, outer: &StateMap) -> std::result::Result<(), RouteError> {
+        let mut missing = Vec::new();
+        for (method, tree) in self.routes.iter() {
+            tree.for_each(|pattern, route| {
+                let registered = |ty: &&StateType| route.values.contains(ty) || self.state.contains(ty) || outer.contains(ty);
+                if let Some(ty) = route.state.iter().find(|ty| !registered(ty)) {
+                    missing.push((format!("{} {}", method, pattern), ty.name()));
+                }
+            });
+        }
+        missing.sort();
+        match missing.into_iter().next() {
+            Some((route, state)) => Err(RouteError::MissingState {
+                route,
+                state: state.to_string(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Give every route its full state: its own, then this router's, then
+    /// `outer`, so requests need no merging when nothing is added later
+    pub(crate) fn resolve_state(&mut self, outer: &StateMap) {
+        let state = self.state.merged(outer);
+        if state.is_empty() {
+            return;
+        }
+        for tree in Arc::make_mut(&mut self.routes).values_mut() {
+            tree.for_each_mut(|route| {
+                *route = Arc::new(Route {
+                    param_names: route.param_names.clone(),
+                    handler: route.handler.clone(),
+                    state: route.state.clone(),
+                    values: route.values.merged(&state),
+                });
+            });
+        }
+        self.state = StateMap::default();
+    }
+
+    fn insert_endpoint(
+        &mut self,
+        method: Method,
+        path: &str,
+        handler: Arc<dyn Endpoint>,
+        state: Arc<[StateType]>,
+        values: StateMap,
+    ) -> std::result::Result<(), RouteError> {
+        let param_names = parse_pattern(path)?
+            .into_iter()
+            .filter_map(|segment| match segment {
+                Segment::Param { name, .. } => Some(Some(name)),
+                Segment::CatchAll(name) => Some(name),
+                Segment::Static(_) => None,
+            })
+            .collect();
+
         let route = Arc::new(Route {
-            pattern,
             param_names,
-            handler: Arc::new(endpoint),
+            handler,
+            state,
+            values,
         });
         
-        self.routes
+        Arc::make_mut(&mut self.routes)
             .entry(method)
-            .or_insert_with(Vec::new)
-            .push(route);
+            .or_default()
+            .insert(path, route)
+    }
+
+    /// Mount every route of `router` under `prefix`
+    ///
+    /// Parameters in the prefix (e.g. `/users/:user_id`) are captured together
+    /// with the nested route's own parameters, so `Path<T>` sees both. Layers
+    /// already applied to `router` with [`Router::route_layer`] stay scoped to
+    /// its routes, and so does state registered on `router`, which wins over
+    /// state of the same type registered here.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let mut api = Router::new();
+    /// api.get("/users/:id", show_user);
+    ///
+    /// let mut app = Router::new();
+    /// app.nest("/api/v1", api); // GET /api/v1/users/:id
+    /// ```
+    pub fn nest(&mut self, prefix: &str, router: Router) {
+        if let Err(err) = self.try_nest(prefix, router) {
+            panic!("{}", err);
+        }
+    }
+
+    /// Like [`Router::nest`], but returns an error on invalid or conflicting routes
+    pub fn try_nest(&mut self, prefix: &str, router: Router) -> std::result::Result<(), RouteError> {
+        let prefix = prefix.trim_end_matches('/');
+        if !prefix.is_empty() && !prefix.starts_with('/') {
+            return Err(RouteError::InvalidPattern {
+                pattern: prefix.to_string(),
+                reason: "prefix must start with `/`".to_string(),
+            });
+        }
+
+        let join = |pattern: &str| match pattern {
+            "/" if !prefix.is_empty() => prefix.to_string(),
+            _ => format!("{}{}", prefix, pattern),
+        };
+
+        let mut routes = Vec::new();
+        for (method, tree) in router.routes.iter() {
+            tree.for_each(|pattern, route| {
+                let values = route.values.merged(&router.state);
+                routes.push((method.clone(), join(pattern), route.handler.clone(), route.state.clone(), values));
+            });
+        }
+
+        // Insert into copies first so an error leaves this router unchanged
+        let mut staged = Router {
+            routes: self.routes.clone(),
+            names: self.names.clone(),
+            state: self.state.clone(),
+        };
+        for (method, path, handler, state, values) in routes {
+            staged.insert_endpoint(method, &path, handler, state, values)?;
+        }
+        for (name, pattern) in router.names.iter() {
+            staged.insert_name(name, &join(pattern))?;
+        }
+        *self = staged;
+        Ok(())
+    }
+
+    /// Build a group of routes under `prefix` and mount it on this router
+    ///
+    /// Layers added inside the closure with [`Router::route_layer`] only wrap
+    /// the group's routes.
+    ///
+    /// # Example
+    /// ```ignore
+    /// app.group("/admin", |admin| {
+    ///     admin.get("/stats", stats);
+    ///     admin.route_layer(AuthLayer::new(secret));
+    /// });
+    /// ```
+    pub fn group(&mut self, prefix: &str, build: impl FnOnce(&mut Router)) {
+        let mut router = Router::new();
+        build(&mut router);
+        self.nest(prefix, router);
+    }
+
+    /// Wrap every route currently registered on this router with a tower `Layer`
+    ///
+    /// Routes added afterwards are not affected, which makes it possible to
+    /// scope middleware such as authentication or timeouts to a subset of the
+    /// application. Unmatched requests never reach the layer, so a 404 or 405
+    /// is still reported for them.
+    pub fn route_layer<L>(&mut self, layer: L)
+    where
+        L: Layer<RouteService>,
+        L::Service: Service<OxiditeRequest, Response = OxiditeResponse, Error = Error> + Clone + Send + Sync + 'static,
+        <L::Service as Service<OxiditeRequest>>::Future: Send + 'static,
+    {
+        for tree in Arc::make_mut(&mut self.routes).values_mut() {
+            tree.for_each_mut(|route| {
+                let service = layer.layer(RouteService {
+                    endpoint: route.handler.clone(),
+                });
+                *route = Arc::new(Route {
+                    param_names: route.param_names.clone(),
+                    handler: Arc::new(LayeredEndpoint { service }),
+                    state: route.state.clone(),
+                    values: route.values.clone(),
+                });
+            });
+        }
+    }
+
+    /// Whether a route matches `method` and `path`, counting `GET` routes for `HEAD`
+    pub(crate) fn has_route(&self, method: &Method, path: &str) -> bool {
+        let matches = |method: &Method| self.routes.get(method).is_some_and(|tree| tree.find(path).is_some());
+        matches(method) || (*method == Method::HEAD && matches(&Method::GET))
+    }
+
+    /// Match `path` against the routes registered for `method`, storing any
+    /// captured parameters in the request extensions
+    fn find_route(&self, method: &Method, path: &str, req: &mut OxiditeRequest) -> Option<Arc<Route>> {
+        let (route, captures) = self.routes.get(method)?.find(path)?;
+
+        let mut params = serde_json::Map::new();
+        for (name, value) in route.param_names.iter().zip(captures) {
+            if let Some(name) = name {
+                let value = percent_decode_str(value).decode_utf8_lossy();
+                params.insert(name.clone(), serde_json::Value::String(value.into_owned()));
+            }
+        }
+
+        // Store params in request extensions
+        if !params.is_empty() {
+            req.extensions_mut().insert(crate::extract::PathParams(
+                serde_json::Value::Object(params),
+            ));
+        }
+
+        Some(route.clone())
+    }
+
+    /// Call the route's handler with the route's state, then this router's,
+    /// then any state from the server or an outer router
+    ///
+    /// Routers served directly have everything on the route already (see
+    /// `resolve_state`), so this only clones an `Arc`.
+    fn call_route(&self, route: &Route, mut req: OxiditeRequest) -> Pin<Box<dyn Future<Output = Result<OxiditeResponse>> + Send>> {
+        let mut state = route.values.merged(&self.state);
+        if let Some(outer) = req.extensions().get::<StateMap>() {
+            state = state.merged(outer);
+        }
+        if !state.is_empty() {
+            req.extensions_mut().insert(state);
+        }
+        route.handler.call(req)
     }
 
     pub async fn handle(&self, mut req: OxiditeRequest) -> Result<OxiditeResponse> {
         let method = req.method().clone();
         let path = req.uri().path().to_string();
-
-        // Helper to try matching routes for a specific method
-        let try_match = |target_method: &Method, req: &mut OxiditeRequest| -> Option<Arc<Route>> {
-            if let Some(routes) = self.routes.get(target_method) {
-                for route in routes {
-                    if let Some(captures) = route.pattern.captures(&path) {
-                        // Extract path parameters
-                        let mut params = serde_json::Map::new();
-                        for (i, name) in route.param_names.iter().enumerate() {
-                            if let Some(value) = captures.get(i + 1) {
-                                params.insert(
-                                    name.clone(),
-                                    serde_json::Value::String(value.as_str().to_string()),
-                                );
-                            }
-                        }
-
-                        // Store params in request extensions
-                        if !params.is_empty() {
-                            req.extensions_mut().insert(crate::extract::PathParams(
-                                serde_json::Value::Object(params),
-                            ));
-                        }
-                        
-                        return Some(route.clone());
-                    }
-                }
-            }
-            None
-        };
+        req.extensions_mut().insert(self.url_generator());
 
         // 1. Try exact method match
-        if let Some(route) = try_match(&method, &mut req) {
-            return route.handler.call(req).await;
+        if let Some(route) = self.find_route(&method, &path, &mut req) {
+            return self.call_route(&route, req).await;
         }
 
         // 2. If HEAD, try GET
         if method == Method::HEAD {
-            if let Some(route) = try_match(&Method::GET, &mut req) {
+            if let Some(route) = self.find_route(&Method::GET, &path, &mut req) {
                 // For HEAD requests, we execute the GET handler but the server/hyper 
                 // will strip the body automatically since it's a HEAD response.
-                return route.handler.call(req).await;
+                return self.call_route(&route, req).await;
             }
         }
 
         // 3. Path exists for other methods => method not allowed
-        let allowed_methods: Vec<String> = self
+        let mut allowed_methods: Vec<String> = self
             .routes
             .iter()
             .filter(|(route_method, _)| **route_method != method)
-            .filter_map(|(route_method, routes)| {
-                if routes.iter().any(|route| route.pattern.is_match(&path)) {
-                    Some(route_method.as_str().to_string())
-                } else {
-                    None
-                }
-            })
+            .filter(|(_, tree)| tree.find(&path).is_some())
+            .map(|(route_method, _)| route_method.as_str().to_string())
             .collect();
         if !allowed_methods.is_empty() {
+            allowed_methods.sort();
             return Err(Error::MethodNotAllowed(format!(
                 "{} {} (allowed: {})",
                 method,
@@ -292,82 +653,340 @@ impl Default for Router {
     }
 }
 
-/// Compile a route path pattern into a regex
-/// Converts `/users/:id` to `^/users/([^/]+)$` and returns param names
-fn compile_path(path: &str) -> (Regex, Vec<String>) {
-    let mut pattern = String::from("^");
-    let mut param_names = Vec::new();
-    let mut chars = path.chars().peekable();
-
-    while let Some(ch) = chars.next() {
-        match ch {
-            ':' => {
-                // Extract parameter name
-                let mut param_name = String::new();
-                while let Some(&next_ch) = chars.peek() {
-                    if next_ch.is_alphanumeric() || next_ch == '_' {
-                        param_name.push(next_ch);
-                        chars.next();
-                    } else {
-                        break;
-                    }
-                }
-                param_names.push(param_name);
-                pattern.push_str("([^/]+)");
-            }
-            '*' => {
-                // Wildcard
-                pattern.push_str("(.*)");
-            }
-            '.' | '+' | '?' | '^' | '$' | '(' | ')' | '[' | ']' | '{' | '}' | '|' | '\\' => {
-                // Escape regex special characters
-                pattern.push('\\');
-                pattern.push(ch);
-            }
-            _ => {
-                pattern.push(ch);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::OxiditeBody;
+
+    async fn body_text(res: OxiditeResponse) -> String {
+        use http_body_util::BodyExt;
+        let bytes = res.into_inner().into_body().collect().await.expect("body").to_bytes();
+        String::from_utf8(bytes.to_vec()).expect("utf8")
+    }
+
+    fn get(uri: &str) -> OxiditeRequest {
+        http::Request::builder()
+            .method(Method::GET)
+            .uri(uri)
+            .body(OxiditeBody::default())
+            .expect("request")
+    }
+
+    #[tokio::test]
+    async fn test_specific_route_wins_regardless_of_order() {
+        let mut router = Router::new();
+        router.get("/users/:id", |req: OxiditeRequest| async move {
+            let params = req.extensions().get::<crate::extract::PathParams>().cloned().expect("params");
+            crate::OxiditeResponse::text(params.0["id"].as_str().unwrap_or_default().to_string())
+        });
+        router.get("/users/me", || async { crate::OxiditeResponse::text("me") });
+
+        assert_eq!(body_text(router.handle(get("/users/me")).await.expect("me")).await, "me");
+        assert_eq!(body_text(router.handle(get("/users/42")).await.expect("id")).await, "42");
+    }
+
+    #[tokio::test]
+    async fn test_named_catch_all_is_captured() {
+        let mut router = Router::new();
+        router.get("/files/*path", |req: OxiditeRequest| async move {
+            let params = req.extensions().get::<crate::extract::PathParams>().cloned().expect("params");
+            crate::OxiditeResponse::text(params.0["path"].as_str().unwrap_or_default().to_string())
+        });
+
+        let res = router.handle(get("/files/css/site.css")).await.expect("file");
+        assert_eq!(body_text(res).await, "css/site.css");
+    }
+
+    #[derive(Clone)]
+    struct TagLayer;
+
+    #[derive(Clone)]
+    struct Tag<S>(S);
+
+    impl<S> Layer<S> for TagLayer {
+        type Service = Tag<S>;
+
+        fn layer(&self, inner: S) -> Self::Service {
+            Tag(inner)
+        }
+    }
+
+    impl<S> Service<OxiditeRequest> for Tag<S>
+    where
+        S: Service<OxiditeRequest, Response = OxiditeResponse, Error = Error> + Send + 'static,
+        S::Future: Send + 'static,
+    {
+        type Response = OxiditeResponse;
+        type Error = Error;
+        type Future = Pin<Box<dyn Future<Output = Result<OxiditeResponse>> + Send>>;
+
+        fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+            self.0.poll_ready(cx)
+        }
+
+        fn call(&mut self, req: OxiditeRequest) -> Self::Future {
+            let fut = self.0.call(req);
+            Box::pin(async move {
+                let mut res = fut.await?;
+                res.headers_mut().insert("x-tagged", http::HeaderValue::from_static("1"));
+                Ok(res)
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_nest_keeps_prefix_params() {
+        let mut posts = Router::new();
+        posts.get("/posts/:post_id", |crate::extract::Path(params): crate::extract::Path<HashMap<String, String>>| async move {
+            crate::OxiditeResponse::text(format!("{}/{}", params["user_id"], params["post_id"]))
+        });
+        posts.get("/", || async { crate::OxiditeResponse::text("index") });
+
+        let mut router = Router::new();
+        router.nest("/users/:user_id", posts);
+
+        let res = router.handle(get("/users/7/posts/9")).await.expect("nested");
+        assert_eq!(body_text(res).await, "7/9");
+        let res = router.handle(get("/users/7")).await.expect("nested index");
+        assert_eq!(body_text(res).await, "index");
+    }
+
+    #[tokio::test]
+    async fn test_group_layer_is_scoped() {
+        let mut router = Router::new();
+        router.get("/public", || async { crate::OxiditeResponse::ok() });
+        router.group("/admin", |admin| {
+            admin.get("/stats", || async { crate::OxiditeResponse::ok() });
+            admin.route_layer(TagLayer);
+        });
+
+        let res = router.handle(get("/admin/stats")).await.expect("admin");
+        assert!(res.headers().contains_key("x-tagged"));
+        let res = router.handle(get("/public")).await.expect("public");
+        assert!(!res.headers().contains_key("x-tagged"));
+    }
+
+    #[test]
+    fn test_try_nest_reports_conflicts() {
+        let mut sub = Router::new();
+        sub.get("/users", || async { crate::OxiditeResponse::ok() });
+        let mut router = Router::new();
+        router.get("/api/users", || async { crate::OxiditeResponse::ok() });
+        assert!(matches!(router.try_nest("/api", sub), Err(RouteError::Conflict { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_failed_try_nest_leaves_router_unchanged() {
+        let mut sub = Router::new();
+        sub.get("/a", || async { crate::OxiditeResponse::ok() }).name("a");
+        sub.get("/users", || async { crate::OxiditeResponse::ok() });
+        let mut router = Router::new();
+        router.get("/api/users", || async { crate::OxiditeResponse::ok() });
+        assert!(router.try_nest("/api", sub).is_err());
+
+        assert!(matches!(router.handle(get("/api/a")).await, Err(Error::NotFound(_))));
+        assert!(router.url_for("a", Vec::<(&str, &str)>::new()).is_err());
+
+        let mut sub = Router::new();
+        sub.get("/b", || async { crate::OxiditeResponse::ok() }).name("dup");
+        router.get("/dup", || async { crate::OxiditeResponse::ok() }).name("dup");
+        assert!(matches!(router.try_nest("/api", sub), Err(RouteError::DuplicateName(_))));
+        assert!(matches!(router.handle(get("/api/b")).await, Err(Error::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_state_and_substate() {
+        use crate::extract::State;
+
+        #[derive(Clone)]
+        struct App {
+            db: String,
+        }
+
+        let mut api = Router::new();
+        api.get("/db", |State(db): State<String>| async move { db });
+        api.state(Arc::new(App { db: "users.db".to_string() }));
+        api.substate(|app: &Arc<App>| app.db.clone()).unwrap();
+
+        let mut router = Router::new();
+        router.get("/count", |State(count): State<Arc<dyn Fn() -> usize + Send + Sync>>| async move {
+            count().to_string()
+        });
+        router.get("/optional", |state: Option<State<u8>>| async move { state.is_some().to_string() });
+        router.get("/scoped", |state: Option<State<String>>| async move { state.is_some().to_string() });
+        router.state(Arc::new(|| 3usize) as Arc<dyn Fn() -> usize + Send + Sync>);
+        router.nest("/api", api);
+        assert!(router.check_state().is_ok());
+        assert!(matches!(
+            router.substate(|app: &Arc<App>| app.db.len()),
+            Err(RouteError::MissingParentState { .. })
+        ));
+
+        for resolved in [false, true] {
+            if resolved {
+                router.resolve_state(&StateMap::default());
             }
+            assert_eq!(body_text(router.handle(get("/api/db")).await.expect("db")).await, "users.db");
+            assert_eq!(body_text(router.handle(get("/count")).await.expect("count")).await, "3");
+            assert_eq!(body_text(router.handle(get("/optional")).await.expect("optional")).await, "false");
+            // State registered on a nested router stays with its routes
+            assert_eq!(body_text(router.handle(get("/scoped")).await.expect("scoped")).await, "false");
         }
     }
 
-    pattern.push('$');
-    let regex = Regex::new(&pattern).expect("Invalid route pattern");
-    (regex, param_names)
-}
+    #[test]
+    fn test_check_state_reports_missing_state() {
+        let mut router = Router::new();
+        router.get("/", || async { "home" });
+        router.post("/users", |_: crate::extract::State<Arc<String>>, _: crate::extract::Json<serde_json::Value>| async { "created" });
+        router.route_layer(tower::layer::util::Identity::new());
+
+        let err = router.check_state().expect_err("missing state");
+        assert_eq!(
+            err,
+            RouteError::MissingState {
+                route: "POST /users".to_string(),
+                state: "alloc::sync::Arc<alloc::string::String>".to_string(),
+            }
+        );
+        router.state(Arc::new("db".to_string()));
+        assert!(router.check_state().is_ok());
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::types::BoxBody;
+    #[test]
+    fn test_url_for_encodes_params() {
+        let mut router = Router::new();
+        router.get("/users/:id<int>", || async { crate::OxiditeResponse::ok() }).name("users.show");
+        router.get("/files/*path", || async { crate::OxiditeResponse::ok() }).name("files");
+        router.get("/tags/:tag", || async { crate::OxiditeResponse::ok() }).name("tags.show");
+
+        assert_eq!(router.url_for("users.show", [("id", 42)]).unwrap(), "/users/42");
+        assert_eq!(
+            router.url_for("users.show", [("id", "7"), ("tab", "a b")]).unwrap(),
+            "/users/7?tab=a+b"
+        );
+        assert_eq!(router.url_for("files", [("path", "a b/c.txt")]).unwrap(), "/files/a%20b/c.txt");
+        assert_eq!(router.url_for("tags.show", [("tag", "c/c++")]).unwrap(), "/tags/c%2Fc++");
+        assert!(matches!(
+            router.url_for("users.show", [("id", "me")]),
+            Err(RouteError::InvalidParam { .. })
+        ));
+        assert!(matches!(
+            router.url_for("users.show", Vec::<(&str, &str)>::new()),
+            Err(RouteError::MissingParam { .. })
+        ));
+        assert!(matches!(router.url_for("nope", [("id", 1)]), Err(RouteError::UnknownName(_))));
+    }
+
+    #[tokio::test]
+    async fn test_nested_names_and_url_for_extractor() {
+        let mut api = Router::new();
+        api.get("/tags/:tag", |urls: UrlFor, crate::extract::Path(params): crate::extract::Path<HashMap<String, String>>| async move {
+            let url = urls.url_for("tags.show", [("tag", params["tag"].as_str())]).expect("url");
+            crate::OxiditeResponse::text(format!("{} {}", params["tag"], url))
+        }).name("tags.show");
+
+        let mut router = Router::new();
+        router.nest("/api", api);
+
+        let res = router.handle(get("/api/tags/rust%20lang")).await.expect("tag");
+        assert_eq!(body_text(res).await, "rust lang /api/tags/rust%20lang");
+    }
 
     #[test]
-    fn test_compile_path() {
-        let (regex, params) = compile_path("/users/:id");
-        assert_eq!(params, vec!["id"]);
-        assert!(regex.is_match("/users/123"));
-        assert!(!regex.is_match("/users/123/posts"));
+    fn test_duplicate_names_are_rejected() {
+        let mut router = Router::new();
+        router.get("/a", || async { crate::OxiditeResponse::ok() }).name("a");
+        let result = router.get("/b", || async { crate::OxiditeResponse::ok() }).try_name("a");
+        assert!(matches!(result, Err(RouteError::DuplicateName(_))));
+    }
+
+    #[tokio::test]
+    async fn test_parts_extractors_before_body_extractor() {
+        use crate::extract::{Json, Path, Query};
+        use http_body_util::BodyExt;
+
+        let mut router = Router::new();
+        router.post("/items/:id", |Path(path): Path<HashMap<String, String>>, Query(query): Query<HashMap<String, String>>, method: Method, Json(body): Json<serde_json::Value>| async move {
+            crate::OxiditeResponse::text(format!("{} {} {} {}", method, path["id"], query["v"], body["name"].as_str().unwrap_or_default()))
+        });
+
+        let req = http::Request::builder()
+            .method(Method::POST)
+            .uri("/items/3?v=x")
+            .header("content-type", "application/json")
+            .body(http_body_util::Full::new(bytes::Bytes::from(r#"{"name":"pen"}"#)).map_err(|e| match e {}).boxed())
+            .expect("request");
+        let res = router.handle(req).await.expect("response");
+        assert_eq!(body_text(res).await, "POST 3 x pen");
+    }
 
-        let (regex, params) = compile_path("/users/:user_id/posts/:post_id");
-        assert_eq!(params, vec!["user_id", "post_id"]);
-        assert!(regex.is_match("/users/1/posts/2"));
+    #[tokio::test]
+    async fn test_twelve_argument_handler() {
+        type H = http::HeaderMap;
+        let mut router = Router::new();
+        router.get("/", |_: H, _: H, _: H, _: H, _: H, _: H, _: H, _: H, _: H, _: H, _: Method, uri: http::Uri| async move {
+            crate::OxiditeResponse::text(uri.path().to_string())
+        });
+        let res = router.handle(get("/")).await.expect("response");
+        assert_eq!(body_text(res).await, "/");
     }
 
     #[test]
-    fn test_exact_match() {
-        let (regex, params) = compile_path("/users");
-        assert_eq!(params.len(), 0);
-        assert!(regex.is_match("/users"));
-        assert!(!regex.is_match("/users/123"));
+    fn test_try_route_reports_conflicts() {
+        let mut router = Router::new();
+        router.get("/users/:id", || async { crate::OxiditeResponse::ok() });
+        let result = router.try_route(Method::GET, "/users/:user_id", || async { crate::OxiditeResponse::ok() });
+        assert!(matches!(result, Err(RouteError::Conflict { .. })));
+
+        let result = router.try_route(Method::POST, "/users/:user_id", || async { crate::OxiditeResponse::ok() });
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid route pattern")]
+    fn test_invalid_pattern_panics_with_message() {
+        let mut router = Router::new();
+        router.get("/files/*path/edit", || async { crate::OxiditeResponse::ok() });
+    }
+
+    #[tokio::test]
+    async fn test_handlers_return_results_with_any_error_response() {
+        struct Teapot;
+        impl IntoResponse for Teapot {
+            fn into_response(self) -> crate::OxiditeResponse {
+                (http::StatusCode::IM_A_TEAPOT, "short and stout").into_response()
+            }
+        }
+
+        type Lookup = std::result::Result<crate::extract::Json<String>, (http::StatusCode, String)>;
+        async fn lookup(crate::extract::Path(params): crate::extract::Path<HashMap<String, String>>) -> Lookup {
+            match params["id"].as_str() {
+                "0" => Err((http::StatusCode::BAD_REQUEST, "id must be positive".to_string())),
+                id => Ok(crate::extract::Json(id.to_string())),
+            }
+        }
+
+        let mut router = Router::new();
+        router.get("/users/:id", lookup);
+        router.get("/brew", || async { Err::<&'static str, _>(Teapot) });
+
+        let found = router.handle(get("/users/7")).await.expect("response");
+        assert_eq!(found.status(), http::StatusCode::OK);
+        let rejected = router.handle(get("/users/0")).await.expect("response");
+        assert_eq!(rejected.status(), http::StatusCode::BAD_REQUEST);
+        let brewed = router.handle(get("/brew")).await.expect("response");
+        assert_eq!(brewed.status(), http::StatusCode::IM_A_TEAPOT);
     }
 
     #[tokio::test]
     async fn test_method_not_allowed_when_path_exists() {
         let mut router = Router::new();
-        router.get("/users", || async { Ok(crate::OxiditeResponse::text("ok")) });
+        router.get("/users", || async { crate::OxiditeResponse::text("ok") });
         let req = http::Request::builder()
             .method(Method::POST)
             .uri("/users")
-            .body(BoxBody::default())
+            .body(OxiditeBody::default())
             .expect("request");
 
         let result = router.handle(req).await;
@@ -380,7 +999,7 @@ mod tests {
         let req = http::Request::builder()
             .method(Method::GET)
             .uri("/missing")
-            .body(BoxBody::default())
+            .body(OxiditeBody::default())
             .expect("request");
 
         let result = router.handle(req).await;
@@ -0,0 +1,428 @@
+//! Prefix tree used by [`Router`](super::Router) to match request paths.
+//!
+//! Patterns are split on `/` into segments. Each segment is either static
+//! (`users`), a parameter (`:id`, optionally constrained as `:id<int>`) or a
+//! trailing catch-all (`*rest`, or an unnamed `*`). Lookups try static
+//! children first, then parameters, then the catch-all, backtracking when a
+//! branch fails further down, so precedence never depends on registration
+//! order.
+
+use regex::Regex;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Error returned when a route cannot be registered or a URL cannot be built for it
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum RouteError {
+    #[error("invalid route pattern `{pattern}`: {reason}")]
+    InvalidPattern { pattern: String, reason: String },
+    #[error("route `{pattern}` conflicts with existing route `{existing}`")]
+    Conflict { pattern: String, existing: String },
+    #[error("route name `{0}` is already in use")]
+    DuplicateName(String),
+    #[error("no route named `{0}`")]
+    UnknownName(String),
+    #[error("missing parameter `{param}` for route `{name}`")]
+    MissingParam { name: String, param: String },
+    #[error("invalid value `{value}` for parameter `{param}` of route `{name}`")]
+    InvalidParam { name: String, param: String, value: String },
+    #[error("handler for `{route}` requires state `{state}`, which was never registered")]
+    MissingState { route: String, state: String },
+    #[error("cannot derive state `{state}`: parent state `{parent}` is not registered")]
+    MissingParentState { state: String, parent: String },
+}
+
+/// Constraint applied to a path parameter, written as `:name<constraint>`
+#[derive(Clone)]
+pub enum Constraint {
+    /// Any non-empty segment
+    Any,
+    /// Signed integer (`int`)
+    Int,
+    /// Unsigned integer (`uint`)
+    Uint,
+    /// Hyphenated UUID (`uuid`)
+    Uuid,
+    /// ASCII letters only (`alpha`)
+    Alpha,
+    /// ASCII letters and digits (`alnum`)
+    Alnum,
+    /// Lowercase letters, digits and hyphens (`slug`)
+    Slug,
+    /// Any other constraint is treated as a regular expression matched against the whole segment
+    Regex(Regex),
+}
+
+impl Constraint {
+    fn parse(spec: &str) -> std::result::Result<Self, String> {
+        Ok(match spec {
+            "" => return Err("empty parameter constraint".to_string()),
+            "int" => Constraint::Int,
+            "uint" => Constraint::Uint,
+            "uuid" => Constraint::Uuid,
+            "alpha" => Constraint::Alpha,
+            "alnum" => Constraint::Alnum,
+            "slug" => Constraint::Slug,
+            _ => Regex::new(&format!("^(?:{})$", spec))
+                .map(Constraint::Regex)
+                .map_err(|e| format!("invalid constraint `{}`: {}", spec, e))?,
+        })
+    }
+
+    pub(crate) fn matches(&self, segment: &str) -> bool {
+        match self {
+            Constraint::Any => true,
+            Constraint::Int => {
+                let digits = segment.strip_prefix('-').unwrap_or(segment);
+                !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+            }
+            Constraint::Uint => segment.bytes().all(|b| b.is_ascii_digit()),
+            Constraint::Uuid => {
+                segment.len() == 36
+                    && segment.bytes().enumerate().all(|(i, b)| match i {
+                        8 | 13 | 18 | 23 => b == b'-',
+                        _ => b.is_ascii_hexdigit(),
+                    })
+            }
+            Constraint::Alpha => segment.bytes().all(|b| b.is_ascii_alphabetic()),
+            Constraint::Alnum => segment.bytes().all(|b| b.is_ascii_alphanumeric()),
+            Constraint::Slug => segment
+                .bytes()
+                .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-'),
+            Constraint::Regex(re) => re.is_match(segment),
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        match self {
+            Constraint::Any => "",
+            Constraint::Int => "int",
+            Constraint::Uint => "uint",
+            Constraint::Uuid => "uuid",
+            Constraint::Alpha => "alpha",
+            Constraint::Alnum => "alnum",
+            Constraint::Slug => "slug",
+            Constraint::Regex(re) => {
+                let src = re.as_str();
+                &src[4..src.len() - 2]
+            }
+        }
+    }
+}
+
+impl PartialEq for Constraint {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl fmt::Debug for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Constraint::Any => f.write_str("Any"),
+            other => write!(f, "<{}>", other.as_str()),
+        }
+    }
+}
+
+/// A single parsed segment of a route pattern
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    Static(String),
+    Param { name: String, constraint: Constraint },
+    CatchAll(Option<String>),
+}
+
+/// Split a route pattern such as `/users/:id<int>/files/*path` into segments
+pub fn parse_pattern(pattern: &str) -> std::result::Result<Vec<Segment>, RouteError> {
+    let invalid = |reason: &str| RouteError::InvalidPattern {
+        pattern: pattern.to_string(),
+        reason: reason.to_string(),
+    };
+
+    let rest = pattern
+        .strip_prefix('/')
+        .ok_or_else(|| invalid("pattern must start with `/`"))?;
+    let raw: Vec<&str> = rest.split('/').collect();
+    let mut segments = Vec::with_capacity(raw.len());
+
+    for (i, part) in raw.iter().enumerate() {
+        if let Some(param) = part.strip_prefix(':') {
+            let (name, constraint) = match param.split_once('<') {
+                Some((name, spec)) => {
+                    let spec = spec
+                        .strip_suffix('>')
+                        .ok_or_else(|| invalid("unterminated parameter constraint"))?;
+                    (name, Constraint::parse(spec).map_err(|e| invalid(&e))?)
+                }
+                None => (param, Constraint::Any),
+            };
+            if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                return Err(invalid("parameter names must be non-empty and alphanumeric"));
+            }
+            segments.push(Segment::Param {
+                name: name.to_string(),
+                constraint,
+            });
+        } else if let Some(name) = part.strip_prefix('*') {
+            if i != raw.len() - 1 {
+                return Err(invalid("catch-all must be the last segment"));
+            }
+            if !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                return Err(invalid("catch-all names must be alphanumeric"));
+            }
+            segments.push(Segment::CatchAll((!name.is_empty()).then(|| name.to_string())));
+        } else if part.contains(':') || part.contains('*') {
+            return Err(invalid("parameters and catch-alls must span a whole segment"));
+        } else {
+            segments.push(Segment::Static(part.to_string()));
+        }
+    }
+
+    Ok(segments)
+}
+
+struct Node<T> {
+    statics: HashMap<String, Node<T>>,
+    params: Vec<(Constraint, Node<T>)>,
+    catch_all: Option<(String, T)>,
+    value: Option<(String, T)>,
+}
+
+impl<T> Default for Node<T> {
+    fn default() -> Self {
+        Self {
+            statics: HashMap::new(),
+            params: Vec::new(),
+            catch_all: None,
+            value: None,
+        }
+    }
+}
+
+impl<T: Clone> Clone for Node<T> {
+    fn clone(&self) -> Self {
+        Self {
+            statics: self.statics.clone(),
+            params: self.params.clone(),
+            catch_all: self.catch_all.clone(),
+            value: self.value.clone(),
+        }
+    }
+}
+
+/// Path matcher keyed by route pattern
+pub struct PathTree<T> {
+    root: Node<T>,
+}
+
+impl<T> Default for PathTree<T> {
+    fn default() -> Self {
+        Self { root: Node::default() }
+    }
+}
+
+impl<T: Clone> Clone for PathTree<T> {
+    fn clone(&self) -> Self {
+        Self { root: self.root.clone() }
+    }
+}
+
+impl<T> PathTree<T> {
+    /// Insert `value` under `pattern`, failing if an equivalent pattern is already present
+    pub fn insert(&mut self, pattern: &str, value: T) -> std::result::Result<(), RouteError> {
+        let segments = parse_pattern(pattern)?;
+        let mut node = &mut self.root;
+
+        for segment in segments {
+            match segment {
+                Segment::Static(s) => {
+                    node = node.statics.entry(s).or_default();
+                }
+                Segment::Param { constraint, .. } => {
+                    let idx = match node.params.iter().position(|(c, _)| *c == constraint) {
+                        Some(idx) => idx,
+                        None => {
+                            // Constrained parameters are tried before unconstrained ones.
+                            let idx = if matches!(constraint, Constraint::Any) {
+                                node.params.len()
+                            } else {
+                                node.params
+                                    .iter()
+                                    .position(|(c, _)| matches!(c, Constraint::Any))
+                                    .unwrap_or(node.params.len())
+                            };
+                            node.params.insert(idx, (constraint, Node::default()));
+                            idx
+                        }
+                    };
+                    node = &mut node.params[idx].1;
+                }
+                Segment::CatchAll(_) => {
+                    if let Some((existing, _)) = &node.catch_all {
+                        return Err(RouteError::Conflict {
+                            pattern: pattern.to_string(),
+                            existing: existing.clone(),
+                        });
+                    }
+                    node.catch_all = Some((pattern.to_string(), value));
+                    return Ok(());
+                }
+            }
+        }
+
+        if let Some((existing, _)) = &node.value {
+            return Err(RouteError::Conflict {
+                pattern: pattern.to_string(),
+                existing: existing.clone(),
+            });
+        }
+        node.value = Some((pattern.to_string(), value));
+        Ok(())
+    }
+
+    /// Visit every stored value along with the pattern it was registered under
+    pub fn for_each(&self, mut f: impl FnMut(&str, &T)) {
+        fn walk<T>(node: &Node<T>, f: &mut impl FnMut(&str, &T)) {
+            for (pattern, value) in node.value.iter().chain(node.catch_all.iter()) {
+                f(pattern, value);
+            }
+            node.statics.values().for_each(|child| walk(child, f));
+            node.params.iter().for_each(|(_, child)| walk(child, f));
+        }
+        walk(&self.root, &mut f);
+    }
+
+    /// Replace every stored value in place
+    pub fn for_each_mut(&mut self, mut f: impl FnMut(&mut T)) {
+        fn walk<T>(node: &mut Node<T>, f: &mut impl FnMut(&mut T)) {
+            for (_, value) in node.value.iter_mut().chain(node.catch_all.iter_mut()) {
+                f(value);
+            }
+            node.statics.values_mut().for_each(|child| walk(child, f));
+            node.params.iter_mut().for_each(|(_, child)| walk(child, f));
+        }
+        walk(&mut self.root, &mut f);
+    }
+
+    /// Find the value matching `path`, returning it along with the captured
+    /// parameter and catch-all values in pattern order
+    pub fn find<'a>(&'a self, path: &'a str) -> Option<(&'a T, Vec<&'a str>)> {
+        let rest = path.strip_prefix('/')?;
+        let segments: Vec<&str> = rest.split('/').collect();
+        let mut captures = Vec::new();
+        let value = find_in(&self.root, rest, &segments, &mut captures)?;
+        Some((value, captures))
+    }
+}
+
+fn find_in<'a, T>(
+    node: &'a Node<T>,
+    remaining: &'a str,
+    segments: &[&'a str],
+    captures: &mut Vec<&'a str>,
+) -> Option<&'a T> {
+    let Some((segment, tail)) = segments.split_first() else {
+        return node.value.as_ref().map(|(_, v)| v);
+    };
+
+    if let Some(child) = node.statics.get(*segment) {
+        if let Some(found) = find_in(child, skip_segment(remaining), tail, captures) {
+            return Some(found);
+        }
+    }
+
+    if !segment.is_empty() {
+        for (constraint, child) in &node.params {
+            if !constraint.matches(segment) {
+                continue;
+            }
+            captures.push(segment);
+            if let Some(found) = find_in(child, skip_segment(remaining), tail, captures) {
+                return Some(found);
+            }
+            captures.pop();
+        }
+    }
+
+    if let Some((_, value)) = &node.catch_all {
+        captures.push(remaining);
+        return Some(value);
+    }
+
+    None
+}
+
+fn skip_segment(remaining: &str) -> &str {
+    remaining.split_once('/').map(|(_, rest)| rest).unwrap_or("")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree(patterns: &[&str]) -> PathTree<&'static str> {
+        let mut tree = PathTree::default();
+        for pattern in patterns {
+            let leaked: &'static str = Box::leak(pattern.to_string().into_boxed_str());
+            tree.insert(pattern, leaked).unwrap();
+        }
+        tree
+    }
+
+    #[test]
+    fn static_beats_param_beats_catch_all() {
+        let tree = tree(&["/users/*rest", "/users/:id", "/users/me"]);
+        assert_eq!(tree.find("/users/me").unwrap().0, &"/users/me");
+        assert_eq!(tree.find("/users/42"), Some((&"/users/:id", vec!["42"])));
+        assert_eq!(tree.find("/users/42/posts"), Some((&"/users/*rest", vec!["42/posts"])));
+    }
+
+    #[test]
+    fn backtracks_from_static_to_param() {
+        let tree = tree(&["/users/me/settings", "/users/:id/posts"]);
+        assert_eq!(tree.find("/users/me/posts"), Some((&"/users/:id/posts", vec!["me"])));
+    }
+
+    #[test]
+    fn constrained_params_take_precedence() {
+        let tree = tree(&["/items/:slug", "/items/:id<int>", "/codes/:code<[A-Z]{3}>"]);
+        assert_eq!(tree.find("/items/12").unwrap().0, &"/items/:id<int>");
+        assert_eq!(tree.find("/items/widget").unwrap().0, &"/items/:slug");
+        assert!(tree.find("/codes/ABC").is_some());
+        assert!(tree.find("/codes/abcd").is_none());
+    }
+
+    #[test]
+    fn params_do_not_match_empty_segments() {
+        let tree = tree(&["/users/:id"]);
+        assert!(tree.find("/users/").is_none());
+        assert!(tree.find("/users").is_none());
+    }
+
+    #[test]
+    fn unnamed_catch_all_matches_root_fallback() {
+        let tree = tree(&["/", "/*"]);
+        assert_eq!(tree.find("/").unwrap().0, &"/");
+        assert_eq!(tree.find("/other/page"), Some((&"/*", vec!["other/page"])));
+    }
+
+    #[test]
+    fn conflicting_patterns_are_rejected() {
+        let mut tree = tree(&["/users/:id", "/files/*path"]);
+        assert!(matches!(tree.insert("/users/:name", "x"), Err(RouteError::Conflict { .. })));
+        assert!(matches!(tree.insert("/files/*other", "x"), Err(RouteError::Conflict { .. })));
+        assert!(tree.insert("/users/:id<int>", "x").is_ok());
+    }
+
+    #[test]
+    fn invalid_patterns_are_rejected() {
+        for pattern in ["users", "/a/*rest/b", "/a/:", "/a/:id<int", "/a/x:y", "/a/:id<(>"] {
+            assert!(
+                matches!(parse_pattern(pattern), Err(RouteError::InvalidPattern { .. })),
+                "{} should be invalid",
+                pattern
+            );
+        }
+    }
+}
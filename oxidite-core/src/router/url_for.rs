@@ -0,0 +1,133 @@
+//! Reverse routing for named routes.
+
+use super::tree::{parse_pattern, RouteError, Segment};
+use crate::error::{Error, Result};
+use crate::extract::FromRequestParts;
+use http::request::Parts;
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Characters that must be escaped inside a single path segment
+const SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// Snapshot of a router's named routes, used to build URLs
+///
+/// Obtain one with [`Router::url_generator`](super::Router::url_generator) or
+/// extract it in a handler, where it reflects the router serving the request.
+///
+/// # Example
+/// ```ignore
+/// router.get("/users/:id", show_user).name("users.show");
+///
+/// async fn handler(urls: UrlFor) -> Result<Response> {
+///     let location = urls.url_for("users.show", [("id", 42)])?;
+///     Ok(Response::text(location)) // "/users/42"
+/// }
+/// ```
+#[derive(Clone, Default)]
+pub struct UrlFor {
+    names: Arc<HashMap<String, String>>,
+}
+
+impl UrlFor {
+    pub(crate) fn new(names: Arc<HashMap<String, String>>) -> Self {
+        Self { names }
+    }
+
+    /// Check whether a route with this name exists
+    pub fn has_route(&self, name: &str) -> bool {
+        self.names.contains_key(name)
+    }
+
+    /// Build the path for the route registered under `name`
+    ///
+    /// Each parameter is percent-encoded and checked against the route's
+    /// constraint. Parameters that do not appear in the pattern are appended
+    /// as a query string.
+    pub fn url_for<I, K, V>(&self, name: &str, params: I) -> std::result::Result<String, RouteError>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: ToString,
+    {
+        let pattern = self
+            .names
+            .get(name)
+            .ok_or_else(|| RouteError::UnknownName(name.to_string()))?;
+        let mut params: Vec<(String, String)> = params
+            .into_iter()
+            .map(|(k, v)| (k.as_ref().to_string(), v.to_string()))
+            .collect();
+
+        let mut take = |param: &str| -> std::result::Result<String, RouteError> {
+            let idx = params
+                .iter()
+                .position(|(k, _)| k == param)
+                .ok_or_else(|| RouteError::MissingParam {
+                    name: name.to_string(),
+                    param: param.to_string(),
+                })?;
+            Ok(params.remove(idx).1)
+        };
+
+        let mut path = String::new();
+        for segment in parse_pattern(pattern)? {
+            path.push('/');
+            match segment {
+                Segment::Static(s) => path.push_str(&s),
+                Segment::Param { name: param, constraint } => {
+                    let value = take(&param)?;
+                    if value.is_empty() || !constraint.matches(&value) {
+                        return Err(RouteError::InvalidParam {
+                            name: name.to_string(),
+                            param,
+                            value,
+                        });
+                    }
+                    path.extend(utf8_percent_encode(&value, SEGMENT));
+                }
+                Segment::CatchAll(param) => {
+                    let value = take(param.as_deref().unwrap_or("*"))?;
+                    let encoded: Vec<String> = value
+                        .split('/')
+                        .map(|part| utf8_percent_encode(part, SEGMENT).to_string())
+                        .collect();
+                    path.push_str(&encoded.join("/"));
+                }
+            }
+        }
+
+        if !params.is_empty() {
+            let query = serde_urlencoded::to_string(&params).map_err(|e| RouteError::InvalidParam {
+                name: name.to_string(),
+                param: "query".to_string(),
+                value: e.to_string(),
+            })?;
+            path.push('?');
+            path.push_str(&query);
+        }
+
+        Ok(path)
+    }
+}
+
+impl FromRequestParts for UrlFor {
+    async fn from_request_parts(parts: &mut Parts) -> Result<Self> {
+        parts.extensions
+            .get::<UrlFor>()
+            .cloned()
+            .ok_or_else(|| Error::InternalServerError("Route table not found in request extensions".to_string()))
+    }
+}
@@ -1,35 +1,81 @@
 use std::net::SocketAddr;
+use std::sync::Arc;
 use tokio::net::TcpListener;
-use hyper::server::conn::http1;
-use hyper_util::rt::TokioIo;
-use hyper_util::service::TowerToHyperService;
 use crate::error::{Error, Result};
+use crate::client_ip::TrustedProxies;
+use crate::extract::BodyLimit;
+use crate::state::{prepare_service, StateMap};
 use crate::types::{OxiditeRequest, OxiditeResponse};
 use tower_service::Service;
-use std::error::Error as StdError;
 
 use http_body_util::BodyExt;
 
-use std::task::{Context, Poll};
+use crate::problem::{render_error, ExposeDetails};
+use hyper::header::{HeaderValue, ACCEPT};
+use pin_project_lite::pin_project;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
 
+mod conn;
+mod listener;
 #[cfg(feature = "http3")]
 pub mod http3_server;
 
+pub use conn::{Http2Settings, HttpVersion, ServerLimits};
+pub(crate) use conn::{accept_loop, plaintext, ConnectionOptions};
+use conn::{Activity, ActivityGuard};
+use listener::{Bound, Listener};
+
 #[cfg(feature = "http3")]
 pub use http3_server::Http3Server;
 
 /// Adapter to convert hyper::Request<Incoming> to OxiditeRequest
 #[derive(Clone)]
-pub struct BodyAdapter<S>(S);
+pub struct BodyAdapter<S> {
+    inner: S,
+    body_limit: Option<BodyLimit>,
+    connection: http::Extensions,
+    activity: Option<Arc<Activity>>,
+    debug: bool,
+}
 
 impl<S> BodyAdapter<S> {
     pub fn new(service: S) -> Self {
-        Self(service)
+        Self {
+            inner: service,
+            body_limit: None,
+            connection: http::Extensions::new(),
+            activity: None,
+            debug: false,
+        }
+    }
+
+    /// Default body limit for requests that do not carry one yet
+    pub(crate) fn body_limit(mut self, limit: Option<BodyLimit>) -> Self {
+        self.body_limit = limit;
+        self
+    }
+
+    /// Per-connection values, such as TLS peer certificates, added to every request
+    pub(crate) fn connection(mut self, extensions: http::Extensions) -> Self {
+        self.connection = extensions;
+        self
     }
-}
 
-use futures_util::future::Map;
-use futures_util::FutureExt;
+    /// Expose 5xx error details in problem responses
+    pub(crate) fn debug(mut self, enabled: bool) -> Self {
+        self.debug = enabled;
+        self
+    }
+
+    /// Count in-flight requests for keep-alive idle tracking
+    pub(crate) fn track(mut self, activity: Arc<Activity>) -> Self {
+        self.activity = Some(activity);
+        self
+    }
+}
 
 impl<S> Service<hyper::Request<hyper::body::Incoming>> for BodyAdapter<S>
 where
@@ -37,29 +83,100 @@ where
 {
     type Response = hyper::Response<crate::types::BoxBody>;
     type Error = Error;
-    type Future = Map<S::Future, fn(std::result::Result<OxiditeResponse, Error>) -> std::result::Result<hyper::Response<crate::types::BoxBody>, Error>>;
+    type Future = ResponseFuture<S::Future>;
 
     fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<std::result::Result<(), Self::Error>> {
-        self.0.poll_ready(cx)
+        self.inner.poll_ready(cx)
     }
 
     fn call(&mut self, req: hyper::Request<hyper::body::Incoming>) -> Self::Future {
-        let req = req.map(|b| b.map_err(|e| e.into()).boxed());
-        fn map_response(res: std::result::Result<OxiditeResponse, Error>) -> std::result::Result<hyper::Response<crate::types::BoxBody>, Error> {
-            match res {
-                Ok(response) => Ok(response.into()),
-                // Convert framework/service errors into proper HTTP responses
-                // so connections are not aborted for expected handler errors.
-                Err(error) => Ok(OxiditeResponse::from(error).into()),
+        let mut req = req.map(|b| b.map_err(|e| e.into()).boxed());
+        if let Some(limit) = self.body_limit {
+            if req.extensions().get::<BodyLimit>().is_none() {
+                req.extensions_mut().insert(limit);
             }
         }
-        self.0.call(req).map(map_response)
+        if !self.connection.is_empty() {
+            req.extensions_mut().extend(self.connection.clone());
+        }
+        if self.debug {
+            req.extensions_mut().insert(ExposeDetails);
+        }
+        let accept = req.headers().get(ACCEPT).cloned();
+        let instance = req.uri().path().to_string();
+        ResponseFuture {
+            guard: self.activity.clone().map(ActivityGuard::new),
+            inner: self.inner.call(req),
+            accept,
+            instance: Some(instance),
+            debug: self.debug,
+        }
+    }
+}
+
+pin_project! {
+    /// Future returned by [`BodyAdapter`]
+    ///
+    /// Converts framework/service errors into problem responses, negotiated
+    /// against the request's `Accept` header, so connections are not aborted
+    /// for expected handler errors.
+    pub struct ResponseFuture<F> {
+        #[pin]
+        inner: F,
+        accept: Option<HeaderValue>,
+        instance: Option<String>,
+        debug: bool,
+        guard: Option<ActivityGuard>,
+    }
+}
+
+impl<F> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<OxiditeResponse>>,
+{
+    type Output = Result<hyper::Response<crate::types::BoxBody>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let response = match ready!(this.inner.poll(cx)) {
+            Ok(response) => response,
+            Err(error) => render_error(&error, *this.debug, this.instance.take(), this.accept.as_ref()),
+        };
+        let mut response: hyper::Response<crate::types::BoxBody> = response.into();
+        // The request stays in flight until its response body is done
+        if let Some(guard) = this.guard.take() {
+            response = response.map(|body| {
+                body.map_err(move |e| {
+                    let _ = &guard;
+                    e
+                })
+                .boxed()
+            });
+        }
+        Poll::Ready(Ok(response))
     }
 }
 
+pub(crate) type ShutdownSignal = Pin<Box<dyn Future<Output = ()> + Send>>;
+type ShutdownHook = Box<dyn FnOnce() -> Pin<Box<dyn Future<Output = ()> + Send>> + Send>;
+
+/// Default time in-flight requests get to finish after the shutdown signal
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
 
 pub struct Server<S> {
     service: S,
+    shutdown: Option<ShutdownSignal>,
+    shutdown_timeout: Duration,
+    hooks: Vec<ShutdownHook>,
+    http_version: HttpVersion,
+    http2: Http2Settings,
+    limits: ServerLimits,
+    trusted_proxies: Option<TrustedProxies>,
+    state: StateMap,
+    debug: bool,
+    bound: Option<Bound>,
+    #[cfg(unix)]
+    unix_permissions: Option<u32>,
 }
 
 impl<S> Server<S>
@@ -70,93 +187,255 @@ where
     pub fn new(service: S) -> Self {
         Self {
             service,
+            shutdown: None,
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+            hooks: Vec::new(),
+            http_version: HttpVersion::Auto,
+            http2: Http2Settings::default(),
+            limits: ServerLimits::default(),
+            trusted_proxies: None,
+            state: StateMap::default(),
+            debug: false,
+            bound: None,
+            #[cfg(unix)]
+            unix_permissions: None,
+        }
+    }
+
+    /// Serve on an already bound TCP listener; start it with [`run`](Self::run)
+    pub fn from_std_listener(service: S, listener: std::net::TcpListener) -> Result<Self> {
+        let mut server = Self::new(service);
+        server.bound = Some(Bound::from_std_tcp(listener)?);
+        Ok(server)
+    }
+
+    /// Serve on the socket passed by systemd socket activation (`LISTEN_FDS`);
+    /// start it with [`run`](Self::run)
+    ///
+    /// TCP and Unix sockets are both supported. Keeping the socket in systemd
+    /// lets the service restart without refusing connections.
+    ///
+    /// # Example
+    /// ```ignore
+    /// // app.socket: ListenStream=/run/app.sock, app.service: ExecStart=/usr/bin/app
+    /// Server::from_listen_fds(router)?
+    ///     .with_graceful_shutdown(oxidite_core::server::shutdown_signal())
+    ///     .run()
+    ///     .await?;
+    /// ```
+    #[cfg(unix)]
+    pub fn from_listen_fds(service: S) -> Result<Self> {
+        let mut server = Self::new(service);
+        server.bound = Some(listener::take_listen_fd()?);
+        Ok(server)
+    }
+
+    /// Apply the connection limits and timeouts from the `[server]` config
+    ///
+    /// # Example
+    /// ```ignore
+    /// let config = oxidite_config::Config::load()?;
+    /// Server::new(router).with_config(&config)?.listen(addr).await?;
+    /// ```
+    ///
+    /// Also trusts the listed `trusted_proxies`, failing if any entry is
+    /// invalid, and takes [`debug`](Self::debug) from `app.debug`.
+    pub fn with_config(mut self, config: &oxidite_config::Config) -> Result<Self> {
+        let server = &config.server;
+        if !server.trusted_proxies.is_empty() {
+            let proxies = TrustedProxies::new(server.trusted_proxies.iter().map(String::as_str))
+                .map_err(|err| Error::InternalServerError(format!("Invalid [server] trusted_proxies: {}", err.detail())))?;
+            self.trusted_proxies = Some(proxies);
         }
+        Ok(self.limits(ServerLimits::from(server)).debug(config.app.debug))
+    }
+
+    /// Believe `Forwarded`/`X-Forwarded-For` from these proxies when
+    /// extracting [`ClientIp`](crate::ClientIp)
+    ///
+    /// # Example
+    /// ```ignore
+    /// Server::new(router)
+    ///     .trusted_proxies(TrustedProxies::new(["10.0.0.0/8", "::1"])?)
+    ///     .listen(addr)
+    ///     .await?;
+    /// ```
+    pub fn trusted_proxies(mut self, proxies: TrustedProxies) -> Self {
+        self.trusted_proxies = Some(proxies);
+        self
+    }
+
+    /// Make `value` available to every request as `State<T>`, including
+    /// requests to services that wrap the router
+    ///
+    /// Values registered on the router take precedence.
+    pub fn state<T: Clone + Send + Sync + 'static>(mut self, value: T) -> Self {
+        self.state.insert(value);
+        self
+    }
+
+    /// Register state derived from a parent registered with [`state`](Self::state)
+    ///
+    /// Fails if the parent type has not been registered.
+    pub fn substate<P, T>(mut self, derive: impl FnOnce(&P) -> T) -> Result<Self>
+    where
+        P: 'static,
+        T: Clone + Send + Sync + 'static,
+    {
+        self.state
+            .derive(derive)
+            .map_err(|err| Error::InternalServerError(err.to_string()))?;
+        Ok(self)
+    }
+
+    /// Set connection limits and timeouts
+    pub fn limits(mut self, limits: ServerLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Expose 5xx error details in problem responses; off by default so
+    /// internal messages do not reach clients in production
+    pub fn debug(mut self, enabled: bool) -> Self {
+        self.debug = enabled;
+        self
+    }
+
+    /// Stop accepting connections once `signal` completes
+    ///
+    /// In-flight requests are allowed to finish (up to the
+    /// [`shutdown_timeout`](Self::shutdown_timeout)), keep-alive connections
+    /// answer with `Connection: close`, then the shutdown hooks run and
+    /// `listen` returns.
+    ///
+    /// # Example
+    /// ```ignore
+    /// Server::new(router)
+    ///     .with_graceful_shutdown(oxidite_core::server::shutdown_signal())
+    ///     .on_shutdown(move || async move {
+    ///         let _ = plugins.shutdown().await;
+    ///     })
+    ///     .listen(addr)
+    ///     .await?;
+    /// ```
+    pub fn with_graceful_shutdown<F>(mut self, signal: F) -> Self
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.shutdown = Some(Box::pin(signal));
+        self
+    }
+
+    /// Deadline for draining in-flight requests after the shutdown signal
+    pub fn shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.shutdown_timeout = timeout;
+        self
+    }
+
+    /// Register a hook that runs after connections are drained
+    ///
+    /// Hooks run in registration order.
+    pub fn on_shutdown<F, Fut>(mut self, hook: F) -> Self
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.hooks.push(Box::new(move || Box::pin(hook())));
+        self
+    }
+
+    /// Restrict the protocol; by default plaintext connections may use
+    /// HTTP/1.1 or prior-knowledge HTTP/2 (h2c)
+    pub fn with_http_version(mut self, version: HttpVersion) -> Self {
+        self.http_version = version;
+        self
+    }
+
+    /// Configure HTTP/2 connections
+    pub fn http2(mut self, settings: Http2Settings) -> Self {
+        self.http2 = settings;
+        self
+    }
+
+    /// File mode applied to the socket created by [`listen_unix`](Self::listen_unix),
+    /// e.g. `0o660` to let a reverse proxy in the same group connect
+    #[cfg(unix)]
+    pub fn unix_permissions(mut self, mode: u32) -> Self {
+        self.unix_permissions = Some(mode);
+        self
     }
 
     pub async fn listen(self, addr: SocketAddr) -> Result<()> {
         let listener = TcpListener::bind(addr).await?;
         println!("Listening on http://{}", addr);
+        self.serve(listener).await
+    }
 
-        loop {
-            let (stream, _) = listener.accept().await?;
-            let io = TokioIo::new(stream);
-            let service = self.service.clone();
-
-            tokio::task::spawn(async move {
-                let service = BodyAdapter::new(service);
-                let hyper_service = TowerToHyperService::new(service);
-                
-                if let Err(err) = http1::Builder::new()
-                    .serve_connection(io, hyper_service)
-                    .await
-                {
-                    // Only log actual server errors, not client errors like 404
-                    // Check if this is a user service error that we can inspect
-                    if let Some(service_err) = err.source().and_then(|e| e.downcast_ref::<Error>()) {
-                        // Only log if it's a server error (5xx), not a client error (4xx)
-                        if service_err.is_server_error() {
-                            eprintln!("Server error: {}", service_err);
-                        }
-                        // Client errors (404, etc.) are silently handled - they're expected
-                    } else {
-                        // For non-service errors (connection issues, etc.), log them
-                        // but only if they're not common expected errors
-                        let err_msg = err.to_string();
-                        // Don't log if it's just a client disconnecting or similar
-                        if !err_msg.contains("NotFound") && !err_msg.contains("connection closed") {
-                            eprintln!("Connection error: {}", err);
-                        }
-                    }
-                }
-            });
+    /// Listen on a Unix domain socket, replacing a stale socket file and
+    /// removing it again after shutdown
+    #[cfg(unix)]
+    pub async fn listen_unix(self, path: impl AsRef<std::path::Path>) -> Result<()> {
+        use std::os::unix::fs::FileTypeExt;
+
+        let path = path.as_ref();
+        if let Ok(meta) = std::fs::symlink_metadata(path) {
+            if meta.file_type().is_socket() {
+                std::fs::remove_file(path)?;
+            }
+        }
+        let listener = listener::bind_unix(path, self.unix_permissions)?;
+        println!("Listening on unix:{}", path.display());
+
+        let result = self.serve(listener).await;
+        let _ = std::fs::remove_file(path);
+        result
+    }
+
+    /// Serve on the listener given to [`from_std_listener`](Self::from_std_listener)
+    /// or [`from_listen_fds`](Self::from_listen_fds)
+    pub async fn run(mut self) -> Result<()> {
+        match self.bound.take() {
+            Some(Bound::Tcp(listener)) => self.serve(listener).await,
+            #[cfg(unix)]
+            Some(Bound::Unix(listener)) => self.serve(listener).await,
+            None => Err(Error::InternalServerError(
+                "Server has no bound listener; use listen(addr) instead".to_string(),
+            )),
+        }
+    }
+
+    fn connection_options(&self) -> ConnectionOptions {
+        ConnectionOptions::new(self.http_version, &self.http2, self.limits.clone(), self.shutdown_timeout)
+            .trusted_proxies(self.trusted_proxies.clone())
+            .state(self.state.clone())
+            .debug(self.debug)
+    }
+
+    async fn serve<L: Listener>(mut self, listener: L) -> Result<()> {
+        self.state = prepare_service(&mut self.service, std::mem::take(&mut self.state))?;
+        let options = self.connection_options();
+        let signal = self.shutdown.unwrap_or_else(|| Box::pin(std::future::pending()));
+        let result = accept_loop(listener, self.service, options, plaintext, signal).await;
+
+        for hook in self.hooks {
+            hook().await;
         }
+        Ok(result?)
     }
 
     /// Listen with both HTTP/1.1 and HTTP/3 support
+    ///
+    /// The [graceful shutdown](Self::with_graceful_shutdown) signal stops both
+    /// listeners; once both have drained, the shutdown hooks run.
     #[cfg(feature = "http3")]
-    pub async fn listen_h3(self, addr: SocketAddr, cert_pem: &str, key_pem: &str) -> Result<()> {
+    pub async fn listen_h3(mut self, addr: SocketAddr, cert_pem: &str, key_pem: &str) -> Result<()> {
         use rustls::ServerConfig;  
         use rustls_pemfile::{certs, pkcs8_private_keys};
         use std::io::Cursor;
+        use tokio::sync::watch;
         
-        // Setup HTTP/1.1 server in background
-        let http1_addr = addr;
-        let http1_service = self.service.clone();
-        
-        tokio::spawn(async move {
-            let listener = TcpListener::bind(http1_addr).await.unwrap();
-            println!("HTTP/1.1 server listening on http://{}", http1_addr);
-            
-            loop {
-                let (stream, _) = listener.accept().await.unwrap();
-                let io = TokioIo::new(stream);
-                let service = http1_service.clone();
-                
-                tokio::task::spawn(async move {
-                    let service = BodyAdapter::new(service);
-                    let hyper_service = TowerToHyperService::new(service);
-                    
-                    if let Err(err) = http1::Builder::new()
-                        .serve_connection(io, hyper_service)
-                        .await
-                    {
-                        // Only log server errors, not client errors
-                        if let Some(service_err) = err.source().and_then(|e| e.downcast_ref::<Error>()) {
-                            if service_err.is_server_error() {
-                                eprintln!("HTTP/1.1 server error: {}", service_err);
-                            }
-                        } else {
-                            let err_msg = err.to_string();
-                            if !err_msg.contains("NotFound") && !err_msg.contains("connection closed") {
-                                eprintln!("HTTP/1.1 connection error: {}", err);
-                            }
-                        }
-                    }
-                });
-            }
-        });
-        
-        // Setup HTTP/3 server
+        self.state = prepare_service(&mut self.service, std::mem::take(&mut self.state))?;
+
         let cert_chain = certs(&mut Cursor::new(cert_pem))
             .collect::<std::result::Result<Vec<_>, _>>()
             .map_err(|e| crate::error::Error::InternalServerError(e.to_string()))?;
@@ -172,10 +451,491 @@ where
             .with_no_client_auth()
             .with_single_cert(cert_chain, rustls::pki_types::PrivateKeyDer::Pkcs8(keys.remove(0)))
             .map_err(|e| crate::error::Error::InternalServerError(e.to_string()))?;
-        
-        let http3_server = Http3Server::new(self.service);
-        http3_server.listen(addr, tls_config).await?;
-        
-        Ok(())
+
+        let listener = TcpListener::bind(addr).await?;
+        println!("HTTP/1.1 server listening on http://{}", addr);
+
+        // Both listeners stop on the shutdown signal, or as soon as either fails
+        let (stop, _) = watch::channel(());
+        let stopped = |mut stop: watch::Receiver<()>| -> ShutdownSignal {
+            Box::pin(async move {
+                let _ = stop.changed().await;
+            })
+        };
+        let tcp = accept_loop(
+            listener,
+            self.service.clone(),
+            self.connection_options(),
+            plaintext,
+            stopped(stop.subscribe()),
+        );
+        let h3 = Http3Server::new(self.service)
+            .state(self.state)
+            .graceful_shutdown(stopped(stop.subscribe()), self.shutdown_timeout)
+            .listen(addr, tls_config);
+
+        let both = async {
+            tokio::join!(
+                async {
+                    let result = tcp.await;
+                    let _ = stop.send(());
+                    result
+                },
+                async {
+                    let result = h3.await;
+                    let _ = stop.send(());
+                    result
+                },
+            )
+        };
+        tokio::pin!(both);
+        let signal = self.shutdown.unwrap_or_else(|| Box::pin(std::future::pending()));
+        let (tcp, h3) = tokio::select! {
+            results = &mut both => results,
+            _ = signal => {
+                let _ = stop.send(());
+                both.await
+            }
+        };
+
+        for hook in self.hooks {
+            hook().await;
+        }
+        tcp?;
+        h3
+    }
+}
+
+/// Completes on Ctrl+C, or SIGTERM on Unix
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sig) => {
+                sig.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::router::Router;
+    use hyper_util::rt::{TokioExecutor, TokioIo};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    #[tokio::test]
+    async fn test_connections_carry_remote_addr_and_trusted_proxies() {
+        let mut router = Router::new();
+        router.get("/ip", |remote: crate::extract::RemoteAddr, client: crate::ClientIp| async move {
+            format!("{} {}", remote.0.ip(), client.0)
+        });
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let addr = listener.local_addr().expect("addr");
+        let proxies = TrustedProxies::new(["127.0.0.0/8"]).unwrap();
+        tokio::spawn(Server::new(router).trusted_proxies(proxies).serve(listener));
+
+        let mut stream = TcpStream::connect(addr).await.expect("connect");
+        stream
+            .write_all(b"GET /ip HTTP/1.1\r\nHost: localhost\r\nX-Forwarded-For: 203.0.113.7\r\nConnection: close\r\n\r\n")
+            .await
+            .expect("write");
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.expect("read");
+        assert!(response.ends_with("\r\n\r\n127.0.0.1 203.0.113.7"), "{response}");
+    }
+
+    #[test]
+    fn test_with_config_rejects_invalid_trusted_proxies() {
+        let mut config = oxidite_config::Config::default();
+        config.server.trusted_proxies = vec!["10.0.0.0/8".to_string(), "not-an-ip".to_string()];
+        let error = Server::new(Router::new()).with_config(&config).err().expect("invalid entry");
+        assert!(error.to_string().contains("'not-an-ip'"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_server_state_and_startup_check() {
+        let mut router = Router::new();
+        router.get("/", |crate::State(name): crate::State<&'static str>, crate::State(n): crate::State<usize>| async move {
+            format!("{} {}", name, n)
+        });
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let err = Server::new(router.clone()).serve(listener).await.expect_err("missing state");
+        assert!(err.to_string().contains("requires state `&str`"), "{err}");
+
+        // Router state wins over server state of the same type
+        router.state(2usize);
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let addr = listener.local_addr().expect("addr");
+        let server = Server::new(router)
+            .state("shop")
+            .substate(|name: &&'static str| name.len())
+            .expect("substate");
+        tokio::spawn(server.serve(listener));
+
+        let mut stream = TcpStream::connect(addr).await.expect("connect");
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .expect("write");
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.expect("read");
+        assert!(response.ends_with("\r\n\r\nshop 2"), "{response}");
+    }
+
+    #[tokio::test]
+    async fn test_graceful_shutdown_drains_in_flight_requests() {
+        let mut router = Router::new();
+        router.get("/slow", || async {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            "done"
+        });
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let addr = listener.local_addr().expect("addr");
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let hook_ran = Arc::new(AtomicBool::new(false));
+        let flag = hook_ran.clone();
+
+        let server = tokio::spawn(
+            Server::new(router)
+                .with_graceful_shutdown(async move {
+                    let _ = rx.await;
+                })
+                .on_shutdown(move || async move {
+                    flag.store(true, Ordering::SeqCst);
+                })
+                .serve(listener),
+        );
+
+        let mut stream = TcpStream::connect(addr).await.expect("connect");
+        stream
+            .write_all(b"GET /slow HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .expect("write");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        tx.send(()).expect("signal");
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.expect("read");
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        assert!(response.to_ascii_lowercase().contains("connection: close"), "{}", response);
+        assert!(response.ends_with("done"));
+
+        server.await.expect("join").expect("serve");
+        assert!(hook_ran.load(Ordering::SeqCst));
+        assert!(TcpStream::connect(addr).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_timeout_aborts_connections_before_hooks() {
+        struct SetOnDrop(Arc<AtomicBool>);
+        impl Drop for SetOnDrop {
+            fn drop(&mut self) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let dropped = Arc::new(AtomicBool::new(false));
+        let guard = dropped.clone();
+        let mut router = Router::new();
+        router.get("/hang", move || {
+            let guard = SetOnDrop(guard.clone());
+            async move {
+                let _guard = guard;
+                tokio::time::sleep(Duration::from_secs(30)).await;
+                "late"
+            }
+        });
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let addr = listener.local_addr().expect("addr");
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let aborted_before_hook = Arc::new(AtomicBool::new(false));
+        let (flag, seen) = (aborted_before_hook.clone(), dropped.clone());
+
+        let server = tokio::spawn(
+            Server::new(router)
+                .with_graceful_shutdown(async move {
+                    let _ = rx.await;
+                })
+                .shutdown_timeout(Duration::from_millis(100))
+                .on_shutdown(move || async move {
+                    flag.store(seen.load(Ordering::SeqCst), Ordering::SeqCst);
+                })
+                .serve(listener),
+        );
+
+        let mut stream = TcpStream::connect(addr).await.expect("connect");
+        stream
+            .write_all(b"GET /hang HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .expect("write");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        tx.send(()).expect("signal");
+
+        tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .expect("serve returns after the shutdown timeout")
+            .expect("join")
+            .expect("serve");
+        assert!(aborted_before_hook.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_plaintext_server_speaks_h2c() {
+        let mut router = Router::new();
+        router.get("/", |req: OxiditeRequest| async move { format!("{:?}", req.version()) });
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let addr = listener.local_addr().expect("addr");
+        tokio::spawn(Server::new(router).serve(listener));
+
+        let stream = TcpStream::connect(addr).await.expect("connect");
+        let (mut sender, conn) = hyper::client::conn::http2::handshake(TokioExecutor::new(), TokioIo::new(stream))
+            .await
+            .expect("h2 handshake");
+        tokio::spawn(conn);
+
+        let req = hyper::Request::builder()
+            .uri(format!("http://{}/", addr))
+            .body(http_body_util::Empty::<bytes::Bytes>::new())
+            .expect("request");
+        let res = sender.send_request(req).await.expect("response");
+        assert_eq!(res.status(), http::StatusCode::OK);
+        assert_eq!(res.version(), http::Version::HTTP_2);
+        let body = res.into_body().collect().await.expect("body").to_bytes();
+        assert_eq!(&body[..], b"HTTP/2.0");
+    }
+
+    fn limits() -> ServerLimits {
+        ServerLimits {
+            max_connections: None,
+            header_read_timeout: None,
+            keep_alive_timeout: None,
+            max_header_size: None,
+            max_body_size: None,
+        }
+    }
+
+    async fn spawn_server(limits: ServerLimits) -> SocketAddr {
+        let mut router = Router::new();
+        router.get("/", || async { "ok" });
+        router.post("/echo", |crate::extract::Json(value): crate::extract::Json<serde_json::Value>| async move {
+            crate::extract::Json(value)
+        });
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let addr = listener.local_addr().expect("addr");
+        tokio::spawn(Server::new(router).limits(limits).serve(listener));
+        addr
+    }
+
+    async fn read_once(stream: &mut TcpStream) -> Option<String> {
+        let mut buf = vec![0; 4096];
+        match tokio::time::timeout(Duration::from_millis(300), stream.read(&mut buf)).await {
+            Ok(Ok(n)) => Some(String::from_utf8_lossy(&buf[..n]).into_owned()),
+            _ => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_debug_flag_is_per_server() {
+        async fn failing() -> Result<&'static str> {
+            Err(Error::InternalServerError("pool exhausted".to_string()))
+        }
+
+        let mut addrs = Vec::new();
+        for debug in [true, false] {
+            let mut router = Router::new();
+            router.get("/", failing);
+            let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+            addrs.push(listener.local_addr().expect("addr"));
+            tokio::spawn(Server::new(router).debug(debug).serve(listener));
+        }
+
+        let mut responses = Vec::new();
+        for addr in addrs {
+            let mut stream = TcpStream::connect(addr).await.expect("connect");
+            stream
+                .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+                .await
+                .expect("write");
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.expect("read");
+            assert!(response.starts_with("HTTP/1.1 500"), "{}", response);
+            responses.push(response);
+        }
+        assert!(responses[0].contains("pool exhausted"), "{}", responses[0]);
+        assert!(!responses[1].contains("pool exhausted"), "{}", responses[1]);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_body_is_rejected() {
+        let addr = spawn_server(ServerLimits { max_body_size: Some(16), ..limits() }).await;
+
+        let body = format!(r#"{{"data":"{}"}}"#, "x".repeat(64));
+        let mut stream = TcpStream::connect(addr).await.expect("connect");
+        let request = format!(
+            "POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        stream.write_all(request.as_bytes()).await.expect("write");
+        let response = read_once(&mut stream).await.expect("response");
+        assert!(response.starts_with("HTTP/1.1 413"), "{}", response);
+        assert!(response.contains("payload_too_large"), "{}", response);
+
+        let mut stream = TcpStream::connect(addr).await.expect("connect");
+        stream
+            .write_all(b"POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: 7\r\n\r\n{\"a\":1}")
+            .await
+            .expect("write");
+        let response = read_once(&mut stream).await.expect("response");
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    }
+
+    #[tokio::test]
+    async fn test_max_connections_applies_backpressure() {
+        let addr = spawn_server(ServerLimits { max_connections: Some(1), ..limits() }).await;
+        let request = b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n";
+
+        let mut first = TcpStream::connect(addr).await.expect("connect");
+        first.write_all(request).await.expect("write");
+        assert!(read_once(&mut first).await.expect("first").starts_with("HTTP/1.1 200"));
+
+        // Sits in the accept backlog while the first connection is open
+        let mut second = TcpStream::connect(addr).await.expect("connect");
+        second.write_all(request).await.expect("write");
+        assert!(read_once(&mut second).await.is_none());
+
+        drop(first);
+        let mut buf = vec![0; 1024];
+        let n = tokio::time::timeout(Duration::from_secs(2), second.read(&mut buf))
+            .await
+            .expect("second served")
+            .expect("read");
+        assert!(String::from_utf8_lossy(&buf[..n]).starts_with("HTTP/1.1 200"));
+    }
+
+    #[tokio::test]
+    async fn test_idle_keep_alive_connections_are_closed() {
+        let addr = spawn_server(ServerLimits { keep_alive_timeout: Some(Duration::from_millis(100)), ..limits() }).await;
+
+        let mut stream = TcpStream::connect(addr).await.expect("connect");
+        stream.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").await.expect("write");
+        assert!(read_once(&mut stream).await.expect("response").starts_with("HTTP/1.1 200"));
+
+        let mut buf = [0; 16];
+        let n = tokio::time::timeout(Duration::from_secs(2), stream.read(&mut buf))
+            .await
+            .expect("closed by server")
+            .expect("read");
+        assert_eq!(n, 0);
+    }
+
+    #[tokio::test]
+    async fn test_from_std_listener_runs() {
+        let mut router = Router::new();
+        router.get("/", || async { "std" });
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+        let addr = listener.local_addr().expect("addr");
+        let server = Server::from_std_listener(router, listener).expect("server");
+        tokio::spawn(server.run());
+
+        let mut stream = TcpStream::connect(addr).await.expect("connect");
+        stream.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").await.expect("write");
+        let response = read_once(&mut stream).await.expect("response");
+        assert!(response.starts_with("HTTP/1.1 200") && response.ends_with("std"), "{}", response);
+
+        assert!(Server::new(Router::new()).run().await.is_err());
+    }
+
+    /// Fails every accept with the next error, last one first
+    struct FailingListener(Vec<std::io::Error>);
+
+    impl Listener for FailingListener {
+        type Io = TcpStream;
+
+        async fn accept(&mut self) -> std::io::Result<(TcpStream, Option<SocketAddr>)> {
+            Err(self.0.pop().expect("accept called after a fatal error"))
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_accept_backs_off_on_emfile_and_stops_on_fatal_errors() {
+        use std::io::{Error as IoError, ErrorKind};
+
+        let listener = FailingListener(vec![
+            IoError::from(ErrorKind::InvalidInput),
+            IoError::from(ErrorKind::ConnectionAborted),
+            IoError::from_raw_os_error(libc::EMFILE),
+        ]);
+        let started = std::time::Instant::now();
+        let result = Server::new(Router::new()).serve(listener).await;
+        assert!(result.is_err());
+        assert!(started.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_listen_unix_sets_permissions_and_cleans_up() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mut router = Router::new();
+        router.get("/", || async { "unix" });
+
+        let path = std::env::temp_dir().join(format!("oxidite-test-{}.sock", std::process::id()));
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let server = tokio::spawn(
+            Server::new(router)
+                .unix_permissions(0o600)
+                .with_graceful_shutdown(async move {
+                    let _ = rx.await;
+                })
+                .listen_unix(path.clone()),
+        );
+
+        let mut stream = loop {
+            match tokio::net::UnixStream::connect(&path).await {
+                Ok(stream) => break stream,
+                Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+            }
+        };
+        let mode = std::fs::metadata(&path).expect("socket").permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .expect("write");
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.expect("read");
+        assert!(response.starts_with("HTTP/1.1 200") && response.ends_with("unix"), "{}", response);
+
+        tx.send(()).expect("signal");
+        server.await.expect("join").expect("serve");
+        assert!(!path.exists());
     }
 }
@@ -0,0 +1,383 @@
+//! Connection handling shared by the plaintext and TLS servers.
+
+use super::listener::Listener;
+use super::{BodyAdapter, ShutdownSignal};
+use crate::client_ip::TrustedProxies;
+use crate::error::Error;
+use crate::extract::{BodyLimit, RemoteAddr};
+use crate::state::StateMap;
+use crate::types::{OxiditeRequest, OxiditeResponse};
+use hyper_util::rt::{TokioExecutor, TokioIo, TokioTimer};
+use hyper_util::server::conn::auto::Builder as AutoBuilder;
+use hyper_util::service::TowerToHyperService;
+use std::error::Error as StdError;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::{watch, Notify, Semaphore};
+use tokio::task::JoinSet;
+use tower_service::Service;
+
+/// HTTP/1.1 never buffers less than this, whatever `max_header_size` says
+const MIN_HTTP1_BUF_SIZE: usize = 8192;
+
+/// Pause before accepting again after running out of file descriptors or memory
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// HTTP protocol version
+#[derive(Debug, Clone, Copy, Default)]
+pub enum HttpVersion {
+    Http1,
+    Http2,
+    /// HTTP/1.1 or HTTP/2, detected from the connection preface (and ALPN over TLS)
+    #[default]
+    Auto,
+}
+
+/// HTTP/2 connection settings; `None` keeps hyper's defaults
+#[derive(Debug, Clone, Default)]
+pub struct Http2Settings {
+    pub max_concurrent_streams: Option<u32>,
+    pub initial_stream_window_size: Option<u32>,
+    pub initial_connection_window_size: Option<u32>,
+    /// Use BDP-based flow control, overriding the window sizes above
+    pub adaptive_window: bool,
+    pub max_frame_size: Option<u32>,
+    /// Interval between keep-alive pings; disabled when `None`
+    pub keep_alive_interval: Option<Duration>,
+    /// How long to wait for a ping acknowledgement before closing
+    pub keep_alive_timeout: Option<Duration>,
+}
+
+/// Connection limits and timeouts; `None` disables a limit
+///
+/// Defaults follow [`oxidite_config::ServerConfig::default`].
+#[derive(Debug, Clone)]
+pub struct ServerLimits {
+    /// Maximum concurrent connections; accepting pauses while at the limit
+    pub max_connections: Option<usize>,
+    /// Time a client has to send the request headers
+    pub header_read_timeout: Option<Duration>,
+    /// Close connections that have had no in-flight request for this long
+    pub keep_alive_timeout: Option<Duration>,
+    /// Maximum request header size in bytes (at least 8 KiB for HTTP/1.1)
+    pub max_header_size: Option<usize>,
+    /// Maximum request body size buffered by extractors such as `Json<T>`;
+    /// a `DefaultBodyLimit` layer overrides it
+    pub max_body_size: Option<usize>,
+}
+
+impl Default for ServerLimits {
+    fn default() -> Self {
+        Self::from(&oxidite_config::ServerConfig::default())
+    }
+}
+
+/// Zero disables the corresponding limit
+impl From<&oxidite_config::ServerConfig> for ServerLimits {
+    fn from(config: &oxidite_config::ServerConfig) -> Self {
+        let secs = |s: u64| (s > 0).then(|| Duration::from_secs(s));
+        let size = |n: usize| (n > 0).then_some(n);
+        Self {
+            max_connections: config.max_connections.and_then(size),
+            header_read_timeout: secs(config.header_read_timeout),
+            keep_alive_timeout: secs(config.keep_alive_timeout),
+            max_header_size: size(config.max_header_size),
+            max_body_size: size(config.max_body_size),
+        }
+    }
+}
+
+/// Everything needed to serve accepted connections
+#[derive(Clone)]
+pub(crate) struct ConnectionOptions {
+    builder: AutoBuilder<TokioExecutor>,
+    limits: ServerLimits,
+    shutdown_timeout: Duration,
+    trusted_proxies: Option<TrustedProxies>,
+    state: StateMap,
+    debug: bool,
+}
+
+impl ConnectionOptions {
+    pub(crate) fn new(
+        version: HttpVersion,
+        http2: &Http2Settings,
+        limits: ServerLimits,
+        shutdown_timeout: Duration,
+    ) -> Self {
+        let mut builder = AutoBuilder::new(TokioExecutor::new());
+        {
+            let mut h1 = builder.http1();
+            h1.timer(TokioTimer::new())
+                .header_read_timeout(limits.header_read_timeout);
+            if let Some(max) = limits.max_header_size {
+                h1.max_buf_size(max.max(MIN_HTTP1_BUF_SIZE));
+            }
+        }
+        {
+            let mut h2 = builder.http2();
+            h2.timer(TokioTimer::new())
+                .max_concurrent_streams(http2.max_concurrent_streams)
+                .initial_stream_window_size(http2.initial_stream_window_size)
+                .initial_connection_window_size(http2.initial_connection_window_size)
+                .adaptive_window(http2.adaptive_window)
+                .max_frame_size(http2.max_frame_size)
+                .keep_alive_interval(http2.keep_alive_interval);
+            if let Some(timeout) = http2.keep_alive_timeout {
+                h2.keep_alive_timeout(timeout);
+            }
+            if let Some(max) = limits.max_header_size {
+                h2.max_header_list_size(u32::try_from(max).unwrap_or(u32::MAX));
+            }
+        }
+        let builder = match version {
+            HttpVersion::Http1 => builder.http1_only(),
+            HttpVersion::Http2 => builder.http2_only(),
+            HttpVersion::Auto => builder,
+        };
+        Self {
+            builder,
+            limits,
+            shutdown_timeout,
+            trusted_proxies: None,
+            state: StateMap::default(),
+            debug: false,
+        }
+    }
+
+    /// Proxies whose forwarding headers `ClientIp` may use
+    pub(crate) fn trusted_proxies(mut self, proxies: Option<TrustedProxies>) -> Self {
+        self.trusted_proxies = proxies;
+        self
+    }
+
+    /// State registered on the server, shared by every request
+    pub(crate) fn state(mut self, state: StateMap) -> Self {
+        self.state = state;
+        self
+    }
+
+    /// Expose 5xx error details in problem responses
+    pub(crate) fn debug(mut self, enabled: bool) -> Self {
+        self.debug = enabled;
+        self
+    }
+}
+
+/// Accept loop shared by [`Server`](super::Server),
+/// [`SecureServer`](crate::tls::SecureServer) and the TCP side of `listen_h3`,
+/// for any [`Listener`]
+///
+/// `handshake` turns each accepted stream into the transport to serve (e.g. a
+/// TLS stream) plus extensions copied into every request on the connection,
+/// and runs on the connection's task, off the accept path. Once `signal`
+/// completes, connections are drained for up to the shutdown timeout.
+pub(crate) async fn accept_loop<L, S, H, Fut, IO>(
+    mut listener: L,
+    service: S,
+    options: ConnectionOptions,
+    handshake: H,
+    mut signal: ShutdownSignal,
+) -> std::io::Result<()>
+where
+    S: Service<OxiditeRequest, Response = OxiditeResponse, Error = Error> + Clone + Send + Sync + 'static,
+    S::Future: Send + 'static,
+    L: Listener,
+    H: Fn(L::Io) -> Fut,
+    Fut: Future<Output = std::io::Result<(IO, http::Extensions)>> + Send + 'static,
+    IO: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let ConnectionOptions { builder, limits, shutdown_timeout, trusted_proxies, state, debug } = options;
+    let permits = limits.max_connections.map(|max| Arc::new(Semaphore::new(max)));
+    let (shutdown_tx, _) = watch::channel(());
+    // Connection tasks are tracked so any still running after the shutdown
+    // timeout can be aborted before the caller moves on
+    let mut connections = JoinSet::new();
+    let mut result = Ok(());
+
+    loop {
+        while connections.try_join_next().is_some() {}
+
+        // Backpressure: stop accepting while all connection slots are taken
+        let permit = match &permits {
+            Some(permits) => tokio::select! {
+                permit = permits.clone().acquire_owned() => Some(permit.expect("connection semaphore closed")),
+                _ = &mut signal => break,
+            },
+            None => None,
+        };
+
+        let (stream, remote) = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(err) if is_connection_error(&err) => continue,
+                Err(err) if is_resource_exhausted(&err) => {
+                    eprintln!("Accept error: {}; retrying in {:?}", err, ACCEPT_BACKOFF);
+                    tokio::time::sleep(ACCEPT_BACKOFF).await;
+                    continue;
+                }
+                Err(err) => {
+                    result = Err(err);
+                    break;
+                }
+            },
+            _ = &mut signal => break,
+        };
+
+        let handshake = handshake(stream);
+        let service = service.clone();
+        let body_limit = Some(BodyLimit(limits.max_body_size));
+        let trusted_proxies = trusted_proxies.clone();
+        let state = state.clone();
+        let builder = builder.clone();
+        let keep_alive = limits.keep_alive_timeout;
+        let mut shutdown = shutdown_tx.subscribe();
+
+        connections.spawn(async move {
+            let _permit = permit;
+            let (io, mut extensions) = match handshake.await {
+                Ok(accepted) => accepted,
+                Err(err) => {
+                    eprintln!("Handshake error: {}", err);
+                    return;
+                }
+            };
+            if let Some(remote) = remote {
+                extensions.insert(RemoteAddr(remote));
+            }
+            if let Some(trusted_proxies) = trusted_proxies {
+                extensions.insert(trusted_proxies);
+            }
+            if !state.is_empty() {
+                extensions.insert(state);
+            }
+            let activity = Arc::new(Activity::default());
+            let service = BodyAdapter::new(service)
+                .body_limit(body_limit)
+                .connection(extensions)
+                .debug(debug)
+                .track(activity.clone());
+            let service = TowerToHyperService::new(service);
+
+            let conn = builder.serve_connection_with_upgrades(TokioIo::new(io), service);
+            tokio::pin!(conn);
+            let result = tokio::select! {
+                result = conn.as_mut() => result,
+                _ = shutdown.changed() => {
+                    conn.as_mut().graceful_shutdown();
+                    conn.await
+                }
+                _ = activity.idle_for(keep_alive) => {
+                    conn.as_mut().graceful_shutdown();
+                    conn.await
+                }
+            };
+            if let Err(err) = result {
+                log_connection_error(&*err);
+            }
+        });
+    }
+
+    drop(listener);
+    let _ = shutdown_tx.send(());
+    let drain = async { while connections.join_next().await.is_some() {} };
+    if tokio::time::timeout(shutdown_timeout, drain).await.is_err() {
+        eprintln!("Shutdown timeout elapsed; aborting {} open connection(s)", connections.len());
+        connections.abort_all();
+        while connections.join_next().await.is_some() {}
+    }
+    result
+}
+
+/// Accept errors that only concern the connection being accepted
+fn is_connection_error(err: &std::io::Error) -> bool {
+    use std::io::ErrorKind;
+    matches!(
+        err.kind(),
+        ErrorKind::ConnectionAborted | ErrorKind::ConnectionReset | ErrorKind::ConnectionRefused
+    )
+}
+
+/// Accept errors that clear up once connections close, e.g. `EMFILE`
+fn is_resource_exhausted(err: &std::io::Error) -> bool {
+    #[cfg(unix)]
+    {
+        matches!(
+            err.raw_os_error(),
+            Some(libc::EMFILE | libc::ENFILE | libc::ENOBUFS | libc::ENOMEM)
+        )
+    }
+    #[cfg(not(unix))]
+    {
+        err.kind() == std::io::ErrorKind::OutOfMemory
+    }
+}
+
+/// Handshake for unencrypted connections
+pub(crate) fn plaintext<IO>(stream: IO) -> std::future::Ready<std::io::Result<(IO, http::Extensions)>> {
+    std::future::ready(Ok((stream, http::Extensions::new())))
+}
+
+/// In-flight request count for one connection
+#[derive(Default)]
+pub(crate) struct Activity {
+    in_flight: AtomicUsize,
+    changed: Notify,
+}
+
+impl Activity {
+    /// Completes once no request has been in flight for `timeout`
+    async fn idle_for(&self, timeout: Option<Duration>) {
+        let Some(timeout) = timeout else {
+            return std::future::pending().await;
+        };
+        loop {
+            let changed = self.changed.notified();
+            if self.in_flight.load(Ordering::Acquire) == 0 {
+                tokio::select! {
+                    _ = tokio::time::sleep(timeout) => return,
+                    _ = changed => {}
+                }
+            } else {
+                changed.await;
+            }
+        }
+    }
+}
+
+/// Marks a request as in flight until dropped along with its response body
+pub(crate) struct ActivityGuard(Arc<Activity>);
+
+impl ActivityGuard {
+    pub(crate) fn new(activity: Arc<Activity>) -> Self {
+        activity.in_flight.fetch_add(1, Ordering::AcqRel);
+        activity.changed.notify_one();
+        Self(activity)
+    }
+}
+
+impl Drop for ActivityGuard {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::AcqRel);
+        self.0.changed.notify_one();
+    }
+}
+
+pub(crate) fn log_connection_error(err: &(dyn StdError + 'static)) {
+    // Only log actual server errors, not client errors like 404
+    let service_err = std::iter::successors(Some(err), |&e| e.source()).find_map(|e| e.downcast_ref::<Error>());
+    if let Some(service_err) = service_err {
+        if service_err.is_server_error() {
+            eprintln!("Server error: {}", service_err);
+        }
+    } else {
+        // Don't log if it's just a client disconnecting or similar
+        let err_msg = err.to_string();
+        if !err_msg.contains("NotFound") && !err_msg.contains("connection closed") {
+            eprintln!("Connection error: {}", err);
+        }
+    }
+}
@@ -2,7 +2,9 @@
 
 use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::sync::Notify;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinSet;
 use rustls::ServerConfig;
 use quinn::{Endpoint, ServerConfig as QuinnServerConfig};
 use h3::server::RequestStream;
@@ -11,11 +13,16 @@ use bytes::Bytes;
 use http::{Request, Response};
 use http_body_util::BodyExt;
 use crate::error::Result;
+use crate::state::StateMap;
+use super::ShutdownSignal;
 use crate::types::{OxiditeRequest, OxiditeResponse};
 use tower_service::Service;
 
 pub struct Http3Server<S> {
     service: S,
+    state: StateMap,
+    shutdown: Option<ShutdownSignal>,
+    shutdown_timeout: Duration,
 }
 
 impl<S> Http3Server<S>
@@ -28,10 +35,29 @@ where
     S::Future: Send + 'static,
 {
     pub fn new(service: S) -> Self {
-        Self { service }
+        Self {
+            service,
+            state: StateMap::default(),
+            shutdown: None,
+            shutdown_timeout: super::DEFAULT_SHUTDOWN_TIMEOUT,
+        }
+    }
+
+    /// State registered on the server, shared by every request
+    pub(crate) fn state(mut self, state: StateMap) -> Self {
+        self.state = state;
+        self
+    }
+
+    /// Stop accepting once `signal` completes, then give open connections
+    /// up to `timeout` to finish their in-flight requests
+    pub(crate) fn graceful_shutdown(mut self, signal: ShutdownSignal, timeout: Duration) -> Self {
+        self.shutdown = Some(signal);
+        self.shutdown_timeout = timeout;
+        self
     }
 
-    pub async fn listen(self, addr: SocketAddr, tls_config: ServerConfig) -> Result<()> {
+    pub async fn listen(mut self, addr: SocketAddr, tls_config: ServerConfig) -> Result<()> {
         let crypto = quinn::crypto::rustls::QuicServerConfig::try_from(Arc::new(tls_config))
             .map_err(|e| crate::error::Error::InternalServerError(e.to_string()))?;
         let quinn_config = QuinnServerConfig::with_crypto(Arc::new(crypto));
@@ -41,52 +67,76 @@ where
 
         println!("HTTP/3 server listening on https://{}", addr);
 
-        let notify_shutdown = Arc::new(Notify::new());
-        let (_shutdown_tx, mut shutdown_rx) = tokio::sync::mpsc::unbounded_channel::<()>();
+        let mut signal = self.shutdown.take().unwrap_or_else(|| Box::pin(std::future::pending()));
+        let (shutdown_tx, _) = watch::channel(());
+        let mut connections = JoinSet::new();
 
         loop {
+            while connections.try_join_next().is_some() {}
+
             tokio::select! {
                 conn = endpoint.accept() => {
-                    if let Some(conn) = conn {
+                    let Some(conn) = conn else { break };
+                    let service = self.service.clone();
+                    let state = self.state.clone();
+                    let shutdown = shutdown_tx.subscribe();
+
+                    connections.spawn(async move {
                         let quic_conn = match conn.await {
                             Ok(conn) => conn,
                             Err(e) => {
                                 eprintln!("Connection error: {}", e);
-                                continue;
+                                return;
                             }
                         };
-
-                        let service = self.service.clone();
-                        let _notify = notify_shutdown.clone();
-                        
-                        tokio::spawn(async move {
-                            if let Err(e) = Self::handle_connection(quic_conn, service).await {
-                                eprintln!("Connection handler error: {}", e);
-                            }
-                        });
-                    }
-                }
-                _ = shutdown_rx.recv() => {
-                    println!("Shutting down HTTP/3 server...");
-                    endpoint.close(0u32.into(), b"shutdown");
-                    break;
+                        if let Err(e) = Self::handle_connection(quic_conn, service, state, shutdown).await {
+                            eprintln!("Connection handler error: {}", e);
+                        }
+                    });
                 }
+                _ = &mut signal => break,
             }
         }
 
+        println!("Shutting down HTTP/3 server...");
+        endpoint.set_server_config(None);
+        let _ = shutdown_tx.send(());
+        let drain = async { while connections.join_next().await.is_some() {} };
+        if tokio::time::timeout(self.shutdown_timeout, drain).await.is_err() {
+            eprintln!("Shutdown timeout elapsed; aborting {} open HTTP/3 connection(s)", connections.len());
+            connections.abort_all();
+            while connections.join_next().await.is_some() {}
+        }
+        endpoint.close(0u32.into(), b"shutdown");
+
         Ok(())
     }
 
     async fn handle_connection(
         quic_conn: quinn::Connection,
         service: S,
+        state: StateMap,
+        mut shutdown: watch::Receiver<()>,
     ) -> Result<()> {
         let mut h3_conn = h3::server::Connection::new(h3_quinn::Connection::new(quic_conn))
             .await
             .map_err(|e| crate::error::Error::InternalServerError(e.to_string()))?;
+        let mut draining = false;
 
         loop {
-            match h3_conn.accept().await {
+            let accepted = tokio::select! {
+                accepted = h3_conn.accept() => accepted,
+                _ = shutdown.changed(), if !draining => {
+                    // GOAWAY: no new requests, let the peer close once idle
+                    draining = true;
+                    h3_conn
+                        .shutdown(0)
+                        .await
+                        .map_err(|e| crate::error::Error::InternalServerError(e.to_string()))?;
+                    continue;
+                }
+            };
+            match accepted {
                 Ok(Some(resolver)) => {
                     let (req, stream) = match resolver.resolve_request().await {
                         Ok(res) => res,
@@ -95,7 +145,7 @@ where
                             continue;
                         }
                     };
-                    Self::handle_request(req, stream, service.clone()).await?;
+                    Self::handle_request(req, stream, service.clone(), &state).await?;
                 }
                 Ok(None) => break, // Connection closed
                 Err(e) => {
@@ -112,6 +162,7 @@ where
         h3_request: Request<()>,
         mut stream: RequestStream<h3_quinn::BidiStream<Bytes>, Bytes>,
         mut service: S,
+        state: &StateMap,
     ) -> Result<()> {
         // Convert H3 request to OxiditeRequest
         let (parts, _) = h3_request.into_parts();
@@ -119,7 +170,10 @@ where
         // For simplicity, we'll create a basic request body
         let body = http_body_util::Full::new(Bytes::new()).map_err(|e| match e {}).boxed();
         
-        let oxidite_req = Request::from_parts(parts, body);
+        let mut oxidite_req = Request::from_parts(parts, body);
+        if !state.is_empty() {
+            oxidite_req.extensions_mut().insert(state.clone());
+        }
 
         // Process with the service
         let response = service.call(oxidite_req).await
@@ -0,0 +1,184 @@
+//! Listener types the accept loop can serve from.
+
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream};
+
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
+
+/// Source of incoming connections
+pub(crate) trait Listener: Send + 'static {
+    type Io: AsyncRead + AsyncWrite + Unpin + Send + 'static;
+
+    /// Accept a connection along with the peer's address, if it has one
+    fn accept(&mut self) -> impl Future<Output = io::Result<(Self::Io, Option<SocketAddr>)>> + Send;
+}
+
+impl Listener for TcpListener {
+    type Io = TcpStream;
+
+    async fn accept(&mut self) -> io::Result<(TcpStream, Option<SocketAddr>)> {
+        TcpListener::accept(self).await.map(|(stream, addr)| (stream, Some(addr)))
+    }
+}
+
+#[cfg(unix)]
+impl Listener for UnixListener {
+    type Io = UnixStream;
+
+    async fn accept(&mut self) -> io::Result<(UnixStream, Option<SocketAddr>)> {
+        UnixListener::accept(self).await.map(|(stream, _)| (stream, None))
+    }
+}
+
+/// A listener bound ahead of time, e.g. inherited from systemd
+pub(crate) enum Bound {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+impl Bound {
+    pub(crate) fn from_std_tcp(listener: std::net::TcpListener) -> io::Result<Self> {
+        listener.set_nonblocking(true)?;
+        TcpListener::from_std(listener).map(Bound::Tcp)
+    }
+
+    #[cfg(unix)]
+    pub(crate) fn from_std_unix(listener: std::os::unix::net::UnixListener) -> io::Result<Self> {
+        listener.set_nonblocking(true)?;
+        UnixListener::from_std(listener).map(Bound::Unix)
+    }
+
+    /// Wrap an inherited listening socket, detecting TCP vs Unix
+    ///
+    /// # Safety
+    /// `fd` must be an open listening socket not owned by anything else.
+    #[cfg(unix)]
+    pub(crate) unsafe fn from_raw_fd(fd: std::os::unix::io::RawFd) -> io::Result<Self> {
+        use std::os::unix::io::{FromRawFd, IntoRawFd};
+
+        let tcp = std::net::TcpListener::from_raw_fd(fd);
+        // std only understands inet addresses, so this fails for Unix sockets
+        if tcp.local_addr().is_ok() {
+            return Self::from_std_tcp(tcp);
+        }
+        let unix = std::os::unix::net::UnixListener::from_raw_fd(tcp.into_raw_fd());
+        unix.local_addr()?;
+        Self::from_std_unix(unix)
+    }
+}
+
+/// First descriptor passed by systemd socket activation
+#[cfg(unix)]
+const SD_LISTEN_FDS_START: std::os::unix::io::RawFd = 3;
+
+/// Set once the inherited socket has been wrapped, so it is never owned twice
+#[cfg(unix)]
+static LISTEN_FD_TAKEN: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// Take the first socket passed via systemd's `LISTEN_FDS` protocol
+///
+/// The environment is only read: changing it is unsound once other threads
+/// run. Child processes still see `LISTEN_PID`, which names this process, so
+/// they ignore it; the descriptor itself is marked close-on-exec.
+#[cfg(unix)]
+pub(crate) fn take_listen_fd() -> io::Result<Bound> {
+    use std::sync::atomic::Ordering;
+
+    let pid = std::env::var("LISTEN_PID").ok().and_then(|p| p.parse::<u32>().ok());
+    let fds = std::env::var("LISTEN_FDS").ok().and_then(|n| n.parse::<u32>().ok());
+    if pid != Some(std::process::id()) || fds.unwrap_or(0) == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "no socket passed via LISTEN_FDS for this process",
+        ));
+    }
+    if LISTEN_FD_TAKEN.swap(true, Ordering::SeqCst) {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "the socket passed via LISTEN_FDS was already taken",
+        ));
+    }
+    // SAFETY: systemd hands the process ownership of descriptors starting at 3,
+    // and the flag above ensures it is wrapped only once
+    unsafe {
+        if libc::fcntl(SD_LISTEN_FDS_START, libc::F_SETFD, libc::FD_CLOEXEC) == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Bound::from_raw_fd(SD_LISTEN_FDS_START)
+    }
+}
+
+/// Bind a Unix socket at `path` that never exists with looser permissions than `mode`
+///
+/// The socket is bound inside a private directory, given its mode and then
+/// renamed into place; changing the umask instead would affect every thread.
+#[cfg(unix)]
+pub(crate) fn bind_unix(path: &std::path::Path, mode: Option<u32>) -> io::Result<UnixListener> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    let Some(mode) = mode else {
+        return UnixListener::bind(path);
+    };
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "socket path has no file name"))?;
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => std::path::Path::new("."),
+    };
+    let mut private = file_name.to_os_string();
+    private.push(format!(".{}.tmp", std::process::id()));
+    let private = parent.join(private);
+    std::fs::DirBuilder::new().mode(0o700).create(&private)?;
+
+    let staged = private.join(file_name);
+    let result = UnixListener::bind(&staged).and_then(|listener| {
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(mode))?;
+        std::fs::rename(&staged, path)?;
+        Ok(listener)
+    });
+    let _ = std::fs::remove_file(&staged);
+    let _ = std::fs::remove_dir(&private);
+    result
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::io::IntoRawFd;
+
+    #[tokio::test]
+    async fn test_inherited_fd_kind_is_detected() {
+        let tcp = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let bound = unsafe { Bound::from_raw_fd(tcp.into_raw_fd()) }.unwrap();
+        assert!(matches!(bound, Bound::Tcp(_)));
+
+        let path = std::env::temp_dir().join(format!("oxidite-fd-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let unix = std::os::unix::net::UnixListener::bind(&path).unwrap();
+        let bound = unsafe { Bound::from_raw_fd(unix.into_raw_fd()) }.unwrap();
+        assert!(matches!(bound, Bound::Unix(_)));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_bind_unix_applies_mode_before_exposing_socket() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("oxidite-bind-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("app.sock");
+        let _listener = bind_unix(&path, Some(0o640)).unwrap();
+
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+        let entries: Vec<_> = std::fs::read_dir(&dir).unwrap().map(|e| e.unwrap().file_name()).collect();
+        assert_eq!(entries, ["app.sock"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
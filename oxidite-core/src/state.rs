@@ -0,0 +1,132 @@
+//! Application state shared with handlers through [`State`](crate::State).
+
+use crate::error::{Error, Result};
+use crate::router::{RouteError, Router};
+use crate::versioning::VersionedRouter;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// A state type an extractor reads, checked when the server starts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StateType {
+    id: TypeId,
+    name: &'static str,
+}
+
+impl StateType {
+    pub fn of<T: 'static>() -> Self {
+        Self {
+            id: TypeId::of::<T>(),
+            name: std::any::type_name::<T>(),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+/// State values registered with `Router::state` or `Server::state`, one per type
+#[derive(Clone, Default)]
+pub(crate) struct StateMap(Arc<HashMap<TypeId, Arc<dyn Any + Send + Sync>>>);
+
+impl StateMap {
+    pub(crate) fn insert<T: Clone + Send + Sync + 'static>(&mut self, value: T) {
+        Arc::make_mut(&mut self.0).insert(TypeId::of::<T>(), Arc::new(value));
+    }
+
+    /// Register a value computed from the already registered `P`
+    pub(crate) fn derive<P, T>(&mut self, derive: impl FnOnce(&P) -> T) -> std::result::Result<(), RouteError>
+    where
+        P: 'static,
+        T: Clone + Send + Sync + 'static,
+    {
+        let parent = self.get::<P>().ok_or_else(|| RouteError::MissingParentState {
+            state: std::any::type_name::<T>().to_string(),
+            parent: std::any::type_name::<P>().to_string(),
+        })?;
+        let value = derive(parent);
+        self.insert(value);
+        Ok(())
+    }
+
+    pub(crate) fn get<T: 'static>(&self) -> Option<&T> {
+        self.0.get(&TypeId::of::<T>()).and_then(|value| value.as_ref().downcast_ref())
+    }
+
+    pub(crate) fn contains(&self, ty: &StateType) -> bool {
+        self.0.contains_key(&ty.id)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// These values, plus those of `other` for types not registered here
+    pub(crate) fn merged(&self, other: &StateMap) -> StateMap {
+        if other.is_empty() {
+            return self.clone();
+        }
+        if self.is_empty() {
+            return other.clone();
+        }
+        let mut merged = (*other.0).clone();
+        merged.extend(self.0.iter().map(|(id, value)| (*id, value.clone())));
+        StateMap(Arc::new(merged))
+    }
+}
+
+/// Fail if `service` is a router with a handler whose state was never
+/// registered on it or in `state`, otherwise hand `state` to the router's
+/// routes once so requests need no merging
+///
+/// Returns the state connections still have to carry, which is empty when a
+/// router took it. Routers wrapped in other services cannot be inspected;
+/// call [`Router::check_state`] on them before wrapping.
+pub(crate) fn prepare_service<S: 'static>(service: &mut S, state: StateMap) -> Result<StateMap> {
+    let service: &mut dyn Any = service;
+    let checked = if let Some(router) = service.downcast_mut::<Router>() {
+        router.check_state_with(&state).map(|()| router.resolve_state(&state))
+    } else if let Some(router) = service.downcast_mut::<VersionedRouter>() {
+        router.check_state_with(&state).map(|()| router.resolve_state(&state))
+    } else {
+        return Ok(state);
+    };
+    checked
+        .map(|()| StateMap::default())
+        .map_err(|err| Error::InternalServerError(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone)]
+    struct App {
+        name: String,
+    }
+
+    #[test]
+    fn test_derive_and_merge() {
+        let mut state = StateMap::default();
+        state.insert(App { name: "shop".to_string() });
+        state.derive(|app: &App| app.name.len()).unwrap();
+        assert_eq!(state.get::<usize>(), Some(&4));
+        assert!(state.contains(&StateType::of::<App>()));
+        assert!(!state.contains(&StateType::of::<u8>()));
+
+        let mut outer = StateMap::default();
+        outer.insert(1usize);
+        outer.insert(7u8);
+        let merged = state.merged(&outer);
+        assert_eq!(merged.get::<usize>(), Some(&4));
+        assert_eq!(merged.get::<u8>(), Some(&7));
+    }
+
+    #[test]
+    fn test_derive_requires_parent() {
+        let err = StateMap::default().derive(|app: &App| app.name.clone()).expect_err("no parent");
+        assert!(matches!(err, RouteError::MissingParentState { .. }));
+    }
+}
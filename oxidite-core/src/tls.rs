@@ -1,27 +1,78 @@
 //! HTTPS and HTTP/2 support for Oxidite
 
+use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::time::Duration;
 use tokio::net::TcpListener;
-use hyper::server::conn::{http1, http2};
-use hyper_util::rt::TokioIo;
-use hyper_util::service::TowerToHyperService;
-use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::rustls::crypto::CryptoProvider;
+use tokio_rustls::rustls::server::{ClientHello, ResolvesServerCert, WebPkiClientVerifier};
+use tokio_rustls::rustls::sign::CertifiedKey;
+use tokio_rustls::rustls::{RootCertStore, ServerConfig};
 use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
 use tokio_rustls::TlsAcceptor;
 use std::sync::Arc;
 use std::fs::File;
 use std::io::BufReader;
+use crate::client_ip::TrustedProxies;
+use crate::state::{prepare_service, StateMap};
 use crate::error::{Error, Result};
 use crate::types::{OxiditeRequest, OxiditeResponse};
 use tower_service::Service;
 
+use crate::server::{accept_loop, plaintext, ConnectionOptions, ServerLimits, DEFAULT_SHUTDOWN_TIMEOUT};
 
-use crate::server::BodyAdapter;
+mod peer;
+mod reload;
+
+pub use crate::server::{Http2Settings, HttpVersion};
+pub use peer::{PeerCertificates, Subject};
+use reload::CertStore;
+
+/// Client certificate verification for mutual TLS
+#[derive(Debug, Clone, Default)]
+pub enum ClientAuth {
+    /// Do not ask clients for a certificate
+    #[default]
+    None,
+    /// Verify a certificate against the CA bundle at this path if the client sends one
+    Optional(String),
+    /// Reject clients without a certificate signed by the CA bundle at this path
+    Required(String),
+}
+
+/// An extra certificate served to clients that ask for `server_name` via SNI
+#[derive(Debug, Clone)]
+pub struct SniCertificate {
+    /// Host name, or a wildcard such as `*.example.com`
+    pub server_name: String,
+    pub cert_path: String,
+    pub key_path: String,
+}
 
 /// TLS configuration for HTTPS
+///
+/// Certificates are re-read on `SIGHUP`, and when their files change if
+/// [`reload_interval`](Self::reload_interval) is set. A failed reload keeps
+/// serving the previous certificates.
+///
+/// # Example
+/// ```ignore
+/// let tls = TlsConfig::new("certs/server.pem", "certs/server.key")
+///     .client_auth(ClientAuth::Required("certs/clients-ca.pem".into()))
+///     .sni("api.example.com", "certs/api.pem", "certs/api.key")
+///     .reload_interval(Duration::from_secs(30));
+///
+/// SecureServer::new(router).with_tls(tls).listen(addr).await?;
+/// ```
+#[derive(Debug, Clone)]
 pub struct TlsConfig {
+    /// Default certificate chain, used when no SNI certificate matches
     pub cert_path: String,
     pub key_path: String,
+    pub client_auth: ClientAuth,
+    pub sni: Vec<SniCertificate>,
+    /// How often to check the certificate files for changes; `None` only reloads on `SIGHUP`
+    pub reload_interval: Option<Duration>,
 }
 
 impl TlsConfig {
@@ -29,19 +80,122 @@ impl TlsConfig {
         Self {
             cert_path: cert_path.into(),
             key_path: key_path.into(),
+            client_auth: ClientAuth::None,
+            sni: Vec::new(),
+            reload_interval: None,
         }
     }
-    
+
+    /// Set client certificate verification
+    pub fn client_auth(mut self, client_auth: ClientAuth) -> Self {
+        self.client_auth = client_auth;
+        self
+    }
+
+    /// Serve a different certificate to clients requesting `server_name`
+    pub fn sni(
+        mut self,
+        server_name: impl Into<String>,
+        cert_path: impl Into<String>,
+        key_path: impl Into<String>,
+    ) -> Self {
+        self.sni.push(SniCertificate {
+            server_name: server_name.into(),
+            cert_path: cert_path.into(),
+            key_path: key_path.into(),
+        });
+        self
+    }
+
+    /// Poll the certificate files and reload them when they change
+    pub fn reload_interval(mut self, interval: Duration) -> Self {
+        self.reload_interval = Some(interval);
+        self
+    }
+
     /// Load certificates and private key
     pub fn load_config(&self) -> Result<ServerConfig> {
-        let certs = load_certs(&self.cert_path)?;
-        let key = load_private_key(&self.key_path)?;
-        
-        Ok(ServerConfig::builder()
-            .with_no_client_auth()
-            .with_single_cert(certs, key)
-            .map_err(|e| Error::InternalServerError(e.to_string()))?)
+        let provider = crypto_provider();
+        let mut resolver = SniResolver {
+            default: load_certified_key(&self.cert_path, &self.key_path, &provider)?,
+            by_name: HashMap::new(),
+        };
+        for sni in &self.sni {
+            let key = load_certified_key(&sni.cert_path, &sni.key_path, &provider)?;
+            resolver.by_name.insert(sni.server_name.to_ascii_lowercase(), key);
+        }
+
+        let builder = ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(|e| Error::InternalServerError(e.to_string()))?;
+        let builder = match &self.client_auth {
+            ClientAuth::None => builder.with_no_client_auth(),
+            ClientAuth::Optional(ca_path) | ClientAuth::Required(ca_path) => {
+                let mut roots = RootCertStore::empty();
+                for cert in load_certs(ca_path)? {
+                    roots.add(cert)
+                        .map_err(|e| Error::InternalServerError(format!("Invalid CA certificate: {}", e)))?;
+                }
+                let mut verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+                if matches!(self.client_auth, ClientAuth::Optional(_)) {
+                    verifier = verifier.allow_unauthenticated();
+                }
+                let verifier = verifier.build().map_err(|e| Error::InternalServerError(e.to_string()))?;
+                builder.with_client_cert_verifier(verifier)
+            }
+        };
+        Ok(builder.with_cert_resolver(Arc::new(resolver)))
+    }
+
+    /// Every file the configuration is loaded from
+    fn paths(&self) -> Vec<&str> {
+        let mut paths = vec![self.cert_path.as_str(), self.key_path.as_str()];
+        for sni in &self.sni {
+            paths.extend([sni.cert_path.as_str(), sni.key_path.as_str()]);
+        }
+        if let ClientAuth::Optional(ca_path) | ClientAuth::Required(ca_path) = &self.client_auth {
+            paths.push(ca_path);
+        }
+        paths
+    }
+}
+
+/// The process-wide rustls provider if one is installed, otherwise aws-lc-rs
+fn crypto_provider() -> Arc<CryptoProvider> {
+    CryptoProvider::get_default()
+        .cloned()
+        .unwrap_or_else(|| Arc::new(tokio_rustls::rustls::crypto::aws_lc_rs::default_provider()))
+}
+
+/// Picks a certificate by the SNI host name, falling back to the default
+#[derive(Debug)]
+struct SniResolver {
+    default: Arc<CertifiedKey>,
+    by_name: HashMap<String, Arc<CertifiedKey>>,
+}
+
+impl ResolvesServerCert for SniResolver {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        let Some(name) = client_hello.server_name().map(str::to_ascii_lowercase) else {
+            return Some(self.default.clone());
+        };
+        let wildcard = name.split_once('.').map(|(_, parent)| format!("*.{}", parent));
+        let key = self.by_name.get(&name)
+            .or_else(|| wildcard.and_then(|wildcard| self.by_name.get(&wildcard)))
+            .unwrap_or(&self.default);
+        Some(key.clone())
+    }
+}
+
+fn load_certified_key(cert_path: &str, key_path: &str, provider: &CryptoProvider) -> Result<Arc<CertifiedKey>> {
+    let certs = load_certs(cert_path)?;
+    if certs.is_empty() {
+        return Err(Error::InternalServerError(format!("No certificates found in {}", cert_path)));
     }
+    let key = load_private_key(key_path)?;
+    CertifiedKey::from_der(certs, key, provider)
+        .map(Arc::new)
+        .map_err(|e| Error::InternalServerError(format!("Invalid certificate or key {}: {}", cert_path, e)))
 }
 
 fn load_certs(path: &str) -> Result<Vec<CertificateDer<'static>>> {
@@ -70,19 +224,15 @@ fn load_private_key(path: &str) -> Result<PrivateKeyDer<'static>> {
     Err(Error::InternalServerError("No supported private key found".to_string()))
 }
 
-/// HTTP protocol version
-#[derive(Debug, Clone, Copy)]
-pub enum HttpVersion {
-    Http1,
-    Http2,
-    Auto, // Automatically negotiate
-}
-
 /// Server builder with HTTPS support
 pub struct SecureServer<S> {
     service: S,
     tls_config: Option<TlsConfig>,
     http_version: HttpVersion,
+    http2: Http2Settings,
+    limits: ServerLimits,
+    trusted_proxies: Option<TrustedProxies>,
+    debug: bool,
 }
 
 impl<S> SecureServer<S>
@@ -95,116 +245,237 @@ where
             service,
             tls_config: None,
             http_version: HttpVersion::Auto,
+            http2: Http2Settings::default(),
+            limits: ServerLimits::default(),
+            trusted_proxies: None,
+            debug: false,
         }
     }
-    
+
     /// Enable HTTPS with TLS certificates
     pub fn with_tls(mut self, tls_config: TlsConfig) -> Self {
         self.tls_config = Some(tls_config);
         self
     }
-    
+
     /// Set HTTP version
     pub fn with_http_version(mut self, version: HttpVersion) -> Self {
         self.http_version = version;
         self
     }
-    
+
+    /// Configure HTTP/2 connections
+    pub fn http2(mut self, settings: Http2Settings) -> Self {
+        self.http2 = settings;
+        self
+    }
+
+    /// Set connection limits and timeouts
+    pub fn limits(mut self, limits: ServerLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Believe forwarding headers from these proxies when extracting
+    /// [`ClientIp`](crate::ClientIp)
+    pub fn trusted_proxies(mut self, proxies: TrustedProxies) -> Self {
+        self.trusted_proxies = Some(proxies);
+        self
+    }
+
+    /// Expose 5xx error details in problem responses
+    pub fn debug(mut self, enabled: bool) -> Self {
+        self.debug = enabled;
+        self
+    }
+
     /// Start the server
     pub async fn listen(self, addr: SocketAddr) -> Result<()> {
-        if let Some(tls_config) = self.tls_config {
-            Self::listen_https(addr, self.service, tls_config, self.http_version).await
-        } else {
-            Self::listen_http(addr, self.service).await
-        }
-    }
-    
-    /// Listen on HTTP
-    async fn listen_http(addr: SocketAddr, service: S) -> Result<()> {
         let listener = TcpListener::bind(addr).await?;
-        println!("Listening on http://{}", addr);
-
-        loop {
-            let (stream, _) = listener.accept().await?;
-            let io = TokioIo::new(stream);
-            let service = service.clone();
-
-            tokio::task::spawn(async move {
-                let service = BodyAdapter::new(service);
-                let hyper_service = TowerToHyperService::new(service);
-                
-                if let Err(err) = http1::Builder::new()
-                    .serve_connection(io, hyper_service)
-                    .await
-                {
-                    eprintln!("Error serving connection: {:?}", err);
-                }
-            });
-        }
+        let scheme = if self.tls_config.is_some() { "https" } else { "http" };
+        println!("Listening on {}://{}", scheme, addr);
+        self.serve(listener).await
     }
-    
-    /// Listen on HTTPS
-    async fn listen_https(addr: SocketAddr, service: S, tls_config: TlsConfig, http_version: HttpVersion) -> Result<()> {
-        let server_config = tls_config.load_config()?;
-        let acceptor = TlsAcceptor::from(Arc::new(server_config));
-        
-        let listener = TcpListener::bind(addr).await?;
-        println!("Listening on https://{}", addr);
-
-        loop {
-            let (stream, _) = listener.accept().await?;
-            let acceptor = acceptor.clone();
-            let service = service.clone();
-
-            tokio::task::spawn(async move {
-                match acceptor.accept(stream).await {
-                    Ok(tls_stream) => {
-                        let io = TokioIo::new(tls_stream);
-                        let service = BodyAdapter::new(service);
-                        let hyper_service = TowerToHyperService::new(service);
-                        
-                        let result = match http_version {
-                            HttpVersion::Http1 => {
-                                http1::Builder::new()
-                                    .serve_connection(io, hyper_service)
-                                    .await
-                            }
-                            HttpVersion::Http2 => {
-                                http2::Builder::new(TokioExecutor)
-                                    .serve_connection(io, hyper_service)
-                                    .await
-                            }
-                            HttpVersion::Auto => {
-                                // Use HTTP/1.1 by default, upgrade to HTTP/2 if requested
-                                http1::Builder::new()
-                                    .serve_connection(io, hyper_service)
-                                    .await
-                            }
-                        };
-                        
-                        if let Err(err) = result {
-                            eprintln!("Error serving TLS connection: {:?}", err);
-                        }
-                    }
-                    Err(err) => {
-                        eprintln!("TLS accept error: {:?}", err);
-                    }
+
+    async fn serve(mut self, listener: TcpListener) -> Result<()> {
+        prepare_service(&mut self.service, StateMap::default())?;
+        let options = ConnectionOptions::new(self.http_version, &self.http2, self.limits, DEFAULT_SHUTDOWN_TIMEOUT)
+            .trusted_proxies(self.trusted_proxies)
+            .debug(self.debug);
+        let signal = Box::pin(std::future::pending());
+
+        let Some(tls_config) = self.tls_config else {
+            return Ok(accept_loop(listener, self.service, options, plaintext, signal).await?);
+        };
+
+        let reload_interval = tls_config.reload_interval;
+        let store = Arc::new(CertStore::new(tls_config, alpn_protocols(self.http_version))?);
+        let watcher = tokio::spawn(store.clone().watch(reload_interval));
+        let handshake = move |stream| {
+            // Each handshake picks up the most recently loaded certificates
+            let acceptor = TlsAcceptor::from(store.current());
+            async move {
+                let stream = acceptor.accept(stream).await?;
+                let mut extensions = http::Extensions::new();
+                if let Some(chain) = stream.get_ref().1.peer_certificates() {
+                    extensions.insert(PeerCertificates::new(chain.to_vec()));
                 }
-            });
-        }
+                Ok((stream, extensions))
+            }
+        };
+        let result = accept_loop(listener, self.service, options, handshake, signal).await;
+        watcher.abort();
+        Ok(result?)
+    }
+}
+
+/// ALPN protocols to advertise for a protocol choice
+fn alpn_protocols(version: HttpVersion) -> Vec<Vec<u8>> {
+    match version {
+        HttpVersion::Http1 => vec![b"http/1.1".to_vec()],
+        HttpVersion::Http2 => vec![b"h2".to_vec()],
+        HttpVersion::Auto => vec![b"h2".to_vec(), b"http/1.1".to_vec()],
     }
 }
 
-// Executor for HTTP/2
-#[derive(Clone)]
-struct TokioExecutor;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::router::Router;
+    use crate::types::OxiditeResponse;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::rustls::pki_types::ServerName;
+    use tokio_rustls::rustls::ClientConfig;
+    use tokio_rustls::TlsConnector;
 
-impl<F> hyper::rt::Executor<F> for TokioExecutor
-where
-    F: std::future::Future + Send + 'static,
-    F::Output: Send + 'static,
-{
-    fn execute(&self, fut: F) {
-        tokio::task::spawn(fut);
+    fn fixture(name: &str) -> String {
+        format!("{}/src/tls/testdata/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    /// Client config trusting the test CA, optionally presenting the client certificate
+    fn client_config(with_cert: bool) -> ClientConfig {
+        let mut roots = RootCertStore::empty();
+        for cert in load_certs(&fixture("ca.pem")).unwrap() {
+            roots.add(cert).unwrap();
+        }
+        let builder = ClientConfig::builder_with_provider(crypto_provider())
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots);
+        let mut config = if with_cert {
+            let certs = load_certs(&fixture("client.pem")).unwrap();
+            let key = load_private_key(&fixture("client.key")).unwrap();
+            builder.with_client_auth_cert(certs, key).unwrap()
+        } else {
+            builder.with_no_client_auth()
+        };
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        config
+    }
+
+    async fn start(tls: TlsConfig) -> SocketAddr {
+        let mut router = Router::new();
+        router.get("/whoami", |cert: Option<PeerCertificates>| async move {
+            let subject = cert.and_then(|cert| cert.subject()).map(|s| s.to_string());
+            Ok::<_, Error>(OxiditeResponse::text(subject.unwrap_or_else(|| "anonymous".to_string())))
+        });
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(SecureServer::new(router).with_tls(tls).serve(listener));
+        addr
+    }
+
+    /// Connect as `server_name`, returning the server's leaf certificate and
+    /// the `/whoami` response body
+    async fn whoami(
+        addr: SocketAddr,
+        server_name: &str,
+        with_cert: bool,
+    ) -> std::io::Result<(CertificateDer<'static>, String)> {
+        let connector = TlsConnector::from(Arc::new(client_config(with_cert)));
+        let tcp = tokio::net::TcpStream::connect(addr).await?;
+        let name = ServerName::try_from(server_name.to_string()).unwrap();
+        let mut tls = connector.connect(name, tcp).await?;
+        let leaf = tls.get_ref().1.peer_certificates().unwrap()[0].clone();
+
+        tls.write_all(b"GET /whoami HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n").await?;
+        let mut response = String::new();
+        tls.read_to_string(&mut response).await?;
+        let body = response.split("\r\n\r\n").nth(1).unwrap_or_default().to_string();
+        Ok((leaf, body))
+    }
+
+    fn leaf(name: &str) -> CertificateDer<'static> {
+        load_certs(&fixture(name)).unwrap().remove(0)
+    }
+
+    #[tokio::test]
+    async fn test_required_client_auth() {
+        let tls = TlsConfig::new(fixture("server_a.pem"), fixture("server_a.key"))
+            .client_auth(ClientAuth::Required(fixture("ca.pem")));
+        let addr = start(tls).await;
+
+        let (_, body) = whoami(addr, "localhost", true).await.unwrap();
+        assert_eq!(body, "CN=service-a, O=Oxidite, C=KE");
+
+        // TLS 1.3 reports the rejected certificate once the server's alert arrives
+        assert!(whoami(addr, "localhost", false).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_optional_client_auth() {
+        let tls = TlsConfig::new(fixture("server_a.pem"), fixture("server_a.key"))
+            .client_auth(ClientAuth::Optional(fixture("ca.pem")));
+        let addr = start(tls).await;
+
+        let (_, body) = whoami(addr, "localhost", true).await.unwrap();
+        assert_eq!(body, "CN=service-a, O=Oxidite, C=KE");
+        let (_, body) = whoami(addr, "localhost", false).await.unwrap();
+        assert_eq!(body, "anonymous");
+    }
+
+    #[tokio::test]
+    async fn test_sni_selects_certificate() {
+        let tls = TlsConfig::new(fixture("server_a.pem"), fixture("server_a.key"))
+            .sni("b.test", fixture("server_b.pem"), fixture("server_b.key"));
+        let addr = start(tls).await;
+
+        let (cert, _) = whoami(addr, "a.test", false).await.unwrap();
+        assert_eq!(cert, leaf("server_a.pem"));
+        let (cert, _) = whoami(addr, "b.test", false).await.unwrap();
+        assert_eq!(cert, leaf("server_b.pem"));
+    }
+
+    #[tokio::test]
+    async fn test_certificates_reload_on_change() {
+        let dir = std::env::temp_dir().join(format!("oxidite-tls-reload-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let cert_path = dir.join("server.pem").to_string_lossy().into_owned();
+        let key_path = dir.join("server.key").to_string_lossy().into_owned();
+        std::fs::copy(fixture("server_a.pem"), &cert_path).unwrap();
+        std::fs::copy(fixture("server_a.key"), &key_path).unwrap();
+
+        let tls = TlsConfig::new(&cert_path, &key_path).reload_interval(Duration::from_millis(20));
+        let addr = start(tls).await;
+        let (cert, _) = whoami(addr, "localhost", false).await.unwrap();
+        assert_eq!(cert, leaf("server_a.pem"));
+
+        // A half-written pair fails to load and keeps the old certificate
+        std::fs::copy(fixture("server_b.pem"), &cert_path).unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let (cert, _) = whoami(addr, "localhost", false).await.unwrap();
+        assert_eq!(cert, leaf("server_a.pem"));
+
+        std::fs::copy(fixture("server_b.key"), &key_path).unwrap();
+        let mut cert = leaf("server_a.pem");
+        for _ in 0..100 {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            cert = whoami(addr, "localhost", false).await.unwrap().0;
+            if cert == leaf("server_b.pem") {
+                break;
+            }
+        }
+        assert_eq!(cert, leaf("server_b.pem"));
+        let _ = std::fs::remove_dir_all(&dir);
     }
 }
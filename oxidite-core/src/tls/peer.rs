@@ -0,0 +1,239 @@
+//! Client certificates verified during a mutual TLS handshake.
+
+use crate::error::{Error, Result};
+use crate::extract::FromRequestParts;
+use http::request::Parts;
+use std::fmt;
+use std::sync::Arc;
+use tokio_rustls::rustls::pki_types::CertificateDer;
+
+/// Certificate chain the client presented and the server verified
+///
+/// Only available on connections using [`ClientAuth`](super::ClientAuth).
+/// Extracting it fails with 401 when the client sent no certificate; use
+/// `Option<PeerCertificates>` where certificates are optional.
+///
+/// # Example
+/// ```ignore
+/// async fn whoami(cert: PeerCertificates) -> Result<Response> {
+///     let subject = cert.subject().ok_or_else(|| Error::Forbidden("Unreadable certificate".into()))?;
+///     Ok(Response::text(subject.common_name().unwrap_or_default().to_string()))
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct PeerCertificates(Arc<[CertificateDer<'static>]>);
+
+impl PeerCertificates {
+    pub(crate) fn new(chain: Vec<CertificateDer<'static>>) -> Self {
+        Self(chain.into())
+    }
+
+    /// The full chain, leaf certificate first
+    pub fn chain(&self) -> &[CertificateDer<'static>] {
+        &self.0
+    }
+
+    /// The client's own certificate
+    pub fn leaf(&self) -> &CertificateDer<'static> {
+        &self.0[0]
+    }
+
+    /// Subject of the leaf certificate, or `None` if it cannot be parsed
+    pub fn subject(&self) -> Option<Subject> {
+        parse_subject(self.leaf())
+    }
+}
+
+impl FromRequestParts for PeerCertificates {
+    async fn from_request_parts(parts: &mut Parts) -> Result<Self> {
+        parts.extensions.get::<Self>()
+            .cloned()
+            .ok_or_else(|| Error::Unauthorized("Client certificate required".to_string()))
+    }
+}
+
+/// Distinguished name of a certificate subject
+///
+/// Attributes keep the order they have in the certificate. Displays as
+/// `CN=service-a, O=Example, C=KE`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subject {
+    attributes: Vec<(String, String)>,
+}
+
+impl Subject {
+    /// Attribute short names (`CN`, `O`, ...) or dotted OIDs, with their values
+    pub fn attributes(&self) -> &[(String, String)] {
+        &self.attributes
+    }
+
+    /// First value of an attribute, e.g. `get("OU")`
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.attributes.iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn common_name(&self) -> Option<&str> {
+        self.get("CN")
+    }
+}
+
+impl fmt::Display for Subject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (key, value)) in self.attributes.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}={}", key, value)?;
+        }
+        Ok(())
+    }
+}
+
+const SEQUENCE: u8 = 0x30;
+const SET: u8 = 0x31;
+const OID: u8 = 0x06;
+const INTEGER: u8 = 0x02;
+const EXPLICIT_VERSION: u8 = 0xa0;
+
+/// Read the subject out of a DER certificate's `tbsCertificate`
+fn parse_subject(cert: &[u8]) -> Option<Subject> {
+    let (cert, _) = expect(SEQUENCE, cert)?;
+    let (tbs, _) = expect(SEQUENCE, cert)?;
+    let mut rest = tbs;
+    if rest.first() == Some(&EXPLICIT_VERSION) {
+        rest = read_tlv(rest)?.2;
+    }
+    rest = expect(INTEGER, rest)?.1; // serialNumber
+    rest = expect(SEQUENCE, rest)?.1; // signature
+    rest = expect(SEQUENCE, rest)?.1; // issuer
+    rest = expect(SEQUENCE, rest)?.1; // validity
+    let (mut name, _) = expect(SEQUENCE, rest)?;
+
+    let mut attributes = Vec::new();
+    while !name.is_empty() {
+        let (mut rdn, tail) = expect(SET, name)?;
+        name = tail;
+        while !rdn.is_empty() {
+            let (attribute, tail) = expect(SEQUENCE, rdn)?;
+            rdn = tail;
+            let (oid, value) = expect(OID, attribute)?;
+            let (tag, value, _) = read_tlv(value)?;
+            attributes.push((attribute_name(oid), decode_string(tag, value)));
+        }
+    }
+    Some(Subject { attributes })
+}
+
+/// Split one element off `input`: `(tag, contents, rest)`
+fn read_tlv(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = input.split_first()?;
+    let (&first, mut rest) = rest.split_first()?;
+    let len = if first & 0x80 == 0 {
+        first as usize
+    } else {
+        let count = (first & 0x7f) as usize;
+        if count == 0 || count > std::mem::size_of::<usize>() || rest.len() < count {
+            return None;
+        }
+        let (bytes, tail) = rest.split_at(count);
+        rest = tail;
+        bytes.iter().fold(0usize, |len, &b| (len << 8) | b as usize)
+    };
+    if rest.len() < len {
+        return None;
+    }
+    let (contents, rest) = rest.split_at(len);
+    Some((tag, contents, rest))
+}
+
+fn expect(tag: u8, input: &[u8]) -> Option<(&[u8], &[u8])> {
+    let (found, contents, rest) = read_tlv(input)?;
+    (found == tag).then_some((contents, rest))
+}
+
+fn attribute_name(oid: &[u8]) -> String {
+    let name = match oid {
+        [0x55, 0x04, 0x03] => "CN",
+        [0x55, 0x04, 0x05] => "serialNumber",
+        [0x55, 0x04, 0x06] => "C",
+        [0x55, 0x04, 0x07] => "L",
+        [0x55, 0x04, 0x08] => "ST",
+        [0x55, 0x04, 0x0a] => "O",
+        [0x55, 0x04, 0x0b] => "OU",
+        [0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x01] => "emailAddress",
+        [0x09, 0x92, 0x26, 0x89, 0x93, 0xf2, 0x2c, 0x64, 0x01, 0x01] => "UID",
+        [0x09, 0x92, 0x26, 0x89, 0x93, 0xf2, 0x2c, 0x64, 0x01, 0x19] => "DC",
+        _ => return dotted_oid(oid),
+    };
+    name.to_string()
+}
+
+fn dotted_oid(oid: &[u8]) -> String {
+    let mut arcs = Vec::new();
+    let mut arc = 0u64;
+    for &byte in oid {
+        arc = (arc << 7) | u64::from(byte & 0x7f);
+        if byte & 0x80 == 0 {
+            if arcs.is_empty() {
+                let first = (arc / 40).min(2);
+                arcs.push(first);
+                arcs.push(arc - first * 40);
+            } else {
+                arcs.push(arc);
+            }
+            arc = 0;
+        }
+    }
+    arcs.iter().map(u64::to_string).collect::<Vec<_>>().join(".")
+}
+
+fn decode_string(tag: u8, value: &[u8]) -> String {
+    match tag {
+        // TeletexString, treated as Latin-1 like most implementations do
+        0x14 => value.iter().map(|&b| b as char).collect(),
+        // BMPString
+        0x1e => {
+            let units: Vec<u16> = value.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect();
+            String::from_utf16_lossy(&units)
+        }
+        // UTF8String, PrintableString, IA5String and anything else
+        _ => String::from_utf8_lossy(value).into_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client_cert() -> CertificateDer<'static> {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/tls/testdata/client.pem");
+        let pem = std::fs::read(path).unwrap();
+        let cert = rustls_pemfile::certs(&mut pem.as_slice()).next().unwrap().unwrap();
+        cert
+    }
+
+    #[test]
+    fn test_subject() {
+        let subject = PeerCertificates::new(vec![client_cert()]).subject().unwrap();
+        assert_eq!(subject.common_name(), Some("service-a"));
+        assert_eq!(subject.get("o"), Some("Oxidite"));
+        assert_eq!(subject.to_string(), "CN=service-a, O=Oxidite, C=KE");
+
+        assert!(parse_subject(&client_cert()[..40]).is_none());
+    }
+
+    #[test]
+    fn test_unknown_attribute_uses_dotted_oid() {
+        assert_eq!(attribute_name(&[0x2b, 0x06, 0x01, 0x04, 0x01, 0x82, 0x37]), "1.3.6.1.4.1.311");
+    }
+
+    #[tokio::test]
+    async fn test_missing_certificate_is_unauthorized() {
+        let (mut parts, _) = http::Request::new(()).into_parts();
+        let err = PeerCertificates::from_request_parts(&mut parts).await.unwrap_err();
+        assert!(matches!(err, Error::Unauthorized(_)));
+        assert!(<Option<PeerCertificates>>::from_request_parts(&mut parts).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,102 @@
+//! Reloading certificates without restarting the server.
+
+use super::TlsConfig;
+use crate::error::Result;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+use tokio_rustls::rustls::ServerConfig;
+
+/// The current `ServerConfig`, rebuilt from disk on demand
+pub(super) struct CertStore {
+    tls: TlsConfig,
+    alpn: Vec<Vec<u8>>,
+    current: RwLock<Arc<ServerConfig>>,
+    /// Modification times seen at the last load attempt
+    modified: Mutex<Vec<Option<SystemTime>>>,
+}
+
+impl CertStore {
+    pub(super) fn new(tls: TlsConfig, alpn: Vec<Vec<u8>>) -> Result<Self> {
+        let modified = Mutex::new(modified_times(&tls));
+        let mut config = tls.load_config()?;
+        config.alpn_protocols = alpn.clone();
+        Ok(Self { tls, alpn, current: RwLock::new(Arc::new(config)), modified })
+    }
+
+    pub(super) fn current(&self) -> Arc<ServerConfig> {
+        self.current.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Re-read every certificate and key; on error the old config stays active
+    pub(super) fn reload(&self) -> Result<()> {
+        let mut config = self.tls.load_config()?;
+        config.alpn_protocols = self.alpn.clone();
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(config);
+        Ok(())
+    }
+
+    /// Whether any file changed since the last check
+    fn files_changed(&self) -> bool {
+        let modified = modified_times(&self.tls);
+        let mut seen = self.modified.lock().unwrap_or_else(|e| e.into_inner());
+        if *seen == modified {
+            return false;
+        }
+        // Recorded even if the reload fails, so a broken file is reported once
+        // and retried when it is next written
+        *seen = modified;
+        true
+    }
+
+    /// Reload on `SIGHUP`, and when the files change if `interval` is set
+    pub(super) async fn watch(self: Arc<Self>, interval: Option<Duration>) {
+        #[cfg(unix)]
+        let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+            .map_err(|e| eprintln!("Failed to listen for SIGHUP: {}", e))
+            .ok();
+        let mut ticker = interval.map(|interval| {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            ticker
+        });
+
+        loop {
+            #[cfg(unix)]
+            let hangup = async {
+                match &mut hangup {
+                    Some(hangup) => hangup.recv().await,
+                    None => std::future::pending().await,
+                }
+            };
+            #[cfg(not(unix))]
+            let hangup = std::future::pending::<Option<()>>();
+            let tick = async {
+                match &mut ticker {
+                    Some(ticker) => ticker.tick().await,
+                    None => std::future::pending().await,
+                }
+            };
+
+            let changed = tokio::select! {
+                _ = hangup => {
+                    self.files_changed();
+                    true
+                }
+                _ = tick => self.files_changed(),
+            };
+            if changed {
+                match self.reload() {
+                    Ok(()) => println!("Reloaded TLS certificates"),
+                    Err(err) => eprintln!("TLS reload failed, keeping previous certificates: {}", err),
+                }
+            }
+        }
+    }
+}
+
+fn modified_times(tls: &TlsConfig) -> Vec<Option<SystemTime>> {
+    tls.paths()
+        .into_iter()
+        .map(|path| std::fs::metadata(path).and_then(|meta| meta.modified()).ok())
+        .collect()
+}
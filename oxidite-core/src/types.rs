@@ -2,7 +2,9 @@ use http_body_util::combinators::BoxBody as HttpBoxBody;
 use bytes::Bytes;
 use hyper::{Request, Response};
 
-pub type BoxBody = HttpBoxBody<Bytes, hyper::Error>;
+/// Error that can end a response body early, e.g. from a failed stream
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+pub type BoxBody = HttpBoxBody<Bytes, BoxError>;
 pub type OxiditeBody = HttpBoxBody<Bytes, hyper::Error>;
 pub type OxiditeRequest = Request<OxiditeBody>;
 
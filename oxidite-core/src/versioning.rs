@@ -1,6 +1,20 @@
 //! API Versioning support
 
 use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use http::header::{HeaderMap, HeaderName, HeaderValue, ACCEPT, LINK};
+use http::request::Parts;
+use http::StatusCode;
+use tower_service::Service;
+use crate::error::{Error, Result};
+use crate::extract::FromRequestParts;
+use crate::problem::{render_error, ExposeDetails, ProblemDetails};
+use crate::router::RouteError;
+use crate::state::StateMap;
 use crate::{Router, OxiditeRequest, OxiditeResponse};
 
 /// API version
@@ -18,10 +32,10 @@ impl ApiVersion {
             "v1" | "1" => Some(ApiVersion::V1),
             "v2" | "2" => Some(ApiVersion::V2),
             "v3" | "3" => Some(ApiVersion::V3),
-            _ => s.trim_start_matches('v').parse::<u8>().ok().map(ApiVersion::Custom),
+            _ => s.strip_prefix('v').unwrap_or(s).parse::<u8>().ok().map(ApiVersion::Custom),
         }
     }
-    
+
     pub fn as_str(&self) -> &'static str {
         match self {
             ApiVersion::V1 => "v1",
@@ -30,78 +44,350 @@ impl ApiVersion {
             ApiVersion::Custom(_) => "custom",
         }
     }
+
+    /// Numeric version, used to order versions for fallback
+    pub fn number(&self) -> u8 {
+        match self {
+            ApiVersion::V1 => 1,
+            ApiVersion::V2 => 2,
+            ApiVersion::V3 => 3,
+            ApiVersion::Custom(n) => *n,
+        }
+    }
+}
+
+/// `v1`, `v2`, ..., including the number of custom versions
+impl fmt::Display for ApiVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "v{}", self.number())
+    }
+}
+
+/// The version a request was dispatched with by [`VersionedRouter`]
+impl FromRequestParts for ApiVersion {
+    async fn from_request_parts(parts: &mut Parts) -> Result<Self> {
+        parts.extensions
+            .get::<ApiVersion>()
+            .copied()
+            .ok_or_else(|| Error::InternalServerError("API version not found in request extensions".to_string()))
+    }
+}
+
+/// Deprecation notice for an API version
+///
+/// Sent as `Deprecation`, `Sunset` (RFC 8594) and `Link` headers on every
+/// successful response served for the version. Dates are HTTP-dates, e.g.
+/// `"Sat, 01 Nov 2025 00:00:00 GMT"`; values that are not valid header values
+/// are skipped.
+#[derive(Debug, Clone, Default)]
+pub struct Deprecation {
+    since: Option<String>,
+    sunset: Option<String>,
+    links: Vec<String>,
+}
+
+impl Deprecation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// When the version was deprecated; `Deprecation: true` is sent otherwise
+    pub fn since(mut self, date: impl Into<String>) -> Self {
+        self.since = Some(date.into());
+        self
+    }
+
+    /// When the version stops being served
+    pub fn sunset(mut self, date: impl Into<String>) -> Self {
+        self.sunset = Some(date.into());
+        self
+    }
+
+    /// Documentation about the deprecation, sent as `rel="deprecation"`
+    pub fn link(mut self, url: impl AsRef<str>) -> Self {
+        self.links.push(format!("<{}>; rel=\"deprecation\"", url.as_ref()));
+        self
+    }
+
+    /// Sunset policy, sent as `rel="sunset"`
+    pub fn sunset_link(mut self, url: impl AsRef<str>) -> Self {
+        self.links.push(format!("<{}>; rel=\"sunset\"", url.as_ref()));
+        self
+    }
+
+    /// Add the deprecation headers to `headers`
+    pub fn apply(&self, headers: &mut HeaderMap) {
+        let deprecation = self.since.as_deref().unwrap_or("true");
+        if let Ok(value) = HeaderValue::from_str(deprecation) {
+            headers.insert(HeaderName::from_static("deprecation"), value);
+        }
+        if let Some(value) = self.sunset.as_deref().and_then(|d| HeaderValue::from_str(d).ok()) {
+            headers.insert(HeaderName::from_static("sunset"), value);
+        }
+        for link in &self.links {
+            if let Ok(value) = HeaderValue::from_str(link) {
+                headers.append(LINK, value);
+            }
+        }
+    }
 }
 
-/// Versioned router
+/// Router that dispatches to one [`Router`] per API version
+///
+/// The version is read from the leading path segment, after the optional
+/// [`path_prefix`](Self::path_prefix) (`/api/v2/users` with the prefix `/api`;
+/// the segment is stripped before routing, so the v2 router registers
+/// `/api/users`), the `Accept` header (`application/vnd.api+json;version=2`) or the `version`
+/// query parameter, falling back to the default version. A route missing from
+/// the requested version is served by the closest lower version that has it.
+/// Unsupported versions get a 400 listing the supported ones.
+///
+/// # Example
+/// ```ignore
+/// let mut api = VersionedRouter::new(ApiVersion::V2);
+/// api.path_prefix("/api");
+/// api.version(ApiVersion::V1, v1_router);
+/// api.version(ApiVersion::V2, v2_router);
+/// api.deprecate(ApiVersion::V1, Deprecation::new()
+///     .sunset("Sat, 01 Nov 2025 00:00:00 GMT")
+///     .link("https://example.com/docs/migrating-to-v2"));
+///
+/// Server::new(api).listen(addr).await?;
+/// ```
+#[derive(Clone)]
 pub struct VersionedRouter {
-    routers: HashMap<ApiVersion, Router>,
+    /// Registered versions, sorted by version number
+    routers: Arc<Vec<(ApiVersion, Router)>>,
+    deprecations: Arc<HashMap<ApiVersion, Deprecation>>,
     default_version: ApiVersion,
+    /// Path segments before the version segment, starting with the empty one
+    prefix: Arc<[String]>,
 }
 
 impl VersionedRouter {
     pub fn new(default_version: ApiVersion) -> Self {
         Self {
-            routers: HashMap::new(),
+            routers: Arc::new(Vec::new()),
+            deprecations: Arc::new(HashMap::new()),
             default_version,
+            prefix: Arc::new([String::new()]),
         }
     }
-    
+
+    /// Path before the version segment, e.g. `/api` for `/api/v2/users`
+    ///
+    /// Only the segment right after the prefix is read as a version, so
+    /// values such as `/files/v1` elsewhere in the path are left alone.
+    pub fn path_prefix(&mut self, prefix: &str) {
+        self.prefix = prefix.trim_end_matches('/').split('/').map(str::to_string).collect();
+    }
+
     /// Add a router for a specific version
     pub fn version(&mut self, version: ApiVersion, router: Router) {
-        self.routers.insert(version, router);
+        let routers = Arc::make_mut(&mut self.routers);
+        routers.retain(|(v, _)| *v != version);
+        routers.push((version, router));
+        routers.sort_by_key(|(v, _)| v.number());
+    }
+
+    /// Mark a version as deprecated
+    pub fn deprecate(&mut self, version: ApiVersion, deprecation: Deprecation) {
+        Arc::make_mut(&mut self.deprecations).insert(version, deprecation);
     }
-    
+
+    /// Registered versions, lowest first
+    pub fn versions(&self) -> impl Iterator<Item = ApiVersion> + '_ {
+        self.routers.iter().map(|(v, _)| *v)
+    }
+
     /// Extract version from request
     /// Supports:
-    /// - URL path: /api/v1/users
+    /// - URL path: /v1/users, or /api/v1/users with the path prefix `/api`
     /// - Header: Accept: application/vnd.api+json;version=1
     /// - Query param: /api/users?version=1
     pub fn extract_version(&self, req: &OxiditeRequest) -> ApiVersion {
-        // Try URL path first
-        if let Some(path) = req.uri().path().split('/').find(|s| s.starts_with('v')) {
-            if let Some(version) = ApiVersion::from_str(path) {
-                return version;
-            }
+        match requested_version(req, &self.prefix) {
+            Some(Requested::Version(version, _)) => version,
+            _ => self.default_version,
         }
-        
-        // Try Accept header
-        if let Some(accept) = req.headers().get("accept") {
-            if let Ok(accept_str) = accept.to_str() {
-                if let Some(version_part) = accept_str.split(";version=").nth(1) {
-                    if let Some(version) = ApiVersion::from_str(version_part.split(',').next().unwrap_or("")) {
-                        return version;
-                    }
-                }
-            }
+    }
+
+    /// Check the state required by every version's handlers; see [`Router::check_state`]
+    pub fn check_state(&self) -> std::result::Result<(), RouteError> {
+        self.check_state_with(&StateMap::default())
+    }
+
+    pub(crate) fn check_state_with(&self, outer: &StateMap) -> std::result::Result<(), RouteError> {
+        self.routers.iter().try_for_each(|(_, router)| router.check_state_with(outer))
+    }
+
+    pub(crate) fn resolve_state(&mut self, outer: &StateMap) {
+        for (_, router) in Arc::make_mut(&mut self.routers).iter_mut() {
+            router.resolve_state(outer);
         }
-        
-        // Try query parameter
-        if let Some(query) = req.uri().query() {
-            for pair in query.split('&') {
-                if let Some((key, value)) = pair.split_once('=') {
-                    if key == "version" {
-                        if let Some(version) = ApiVersion::from_str(value) {
-                            return version;
-                        }
-                    }
+    }
+
+    /// Get router for version
+    pub fn get_router(&self, version: ApiVersion) -> Option<&Router> {
+        self.routers.iter().find(|(v, _)| *v == version).map(|(_, router)| router)
+    }
+
+    /// Route a request to the router for its version
+    pub async fn handle(&self, mut req: OxiditeRequest) -> Result<OxiditeResponse> {
+        let instance = req.uri().path().to_string();
+        let version = match requested_version(&req, &self.prefix) {
+            Some(Requested::Version(version, segment)) => {
+                if let Some(segment) = segment {
+                    strip_segment(&mut req, segment)?;
                 }
+                version
             }
+            Some(Requested::Invalid(raw)) => return Err(self.unsupported(&raw)),
+            None => self.default_version,
+        };
+        if self.get_router(version).is_none() {
+            return Err(self.unsupported(&version.to_string()));
         }
-        
-        // Return default
-        self.default_version
+
+        // Closest version at or below the requested one that has the route,
+        // or the requested version itself so it reports 404/405
+        let path = req.uri().path();
+        let router = self.routers
+            .iter()
+            .rev()
+            .filter(|(v, _)| v.number() <= version.number())
+            .find(|(_, router)| router.has_route(req.method(), path))
+            .or_else(|| self.routers.iter().find(|(v, _)| *v == version))
+            .map(|(_, router)| router.clone())
+            .expect("requested version is registered");
+
+        req.extensions_mut().insert(version);
+        let Some(deprecation) = self.deprecations.get(&version) else {
+            return router.handle(req).await;
+        };
+
+        // Errors are rendered here so that 404s, 405s and handler errors
+        // carry the deprecation headers too
+        let accept = req.headers().get(ACCEPT).cloned();
+        let expose = req.extensions().get::<ExposeDetails>().is_some();
+        let mut response = match router.handle(req).await {
+            Ok(response) => response,
+            Err(error) => render_error(&error, expose, Some(instance), accept.as_ref()),
+        };
+        deprecation.apply(response.headers_mut());
+        Ok(response)
     }
-    
-    /// Get router for version
-    pub fn get_router(&self, version: ApiVersion) -> Option<&Router> {
-        self.routers.get(&version)
+
+    fn unsupported(&self, requested: &str) -> Error {
+        let supported: Vec<String> = self.versions().map(|v| v.to_string()).collect();
+        ProblemDetails::new(StatusCode::BAD_REQUEST)
+            .with_title("Unsupported API Version")
+            .with_detail(format!(
+                "API version '{}' is not supported; supported versions: {}",
+                requested,
+                supported.join(", ")
+            ))
+            .with_extension("code", "unsupported_version")
+            .with_extension("supported_versions", supported)
+            .into()
+    }
+}
+
+impl Service<OxiditeRequest> for VersionedRouter {
+    type Response = OxiditeResponse;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<std::result::Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: OxiditeRequest) -> Self::Future {
+        let router = self.clone();
+        Box::pin(async move {
+            router.handle(req).await
+        })
     }
 }
 
+/// Version found in a request
+enum Requested {
+    /// Parsed version and, for path versioning, the index of its segment
+    Version(ApiVersion, Option<usize>),
+    /// A version was given in the header or query but could not be parsed
+    Invalid(String),
+}
+
+fn requested_version(req: &OxiditeRequest, prefix: &[String]) -> Option<Requested> {
+    // Try URL path first: the segment right after the prefix
+    let mut segments = req.uri().path().split('/');
+    if prefix.iter().all(|expected| segments.next() == Some(expected.as_str())) {
+        let from_path = segments
+            .next()
+            .filter(|segment| segment.starts_with('v'))
+            .and_then(ApiVersion::from_str)
+            .map(|version| Requested::Version(version, Some(prefix.len())));
+        if from_path.is_some() {
+            return from_path;
+        }
+    }
+
+    let parse = |raw: &str| match ApiVersion::from_str(raw.trim()) {
+        Some(version) => Requested::Version(version, None),
+        None => Requested::Invalid(raw.trim().to_string()),
+    };
+
+    // Try Accept header
+    let accept_version = req.headers()
+        .get_all(http::header::ACCEPT)
+        .iter()
+        .filter_map(|accept| accept.to_str().ok())
+        .flat_map(|accept| accept.split([';', ',']))
+        .find_map(|param| param.trim().strip_prefix("version="));
+    if let Some(raw) = accept_version {
+        return Some(parse(raw));
+    }
+
+    // Try query parameter
+    req.uri()
+        .query()
+        .and_then(|query| {
+            query.split('&')
+                .filter_map(|pair| pair.split_once('='))
+                .find(|(key, _)| *key == "version")
+        })
+        .map(|(_, raw)| parse(raw))
+}
+
+/// Remove the path segment at `index`, keeping the query string
+fn strip_segment(req: &mut OxiditeRequest, index: usize) -> Result<()> {
+    let mut path = req.uri()
+        .path()
+        .split('/')
+        .enumerate()
+        .filter(|(i, _)| *i != index)
+        .map(|(_, segment)| segment)
+        .collect::<Vec<_>>()
+        .join("/");
+    if path.is_empty() {
+        path.push('/');
+    }
+    if let Some(query) = req.uri().query() {
+        path.push('?');
+        path.push_str(query);
+    }
+
+    let mut parts = req.uri().clone().into_parts();
+    parts.path_and_query = Some(path.parse().map_err(|e| Error::BadRequest(format!("Invalid path: {}", e)))?);
+    *req.uri_mut() = http::Uri::from_parts(parts).map_err(|e| Error::BadRequest(format!("Invalid path: {}", e)))?;
+    Ok(())
+}
+
 /// Version deprecation middleware
 pub struct DeprecationMiddleware {
     deprecated_versions: Vec<ApiVersion>,
     sunset_date: Option<String>,
+    link: Option<String>,
 }
 
 impl DeprecationMiddleware {
@@ -109,33 +395,165 @@ impl DeprecationMiddleware {
         Self {
             deprecated_versions,
             sunset_date: None,
+            link: None,
         }
     }
-    
+
     pub fn with_sunset_date(mut self, date: String) -> Self {
         self.sunset_date = Some(date);
         self
     }
-    
+
+    /// Documentation URL sent as `Link: <url>; rel="deprecation"`
+    pub fn with_link(mut self, url: impl Into<String>) -> Self {
+        self.link = Some(url.into());
+        self
+    }
+
     /// Add deprecation headers to response
     pub fn add_headers(&self, version: ApiVersion, response: &mut OxiditeResponse) {
         if self.deprecated_versions.contains(&version) {
-            response.headers_mut().insert(
-                "Deprecation",
-                "true".parse().unwrap()
-            );
-            
+            let mut deprecation = Deprecation::new();
             if let Some(date) = &self.sunset_date {
-                response.headers_mut().insert(
-                    "Sunset",
-                    date.parse().unwrap()
-                );
+                deprecation = deprecation.sunset(date);
+            }
+            if let Some(link) = &self.link {
+                deprecation = deprecation.link(link);
             }
-            
-            response.headers_mut().insert(
-                "Link",
-                format!("</api/docs>; rel=\"deprecation\"").parse().unwrap()
-            );
+            deprecation.apply(response.headers_mut());
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::Method;
+    use http_body_util::BodyExt;
+
+    fn request(method: Method, uri: &str) -> OxiditeRequest {
+        http::Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(crate::types::OxiditeBody::default())
+            .expect("request")
+    }
+
+    async fn body_text(res: OxiditeResponse) -> String {
+        let bytes = res.into_inner().into_body().collect().await.expect("body").to_bytes();
+        String::from_utf8(bytes.to_vec()).expect("utf8")
+    }
+
+    fn api() -> VersionedRouter {
+        let mut v1 = Router::new();
+        v1.get("/api/users", || async { "v1 users" });
+        v1.get("/api/reports", || async { "v1 reports" });
+        v1.post("/api/reports", || async { "v1 create report" });
+
+        let mut v2 = Router::new();
+        v2.get("/api/users", |version: ApiVersion| async move { format!("v2 users ({})", version) });
+
+        let mut api = VersionedRouter::new(ApiVersion::V2);
+        api.path_prefix("/api/");
+        api.version(ApiVersion::V1, v1);
+        api.version(ApiVersion::V2, v2);
+        api.deprecate(
+            ApiVersion::V1,
+            Deprecation::new()
+                .sunset("Sat, 01 Nov 2025 00:00:00 GMT")
+                .link("https://example.com/migrate"),
+        );
+        api
+    }
+
+    #[tokio::test]
+    async fn test_dispatches_by_path_header_and_query() {
+        let api = api();
+
+        let res = api.handle(request(Method::GET, "/api/v1/users")).await.unwrap();
+        assert_eq!(body_text(res).await, "v1 users");
+
+        let res = api.handle(request(Method::GET, "/api/users?version=2")).await.unwrap();
+        assert_eq!(body_text(res).await, "v2 users (v2)");
+
+        let mut req = request(Method::GET, "/api/users");
+        req.headers_mut().insert(http::header::ACCEPT, HeaderValue::from_static("application/vnd.api+json; version=1"));
+        let res = api.handle(req).await.unwrap();
+        assert_eq!(body_text(res).await, "v1 users");
+
+        // No version given: the default
+        let res = api.handle(request(Method::GET, "/api/users")).await.unwrap();
+        assert_eq!(body_text(res).await, "v2 users (v2)");
+    }
+
+    #[tokio::test]
+    async fn test_missing_routes_fall_back_to_older_versions() {
+        let api = api();
+        // Served by v1, but the client asked for v2, which is not deprecated
+        let res = api.handle(request(Method::GET, "/api/v2/reports")).await.unwrap();
+        assert!(!res.headers().contains_key("deprecation"));
+        assert_eq!(body_text(res).await, "v1 reports");
+
+        let res = api.handle(request(Method::POST, "/api/v2/reports")).await.unwrap();
+        assert_eq!(body_text(res).await, "v1 create report");
+
+        let err = api.handle(request(Method::GET, "/api/v2/missing")).await.err().expect("error");
+        assert!(matches!(err, Error::NotFound(_)));
+    }
+
+    #[tokio::test]
+    async fn test_deprecated_versions_get_rfc_8594_headers() {
+        let res = api().handle(request(Method::GET, "/api/v1/users")).await.unwrap();
+        assert_eq!(res.headers()["deprecation"], "true");
+        assert_eq!(res.headers()["sunset"], "Sat, 01 Nov 2025 00:00:00 GMT");
+        assert_eq!(res.headers()[LINK], "<https://example.com/migrate>; rel=\"deprecation\"");
+
+        // Errors from the deprecated version are still responses with the headers
+        let res = api().handle(request(Method::GET, "/api/v1/missing")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        assert_eq!(res.headers()["deprecation"], "true");
+        assert!(body_text(res).await.contains(r#""instance":"/api/v1/missing""#));
+        let res = api().handle(request(Method::DELETE, "/api/v1/reports")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(res.headers()["sunset"], "Sat, 01 Nov 2025 00:00:00 GMT");
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_versions_list_supported_ones() {
+        let api = api();
+        for uri in ["/api/v7/users", "/api/users?version=beta"] {
+            let err = api.handle(request(Method::GET, uri)).await.err().expect("error");
+            assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+            assert_eq!(err.code(), "unsupported_version");
+            assert!(err.to_string().contains("v1, v2"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_is_a_server_service() {
+        let _server = crate::Server::new(api());
+    }
+
+    #[tokio::test]
+    async fn test_only_the_segment_after_the_prefix_is_a_version() {
+        let mut v1 = Router::new();
+        v1.get("/files/:name", |crate::extract::Path(params): crate::extract::Path<HashMap<String, String>>| async move {
+            params["name"].clone()
+        });
+        let mut api = VersionedRouter::new(ApiVersion::V1);
+        api.version(ApiVersion::V1, v1);
+
+        let res = api.handle(request(Method::GET, "/v1/files/v2")).await.unwrap();
+        assert_eq!(body_text(res).await, "v2");
+        let res = api.handle(request(Method::GET, "/files/v1")).await.unwrap();
+        assert_eq!(body_text(res).await, "v1");
+    }
+
+    #[test]
+    fn test_custom_versions_display_with_number() {
+        assert_eq!(ApiVersion::Custom(7).as_str(), "custom");
+        assert_eq!(ApiVersion::Custom(7).to_string(), "v7");
+        assert_eq!(ApiVersion::from_str("v12"), Some(ApiVersion::Custom(12)));
+        assert_eq!(ApiVersion::V2.to_string(), "v2");
+    }
+}
@@ -0,0 +1,119 @@
+use crate::QueryValue;
+use std::fmt;
+use std::marker::PhantomData;
+
+/// A column of model `M` holding values of the Rust type `T`
+///
+/// `#[derive(Model)]` generates one associated constant per field, named
+/// after the field in upper case, so column names and value types are
+/// checked at compile time:
+///
+/// ```ignore
+/// User::query()
+///     .filter_eq(User::EMAIL, "alice@example.com")
+///     .order_by(User::CREATED_AT, SortDirection::Desc);
+///
+/// // error: `{integer}` is not a valid value for a `String` column
+/// User::query().filter_eq(User::EMAIL, 5);
+/// ```
+pub struct Column<M, T> {
+    name: &'static str,
+    _phantom: PhantomData<fn() -> (M, T)>,
+}
+
+impl<M, T> Column<M, T> {
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            _phantom: PhantomData,
+        }
+    }
+
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl<M, T> Clone for Column<M, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<M, T> Copy for Column<M, T> {}
+
+impl<M, T> fmt::Debug for Column<M, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Column").field(&self.name).finish()
+    }
+}
+
+/// A column of model `M`: a typed [`Column`] or an unchecked string name
+pub trait IntoColumn<M> {
+    fn column_name(&self) -> &str;
+}
+
+impl<M> IntoColumn<M> for &str {
+    fn column_name(&self) -> &str {
+        self
+    }
+}
+
+impl<M> IntoColumn<M> for String {
+    fn column_name(&self) -> &str {
+        self
+    }
+}
+
+impl<M> IntoColumn<M> for &String {
+    fn column_name(&self) -> &str {
+        self
+    }
+}
+
+impl<M, T> IntoColumn<M> for Column<M, T> {
+    fn column_name(&self) -> &str {
+        self.name
+    }
+}
+
+/// A column of model `M` that can be compared with values of type `V`
+///
+/// String names accept any value; a [`Column`] only accepts values that
+/// implement [`ColumnValue`] for its type.
+pub trait CompareColumn<M, V>: IntoColumn<M> {}
+
+impl<M, V: Into<QueryValue>> CompareColumn<M, V> for &str {}
+
+impl<M, V: Into<QueryValue>> CompareColumn<M, V> for String {}
+
+impl<M, V: Into<QueryValue>> CompareColumn<M, V> for &String {}
+
+impl<M, T, V: ColumnValue<T>> CompareColumn<M, V> for Column<M, T> {}
+
+/// A value that can be bound against a column of type `T`
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not a valid value for a `{T}` column",
+    label = "expected a value matching the column type"
+)]
+pub trait ColumnValue<T>: Into<QueryValue> {}
+
+macro_rules! column_values {
+    ($($column:ty => $($value:ty),+;)*) => {
+        $($(
+            impl ColumnValue<$column> for $value {}
+            impl ColumnValue<Option<$column>> for $value {}
+        )+)*
+    };
+}
+
+column_values! {
+    i64 => i64, i32;
+    i32 => i32;
+    String => String, &str;
+    bool => bool;
+    f64 => f64;
+    uuid::Uuid => uuid::Uuid;
+    chrono::DateTime<chrono::Utc> => chrono::DateTime<chrono::Utc>;
+    serde_json::Value => serde_json::Value;
+}
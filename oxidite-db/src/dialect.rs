@@ -0,0 +1,279 @@
+use crate::DatabaseType;
+
+/// SQL syntax differences between the supported backends
+///
+/// Get one from [`Database::dialect`](crate::Database::dialect) so generated
+/// SQL matches the connected database:
+///
+/// - placeholders are `$1, $2, ...` on Postgres and `?` elsewhere
+/// - identifiers are quoted with `"..."`, or backticks on MySQL
+/// - `OFFSET` without `LIMIT` gets the backend's "no limit" value
+/// - boolean literals are `TRUE`/`FALSE`, or `1`/`0` on SQLite
+/// - `RETURNING` is only emitted where supported (Postgres and SQLite)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dialect {
+    db_type: DatabaseType,
+}
+
+impl Dialect {
+    pub const fn new(db_type: DatabaseType) -> Self {
+        Self { db_type }
+    }
+
+    pub fn db_type(&self) -> DatabaseType {
+        self.db_type
+    }
+
+    /// Placeholder for the `index`-th bound value, starting at 1
+    pub fn placeholder(&self, index: usize) -> String {
+        match self.db_type {
+            DatabaseType::Postgres => format!("${index}"),
+            DatabaseType::MySql | DatabaseType::Sqlite => "?".to_string(),
+        }
+    }
+
+    /// `count` comma-separated placeholders, the first one for value `start`
+    pub fn placeholders(&self, start: usize, count: usize) -> String {
+        (start..start + count)
+            .map(|index| self.placeholder(index))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Quote an identifier; `table.column` is quoted per part and `*` is left as is
+    pub fn quote(&self, identifier: &str) -> String {
+        identifier
+            .split('.')
+            .map(|part| match (part, self.db_type) {
+                ("*", _) => part.to_string(),
+                (_, DatabaseType::MySql) => format!("`{}`", part.replace('`', "``")),
+                (_, DatabaseType::Postgres | DatabaseType::Sqlite) => {
+                    format!("\"{}\"", part.replace('"', "\"\""))
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+
+    /// `LIMIT`/`OFFSET` clause with a leading space, empty if neither is set
+    pub fn limit_offset(&self, limit: Option<usize>, offset: Option<usize>) -> String {
+        let limit = match (limit, offset, self.db_type) {
+            (Some(limit), _, _) => Some(limit.to_string()),
+            (None, None, _) | (None, Some(_), DatabaseType::Postgres) => None,
+            // SQLite and MySQL only accept OFFSET after a LIMIT
+            (None, Some(_), DatabaseType::Sqlite) => Some("-1".to_string()),
+            (None, Some(_), DatabaseType::MySql) => Some(u64::MAX.to_string()),
+        };
+
+        let mut sql = String::new();
+        if let Some(limit) = limit {
+            sql.push_str(&format!(" LIMIT {limit}"));
+        }
+        if let Some(offset) = offset {
+            sql.push_str(&format!(" OFFSET {offset}"));
+        }
+        sql
+    }
+
+    /// Boolean literal
+    pub fn boolean(&self, value: bool) -> &'static str {
+        match (self.db_type, value) {
+            (DatabaseType::Sqlite, true) => "1",
+            (DatabaseType::Sqlite, false) => "0",
+            (DatabaseType::Postgres | DatabaseType::MySql, true) => "TRUE",
+            (DatabaseType::Postgres | DatabaseType::MySql, false) => "FALSE",
+        }
+    }
+
+    /// Case-insensitive `LIKE` of an already quoted column against a placeholder
+    pub fn ilike(&self, column: &str, placeholder: &str) -> String {
+        match self.db_type {
+            DatabaseType::Postgres => format!("{column} ILIKE {placeholder}"),
+            DatabaseType::MySql | DatabaseType::Sqlite => {
+                format!("LOWER({column}) LIKE LOWER({placeholder})")
+            }
+        }
+    }
+
+    /// Whether `INSERT`/`UPDATE`/`DELETE` can return rows with `RETURNING`
+    pub fn supports_returning(&self) -> bool {
+        !matches!(self.db_type, DatabaseType::MySql)
+    }
+
+    /// `RETURNING` clause with a leading space, empty where unsupported
+    pub fn returning(&self, columns: &[&str]) -> String {
+        if !self.supports_returning() || columns.is_empty() {
+            return String::new();
+        }
+        let columns = columns
+            .iter()
+            .map(|column| self.quote(column))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!(" RETURNING {columns}")
+    }
+
+    /// Most values one statement can bind
+    pub fn max_bind_params(&self) -> usize {
+        match self.db_type {
+            DatabaseType::Postgres | DatabaseType::MySql => 65_535,
+            DatabaseType::Sqlite => 32_766,
+        }
+    }
+
+    /// `INSERT` of one row, binding `columns` in order
+    pub fn insert(&self, table: &str, columns: &[&str]) -> String {
+        self.insert_rows(table, columns, 1)
+    }
+
+    /// `INSERT` of `rows` rows, binding `columns` in order for each row
+    ///
+    /// Without columns only a single row of defaults can be inserted.
+    pub fn insert_rows(&self, table: &str, columns: &[&str], rows: usize) -> String {
+        if columns.is_empty() {
+            return match self.db_type {
+                DatabaseType::MySql => format!("INSERT INTO {} () VALUES ()", self.quote(table)),
+                DatabaseType::Postgres | DatabaseType::Sqlite => {
+                    format!("INSERT INTO {} DEFAULT VALUES", self.quote(table))
+                }
+            };
+        }
+
+        let values = (0..rows)
+            .map(|row| {
+                format!(
+                    "({})",
+                    self.placeholders(row * columns.len() + 1, columns.len())
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!(
+            "INSERT INTO {} ({}) VALUES {}",
+            self.quote(table),
+            self.column_list(columns),
+            values
+        )
+    }
+
+    /// `UPDATE` of the row matching `key`, binding `columns` in order and then the key
+    pub fn update(&self, table: &str, columns: &[&str], key: &str) -> String {
+        let sets = columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| format!("{} = {}", self.quote(column), self.placeholder(i + 1)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!(
+            "UPDATE {} SET {} WHERE {} = {}",
+            self.quote(table),
+            sets,
+            self.quote(key),
+            self.placeholder(columns.len() + 1)
+        )
+    }
+
+    /// `DELETE` of the row matching `key`, which is bound first
+    pub fn delete(&self, table: &str, key: &str) -> String {
+        format!(
+            "DELETE FROM {} WHERE {} = {}",
+            self.quote(table),
+            self.quote(key),
+            self.placeholder(1)
+        )
+    }
+
+    fn column_list(&self, columns: &[&str]) -> String {
+        columns
+            .iter()
+            .map(|column| self.quote(column))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+impl From<DatabaseType> for Dialect {
+    fn from(db_type: DatabaseType) -> Self {
+        Self::new(db_type)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Dialect;
+    use crate::DatabaseType;
+
+    const POSTGRES: Dialect = Dialect::new(DatabaseType::Postgres);
+    const MYSQL: Dialect = Dialect::new(DatabaseType::MySql);
+    const SQLITE: Dialect = Dialect::new(DatabaseType::Sqlite);
+
+    #[test]
+    fn placeholders_per_backend() {
+        assert_eq!(POSTGRES.placeholders(2, 3), "$2, $3, $4");
+        assert_eq!(MYSQL.placeholders(2, 3), "?, ?, ?");
+        assert_eq!(SQLITE.placeholders(1, 0), "");
+    }
+
+    #[test]
+    fn quotes_identifiers() {
+        assert_eq!(POSTGRES.quote("users.name"), r#""users"."name""#);
+        assert_eq!(SQLITE.quote(r#"we"ird"#), r#""we""ird""#);
+        assert_eq!(MYSQL.quote("users.*"), "`users`.*");
+        assert_eq!(MYSQL.quote("we`ird"), "`we``ird`");
+    }
+
+    #[test]
+    fn limit_offset_per_backend() {
+        assert_eq!(POSTGRES.limit_offset(None, None), "");
+        assert_eq!(
+            POSTGRES.limit_offset(Some(10), Some(20)),
+            " LIMIT 10 OFFSET 20"
+        );
+        assert_eq!(POSTGRES.limit_offset(None, Some(20)), " OFFSET 20");
+        assert_eq!(SQLITE.limit_offset(None, Some(20)), " LIMIT -1 OFFSET 20");
+        assert_eq!(
+            MYSQL.limit_offset(None, Some(20)),
+            " LIMIT 18446744073709551615 OFFSET 20"
+        );
+    }
+
+    #[test]
+    fn booleans_and_returning() {
+        assert_eq!(SQLITE.boolean(true), "1");
+        assert_eq!(POSTGRES.boolean(false), "FALSE");
+        assert_eq!(POSTGRES.returning(&["id"]), r#" RETURNING "id""#);
+        assert_eq!(MYSQL.returning(&["id"]), "");
+    }
+
+    #[test]
+    fn statements() {
+        assert_eq!(
+            POSTGRES.insert("users", &["name", "email"]),
+            r#"INSERT INTO "users" ("name", "email") VALUES ($1, $2)"#
+        );
+        assert_eq!(
+            POSTGRES.insert_rows("users", &["name", "email"], 2),
+            r#"INSERT INTO "users" ("name", "email") VALUES ($1, $2), ($3, $4)"#
+        );
+        assert_eq!(
+            MYSQL.insert_rows("users", &["name"], 3),
+            "INSERT INTO `users` (`name`) VALUES (?), (?), (?)"
+        );
+        assert_eq!(
+            MYSQL.insert("users", &[]),
+            "INSERT INTO `users` () VALUES ()"
+        );
+        assert_eq!(
+            SQLITE.insert("users", &[]),
+            r#"INSERT INTO "users" DEFAULT VALUES"#
+        );
+        assert_eq!(
+            POSTGRES.update("users", &["name", "updated_at"], "id"),
+            r#"UPDATE "users" SET "name" = $1, "updated_at" = $2 WHERE "id" = $3"#
+        );
+        assert_eq!(
+            MYSQL.delete("users", "id"),
+            "DELETE FROM `users` WHERE `id` = ?"
+        );
+    }
+}
@@ -7,11 +7,17 @@ use thiserror::Error;
 
 pub use sqlx;
 
+pub mod column;
+pub use column::{Column, ColumnValue, CompareColumn, IntoColumn};
+
+pub mod dialect;
+pub use dialect::Dialect;
+
 pub mod migrations;
 pub use migrations::{Migration, MigrationManager};
 
 pub mod relations;
-pub use relations::{BelongsTo, HasMany, HasOne};
+pub use relations::{BelongsTo, HasMany, HasOne, Loaded, Relation, RelationKind};
 
 pub type Result<T> = std::result::Result<T, sqlx::Error>;
 pub type OrmResult<T> = std::result::Result<T, OrmError>;
@@ -28,6 +34,13 @@ pub enum OrmError {
     InvalidIdentifier { kind: &'static str, value: String },
     #[error("invalid pagination: {0}")]
     InvalidPagination(&'static str),
+    #[error("invalid query: {0}")]
+    InvalidQuery(String),
+    #[error("model `{model}` has no relation `{relation}`")]
+    UnknownRelation {
+        model: &'static str,
+        relation: String,
+    },
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -101,6 +114,11 @@ pub trait Database: Send + Sync + Debug {
     /// Get the database type
     fn db_type(&self) -> DatabaseType;
 
+    /// SQL dialect of the database type
+    fn dialect(&self) -> Dialect {
+        Dialect::new(self.db_type())
+    }
+
     /// Execute a query
     async fn execute(&self, query: &str) -> Result<u64>;
 
@@ -116,12 +134,67 @@ pub trait Database: Send + Sync + Debug {
     /// Begin a transaction
     async fn begin_transaction(&self) -> Result<DbTransaction>;
 
+    /// Whether queries already run inside a transaction, in which case
+    /// [`begin_transaction`](Self::begin_transaction) is unavailable
+    fn in_transaction(&self) -> bool {
+        false
+    }
+
     /// Execute a sqlx Query
     async fn execute_query<'q>(
         &self,
         query: sqlx::query::Query<'q, sqlx::Any, sqlx::any::AnyArguments<'q>>,
     ) -> Result<u64>;
 
+    /// Execute an INSERT and return the id the backend reports for it
+    /// (`LAST_INSERT_ID()` on MySQL, the last rowid on SQLite)
+    async fn execute_insert<'q>(
+        &self,
+        query: sqlx::query::Query<'q, sqlx::Any, sqlx::any::AnyArguments<'q>>,
+    ) -> Result<Option<i64>> {
+        self.execute_query(query).await?;
+        Ok(None)
+    }
+
+    /// Execute an INSERT of `rows` rows and return the generated ids in row order
+    ///
+    /// The SQL must end with `Dialect::returning(&["id"])`, so the ids are read
+    /// from `RETURNING` on Postgres and SQLite. MySQL only reports the id of
+    /// the first row; the rest are taken to follow it consecutively, which
+    /// holds for a single multi-row INSERT under `innodb_autoinc_lock_mode`
+    /// 0 or 1 with the default `auto_increment_increment` of 1.
+    async fn insert_returning_ids<'q>(
+        &self,
+        query: sqlx::query::Query<'q, sqlx::Any, sqlx::any::AnyArguments<'q>>,
+        rows: usize,
+    ) -> Result<Vec<i64>> {
+        use sqlx::Row;
+
+        if self.dialect().supports_returning() {
+            let mut ids = self
+                .fetch_all(query)
+                .await?
+                .iter()
+                .map(|row| row.try_get::<i64, _>("id"))
+                .collect::<Result<Vec<_>>>()?;
+            if ids.len() != rows {
+                return Err(sqlx::Error::Protocol(format!(
+                    "INSERT of {rows} rows returned {} ids",
+                    ids.len()
+                )));
+            }
+            // RETURNING order is unspecified, but ids are assigned in VALUES
+            // order within a statement
+            ids.sort_unstable();
+            return Ok(ids);
+        }
+
+        let first = self.execute_insert(query).await?.ok_or_else(|| {
+            sqlx::Error::Protocol("INSERT did not report a generated id".to_string())
+        })?;
+        Ok((first..).take(rows).collect())
+    }
+
     /// Fetch all from a sqlx Query
     async fn fetch_all<'q>(
         &self,
@@ -245,6 +318,14 @@ impl Database for DbPool {
         Ok(result.rows_affected())
     }
 
+    async fn execute_insert<'q>(
+        &self,
+        query: sqlx::query::Query<'q, sqlx::Any, sqlx::any::AnyArguments<'q>>,
+    ) -> Result<Option<i64>> {
+        let result = query.execute(&self.pool).await?;
+        Ok(result.last_insert_id())
+    }
+
     async fn fetch_all<'q>(
         &self,
         query: sqlx::query::Query<'q, sqlx::Any, sqlx::any::AnyArguments<'q>>,
@@ -348,6 +429,10 @@ impl Database for DbTransaction {
         Ok(())
     }
 
+    fn in_transaction(&self) -> bool {
+        true
+    }
+
     async fn begin_transaction(&self) -> Result<DbTransaction> {
         // Nested transactions not supported by this simple wrapper yet
         // Could use savepoints if needed.
@@ -369,6 +454,19 @@ impl Database for DbTransaction {
         }
     }
 
+    async fn execute_insert<'q>(
+        &self,
+        query: sqlx::query::Query<'q, sqlx::Any, sqlx::any::AnyArguments<'q>>,
+    ) -> Result<Option<i64>> {
+        let mut lock = self.tx.lock().await;
+        if let Some(ref mut tx) = *lock {
+            let result = query.execute(&mut **tx).await?;
+            Ok(result.last_insert_id())
+        } else {
+            Err(sqlx::Error::PoolClosed)
+        }
+    }
+
     async fn fetch_all<'q>(
         &self,
         query: sqlx::query::Query<'q, sqlx::Any, sqlx::any::AnyArguments<'q>>,
@@ -477,18 +575,221 @@ impl From<serde_json::Value> for QueryValue {
     }
 }
 
+/// Comparison operator for `having` (and the `filter_*` comparisons)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+}
+
+impl Comparison {
+    fn as_sql(self) -> &'static str {
+        match self {
+            Comparison::Eq => "=",
+            Comparison::Ne => "<>",
+            Comparison::Gt => ">",
+            Comparison::Gte => ">=",
+            Comparison::Lt => "<",
+            Comparison::Lte => "<=",
+        }
+    }
+}
+
+/// Aggregate function for `ModelQuery::select_aggregate` and `ModelQuery::having`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Aggregate {
+    function: &'static str,
+    column: Option<String>,
+}
+
+impl Aggregate {
+    /// `COUNT(*)`
+    pub fn count() -> Self {
+        Self {
+            function: "COUNT",
+            column: None,
+        }
+    }
+
+    pub fn sum(column: &str) -> Self {
+        Self::over("SUM", column)
+    }
+
+    pub fn avg(column: &str) -> Self {
+        Self::over("AVG", column)
+    }
+
+    pub fn min(column: &str) -> Self {
+        Self::over("MIN", column)
+    }
+
+    pub fn max(column: &str) -> Self {
+        Self::over("MAX", column)
+    }
+
+    fn over(function: &'static str, column: &str) -> Self {
+        Self {
+            function,
+            column: Some(column.to_string()),
+        }
+    }
+
+    fn to_sql(&self, dialect: Dialect) -> String {
+        match &self.column {
+            Some(column) => format!("{}({})", self.function, dialect.quote(column)),
+            None => format!("{}(*)", self.function),
+        }
+    }
+}
+
+/// Rust type an aggregate can be read as
+///
+/// Aggregates are cast to a matching SQL type, since backends otherwise
+/// return types such as `NUMERIC` that `sqlx::Any` cannot decode.
+pub trait AggregateValue:
+    for<'r> sqlx::Decode<'r, sqlx::Any> + sqlx::Type<sqlx::Any> + Send + Unpin + 'static
+{
+    fn sql_type(dialect: Dialect) -> &'static str;
+}
+
+impl AggregateValue for i64 {
+    fn sql_type(dialect: Dialect) -> &'static str {
+        match dialect.db_type() {
+            DatabaseType::Postgres => "BIGINT",
+            DatabaseType::MySql => "SIGNED",
+            DatabaseType::Sqlite => "INTEGER",
+        }
+    }
+}
+
+impl AggregateValue for f64 {
+    fn sql_type(dialect: Dialect) -> &'static str {
+        match dialect.db_type() {
+            DatabaseType::Postgres => "DOUBLE PRECISION",
+            DatabaseType::MySql => "DOUBLE",
+            DatabaseType::Sqlite => "REAL",
+        }
+    }
+}
+
+impl AggregateValue for String {
+    fn sql_type(dialect: Dialect) -> &'static str {
+        match dialect.db_type() {
+            DatabaseType::Postgres | DatabaseType::Sqlite => "TEXT",
+            DatabaseType::MySql => "CHAR",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Selection {
+    Column(String),
+    Aliased {
+        column: String,
+        alias: String,
+    },
+    Aggregate {
+        aggregate: Aggregate,
+        sql_type: fn(Dialect) -> &'static str,
+        alias: String,
+    },
+}
+
 #[derive(Debug, Clone)]
 enum Filter {
-    Eq { column: String, value: QueryValue },
-    Like { column: String, value: String },
-    IsNull { column: String },
-    IsNotNull { column: String },
+    Compare {
+        column: String,
+        op: Comparison,
+        value: QueryValue,
+    },
+    Like {
+        column: String,
+        value: String,
+        case_insensitive: bool,
+    },
+    In {
+        column: String,
+        values: Vec<QueryValue>,
+        negated: bool,
+    },
+    Between {
+        column: String,
+        low: QueryValue,
+        high: QueryValue,
+    },
+    IsNull {
+        column: String,
+    },
+    IsNotNull {
+        column: String,
+    },
+    Raw {
+        sql: String,
+        values: Vec<QueryValue>,
+    },
+    Group(Vec<Condition>),
+    /// `EXISTS` over a related table aliased as the relation name
+    Exists {
+        table: String,
+        alias: String,
+        inner_key: String,
+        outer_key: String,
+        soft_delete: bool,
+        conditions: Vec<Condition>,
+    },
+}
+
+/// A filter and whether it is OR-ed (rather than AND-ed) with the ones before it
+#[derive(Debug, Clone)]
+struct Condition {
+    or: bool,
+    filter: Filter,
+}
+
+#[derive(Debug, Clone)]
+struct Join {
+    kind: &'static str,
+    table: String,
+    left: String,
+    right: String,
+}
+
+#[derive(Debug, Clone)]
+enum HavingClause {
+    Compare {
+        aggregate: Aggregate,
+        op: Comparison,
+        value: QueryValue,
+    },
+    Raw {
+        sql: String,
+        values: Vec<QueryValue>,
+    },
 }
 
 #[derive(Debug, Clone)]
 enum QueryBuildError {
-    InvalidIdentifier { kind: &'static str, value: String },
+    InvalidIdentifier {
+        kind: &'static str,
+        value: String,
+    },
     EmptySelectFields,
+    RawBindCount {
+        placeholders: usize,
+        values: usize,
+    },
+    UnknownRelation {
+        model: &'static str,
+        relation: String,
+    },
+    RelationMismatch {
+        relation: String,
+        table: &'static str,
+    },
 }
 
 impl QueryBuildError {
@@ -500,42 +801,112 @@ impl QueryBuildError {
             QueryBuildError::EmptySelectFields => {
                 OrmError::InvalidPagination("select fields cannot be empty")
             }
+            QueryBuildError::RawBindCount {
+                placeholders,
+                values,
+            } => OrmError::InvalidQuery(format!(
+                "raw SQL fragment has {placeholders} `?` placeholders but {values} values"
+            )),
+            QueryBuildError::UnknownRelation { model, relation } => {
+                OrmError::UnknownRelation { model, relation }
+            }
+            QueryBuildError::RelationMismatch { relation, table } => OrmError::InvalidQuery(
+                format!("relation `{relation}` is not a relation to `{table}`"),
+            ),
         }
     }
 }
 
+/// Typed query over a model's table
+///
+/// `R` is what `fetch_all`/`fetch_one` return: the model itself, or
+/// [`Loaded<M>`] once relations are requested with [`ModelQuery::with`].
 #[derive(Debug, Clone)]
-pub struct ModelQuery<M: Model> {
-    select_fields: Vec<String>,
-    filters: Vec<Filter>,
+pub struct ModelQuery<M: Model, R = M> {
+    select_fields: Vec<Selection>,
+    distinct: bool,
+    joins: Vec<Join>,
+    filters: Vec<Condition>,
+    group_by: Vec<String>,
+    having: Vec<HavingClause>,
     order_by: Vec<(String, SortDirection)>,
     limit: Option<usize>,
     offset: Option<usize>,
     include_soft_deleted: bool,
     build_error: Option<QueryBuildError>,
-    _phantom: std::marker::PhantomData<M>,
+    relations: Vec<String>,
+    _phantom: std::marker::PhantomData<(M, fn() -> R)>,
 }
 
-impl<M: Model> Default for ModelQuery<M> {
+impl<M: Model, R> Default for ModelQuery<M, R> {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl<M: Model> ModelQuery<M> {
+impl<M: Model, R> ModelQuery<M, R> {
     pub fn new() -> Self {
         Self {
-            select_fields: vec!["*".to_string()],
+            select_fields: Vec::new(),
+            distinct: false,
+            joins: Vec::new(),
             filters: Vec::new(),
+            group_by: Vec::new(),
+            having: Vec::new(),
             order_by: Vec::new(),
             limit: None,
             offset: None,
             include_soft_deleted: false,
             build_error: None,
+            relations: Vec::new(),
             _phantom: std::marker::PhantomData,
         }
     }
 
+    /// Record an invalid identifier; returns whether `value` is valid
+    fn check_identifier(&mut self, kind: &'static str, value: &str) -> bool {
+        if is_valid_identifier(value) {
+            return true;
+        }
+        self.build_error = Some(QueryBuildError::InvalidIdentifier {
+            kind,
+            value: value.to_string(),
+        });
+        false
+    }
+
+    /// Record an invalid column; columns may be qualified as `table.column`
+    fn check_column(&mut self, value: &str) -> bool {
+        if is_valid_column(value) {
+            return true;
+        }
+        self.build_error = Some(QueryBuildError::InvalidIdentifier {
+            kind: "column",
+            value: value.to_string(),
+        });
+        false
+    }
+
+    /// Record a raw fragment whose `?` count does not match its values
+    fn check_raw(&mut self, sql: &str, values: usize) -> bool {
+        let placeholders = rewrite_placeholders(sql, String::new).1;
+        if placeholders == values {
+            return true;
+        }
+        self.build_error = Some(QueryBuildError::RawBindCount {
+            placeholders,
+            values,
+        });
+        false
+    }
+
+    fn push_filter(mut self, column: &str, filter: Filter) -> Self {
+        if self.check_column(column) {
+            self.filters.push(Condition { or: false, filter });
+        }
+        self
+    }
+
     pub fn select(mut self, fields: &[&str]) -> Self {
         if fields.is_empty() {
             self.build_error = Some(QueryBuildError::EmptySelectFields);
@@ -544,91 +915,446 @@ impl<M: Model> ModelQuery<M> {
 
         let mut projected = Vec::with_capacity(fields.len());
         for field in fields {
-            if !is_valid_identifier(field) {
-                self.build_error = Some(QueryBuildError::InvalidIdentifier {
-                    kind: "column",
-                    value: (*field).to_string(),
-                });
+            // `table.*` selects every column of a joined table
+            let column = field.strip_suffix(".*").unwrap_or(field);
+            if !self.check_column(column) {
                 return self;
             }
-            projected.push((*field).to_string());
+            projected.push(Selection::Column((*field).to_string()));
         }
 
         self.select_fields = projected;
         self
     }
 
-    pub fn filter_eq(mut self, column: &str, value: impl Into<QueryValue>) -> Self {
-        if !is_valid_identifier(column) {
-            self.build_error = Some(QueryBuildError::InvalidIdentifier {
-                kind: "column",
-                value: column.to_string(),
+    /// Add `column AS alias` to the selected columns, e.g. to tell apart
+    /// same-named columns of joined tables in `fetch_rows`
+    pub fn select_as(mut self, column: &str, alias: &str) -> Self {
+        if self.check_column(column) && self.check_identifier("alias", alias) {
+            self.select_fields.push(Selection::Aliased {
+                column: column.to_string(),
+                alias: alias.to_string(),
             });
+        }
+        self
+    }
+
+    /// Add an aggregate to the selected columns, read back as `T` under `alias`
+    ///
+    /// Use with `group_by` and `fetch_rows`.
+    pub fn select_aggregate<T: AggregateValue>(
+        mut self,
+        aggregate: Aggregate,
+        alias: &str,
+    ) -> Self {
+        if let Some(column) = aggregate.column.as_deref() {
+            if !self.check_column(column) {
+                return self;
+            }
+        }
+        if !self.check_identifier("alias", alias) {
             return self;
         }
 
-        self.filters.push(Filter::Eq {
+        self.select_fields.push(Selection::Aggregate {
+            aggregate,
+            sql_type: T::sql_type,
+            alias: alias.to_string(),
+        });
+        self
+    }
+
+    /// `SELECT DISTINCT`
+    pub fn distinct(mut self) -> Self {
+        self.distinct = true;
+        self
+    }
+
+    pub fn filter_eq<V: Into<QueryValue>>(
+        self,
+        column: impl CompareColumn<M, V>,
+        value: V,
+    ) -> Self {
+        self.filter_cmp(column, Comparison::Eq, value)
+    }
+
+    pub fn filter_ne<V: Into<QueryValue>>(
+        self,
+        column: impl CompareColumn<M, V>,
+        value: V,
+    ) -> Self {
+        self.filter_cmp(column, Comparison::Ne, value)
+    }
+
+    pub fn filter_gt<V: Into<QueryValue>>(
+        self,
+        column: impl CompareColumn<M, V>,
+        value: V,
+    ) -> Self {
+        self.filter_cmp(column, Comparison::Gt, value)
+    }
+
+    pub fn filter_gte<V: Into<QueryValue>>(
+        self,
+        column: impl CompareColumn<M, V>,
+        value: V,
+    ) -> Self {
+        self.filter_cmp(column, Comparison::Gte, value)
+    }
+
+    pub fn filter_lt<V: Into<QueryValue>>(
+        self,
+        column: impl CompareColumn<M, V>,
+        value: V,
+    ) -> Self {
+        self.filter_cmp(column, Comparison::Lt, value)
+    }
+
+    pub fn filter_lte<V: Into<QueryValue>>(
+        self,
+        column: impl CompareColumn<M, V>,
+        value: V,
+    ) -> Self {
+        self.filter_cmp(column, Comparison::Lte, value)
+    }
+
+    pub fn filter_cmp<V: Into<QueryValue>>(
+        self,
+        column: impl CompareColumn<M, V>,
+        op: Comparison,
+        value: V,
+    ) -> Self {
+        let column = column.column_name();
+        let filter = Filter::Compare {
             column: column.to_string(),
+            op,
             value: value.into(),
-        });
+        };
+        self.push_filter(column, filter)
+    }
+
+    pub fn filter_like(
+        self,
+        column: impl CompareColumn<M, String>,
+        value: impl Into<String>,
+    ) -> Self {
+        let column = column.column_name();
+        let filter = Filter::Like {
+            column: column.to_string(),
+            value: value.into(),
+            case_insensitive: false,
+        };
+        self.push_filter(column, filter)
+    }
+
+    /// Case-insensitive `LIKE` (`ILIKE` on Postgres)
+    pub fn filter_ilike(
+        self,
+        column: impl CompareColumn<M, String>,
+        value: impl Into<String>,
+    ) -> Self {
+        let column = column.column_name();
+        let filter = Filter::Like {
+            column: column.to_string(),
+            value: value.into(),
+            case_insensitive: true,
+        };
+        self.push_filter(column, filter)
+    }
+
+    /// `column IN (...)`; an empty list matches nothing
+    pub fn filter_in<V: Into<QueryValue>>(
+        self,
+        column: impl CompareColumn<M, V>,
+        values: impl IntoIterator<Item = V>,
+    ) -> Self {
+        let column = column.column_name();
+        let filter = Filter::In {
+            column: column.to_string(),
+            values: values.into_iter().map(Into::into).collect(),
+            negated: false,
+        };
+        self.push_filter(column, filter)
+    }
+
+    /// `column NOT IN (...)`; an empty list matches everything
+    pub fn filter_not_in<V: Into<QueryValue>>(
+        self,
+        column: impl CompareColumn<M, V>,
+        values: impl IntoIterator<Item = V>,
+    ) -> Self {
+        let column = column.column_name();
+        let filter = Filter::In {
+            column: column.to_string(),
+            values: values.into_iter().map(Into::into).collect(),
+            negated: true,
+        };
+        self.push_filter(column, filter)
+    }
+
+    /// `column BETWEEN low AND high`, both ends inclusive
+    pub fn filter_between<V: Into<QueryValue>>(
+        self,
+        column: impl CompareColumn<M, V>,
+        low: V,
+        high: V,
+    ) -> Self {
+        let column = column.column_name();
+        let filter = Filter::Between {
+            column: column.to_string(),
+            low: low.into(),
+            high: high.into(),
+        };
+        self.push_filter(column, filter)
+    }
+
+    pub fn filter_is_null(self, column: impl IntoColumn<M>) -> Self {
+        let column = column.column_name();
+        let filter = Filter::IsNull {
+            column: column.to_string(),
+        };
+        self.push_filter(column, filter)
+    }
+
+    pub fn filter_is_not_null(self, column: impl IntoColumn<M>) -> Self {
+        let column = column.column_name();
+        let filter = Filter::IsNotNull {
+            column: column.to_string(),
+        };
+        self.push_filter(column, filter)
+    }
+
+    /// A raw SQL condition with `?` placeholders, one per value
+    ///
+    /// The placeholders are rewritten for the backend and the values are
+    /// bound, never interpolated. Identifiers in `sql` are not validated.
+    ///
+    /// ```ignore
+    /// User::query().filter_raw("LENGTH(name) > ?", [3])
+    /// ```
+    pub fn filter_raw<V: Into<QueryValue>>(
+        mut self,
+        sql: &str,
+        values: impl IntoIterator<Item = V>,
+    ) -> Self {
+        let values = values.into_iter().map(Into::into).collect::<Vec<_>>();
+        if self.check_raw(sql, values.len()) {
+            self.filters.push(Condition {
+                or: false,
+                filter: Filter::Raw {
+                    sql: sql.to_string(),
+                    values,
+                },
+            });
+        }
         self
     }
 
-    pub fn filter_like(mut self, column: &str, value: impl Into<String>) -> Self {
-        if !is_valid_identifier(column) {
-            self.build_error = Some(QueryBuildError::InvalidIdentifier {
-                kind: "column",
-                value: column.to_string(),
+    /// AND a parenthesized group of the filters added by `group`
+    ///
+    /// ```ignore
+    /// // deleted_at IS NULL AND role = ? AND (age >= ? OR verified = TRUE)
+    /// User::query()
+    ///     .filter_eq("role", "admin")
+    ///     .and_where(|q| q.filter_gte("age", 18).or_where(|q| q.filter_eq("verified", true)))
+    /// ```
+    pub fn and_where(self, group: impl FnOnce(Self) -> Self) -> Self {
+        self.push_group(false, group)
+    }
+
+    /// OR a parenthesized group of the filters added by `group`
+    ///
+    /// Only the group's filters are used; its ordering, limits and other
+    /// settings are ignored.
+    pub fn or_where(self, group: impl FnOnce(Self) -> Self) -> Self {
+        self.push_group(true, group)
+    }
+
+    fn push_group(mut self, or: bool, group: impl FnOnce(Self) -> Self) -> Self {
+        let group = group(Self::new());
+        if let Some(err) = group.build_error {
+            self.build_error = Some(err);
+        } else if !group.filters.is_empty() {
+            self.filters.push(Condition {
+                or,
+                filter: Filter::Group(group.filters),
             });
-            return self;
         }
+        self
+    }
 
-        self.filters.push(Filter::Like {
-            column: column.to_string(),
-            value: value.into(),
-        });
+    /// `INNER JOIN table ON left = right`, with `table.column` names
+    ///
+    /// Without `select`, only this model's columns are selected so rows still
+    /// map to `M`; select joined columns and read them with `fetch_rows`.
+    ///
+    /// ```ignore
+    /// let rows = Post::query()
+    ///     .join("users", "posts.user_id", "users.id")
+    ///     .select(&["posts.title"])
+    ///     .select_as("users.name", "author")
+    ///     .fetch_rows(&db)
+    ///     .await?;
+    /// ```
+    pub fn join(self, table: &str, left: &str, right: &str) -> Self {
+        self.push_join("INNER JOIN", table, left, right)
+    }
+
+    /// `LEFT JOIN table ON left = right`, keeping rows without a match
+    pub fn left_join(self, table: &str, left: &str, right: &str) -> Self {
+        self.push_join("LEFT JOIN", table, left, right)
+    }
+
+    fn push_join(mut self, kind: &'static str, table: &str, left: &str, right: &str) -> Self {
+        if self.check_identifier("table name", table)
+            && self.check_column(left)
+            && self.check_column(right)
+        {
+            self.joins.push(Join {
+                kind,
+                table: table.to_string(),
+                left: left.to_string(),
+                right: right.to_string(),
+            });
+        }
         self
     }
 
-    pub fn filter_is_null(mut self, column: &str) -> Self {
-        if !is_valid_identifier(column) {
-            self.build_error = Some(QueryBuildError::InvalidIdentifier {
-                kind: "column",
-                value: column.to_string(),
+    /// Keep rows with at least one related `C` row matching `constraint`
+    ///
+    /// `relation` is a relation declared on the model with `#[model(...)]`.
+    ///
+    /// ```ignore
+    /// // Users with a published post
+    /// User::query().where_has("posts", |q: ModelQuery<Post>| q.filter_eq("published", true))
+    /// ```
+    pub fn where_has<C: Model>(
+        mut self,
+        relation: &str,
+        constraint: impl FnOnce(ModelQuery<C>) -> ModelQuery<C>,
+    ) -> Self {
+        if !self.check_identifier("relation", relation) {
+            return self;
+        }
+        let Some(declared) = M::relation(relation) else {
+            self.build_error = Some(QueryBuildError::UnknownRelation {
+                model: M::table_name(),
+                relation: relation.to_string(),
+            });
+            return self;
+        };
+        if declared.table != C::table_name() {
+            self.build_error = Some(QueryBuildError::RelationMismatch {
+                relation: relation.to_string(),
+                table: C::table_name(),
             });
             return self;
         }
 
-        self.filters.push(Filter::IsNull {
-            column: column.to_string(),
+        let related = constraint(ModelQuery::new());
+        if let Some(err) = related.build_error {
+            self.build_error = Some(err);
+            return self;
+        }
+
+        let (inner_key, outer_key) = match declared.kind {
+            RelationKind::HasMany | RelationKind::HasOne => (declared.foreign_key, "id"),
+            RelationKind::BelongsTo => ("id", declared.foreign_key),
+        };
+        self.filters.push(Condition {
+            or: false,
+            filter: Filter::Exists {
+                table: declared.table.to_string(),
+                alias: relation.to_string(),
+                inner_key: format!("{relation}.{inner_key}"),
+                outer_key: format!("{}.{}", M::table_name(), outer_key),
+                soft_delete: C::has_soft_delete() && !related.include_soft_deleted,
+                conditions: related.filters,
+            },
         });
         self
     }
 
-    pub fn filter_is_not_null(mut self, column: &str) -> Self {
-        if !is_valid_identifier(column) {
-            self.build_error = Some(QueryBuildError::InvalidIdentifier {
-                kind: "column",
-                value: column.to_string(),
-            });
-            return self;
+    /// Load `relation` for every fetched model
+    ///
+    /// Relations are declared with `#[model(has_many(..))]`, `has_one` or
+    /// `belongs_to`; nest them with dots and call `with` again for more.
+    ///
+    /// ```ignore
+    /// let users = User::query().with("posts.comments").with("profile").fetch_all(&db).await?;
+    /// let posts: &[Loaded<Post>] = users[0].many("posts").unwrap_or_default();
+    /// ```
+    pub fn with(mut self, relation: &str) -> ModelQuery<M, Loaded<M>> {
+        self.relations.push(relation.to_string());
+        ModelQuery {
+            select_fields: self.select_fields,
+            distinct: self.distinct,
+            joins: self.joins,
+            filters: self.filters,
+            group_by: self.group_by,
+            having: self.having,
+            order_by: self.order_by,
+            limit: self.limit,
+            offset: self.offset,
+            include_soft_deleted: self.include_soft_deleted,
+            build_error: self.build_error,
+            relations: self.relations,
+            _phantom: std::marker::PhantomData,
         }
+    }
 
-        self.filters.push(Filter::IsNotNull {
-            column: column.to_string(),
+    pub fn group_by(mut self, columns: &[&str]) -> Self {
+        for column in columns {
+            if !self.check_column(column) {
+                return self;
+            }
+            self.group_by.push((*column).to_string());
+        }
+        self
+    }
+
+    /// `HAVING aggregate op value`, AND-ed with other `having` conditions
+    pub fn having(
+        mut self,
+        aggregate: Aggregate,
+        op: Comparison,
+        value: impl Into<QueryValue>,
+    ) -> Self {
+        if let Some(column) = aggregate.column.as_deref() {
+            if !self.check_column(column) {
+                return self;
+            }
+        }
+
+        self.having.push(HavingClause::Compare {
+            aggregate,
+            op,
+            value: value.into(),
         });
         self
     }
 
-    pub fn order_by(mut self, column: &str, direction: SortDirection) -> Self {
-        if !is_valid_identifier(column) {
-            self.build_error = Some(QueryBuildError::InvalidIdentifier {
-                kind: "column",
-                value: column.to_string(),
+    /// A raw `HAVING` condition with `?` placeholders, like `filter_raw`
+    pub fn having_raw<V: Into<QueryValue>>(
+        mut self,
+        sql: &str,
+        values: impl IntoIterator<Item = V>,
+    ) -> Self {
+        let values = values.into_iter().map(Into::into).collect::<Vec<_>>();
+        if self.check_raw(sql, values.len()) {
+            self.having.push(HavingClause::Raw {
+                sql: sql.to_string(),
+                values,
             });
-            return self;
         }
-        self.order_by.push((column.to_string(), direction));
+        self
+    }
+
+    pub fn order_by(mut self, column: impl IntoColumn<M>, direction: SortDirection) -> Self {
+        let column = column.column_name();
+        if self.check_column(column) {
+            self.order_by.push((column.to_string(), direction));
+        }
         self
     }
 
@@ -653,7 +1379,11 @@ impl<M: Model> ModelQuery<M> {
         self
     }
 
-    fn build_sql(&self, count_only: bool) -> OrmResult<(String, Vec<QueryValue>)> {
+    fn build_sql(
+        &self,
+        dialect: Dialect,
+        count_only: bool,
+    ) -> OrmResult<(String, Vec<QueryValue>)> {
         if let Some(err) = self.build_error.as_ref() {
             return Err(err.clone().into_orm_error());
         }
@@ -665,32 +1395,73 @@ impl<M: Model> ModelQuery<M> {
             });
         }
 
-        let select = if count_only {
-            "COUNT(*) as count".to_string()
+        // DISTINCT and GROUP BY change what a row is, so count the rows of the full query
+        let count_rows = count_only && (self.distinct || !self.group_by.is_empty());
+        let select = if count_only && !count_rows {
+            "COUNT(*) AS count".to_string()
+        } else if self.select_fields.is_empty() && !self.joins.is_empty() {
+            dialect.quote(&format!("{}.*", M::table_name()))
+        } else if self.select_fields.is_empty() {
+            "*".to_string()
         } else {
-            self.select_fields.join(", ")
+            self.select_fields
+                .iter()
+                .map(|selection| match selection {
+                    Selection::Column(column) => dialect.quote(column),
+                    Selection::Aliased { column, alias } => {
+                        format!("{} AS {}", dialect.quote(column), dialect.quote(alias))
+                    }
+                    Selection::Aggregate {
+                        aggregate,
+                        sql_type,
+                        alias,
+                    } => format!(
+                        "CAST({} AS {}) AS {}",
+                        aggregate.to_sql(dialect),
+                        sql_type(dialect),
+                        dialect.quote(alias)
+                    ),
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
         };
 
-        let mut sql = format!("SELECT {} FROM {}", select, M::table_name());
+        let distinct = if self.distinct { "DISTINCT " } else { "" };
+        let mut sql = format!(
+            "SELECT {}{} FROM {}",
+            distinct,
+            select,
+            dialect.quote(M::table_name())
+        );
+        for join in &self.joins {
+            sql.push_str(&format!(
+                " {} {} ON {} = {}",
+                join.kind,
+                dialect.quote(&join.table),
+                dialect.quote(&join.left),
+                dialect.quote(&join.right)
+            ));
+        }
+
         let mut clauses = Vec::new();
         let mut binds = Vec::new();
 
         if M::has_soft_delete() && !self.include_soft_deleted {
-            clauses.push("deleted_at IS NULL".to_string());
+            let deleted_at = if self.joins.is_empty() {
+                "deleted_at".to_string()
+            } else {
+                format!("{}.deleted_at", M::table_name())
+            };
+            clauses.push(format!("{} IS NULL", dialect.quote(&deleted_at)));
         }
 
-        for filter in &self.filters {
-            match filter {
-                Filter::Eq { column, value } => {
-                    clauses.push(format!("{column} = ?"));
-                    binds.push(value.clone());
-                }
-                Filter::Like { column, value } => {
-                    clauses.push(format!("{column} LIKE ?"));
-                    binds.push(QueryValue::String(value.clone()));
-                }
-                Filter::IsNull { column } => clauses.push(format!("{column} IS NULL")),
-                Filter::IsNotNull { column } => clauses.push(format!("{column} IS NOT NULL")),
+        let (filters, has_or) = render_conditions(&self.filters, dialect, &mut binds);
+        if !filters.is_empty() {
+            // Keep the soft-delete check outside of any top-level OR
+            if has_or && !clauses.is_empty() {
+                clauses.push(format!("({filters})"));
+            } else {
+                clauses.push(filters);
             }
         }
 
@@ -698,44 +1469,71 @@ impl<M: Model> ModelQuery<M> {
             sql.push_str(&format!(" WHERE {}", clauses.join(" AND ")));
         }
 
+        if !self.group_by.is_empty() {
+            let columns = self
+                .group_by
+                .iter()
+                .map(|column| dialect.quote(column))
+                .collect::<Vec<_>>()
+                .join(", ");
+            sql.push_str(&format!(" GROUP BY {columns}"));
+        }
+
+        if !self.having.is_empty() {
+            let having = self
+                .having
+                .iter()
+                .map(|clause| match clause {
+                    HavingClause::Compare {
+                        aggregate,
+                        op,
+                        value,
+                    } => {
+                        binds.push(value.clone());
+                        format!(
+                            "{} {} {}",
+                            aggregate.to_sql(dialect),
+                            op.as_sql(),
+                            dialect.placeholder(binds.len())
+                        )
+                    }
+                    HavingClause::Raw { sql, values } => {
+                        render_raw(sql, values, dialect, &mut binds)
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(" AND ");
+            sql.push_str(&format!(" HAVING {having}"));
+        }
+
+        if count_rows {
+            return Ok((
+                format!("SELECT COUNT(*) AS count FROM ({sql}) AS counted"),
+                binds,
+            ));
+        }
+
         if !count_only && !self.order_by.is_empty() {
             let order = self
                 .order_by
                 .iter()
-                .map(|(column, direction)| format!("{} {}", column, direction.as_sql()))
+                .map(|(column, direction)| {
+                    format!("{} {}", dialect.quote(column), direction.as_sql())
+                })
                 .collect::<Vec<_>>()
                 .join(", ");
             sql.push_str(&format!(" ORDER BY {order}"));
         }
 
         if !count_only {
-            if let Some(limit) = self.limit {
-                sql.push_str(&format!(" LIMIT {limit}"));
-            }
-            if let Some(offset) = self.offset {
-                sql.push_str(&format!(" OFFSET {offset}"));
-            }
+            sql.push_str(&dialect.limit_offset(self.limit, self.offset));
         }
 
         Ok((sql, binds))
     }
 
-    pub async fn fetch_all(self, db: &impl Database) -> OrmResult<Vec<M>> {
-        let (sql, binds) = self.build_sql(false)?;
-        let mut query = sqlx::query(&sql);
-        for bind in binds {
-            query = match bind {
-                QueryValue::I64(value) => query.bind(value),
-                QueryValue::String(value) => query.bind(value),
-                QueryValue::Bool(value) => query.bind(value),
-                QueryValue::F64(value) => query.bind(value),
-                QueryValue::Uuid(value) => query.bind(value),
-                QueryValue::DateTimeUtc(value) => query.bind(value),
-                QueryValue::Json(value) => query.bind(value),
-            };
-        }
-
-        let rows = db.fetch_all(query).await?;
+    async fn fetch_models(self, db: &impl Database) -> OrmResult<Vec<M>> {
+        let rows = self.fetch_rows(db).await?;
         let mut models = Vec::with_capacity(rows.len());
         for row in rows {
             models.push(M::from_row(&row)?);
@@ -743,54 +1541,293 @@ impl<M: Model> ModelQuery<M> {
         Ok(models)
     }
 
-    pub async fn fetch_one(self, db: &impl Database) -> OrmResult<Option<M>> {
-        let (sql, binds) = self.limit(1).build_sql(false)?;
-        let mut query = sqlx::query(&sql);
-        for bind in binds {
-            query = match bind {
-                QueryValue::I64(value) => query.bind(value),
-                QueryValue::String(value) => query.bind(value),
-                QueryValue::Bool(value) => query.bind(value),
-                QueryValue::F64(value) => query.bind(value),
-                QueryValue::Uuid(value) => query.bind(value),
-                QueryValue::DateTimeUtc(value) => query.bind(value),
-                QueryValue::Json(value) => query.bind(value),
-            };
+    /// Fetch raw rows, for projections that are not a whole model such as
+    /// `select_aggregate` with `group_by`
+    pub async fn fetch_rows(self, db: &impl Database) -> OrmResult<Vec<AnyRow>> {
+        let (sql, binds) = self.build_sql(db.dialect(), false)?;
+        Ok(db.fetch_all(bind_values(sqlx::query(&sql), binds)).await?)
+    }
+
+    pub async fn count(self, db: &impl Database) -> OrmResult<i64> {
+        use sqlx::Row;
+
+        let (sql, binds) = self.build_sql(db.dialect(), true)?;
+        let row = db.fetch_one(bind_values(sqlx::query(&sql), binds)).await?;
+        let row = row.ok_or(OrmError::NotFound {
+            model: M::table_name(),
+            id: 0,
+        })?;
+        Ok(row.try_get::<i64, _>("count")?)
+    }
+
+    /// Sum of `column` over the matching rows, `None` if there are none
+    pub async fn sum<T: AggregateValue>(
+        self,
+        column: impl IntoColumn<M>,
+        db: &impl Database,
+    ) -> OrmResult<Option<T>> {
+        self.aggregate(Aggregate::sum(column.column_name()), db)
+            .await
+    }
+
+    /// Average of `column` over the matching rows, `None` if there are none
+    pub async fn avg<T: AggregateValue>(
+        self,
+        column: impl IntoColumn<M>,
+        db: &impl Database,
+    ) -> OrmResult<Option<T>> {
+        self.aggregate(Aggregate::avg(column.column_name()), db)
+            .await
+    }
+
+    /// Smallest `column` value of the matching rows, `None` if there are none
+    pub async fn min<T: AggregateValue>(
+        self,
+        column: impl IntoColumn<M>,
+        db: &impl Database,
+    ) -> OrmResult<Option<T>> {
+        self.aggregate(Aggregate::min(column.column_name()), db)
+            .await
+    }
+
+    /// Largest `column` value of the matching rows, `None` if there are none
+    pub async fn max<T: AggregateValue>(
+        self,
+        column: impl IntoColumn<M>,
+        db: &impl Database,
+    ) -> OrmResult<Option<T>> {
+        self.aggregate(Aggregate::max(column.column_name()), db)
+            .await
+    }
+
+    async fn aggregate<T: AggregateValue>(
+        mut self,
+        aggregate: Aggregate,
+        db: &impl Database,
+    ) -> OrmResult<Option<T>> {
+        use sqlx::Row;
+
+        if !self.group_by.is_empty() {
+            return Err(OrmError::InvalidQuery(
+                "aggregates over a grouped query need `select_aggregate` and `fetch_rows`"
+                    .to_string(),
+            ));
         }
 
-        let row = db.fetch_one(query).await?;
+        self.select_fields.clear();
+        self.order_by.clear();
+        self.limit = None;
+        self.offset = None;
+        let (sql, binds) = self
+            .select_aggregate::<T>(aggregate, "aggregate")
+            .build_sql(db.dialect(), false)?;
+        let row = db.fetch_one(bind_values(sqlx::query(&sql), binds)).await?;
+        match row {
+            Some(row) => Ok(row.try_get::<Option<T>, _>("aggregate")?),
+            None => Ok(None),
+        }
+    }
+}
+
+impl<M: Model> ModelQuery<M> {
+    pub async fn fetch_all(self, db: &impl Database) -> OrmResult<Vec<M>> {
+        self.fetch_models(db).await
+    }
+
+    pub async fn fetch_one(self, db: &impl Database) -> OrmResult<Option<M>> {
+        let (sql, binds) = self.limit(1).build_sql(db.dialect(), false)?;
+        let row = db.fetch_one(bind_values(sqlx::query(&sql), binds)).await?;
         match row {
             Some(row) => Ok(Some(M::from_row(&row)?)),
             None => Ok(None),
         }
     }
+}
 
-    pub async fn count(self, db: &impl Database) -> OrmResult<i64> {
-        use sqlx::Row;
+impl<M: Model> ModelQuery<M, Loaded<M>> {
+    /// Fetch the models, then load the relations requested with `with`
+    pub async fn fetch_all(self, db: &impl Database) -> OrmResult<Vec<Loaded<M>>> {
+        let relations = self.relations.clone();
+        let mut models = self
+            .fetch_models(db)
+            .await?
+            .into_iter()
+            .map(Loaded::new)
+            .collect::<Vec<_>>();
+        relations::load_nested(db, &mut models, &relations).await?;
+        Ok(models)
+    }
 
-        let (sql, binds) = self.build_sql(true)?;
-        let mut query = sqlx::query(&sql);
-        for bind in binds {
-            query = match bind {
-                QueryValue::I64(value) => query.bind(value),
-                QueryValue::String(value) => query.bind(value),
-                QueryValue::Bool(value) => query.bind(value),
-                QueryValue::F64(value) => query.bind(value),
-                QueryValue::Uuid(value) => query.bind(value),
-                QueryValue::DateTimeUtc(value) => query.bind(value),
-                QueryValue::Json(value) => query.bind(value),
-            };
+    pub async fn fetch_one(self, db: &impl Database) -> OrmResult<Option<Loaded<M>>> {
+        Ok(self.limit(1).fetch_all(db).await?.into_iter().next())
+    }
+}
+
+fn bind_values<'q>(
+    mut query: sqlx::query::Query<'q, sqlx::Any, sqlx::any::AnyArguments<'q>>,
+    binds: Vec<QueryValue>,
+) -> sqlx::query::Query<'q, sqlx::Any, sqlx::any::AnyArguments<'q>> {
+    for bind in binds {
+        query = match bind {
+            QueryValue::I64(value) => query.bind(value),
+            QueryValue::String(value) => query.bind(value),
+            QueryValue::Bool(value) => query.bind(value),
+            QueryValue::F64(value) => query.bind(value),
+            QueryValue::Uuid(value) => query.bind(value),
+            QueryValue::DateTimeUtc(value) => query.bind(value),
+            QueryValue::Json(value) => query.bind(value),
+        };
+    }
+    query
+}
+
+/// Render AND/OR-joined conditions, returning whether any top-level join is OR
+fn render_conditions(
+    conditions: &[Condition],
+    dialect: Dialect,
+    binds: &mut Vec<QueryValue>,
+) -> (String, bool) {
+    let mut sql = String::new();
+    let mut has_or = false;
+    for condition in conditions {
+        let rendered = render_filter(&condition.filter, dialect, binds);
+        if !sql.is_empty() {
+            has_or |= condition.or;
+            sql.push_str(if condition.or { " OR " } else { " AND " });
         }
+        sql.push_str(&rendered);
+    }
+    (sql, has_or)
+}
 
-        let row = db.fetch_one(query).await?;
-        let row = row.ok_or(OrmError::NotFound {
-            model: M::table_name(),
-            id: 0,
-        })?;
-        Ok(row.try_get::<i64, _>("count")?)
+fn render_filter(filter: &Filter, dialect: Dialect, binds: &mut Vec<QueryValue>) -> String {
+    let mut bind = |value: &QueryValue| {
+        binds.push(value.clone());
+        dialect.placeholder(binds.len())
+    };
+
+    match filter {
+        Filter::Compare {
+            column,
+            op,
+            value: QueryValue::Bool(value),
+        } => format!(
+            "{} {} {}",
+            dialect.quote(column),
+            op.as_sql(),
+            dialect.boolean(*value)
+        ),
+        Filter::Compare { column, op, value } => {
+            format!("{} {} {}", dialect.quote(column), op.as_sql(), bind(value))
+        }
+        Filter::Like {
+            column,
+            value,
+            case_insensitive,
+        } => {
+            let placeholder = bind(&QueryValue::String(value.clone()));
+            if *case_insensitive {
+                dialect.ilike(&dialect.quote(column), &placeholder)
+            } else {
+                format!("{} LIKE {}", dialect.quote(column), placeholder)
+            }
+        }
+        Filter::In {
+            column: _,
+            values,
+            negated,
+        } if values.is_empty() => if *negated { "1 = 1" } else { "1 = 0" }.to_string(),
+        Filter::In {
+            column,
+            values,
+            negated,
+        } => {
+            let placeholders = values.iter().map(&mut bind).collect::<Vec<_>>().join(", ");
+            let not = if *negated { "NOT " } else { "" };
+            format!("{} {}IN ({})", dialect.quote(column), not, placeholders)
+        }
+        Filter::Between { column, low, high } => {
+            let low = bind(low);
+            let high = bind(high);
+            format!("{} BETWEEN {} AND {}", dialect.quote(column), low, high)
+        }
+        Filter::IsNull { column } => format!("{} IS NULL", dialect.quote(column)),
+        Filter::IsNotNull { column } => format!("{} IS NOT NULL", dialect.quote(column)),
+        Filter::Raw { sql, values } => render_raw(sql, values, dialect, binds),
+        Filter::Group(conditions) => {
+            format!("({})", render_conditions(conditions, dialect, binds).0)
+        }
+        Filter::Exists {
+            table,
+            alias,
+            inner_key,
+            outer_key,
+            soft_delete,
+            conditions,
+        } => {
+            let mut clauses = vec![format!(
+                "{} = {}",
+                dialect.quote(inner_key),
+                dialect.quote(outer_key)
+            )];
+            if *soft_delete {
+                clauses.push(format!(
+                    "{} IS NULL",
+                    dialect.quote(&format!("{alias}.deleted_at"))
+                ));
+            }
+            let (conditions, _) = render_conditions(conditions, dialect, binds);
+            if !conditions.is_empty() {
+                clauses.push(format!("({conditions})"));
+            }
+            format!(
+                "EXISTS (SELECT 1 FROM {} AS {} WHERE {})",
+                dialect.quote(table),
+                dialect.quote(alias),
+                clauses.join(" AND ")
+            )
+        }
     }
 }
 
+fn render_raw(
+    sql: &str,
+    values: &[QueryValue],
+    dialect: Dialect,
+    binds: &mut Vec<QueryValue>,
+) -> String {
+    let mut values = values.iter();
+    let (rendered, _) = rewrite_placeholders(sql, || {
+        if let Some(value) = values.next() {
+            binds.push(value.clone());
+        }
+        dialect.placeholder(binds.len())
+    });
+    format!("({rendered})")
+}
+
+/// Replace each `?` outside of quoted strings and identifiers with `next()`,
+/// returning the new SQL and the number of placeholders
+fn rewrite_placeholders(sql: &str, mut next: impl FnMut() -> String) -> (String, usize) {
+    let mut rendered = String::with_capacity(sql.len());
+    let mut quote = None;
+    let mut count = 0;
+    for c in sql.chars() {
+        match (quote, c) {
+            (None, '\'' | '"' | '`') => quote = Some(c),
+            (Some(open), _) if c == open => quote = None,
+            (None, '?') => {
+                count += 1;
+                rendered.push_str(&next());
+                continue;
+            }
+            _ => {}
+        }
+        rendered.push(c);
+    }
+    (rendered, count)
+}
+
 /// Query builder (simplified for now)
 pub struct QueryBuilder {
     table: String,
@@ -879,6 +1916,12 @@ pub trait Model: Sized + Send + Sync + Unpin + for<'r> sqlx::FromRow<'r, AnyRow>
     /// Get the list of fields (columns)
     fn fields() -> &'static [&'static str];
 
+    /// Primary key, `0` until the row has been created
+    ///
+    /// `#[derive(Model)]` returns the `id` field. This method has no default,
+    /// so hand-written implementations need `fn id(&self) -> i64 { self.id }`.
+    fn id(&self) -> i64;
+
     /// Check if the model supports soft deletes
     fn has_soft_delete() -> bool {
         false
@@ -889,11 +1932,36 @@ pub trait Model: Sized + Send + Sync + Unpin + for<'r> sqlx::FromRow<'r, AnyRow>
         ModelQuery::new()
     }
 
+    /// Relation declared as `name` with `#[model(has_many(..))]`, `has_one` or `belongs_to`
+    fn relation(_name: &str) -> Option<Relation> {
+        None
+    }
+
+    /// Load the relation `name` into `models`, then the `nested` relation
+    /// paths of the related models; used by [`ModelQuery::with`]
+    async fn load_relation(
+        _db: &impl Database,
+        name: &str,
+        _models: &mut [Loaded<Self>],
+        _nested: &[String],
+    ) -> OrmResult<()> {
+        Err(OrmError::UnknownRelation {
+            model: Self::table_name(),
+            relation: name.to_string(),
+        })
+    }
+
     /// Find a record by ID
     async fn find(db: &impl Database, id: i64) -> Result<Option<Self>> {
-        let mut query = format!("SELECT * FROM {} WHERE id = ?", Self::table_name());
+        let dialect = db.dialect();
+        let mut query = format!(
+            "SELECT * FROM {} WHERE {} = {}",
+            dialect.quote(Self::table_name()),
+            dialect.quote("id"),
+            dialect.placeholder(1)
+        );
         if Self::has_soft_delete() {
-            query.push_str(" AND deleted_at IS NULL");
+            query.push_str(&format!(" AND {} IS NULL", dialect.quote("deleted_at")));
         }
         let row = db.fetch_one(sqlx::query(&query).bind(id)).await?;
 
@@ -905,9 +1973,10 @@ pub trait Model: Sized + Send + Sync + Unpin + for<'r> sqlx::FromRow<'r, AnyRow>
 
     /// Find all records
     async fn all(db: &impl Database) -> Result<Vec<Self>> {
-        let mut query = format!("SELECT * FROM {}", Self::table_name());
+        let dialect = db.dialect();
+        let mut query = format!("SELECT * FROM {}", dialect.quote(Self::table_name()));
         if Self::has_soft_delete() {
-            query.push_str(" WHERE deleted_at IS NULL");
+            query.push_str(&format!(" WHERE {} IS NULL", dialect.quote("deleted_at")));
         }
         let rows = db.fetch_all(sqlx::query(&query)).await?;
 
@@ -937,18 +2006,16 @@ pub trait Model: Sized + Send + Sync + Unpin + for<'r> sqlx::FromRow<'r, AnyRow>
             return Ok(Vec::new());
         }
 
-        let placeholders = std::iter::repeat("?")
-            .take(ids.len())
-            .collect::<Vec<_>>()
-            .join(", ");
+        let dialect = db.dialect();
         let mut query = format!(
-            "SELECT * FROM {} WHERE id IN ({})",
-            Self::table_name(),
-            placeholders
+            "SELECT * FROM {} WHERE {} IN ({})",
+            dialect.quote(Self::table_name()),
+            dialect.quote("id"),
+            dialect.placeholders(1, ids.len())
         );
 
         if Self::has_soft_delete() {
-            query.push_str(" AND deleted_at IS NULL");
+            query.push_str(&format!(" AND {} IS NULL", dialect.quote("deleted_at")));
         }
 
         let mut sql_query = sqlx::query(&query);
@@ -964,7 +2031,7 @@ pub trait Model: Sized + Send + Sync + Unpin + for<'r> sqlx::FromRow<'r, AnyRow>
         Ok(models)
     }
 
-    /// Create a new record
+    /// Create a new record, setting its generated id
     async fn create(&mut self, db: &impl Database) -> Result<()>;
 
     /// Update an existing record
@@ -1009,12 +2076,18 @@ pub trait Model: Sized + Send + Sync + Unpin + for<'r> sqlx::FromRow<'r, AnyRow>
         Ok(())
     }
 
-    /// Insert many models in sequence.
-    async fn insert_many(db: &impl Database, models: &mut [Self]) -> Result<()> {
+    /// Insert many models and return their generated ids, which are also set
+    /// on the models.
+    ///
+    /// Derived models send batched INSERT statements; this default creates
+    /// the models one by one.
+    async fn insert_many(db: &impl Database, models: &mut [Self]) -> Result<Vec<i64>> {
+        let mut ids = Vec::with_capacity(models.len());
         for model in models {
             model.create(db).await?;
+            ids.push(model.id());
         }
-        Ok(())
+        Ok(ids)
     }
 
     /// Update many models in sequence.
@@ -1079,11 +2152,19 @@ pub(crate) fn is_valid_identifier(value: &str) -> bool {
     !value.is_empty() && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
 }
 
+/// A column name, optionally qualified as `table.column`
+pub(crate) fn is_valid_column(value: &str) -> bool {
+    match value.split_once('.') {
+        Some((table, column)) => is_valid_identifier(table) && is_valid_identifier(column),
+        None => is_valid_identifier(value),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::{
-        parse_database_type, sqlite_path_from_url, DatabaseType, Model, Pagination, QueryBuilder,
-        QueryValue, SortDirection,
+        parse_database_type, sqlite_path_from_url, Aggregate, Comparison, DatabaseType, Dialect,
+        Model, ModelQuery, OrmError, Pagination, QueryBuilder, QueryValue, SortDirection,
     };
     use crate as oxidite_db;
     use crate::sqlx;
@@ -1097,6 +2178,17 @@ mod tests {
         deleted_at: Option<i64>,
     }
 
+    #[allow(dead_code)]
+    #[derive(Model, sqlx::FromRow)]
+    #[model(
+        table = "authors",
+        has_many(TestModel, name = "books", foreign_key = "author_id")
+    )]
+    struct Author {
+        id: i64,
+        name: String,
+    }
+
     #[test]
     fn query_builder_escapes_single_quotes() {
         let query = QueryBuilder::new("users")
@@ -1178,12 +2270,47 @@ mod tests {
             .filter_eq("name", "alice")
             .order_by("id", SortDirection::Desc)
             .paginate(pagination)
-            .build_sql(false)
+            .build_sql(Dialect::new(DatabaseType::Sqlite), false)
             .unwrap();
 
         assert_eq!(
             sql,
-            "SELECT * FROM testmodels WHERE deleted_at IS NULL AND name = ? ORDER BY id DESC LIMIT 25 OFFSET 25"
+            r#"SELECT * FROM "testmodels" WHERE "deleted_at" IS NULL AND "name" = ? ORDER BY "id" DESC LIMIT 25 OFFSET 25"#
+        );
+    }
+
+    #[test]
+    fn model_query_renders_backend_specific_sql() {
+        let query = TestModel::query()
+            .select(&["id", "name"])
+            .filter_eq("name", "alice")
+            .filter_like("name", "a%")
+            .filter_eq("id", true)
+            .offset(5);
+
+        let (sql, binds) = query
+            .build_sql(Dialect::new(DatabaseType::Postgres), false)
+            .unwrap();
+        assert_eq!(
+            sql,
+            r#"SELECT "id", "name" FROM "testmodels" WHERE "deleted_at" IS NULL AND "name" = $1 AND "name" LIKE $2 AND "id" = TRUE OFFSET 5"#
+        );
+        assert_eq!(binds.len(), 2);
+
+        let (sql, _) = query
+            .build_sql(Dialect::new(DatabaseType::MySql), false)
+            .unwrap();
+        assert_eq!(
+            sql,
+            "SELECT `id`, `name` FROM `testmodels` WHERE `deleted_at` IS NULL AND `name` = ? AND `name` LIKE ? AND `id` = TRUE LIMIT 18446744073709551615 OFFSET 5"
+        );
+
+        let (sql, _) = query
+            .build_sql(Dialect::new(DatabaseType::Sqlite), true)
+            .unwrap();
+        assert_eq!(
+            sql,
+            r#"SELECT COUNT(*) AS count FROM "testmodels" WHERE "deleted_at" IS NULL AND "name" = ? AND "name" LIKE ? AND "id" = 1"#
         );
     }
 
@@ -1191,10 +2318,138 @@ mod tests {
     fn model_query_rejects_invalid_identifier() {
         let result = TestModel::query()
             .filter_eq("name;drop_table", "alice")
-            .build_sql(false);
+            .build_sql(Dialect::new(DatabaseType::Sqlite), false);
         assert!(result.is_err());
     }
 
+    #[test]
+    fn model_query_renders_predicates_and_groups() {
+        let query = TestModel::query()
+            .filter_gte("id", 10)
+            .filter_ne("name", "root")
+            .filter_in("id", [1, 2])
+            .filter_not_in("id", Vec::<i64>::new())
+            .filter_between("id", 1, 5)
+            .filter_ilike("name", "A%")
+            .filter_raw("LENGTH(name) > ? AND name <> '?'", [3])
+            .or_where(|q| q.filter_lt("id", 0).and_where(|q| q.filter_is_null("name")));
+
+        let (sql, binds) = query
+            .build_sql(Dialect::new(DatabaseType::Postgres), false)
+            .unwrap();
+        assert_eq!(
+            sql,
+            r#"SELECT * FROM "testmodels" WHERE "deleted_at" IS NULL AND ("id" >= $1 AND "name" <> $2 AND "id" IN ($3, $4) AND 1 = 1 AND "id" BETWEEN $5 AND $6 AND "name" ILIKE $7 AND (LENGTH(name) > $8 AND name <> '?') OR ("id" < $9 AND ("name" IS NULL)))"#
+        );
+        assert_eq!(binds.len(), 9);
+
+        let (sql, _) = TestModel::query()
+            .filter_ilike("name", "A%")
+            .filter_in("id", Vec::<i64>::new())
+            .build_sql(Dialect::new(DatabaseType::MySql), false)
+            .unwrap();
+        assert_eq!(
+            sql,
+            "SELECT * FROM `testmodels` WHERE `deleted_at` IS NULL AND LOWER(`name`) LIKE LOWER(?) AND 1 = 0"
+        );
+    }
+
+    #[test]
+    fn model_query_renders_grouping_and_aggregates() {
+        let query = TestModel::query()
+            .with_deleted()
+            .select(&["name"])
+            .select_aggregate::<i64>(Aggregate::sum("id"), "total")
+            .filter_gt("id", 0)
+            .group_by(&["name"])
+            .having(Aggregate::count(), Comparison::Gte, 2)
+            .having_raw("MAX(id) < ?", [100])
+            .order_by("name", SortDirection::Asc);
+
+        let (sql, binds) = query
+            .build_sql(Dialect::new(DatabaseType::Postgres), false)
+            .unwrap();
+        assert_eq!(
+            sql,
+            r#"SELECT "name", CAST(SUM("id") AS BIGINT) AS "total" FROM "testmodels" WHERE "id" > $1 GROUP BY "name" HAVING COUNT(*) >= $2 AND (MAX(id) < $3) ORDER BY "name" ASC"#
+        );
+        assert_eq!(binds.len(), 3);
+
+        let (sql, _) = query
+            .build_sql(Dialect::new(DatabaseType::Sqlite), true)
+            .unwrap();
+        assert_eq!(
+            sql,
+            r#"SELECT COUNT(*) AS count FROM (SELECT "name", CAST(SUM("id") AS INTEGER) AS "total" FROM "testmodels" WHERE "id" > ? GROUP BY "name" HAVING COUNT(*) >= ? AND (MAX(id) < ?)) AS counted"#
+        );
+
+        let (sql, _) = TestModel::query()
+            .with_deleted()
+            .distinct()
+            .select(&["name"])
+            .build_sql(Dialect::new(DatabaseType::MySql), false)
+            .unwrap();
+        assert_eq!(sql, "SELECT DISTINCT `name` FROM `testmodels`");
+    }
+
+    #[test]
+    fn model_query_renders_joins_and_where_has() {
+        let (sql, binds) = Author::query()
+            .where_has("books", |q: ModelQuery<TestModel>| {
+                q.filter_like("name", "R%")
+            })
+            .left_join("testmodels", "authors.id", "testmodels.author_id")
+            .build_sql(Dialect::new(DatabaseType::Postgres), false)
+            .unwrap();
+        assert_eq!(
+            sql,
+            r#"SELECT "authors".* FROM "authors" LEFT JOIN "testmodels" ON "authors"."id" = "testmodels"."author_id" WHERE EXISTS (SELECT 1 FROM "testmodels" AS "books" WHERE "books"."author_id" = "authors"."id" AND "books"."deleted_at" IS NULL AND ("name" LIKE $1))"#
+        );
+        assert_eq!(binds.len(), 1);
+
+        let (sql, _) = TestModel::query()
+            .join("authors", "testmodels.author_id", "authors.id")
+            .select(&["testmodels.*"])
+            .select_as("authors.name", "author")
+            .build_sql(Dialect::new(DatabaseType::MySql), false)
+            .unwrap();
+        assert_eq!(
+            sql,
+            "SELECT `testmodels`.*, `authors`.`name` AS `author` FROM `testmodels` INNER JOIN `authors` ON `testmodels`.`author_id` = `authors`.`id` WHERE `testmodels`.`deleted_at` IS NULL"
+        );
+
+        let result = Author::query()
+            .where_has("nope", |q: ModelQuery<TestModel>| q)
+            .build_sql(Dialect::new(DatabaseType::Sqlite), false);
+        assert!(matches!(result, Err(OrmError::UnknownRelation { .. })));
+        let result = Author::query()
+            .where_has("books", |q: ModelQuery<Author>| q)
+            .build_sql(Dialect::new(DatabaseType::Sqlite), false);
+        assert!(matches!(result, Err(OrmError::InvalidQuery(_))));
+        let result = Author::query()
+            .join("testmodels", "authors.id;", "testmodels.author_id")
+            .build_sql(Dialect::new(DatabaseType::Sqlite), false);
+        assert!(matches!(result, Err(OrmError::InvalidIdentifier { .. })));
+    }
+
+    #[test]
+    fn model_query_rejects_invalid_predicates() {
+        let result = TestModel::query()
+            .or_where(|q| q.filter_in("id;", [1]))
+            .build_sql(Dialect::new(DatabaseType::Sqlite), false);
+        assert!(matches!(result, Err(OrmError::InvalidIdentifier { .. })));
+
+        let result = TestModel::query()
+            .filter_raw("id = ? OR id = ?", [1])
+            .build_sql(Dialect::new(DatabaseType::Sqlite), false);
+        assert!(matches!(result, Err(OrmError::InvalidQuery(_))));
+
+        let result = TestModel::query()
+            .having(Aggregate::max("name)"), Comparison::Gt, 1)
+            .build_sql(Dialect::new(DatabaseType::Sqlite), false);
+        assert!(matches!(result, Err(OrmError::InvalidIdentifier { .. })));
+    }
+
     #[test]
     fn query_value_supports_uuid_datetime_and_json() {
         let uuid = uuid::Uuid::new_v4();
@@ -225,9 +225,10 @@ impl MigrationManager {
         self.ensure_migrations_table(db).await?;
 
         let timestamp = chrono::Utc::now().timestamp();
-        let query = sqlx::query("INSERT INTO _migrations (version, applied_at) VALUES (?, ?)")
-            .bind(version)
-            .bind(timestamp);
+        let sql = db
+            .dialect()
+            .insert("_migrations", &["version", "applied_at"]);
+        let query = sqlx::query(&sql).bind(version).bind(timestamp);
         db.execute_query(query).await?;
         Ok(())
     }
@@ -238,7 +239,8 @@ impl MigrationManager {
         db: &impl crate::Database,
         version: &str,
     ) -> crate::Result<()> {
-        let query = sqlx::query("DELETE FROM _migrations WHERE version = ?").bind(version);
+        let sql = db.dialect().delete("_migrations", "version");
+        let query = sqlx::query(&sql).bind(version);
         db.execute_query(query).await?;
         Ok(())
     }
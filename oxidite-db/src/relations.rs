@@ -1,7 +1,11 @@
-use crate::{is_valid_identifier, Database, Model, Result};
+use crate::{is_valid_identifier, Database, Model, OrmResult, Result, SortDirection};
 use sqlx::Row;
+use std::any::Any;
 use std::collections::HashMap;
+use std::fmt;
 use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
 
 /// Represents a one-to-many relationship
 pub struct HasMany<P, C> {
@@ -31,10 +35,12 @@ where
             ));
         }
 
+        let dialect = db.dialect();
         let query = format!(
-            "SELECT * FROM {} WHERE {} = ?",
-            C::table_name(),
-            self.foreign_key,
+            "SELECT * FROM {} WHERE {} = {}",
+            dialect.quote(C::table_name()),
+            dialect.quote(&self.foreign_key),
+            dialect.placeholder(1)
         );
         let rows = db
             .fetch_all(sqlx::query(&query).bind(self.parent_id))
@@ -64,16 +70,12 @@ where
             return Ok(HashMap::new());
         }
 
-        let placeholders = std::iter::repeat("?")
-            .take(parent_ids.len())
-            .collect::<Vec<_>>()
-            .join(", ");
-
+        let dialect = db.dialect();
         let query = format!(
             "SELECT * FROM {} WHERE {} IN ({})",
-            C::table_name(),
-            foreign_key,
-            placeholders
+            dialect.quote(C::table_name()),
+            dialect.quote(foreign_key),
+            dialect.placeholders(1, parent_ids.len())
         );
 
         let mut sql_query = sqlx::query(&query);
@@ -125,10 +127,12 @@ where
             ));
         }
 
+        let dialect = db.dialect();
         let query = format!(
-            "SELECT * FROM {} WHERE {} = ?",
-            C::table_name(),
-            self.foreign_key,
+            "SELECT * FROM {} WHERE {} = {}",
+            dialect.quote(C::table_name()),
+            dialect.quote(&self.foreign_key),
+            dialect.placeholder(1)
         );
         let row = db
             .fetch_one(sqlx::query(&query).bind(self.parent_id))
@@ -157,16 +161,12 @@ where
             return Ok(HashMap::new());
         }
 
-        let placeholders = std::iter::repeat("?")
-            .take(parent_ids.len())
-            .collect::<Vec<_>>()
-            .join(", ");
-
+        let dialect = db.dialect();
         let query = format!(
             "SELECT * FROM {} WHERE {} IN ({})",
-            C::table_name(),
-            foreign_key,
-            placeholders
+            dialect.quote(C::table_name()),
+            dialect.quote(foreign_key),
+            dialect.placeholders(1, parent_ids.len())
         );
 
         let mut sql_query = sqlx::query(&query);
@@ -213,3 +213,243 @@ where
         P::find(db, self.foreign_key_value).await
     }
 }
+
+/// Kind of a relation declared with `#[model(has_many(..))]`, `has_one` or `belongs_to`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelationKind {
+    HasMany,
+    HasOne,
+    BelongsTo,
+}
+
+/// A relation declared on a model, as returned by [`Model::relation`]
+///
+/// For `HasMany`/`HasOne` the foreign key is a column of the related table
+/// pointing at this model's `id`; for `BelongsTo` it is a column of this
+/// model pointing at the related `id`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Relation {
+    pub kind: RelationKind,
+    /// Table of the related model
+    pub table: &'static str,
+    pub foreign_key: &'static str,
+}
+
+/// A model together with the relations loaded by [`ModelQuery::with`](crate::ModelQuery::with)
+///
+/// Dereferences to the model; loaded relations are read by name with the
+/// related model's type:
+///
+/// ```ignore
+/// let users = User::query().with("posts.comments").fetch_all(&db).await?;
+/// for post in users[0].many::<Post>("posts").unwrap_or_default() {
+///     let comments: &[Loaded<Comment>] = post.many("comments").unwrap_or_default();
+/// }
+/// ```
+pub struct Loaded<M> {
+    model: M,
+    relations: HashMap<String, Box<dyn Any + Send + Sync>>,
+}
+
+impl<M> Loaded<M> {
+    pub fn new(model: M) -> Self {
+        Self {
+            model,
+            relations: HashMap::new(),
+        }
+    }
+
+    pub fn into_inner(self) -> M {
+        self.model
+    }
+
+    /// Whether `relation` has been loaded
+    pub fn is_loaded(&self, relation: &str) -> bool {
+        self.relations.contains_key(relation)
+    }
+
+    /// Rows of a loaded `has_many` relation, `None` if it was not loaded as `C`
+    pub fn many<C: 'static>(&self, relation: &str) -> Option<&[Loaded<C>]> {
+        self.relations
+            .get(relation)?
+            .downcast_ref::<Vec<Loaded<C>>>()
+            .map(Vec::as_slice)
+    }
+
+    /// Row of a loaded `has_one` or `belongs_to` relation, `None` if there is
+    /// no related row or it was not loaded as `C`
+    pub fn one<C: 'static>(&self, relation: &str) -> Option<&Loaded<C>> {
+        self.relations
+            .get(relation)?
+            .downcast_ref::<Option<Arc<Loaded<C>>>>()?
+            .as_deref()
+    }
+}
+
+impl<M> Deref for Loaded<M> {
+    type Target = M;
+
+    fn deref(&self) -> &M {
+        &self.model
+    }
+}
+
+impl<M> DerefMut for Loaded<M> {
+    fn deref_mut(&mut self) -> &mut M {
+        &mut self.model
+    }
+}
+
+impl<M: fmt::Debug> fmt::Debug for Loaded<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Loaded")
+            .field("model", &self.model)
+            .field("relations", &self.relations.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+/// Load each relation path, grouping nested paths under their first relation
+pub(crate) async fn load_nested<M: Model>(
+    db: &impl Database,
+    models: &mut [Loaded<M>],
+    paths: &[String],
+) -> OrmResult<()> {
+    let mut relations: Vec<(&str, Vec<String>)> = Vec::new();
+    for path in paths {
+        let (relation, rest) = match path.split_once('.') {
+            Some((relation, rest)) => (relation, Some(rest)),
+            None => (path.as_str(), None),
+        };
+        let index = match relations.iter().position(|(name, _)| *name == relation) {
+            Some(index) => index,
+            None => {
+                relations.push((relation, Vec::new()));
+                relations.len() - 1
+            }
+        };
+        if let Some(rest) = rest {
+            relations[index].1.push(rest.to_string());
+        }
+    }
+
+    for (relation, nested) in relations {
+        M::load_relation(db, relation, models, &nested).await?;
+    }
+    Ok(())
+}
+
+/// Foreign key field value, as read by generated `belongs_to` loaders
+#[doc(hidden)]
+pub trait ForeignKey {
+    fn foreign_key(&self) -> Option<i64>;
+}
+
+impl ForeignKey for i64 {
+    fn foreign_key(&self) -> Option<i64> {
+        Some(*self)
+    }
+}
+
+impl ForeignKey for Option<i64> {
+    fn foreign_key(&self) -> Option<i64> {
+        *self
+    }
+}
+
+fn unique_keys(keys: impl Iterator<Item = i64>) -> Vec<i64> {
+    let mut keys = keys.collect::<Vec<_>>();
+    keys.sort_unstable();
+    keys.dedup();
+    keys
+}
+
+/// Load a `has_many` (`many`) or `has_one` relation of `parents`
+///
+/// Called by the `load_relation` implementation of `#[derive(Model)]`.
+#[doc(hidden)]
+pub async fn load_has<P, C>(
+    db: &impl Database,
+    parents: &mut [Loaded<P>],
+    relation: &str,
+    foreign_key: &str,
+    many: bool,
+    nested: &[String],
+) -> OrmResult<()>
+where
+    P: Model,
+    C: Model + 'static,
+{
+    let ids = unique_keys(parents.iter().map(|parent| parent.id()));
+    let mut keys = Vec::new();
+    let mut children = Vec::new();
+    for chunk in ids.chunks(db.dialect().max_bind_params()) {
+        let rows = C::query()
+            .filter_in(foreign_key, chunk.iter().copied())
+            .order_by("id", SortDirection::Asc)
+            .fetch_rows(db)
+            .await?;
+        for row in rows {
+            if let Some(key) = row.try_get::<Option<i64>, _>(foreign_key)? {
+                keys.push(key);
+                children.push(Loaded::new(C::from_row(&row)?));
+            }
+        }
+    }
+    load_nested(db, &mut children, nested).await?;
+
+    let mut grouped = HashMap::<i64, Vec<Loaded<C>>>::new();
+    for (key, child) in keys.into_iter().zip(children) {
+        grouped.entry(key).or_default().push(child);
+    }
+
+    for parent in parents.iter_mut() {
+        let related = grouped.remove(&parent.id()).unwrap_or_default();
+        let related: Box<dyn Any + Send + Sync> = if many {
+            Box::new(related)
+        } else {
+            Box::new(related.into_iter().next().map(Arc::new))
+        };
+        parent.relations.insert(relation.to_string(), related);
+    }
+    Ok(())
+}
+
+/// Load a `belongs_to` relation of `models`, reading the foreign key with `key`
+///
+/// Called by the `load_relation` implementation of `#[derive(Model)]`.
+#[doc(hidden)]
+pub async fn load_belongs_to<M, P>(
+    db: &impl Database,
+    models: &mut [Loaded<M>],
+    relation: &str,
+    key: fn(&M) -> Option<i64>,
+    nested: &[String],
+) -> OrmResult<()>
+where
+    M: Model,
+    P: Model + 'static,
+{
+    let ids = unique_keys(models.iter().filter_map(|model| key(model)));
+    let mut parents = Vec::new();
+    for chunk in ids.chunks(db.dialect().max_bind_params()) {
+        let rows = P::query()
+            .filter_in("id", chunk.iter().copied())
+            .fetch_all(db)
+            .await?;
+        parents.extend(rows.into_iter().map(Loaded::new));
+    }
+    load_nested(db, &mut parents, nested).await?;
+
+    let parents = parents
+        .into_iter()
+        .map(|parent| (parent.id(), Arc::new(parent)))
+        .collect::<HashMap<_, _>>();
+    for model in models.iter_mut() {
+        let parent = key(model).and_then(|id| parents.get(&id).cloned());
+        model
+            .relations
+            .insert(relation.to_string(), Box::new(parent));
+    }
+    Ok(())
+}
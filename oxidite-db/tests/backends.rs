@@ -0,0 +1,483 @@
+//! The same model code against every backend.
+//!
+//! SQLite always runs in memory; set `OXIDITE_TEST_POSTGRES_URL` and/or
+//! `OXIDITE_TEST_MYSQL_URL` to run the suite against those servers too.
+
+use oxidite_db::{
+    sqlx, Aggregate, Comparison, Database, DatabaseType, DbPool, HasMany, Loaded, Model,
+    ModelQuery, OrmError, SortDirection,
+};
+
+#[derive(Model, sqlx::FromRow, Debug, Clone)]
+#[model(table = "backend_users", has_many(Post, foreign_key = "user_id"))]
+struct User {
+    id: i64,
+    name: String,
+    score: i64,
+    created_at: i64,
+    updated_at: i64,
+    deleted_at: Option<i64>,
+}
+
+#[derive(Model, sqlx::FromRow, Debug, Clone)]
+#[model(
+    table = "backend_posts",
+    belongs_to(User, name = "author"),
+    has_many(Comment)
+)]
+struct Post {
+    id: i64,
+    user_id: i64,
+    title: String,
+}
+
+#[derive(Model, sqlx::FromRow, Debug, Clone)]
+#[model(table = "backend_comments", belongs_to(Post))]
+struct Comment {
+    id: i64,
+    post_id: i64,
+    body: String,
+}
+
+fn user(name: &str, score: i64) -> User {
+    User {
+        id: 0,
+        name: name.to_string(),
+        score,
+        created_at: 0,
+        updated_at: 0,
+        deleted_at: None,
+    }
+}
+
+async fn setup(db: &DbPool) {
+    let (id, text) = match db.db_type() {
+        DatabaseType::Sqlite => ("INTEGER PRIMARY KEY AUTOINCREMENT", "TEXT"),
+        DatabaseType::Postgres => ("BIGSERIAL PRIMARY KEY", "TEXT"),
+        DatabaseType::MySql => ("BIGINT AUTO_INCREMENT PRIMARY KEY", "VARCHAR(255)"),
+    };
+
+    db.execute("DROP TABLE IF EXISTS backend_comments")
+        .await
+        .unwrap();
+    db.execute("DROP TABLE IF EXISTS backend_posts")
+        .await
+        .unwrap();
+    db.execute("DROP TABLE IF EXISTS backend_users")
+        .await
+        .unwrap();
+    db.execute(&format!(
+        "CREATE TABLE backend_users (id {id}, name {text} NOT NULL, score BIGINT NOT NULL, \
+         created_at BIGINT NOT NULL, updated_at BIGINT NOT NULL, deleted_at BIGINT NULL)"
+    ))
+    .await
+    .unwrap();
+    db.execute(&format!(
+        "CREATE TABLE backend_posts (id {id}, user_id BIGINT NOT NULL, title {text} NOT NULL)"
+    ))
+    .await
+    .unwrap();
+    db.execute(&format!(
+        "CREATE TABLE backend_comments (id {id}, post_id BIGINT NOT NULL, body {text} NOT NULL)"
+    ))
+    .await
+    .unwrap();
+}
+
+async fn by_name(db: &DbPool, name: &str) -> User {
+    User::query()
+        .filter_eq(User::NAME, name)
+        .fetch_one(db)
+        .await
+        .unwrap()
+        .unwrap_or_else(|| panic!("user `{name}` not found"))
+}
+
+async fn run_suite(db: DbPool) {
+    setup(&db).await;
+
+    let mut alice = user("alice", 10);
+    alice.create(&db).await.unwrap();
+    assert!(alice.is_persisted());
+    assert!(alice.created_at > 0);
+    assert_eq!(by_name(&db, "alice").await.id, alice.id);
+
+    // save() after create() updates the row instead of inserting a duplicate
+    alice.score = 12;
+    alice.save(&db).await.unwrap();
+    assert_eq!(
+        User::query()
+            .filter_eq("name", "alice")
+            .count(&db)
+            .await
+            .unwrap(),
+        1
+    );
+
+    let mut users = vec![user("bob", 20), user("carol", 30)];
+    let ids = User::insert_many(&db, &mut users).await.unwrap();
+    assert_eq!(ids, users.iter().map(|u| u.id).collect::<Vec<_>>());
+    assert!(ids[0] > alice.id && ids[1] > ids[0]);
+    assert!(users.iter().all(|u| u.created_at > 0 && u.updated_at > 0));
+    let bob = users.remove(0);
+    assert_eq!(by_name(&db, "bob").await.id, bob.id);
+    assert_eq!(by_name(&db, "carol").await.id, users[0].id);
+    assert!(User::insert_many(&db, &mut []).await.unwrap().is_empty());
+
+    let found = User::find(&db, alice.id).await.unwrap().unwrap();
+    assert_eq!(found.name, "alice");
+    let many = User::find_many(&db, &[alice.id, bob.id]).await.unwrap();
+    assert_eq!(many.len(), 2);
+
+    let page = User::query()
+        .order_by("score", SortDirection::Desc)
+        .offset(1)
+        .fetch_all(&db)
+        .await
+        .unwrap();
+    assert_eq!(
+        page.iter().map(|u| u.name.as_str()).collect::<Vec<_>>(),
+        ["bob", "alice"]
+    );
+    let page = User::query()
+        .filter_like("name", "%o%")
+        .order_by("name", SortDirection::Asc)
+        .limit(1)
+        .fetch_all(&db)
+        .await
+        .unwrap();
+    assert_eq!(page[0].name, "bob");
+
+    predicates_and_aggregates(&db).await;
+
+    alice.score = 11;
+    alice.update(&db).await.unwrap();
+    assert_eq!(User::find(&db, alice.id).await.unwrap().unwrap().score, 11);
+
+    let mut posts = ["first", "second"].map(|title| Post {
+        id: 0,
+        user_id: alice.id,
+        title: title.to_string(),
+    });
+    let post_ids = Post::insert_many(&db, &mut posts).await.unwrap();
+    assert_eq!(post_ids.len(), 2);
+    assert_eq!(
+        Post::find(&db, post_ids[1]).await.unwrap().unwrap().title,
+        "second"
+    );
+    let posts = HasMany::<User, Post>::new(alice.id, "user_id")
+        .get(&db)
+        .await
+        .unwrap();
+    assert_eq!(posts.len(), 2);
+    let grouped = HasMany::<User, Post>::eager_load(&db, &[alice.id, bob.id], "user_id")
+        .await
+        .unwrap();
+    assert_eq!(grouped[&alice.id].len(), 2);
+    assert!(grouped[&bob.id].is_empty());
+
+    bob.delete(&db).await.unwrap();
+    assert!(User::find(&db, bob.id).await.unwrap().is_none());
+    assert_eq!(User::query().count(&db).await.unwrap(), 2);
+    assert_eq!(User::query().with_deleted().count(&db).await.unwrap(), 3);
+    let trashed = User::query()
+        .with_deleted()
+        .filter_is_not_null("deleted_at")
+        .fetch_all(&db)
+        .await
+        .unwrap();
+    assert_eq!(trashed.len(), 1);
+    assert!(trashed[0].deleted_at.is_some());
+
+    bob.force_delete(&db).await.unwrap();
+
+    relations_and_joins(&db, &alice).await;
+    assert_eq!(User::query().with_deleted().count(&db).await.unwrap(), 2);
+}
+
+/// Runs with alice (12), bob (20) and carol (30)
+async fn predicates_and_aggregates(db: &DbPool) {
+    use sqlx::Row;
+
+    let names = |users: Vec<User>| users.into_iter().map(|u| u.name).collect::<Vec<_>>();
+    let sorted = || User::query().order_by(User::NAME, SortDirection::Asc);
+
+    let found = sorted()
+        .filter_gt(User::SCORE, 12)
+        .filter_lte("score", 30)
+        .fetch_all(db)
+        .await;
+    assert_eq!(names(found.unwrap()), ["bob", "carol"]);
+    let found = sorted()
+        .filter_in("name", ["alice", "carol", "dave"])
+        .fetch_all(db)
+        .await;
+    assert_eq!(names(found.unwrap()), ["alice", "carol"]);
+    let found = sorted()
+        .filter_not_in("name", ["alice"])
+        .filter_ne("score", 30)
+        .fetch_all(db)
+        .await;
+    assert_eq!(names(found.unwrap()), ["bob"]);
+    let found = sorted()
+        .filter_in("name", Vec::<String>::new())
+        .fetch_all(db)
+        .await;
+    assert!(found.unwrap().is_empty());
+    let found = sorted().filter_between("score", 12, 20).fetch_all(db).await;
+    assert_eq!(names(found.unwrap()), ["alice", "bob"]);
+    let found = sorted().filter_ilike("name", "%AR%").fetch_all(db).await;
+    assert_eq!(names(found.unwrap()), ["carol"]);
+    let found = sorted()
+        .filter_raw("score + ? > ?", [5, 30])
+        .fetch_all(db)
+        .await;
+    assert_eq!(names(found.unwrap()), ["carol"]);
+
+    // score < 15 OR (name = 'carol' AND score >= 30)
+    let found = sorted()
+        .filter_lt("score", 15)
+        .or_where(|q| q.filter_eq("name", "carol").filter_gte("score", 30))
+        .fetch_all(db)
+        .await;
+    assert_eq!(names(found.unwrap()), ["alice", "carol"]);
+    let count = User::query()
+        .filter_gt("score", 10)
+        .and_where(|q| {
+            q.filter_eq("name", "alice")
+                .or_where(|q| q.filter_eq("name", "bob"))
+        })
+        .count(db)
+        .await;
+    assert_eq!(count.unwrap(), 2);
+
+    assert_eq!(
+        User::query().sum::<i64>("score", db).await.unwrap(),
+        Some(62)
+    );
+    assert_eq!(
+        User::query()
+            .filter_gt("score", 12)
+            .avg::<f64>("score", db)
+            .await
+            .unwrap(),
+        Some(25.0)
+    );
+    assert_eq!(
+        User::query().min::<i64>("score", db).await.unwrap(),
+        Some(12)
+    );
+    assert_eq!(
+        User::query()
+            .max::<String>("name", db)
+            .await
+            .unwrap()
+            .as_deref(),
+        Some("carol")
+    );
+    assert_eq!(
+        User::query()
+            .filter_gt("score", 100)
+            .sum::<i64>("score", db)
+            .await
+            .unwrap(),
+        None
+    );
+
+    let mut posts = [(0, "a"), (0, "b"), (1, "c")].map(|(user, title)| Post {
+        id: 0,
+        user_id: user,
+        title: title.to_string(),
+    });
+    Post::insert_many(db, &mut posts).await.unwrap();
+    let rows = Post::query()
+        .select(&["user_id"])
+        .select_aggregate::<i64>(Aggregate::count(), "posts")
+        .group_by(&["user_id"])
+        .having(Aggregate::count(), Comparison::Gt, 1)
+        .order_by("user_id", SortDirection::Asc)
+        .fetch_rows(db)
+        .await
+        .unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].try_get::<i64, _>("user_id").unwrap(), 0);
+    assert_eq!(rows[0].try_get::<i64, _>("posts").unwrap(), 2);
+    assert_eq!(
+        Post::query()
+            .distinct()
+            .select(&["user_id"])
+            .count(db)
+            .await
+            .unwrap(),
+        2
+    );
+    assert_eq!(
+        Post::query()
+            .group_by(&["user_id"])
+            .sum::<i64>("id", db)
+            .await
+            .ok(),
+        None
+    );
+    db.execute("DELETE FROM backend_posts").await.unwrap();
+}
+
+/// Runs with alice (posts "first" and "second") and carol (no posts)
+async fn relations_and_joins(db: &DbPool, alice: &User) {
+    use sqlx::Row;
+
+    let first = Post::query()
+        .filter_eq(Post::TITLE, "first")
+        .fetch_one(db)
+        .await
+        .unwrap()
+        .unwrap();
+    let mut comments = ["nice", "thanks"].map(|body| Comment {
+        id: 0,
+        post_id: first.id,
+        body: body.to_string(),
+    });
+    Comment::insert_many(db, &mut comments).await.unwrap();
+
+    let users = User::query()
+        .with("posts.comments")
+        .with("posts.author")
+        .order_by(User::NAME, SortDirection::Asc)
+        .fetch_all(db)
+        .await
+        .unwrap();
+    assert_eq!(users.len(), 2);
+    let posts = users[0].many::<Post>("posts").unwrap();
+    assert_eq!(
+        posts.iter().map(|p| p.title.as_str()).collect::<Vec<_>>(),
+        ["first", "second"]
+    );
+    let comments: &[Loaded<Comment>] = posts[0].many("comments").unwrap();
+    assert_eq!(comments.len(), 2);
+    assert!(posts[1].many::<Comment>("comments").unwrap().is_empty());
+    assert_eq!(posts[1].one::<User>("author").unwrap().id, alice.id);
+    assert_eq!(users[1].name, "carol");
+    assert!(users[1].many::<Post>("posts").unwrap().is_empty());
+    assert!(users[1].many::<Comment>("posts").is_none());
+
+    let comment = Comment::query()
+        .with("post.author")
+        .fetch_one(db)
+        .await
+        .unwrap()
+        .unwrap();
+    let post = comment.one::<Post>("post").unwrap();
+    assert_eq!(post.id, first.id);
+    assert_eq!(post.one::<User>("author").unwrap().name, "alice");
+
+    let err = User::query().with("posts.nope").fetch_all(db).await;
+    assert!(matches!(err, Err(OrmError::UnknownRelation { .. })));
+
+    let with_posts = User::query()
+        .where_has("posts", |q: ModelQuery<Post>| {
+            q.filter_eq(Post::TITLE, "second")
+        })
+        .fetch_all(db)
+        .await
+        .unwrap();
+    assert_eq!(with_posts.len(), 1);
+    assert_eq!(with_posts[0].id, alice.id);
+    let commented = Post::query()
+        .where_has("comments", |q: ModelQuery<Comment>| q)
+        .where_has("author", |q: ModelQuery<User>| {
+            q.filter_eq(User::NAME, "alice")
+        })
+        .count(db)
+        .await
+        .unwrap();
+    assert_eq!(commented, 1);
+
+    let rows = Post::query()
+        .join("backend_users", "backend_posts.user_id", "backend_users.id")
+        .select(&["backend_posts.title"])
+        .select_as("backend_users.name", "author")
+        .order_by("backend_posts.id", SortDirection::Asc)
+        .fetch_rows(db)
+        .await
+        .unwrap();
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0].try_get::<String, _>("title").unwrap(), "first");
+    assert_eq!(rows[0].try_get::<String, _>("author").unwrap(), "alice");
+
+    let rows = User::query()
+        .left_join("backend_posts", "backend_users.id", "backend_posts.user_id")
+        .select_as("backend_users.name", "name")
+        .select_as("backend_posts.title", "title")
+        .filter_is_null("backend_posts.id")
+        .fetch_rows(db)
+        .await
+        .unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].try_get::<String, _>("name").unwrap(), "carol");
+    assert_eq!(rows[0].try_get::<Option<String>, _>("title").unwrap(), None);
+
+    // Joined rows still map to the model without a `select`
+    let authors = User::query()
+        .join("backend_posts", "backend_users.id", "backend_posts.user_id")
+        .fetch_all(db)
+        .await
+        .unwrap();
+    assert_eq!(authors.len(), 2);
+    assert!(authors.iter().all(|u| u.name == "alice"));
+}
+
+#[tokio::test]
+async fn sqlite_backend() {
+    run_suite(DbPool::connect("sqlite::memory:").await.unwrap()).await;
+}
+
+#[tokio::test]
+async fn insert_many_splits_batches_at_the_bind_limit() {
+    let db = DbPool::connect("sqlite::memory:").await.unwrap();
+    setup(&db).await;
+
+    // Four bound columns per row, so SQLite's 32766 values allow 8191 rows per statement
+    let mut users = (0..10_000)
+        .map(|i| user(&format!("user{i}"), i))
+        .collect::<Vec<_>>();
+    let ids = User::insert_many(&db, &mut users).await.unwrap();
+    assert_eq!(ids, (1..=10_000).collect::<Vec<i64>>());
+    assert_eq!(users[9_999].id, 10_000);
+    assert_eq!(User::query().count(&db).await.unwrap(), 10_000);
+}
+
+#[tokio::test]
+async fn insert_many_assigns_each_model_the_id_of_its_row() {
+    let db = DbPool::connect("sqlite::memory:").await.unwrap();
+    setup(&db).await;
+
+    let mut users = (0..50)
+        .map(|i| user(&format!("user{i}"), i))
+        .collect::<Vec<_>>();
+    // Inside a caller's transaction, insert_many joins it
+    let tx = db.begin_transaction().await.unwrap();
+    let ids = User::insert_many(&tx, &mut users).await.unwrap();
+    tx.commit().await.unwrap();
+    assert_eq!(ids, users.iter().map(|u| u.id).collect::<Vec<_>>());
+    for model in &users {
+        let row = User::find(&db, model.id).await.unwrap().unwrap();
+        assert_eq!(
+            (row.name.as_str(), row.score),
+            (model.name.as_str(), model.score)
+        );
+    }
+}
+
+#[tokio::test]
+async fn postgres_backend() {
+    if let Ok(url) = std::env::var("OXIDITE_TEST_POSTGRES_URL") {
+        run_suite(DbPool::connect(&url).await.unwrap()).await;
+    }
+}
+
+#[tokio::test]
+async fn mysql_backend() {
+    if let Ok(url) = std::env::var("OXIDITE_TEST_MYSQL_URL") {
+        run_suite(DbPool::connect(&url).await.unwrap()).await;
+    }
+}
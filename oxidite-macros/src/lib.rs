@@ -18,7 +18,8 @@ fn derive_model_impl(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStrea
     let name = &input.ident;
 
     let default_table_name = format!("{}s", name.to_string().to_lowercase());
-    let table_name = parse_table_name(input)?.unwrap_or(default_table_name);
+    let attrs = parse_model_attrs(input)?;
+    let table_name = attrs.table_name.unwrap_or(default_table_name);
 
     let named_fields = match &input.data {
         Data::Struct(data) => match &data.fields {
@@ -134,43 +135,22 @@ fn derive_model_impl(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStrea
         create_cols_list.push("updated_at".to_string());
     }
 
-    let create_query = if create_cols_list.is_empty() {
-        format!("INSERT INTO {} DEFAULT VALUES", table_name)
-    } else {
-        let create_cols = create_cols_list.join(", ");
-        let create_placeholders: Vec<_> = (1..=create_cols_list.len())
-            .map(|i| format!("${}", i))
-            .collect();
-        let create_placeholders_str = create_placeholders.join(", ");
-        format!(
-            "INSERT INTO {} ({}) VALUES ({})",
-            table_name, create_cols, create_placeholders_str
-        )
-    };
-
-    let mut update_sets_list = Vec::new();
-    for (i, field_name) in non_id_names_str.iter().enumerate() {
-        update_sets_list.push(format!("{} = ${}", field_name, i + 1));
-    }
+    let create_idents: Vec<_> = create_cols_list
+        .iter()
+        .map(|col| syn::Ident::new(col, proc_macro2::Span::call_site()))
+        .collect();
 
-    let mut param_count = non_id_names_str.len();
+    let mut update_cols_list = non_id_names_str.clone();
     if has_updated_at {
-        param_count += 1;
-        update_sets_list.push(format!("updated_at = ${}", param_count));
+        update_cols_list.push("updated_at".to_string());
     }
 
-    let update_sets_str = update_sets_list.join(", ");
-    let update_where = format!("WHERE id = ${}", param_count + 1);
-    let update_query = format!("UPDATE {} SET {} {}", table_name, update_sets_str, update_where);
-
-    let hard_delete_query = format!("DELETE FROM {} WHERE id = $1", table_name);
-
     let delete_impl = if has_deleted_at {
-        let soft_delete_query = format!("UPDATE {} SET deleted_at = $1 WHERE id = $2", table_name);
         quote! {
             async fn delete(&self, db: &impl oxidite_db::Database) -> oxidite_db::Result<()> {
                 let now = oxidite_db::chrono::Utc::now().timestamp();
-                let query = oxidite_db::sqlx::query(#soft_delete_query)
+                let sql = db.dialect().update(#table_name, &["deleted_at"], "id");
+                let query = oxidite_db::sqlx::query(&sql)
                     .bind(now)
                     .bind(&self.id);
                 db.execute_query(query).await?;
@@ -180,7 +160,8 @@ fn derive_model_impl(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStrea
     } else {
         quote! {
             async fn delete(&self, db: &impl oxidite_db::Database) -> oxidite_db::Result<()> {
-                let query = oxidite_db::sqlx::query(#hard_delete_query)
+                let sql = db.dialect().delete(#table_name, "id");
+                let query = oxidite_db::sqlx::query(&sql)
                     .bind(&self.id);
                 db.execute_query(query).await?;
                 Ok(())
@@ -218,6 +199,88 @@ fn derive_model_impl(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStrea
         quote! {}
     };
 
+    // Without columns only one row of defaults can be inserted per statement,
+    // so keep the trait's row-by-row `insert_many`
+    let insert_many_impl = if create_cols_list.is_empty() {
+        quote! {}
+    } else {
+        let mut timestamps = Vec::new();
+        if has_created_at {
+            timestamps.push(quote! { model.created_at = now; });
+        }
+        if has_updated_at {
+            timestamps.push(quote! { model.updated_at = now; });
+        }
+        let timestamps_logic = if timestamps.is_empty() {
+            quote! {}
+        } else {
+            quote! {
+                let now = oxidite_db::chrono::Utc::now().timestamp();
+                for model in models.iter_mut() {
+                    #(#timestamps)*
+                }
+            }
+        };
+
+        quote! {
+            async fn insert_many(db: &impl oxidite_db::Database, models: &mut [Self]) -> oxidite_db::Result<Vec<i64>> {
+                const COLUMNS: &[&str] = &[#(#create_cols_list),*];
+                let dialect = db.dialect();
+                #timestamps_logic
+
+                // All chunks commit together, in the caller's transaction if
+                // there is one
+                let tx = if db.in_transaction() {
+                    None
+                } else {
+                    Some(db.begin_transaction().await?)
+                };
+                let conn: &dyn oxidite_db::Database = match &tx {
+                    Some(tx) => tx,
+                    None => db,
+                };
+
+                let rows_per_statement = (dialect.max_bind_params() / COLUMNS.len()).max(1);
+                let result: oxidite_db::Result<Vec<i64>> = async {
+                    let mut ids = Vec::with_capacity(models.len());
+                    for chunk in models.chunks_mut(rows_per_statement) {
+                        let sql = format!(
+                            "{}{}",
+                            dialect.insert_rows(#table_name, COLUMNS, chunk.len()),
+                            dialect.returning(&["id"])
+                        );
+                        let mut query = oxidite_db::sqlx::query(&sql);
+                        for model in chunk.iter() {
+                            #(
+                                query = query.bind(&model.#create_idents);
+                            )*
+                        }
+
+                        let chunk_ids = conn.insert_returning_ids(query, chunk.len()).await?;
+                        for (model, id) in chunk.iter_mut().zip(&chunk_ids) {
+                            model.id = *id;
+                        }
+                        ids.extend(chunk_ids);
+                    }
+                    Ok(ids)
+                }
+                .await;
+
+                match (tx, result) {
+                    (Some(tx), Ok(ids)) => {
+                        tx.commit().await?;
+                        Ok(ids)
+                    }
+                    (Some(tx), Err(err)) => {
+                        let _ = tx.rollback().await;
+                        Err(err)
+                    }
+                    (None, result) => result,
+                }
+            }
+        }
+    };
+
     let mut validation_checks = Vec::new();
     for field in &named_fields {
         let field_name = field
@@ -250,7 +313,134 @@ fn derive_model_impl(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStrea
         }
     }
 
+    let relations_impl = if attrs.relations.is_empty() {
+        quote! {}
+    } else {
+        let mut names = Vec::new();
+        let mut descriptors = Vec::new();
+        let mut loaders = Vec::new();
+        for relation in &attrs.relations {
+            let target = &relation.target;
+            let target_name = target
+                .segments
+                .last()
+                .map(|segment| segment.ident.to_string())
+                .unwrap_or_default();
+            let relation_name = relation
+                .name
+                .clone()
+                .unwrap_or_else(|| match relation.kind {
+                    RelationKind::HasMany => format!("{}s", to_snake_case(&target_name)),
+                    RelationKind::HasOne | RelationKind::BelongsTo => to_snake_case(&target_name),
+                });
+            if names.contains(&relation_name) {
+                return Err(syn::Error::new(
+                    relation.span,
+                    format!(
+                        "duplicate relation `{relation_name}`; set a different `name = \"...\"`"
+                    ),
+                ));
+            }
+
+            let foreign_key = relation
+                .foreign_key
+                .clone()
+                .unwrap_or_else(|| match relation.kind {
+                    RelationKind::HasMany | RelationKind::HasOne => {
+                        format!("{}_id", to_snake_case(&name.to_string()))
+                    }
+                    RelationKind::BelongsTo => format!("{}_id", to_snake_case(&target_name)),
+                });
+
+            let kind = match relation.kind {
+                RelationKind::HasMany => quote! { HasMany },
+                RelationKind::HasOne => quote! { HasOne },
+                RelationKind::BelongsTo => quote! { BelongsTo },
+            };
+            descriptors.push(quote! {
+                oxidite_db::Relation {
+                    kind: oxidite_db::RelationKind::#kind,
+                    table: <#target as oxidite_db::Model>::table_name(),
+                    foreign_key: #foreign_key,
+                }
+            });
+
+            loaders.push(match relation.kind {
+                RelationKind::HasMany | RelationKind::HasOne => {
+                    let many = matches!(relation.kind, RelationKind::HasMany);
+                    quote! {
+                        oxidite_db::relations::load_has::<Self, #target>(
+                            db, models, name, #foreign_key, #many, nested,
+                        ).await
+                    }
+                }
+                RelationKind::BelongsTo => {
+                    let Some(field) = find_field(&foreign_key).and_then(|f| f.ident.as_ref()) else {
+                        return Err(syn::Error::new(
+                            relation.span,
+                            format!("`belongs_to` foreign key `{foreign_key}` is not a field of `{name}`"),
+                        ));
+                    };
+                    quote! {
+                        oxidite_db::relations::load_belongs_to::<Self, #target>(
+                            db,
+                            models,
+                            name,
+                            |model: &Self| oxidite_db::relations::ForeignKey::foreign_key(&model.#field),
+                            nested,
+                        ).await
+                    }
+                }
+            });
+            names.push(relation_name);
+        }
+
+        quote! {
+            fn relation(name: &str) -> Option<oxidite_db::Relation> {
+                match name {
+                    #(#names => Some(#descriptors),)*
+                    _ => None,
+                }
+            }
+
+            async fn load_relation(
+                db: &impl oxidite_db::Database,
+                name: &str,
+                models: &mut [oxidite_db::Loaded<Self>],
+                nested: &[String],
+            ) -> oxidite_db::OrmResult<()> {
+                match name {
+                    #(#names => #loaders,)*
+                    _ => Err(oxidite_db::OrmError::UnknownRelation {
+                        model: #table_name,
+                        relation: name.to_string(),
+                    }),
+                }
+            }
+        }
+    };
+
+    let column_consts = named_fields.iter().filter_map(|field| {
+        let ident = field.ident.as_ref()?;
+        let column = ident.to_string();
+        let const_name = syn::Ident::new(
+            &column.trim_start_matches("r#").to_uppercase(),
+            ident.span(),
+        );
+        let ty = &field.ty;
+        let doc = format!("The `{column}` column");
+        Some(quote! {
+            #[doc = #doc]
+            pub const #const_name: oxidite_db::Column<#name, #ty> = oxidite_db::Column::new(#column);
+        })
+    });
+
     let expanded = quote! {
+        #[allow(dead_code)]
+        impl #name {
+            #(#column_consts)*
+        }
+
         #[oxidite_db::async_trait]
         impl oxidite_db::Model for #name {
             fn table_name() -> &'static str {
@@ -265,20 +455,34 @@ fn derive_model_impl(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStrea
                 #has_deleted_at
             }
 
+            fn id(&self) -> i64 {
+                self.id
+            }
+
             async fn create(&mut self, db: &impl oxidite_db::Database) -> oxidite_db::Result<()> {
-                let query = oxidite_db::sqlx::query(#create_query);
+                let dialect = db.dialect();
+                let sql = format!(
+                    "{}{}",
+                    dialect.insert(#table_name, &[#(#create_cols_list),*]),
+                    dialect.returning(&["id"])
+                );
+                let query = oxidite_db::sqlx::query(&sql);
                 #(
                     let query = query.bind(&self.#non_id_names);
                 )*
                 #created_at_logic
                 #updated_at_create_logic
 
-                db.execute_query(query).await?;
+                let ids = db.insert_returning_ids(query, 1).await?;
+                self.id = ids[0];
                 Ok(())
             }
 
+            #insert_many_impl
+
             async fn update(&mut self, db: &impl oxidite_db::Database) -> oxidite_db::Result<()> {
-                let query = oxidite_db::sqlx::query(#update_query);
+                let sql = db.dialect().update(#table_name, &[#(#update_cols_list),*], "id");
+                let query = oxidite_db::sqlx::query(&sql);
                 #(
                     let query = query.bind(&self.#non_id_names);
                 )*
@@ -292,7 +496,8 @@ fn derive_model_impl(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStrea
             #delete_impl
 
             async fn force_delete(&self, db: &impl oxidite_db::Database) -> oxidite_db::Result<()> {
-                let query = oxidite_db::sqlx::query(#hard_delete_query)
+                let sql = db.dialect().delete(#table_name, "id");
+                let query = oxidite_db::sqlx::query(&sql)
                     .bind(&self.id);
                 db.execute_query(query).await?;
                 Ok(())
@@ -306,15 +511,38 @@ fn derive_model_impl(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStrea
             fn is_persisted(&self) -> bool {
                 self.id > 0
             }
+
+            #relations_impl
         }
     };
 
     Ok(expanded)
 }
 
-fn parse_table_name(input: &DeriveInput) -> syn::Result<Option<String>> {
+#[derive(Clone, Copy)]
+enum RelationKind {
+    HasMany,
+    HasOne,
+    BelongsTo,
+}
+
+struct RelationAttr {
+    kind: RelationKind,
+    target: syn::Path,
+    foreign_key: Option<String>,
+    name: Option<String>,
+    span: proc_macro2::Span,
+}
+
+struct ModelAttrs {
+    table_name: Option<String>,
+    relations: Vec<RelationAttr>,
+}
+
+fn parse_model_attrs(input: &DeriveInput) -> syn::Result<ModelAttrs> {
     let mut table_name = None;
     let mut table_alias = None;
+    let mut relations = Vec::new();
 
     for attr in &input.attrs {
         if !attr.path().is_ident("model") {
@@ -340,9 +568,57 @@ fn parse_table_name(input: &DeriveInput) -> syn::Result<Option<String>> {
                 return Ok(());
             }
 
-            Err(meta.error(
-                "unsupported model attribute; expected `table_name = \"...\"` or `table = \"...\"`",
-            ))
+            let kind = if meta.path.is_ident("has_many") {
+                RelationKind::HasMany
+            } else if meta.path.is_ident("has_one") {
+                RelationKind::HasOne
+            } else if meta.path.is_ident("belongs_to") {
+                RelationKind::BelongsTo
+            } else {
+                return Err(meta.error(
+                    "unsupported model attribute; expected `table_name = \"...\"`, `table = \"...\"`, \
+                     `has_many(...)`, `has_one(...)` or `belongs_to(...)`",
+                ));
+            };
+
+            let span = meta.path.span();
+            let mut target = None;
+            let mut foreign_key = None;
+            let mut name = None;
+            meta.parse_nested_meta(|inner| {
+                if inner.path.is_ident("foreign_key") {
+                    let lit: LitStr = inner.value()?.parse()?;
+                    foreign_key = Some(lit.value());
+                    return Ok(());
+                }
+
+                if inner.path.is_ident("name") {
+                    let lit: LitStr = inner.value()?.parse()?;
+                    name = Some(lit.value());
+                    return Ok(());
+                }
+
+                if target.is_some() || inner.input.peek(syn::Token![=]) {
+                    return Err(inner.error(
+                        "unsupported relation attribute; expected the related model, \
+                         `foreign_key = \"...\"` or `name = \"...\"`",
+                    ));
+                }
+                target = Some(inner.path);
+                Ok(())
+            })?;
+
+            let target = target.ok_or_else(|| {
+                syn::Error::new(span, "relation requires the related model, e.g. `has_many(Post)`")
+            })?;
+            relations.push(RelationAttr {
+                kind,
+                target,
+                foreign_key,
+                name,
+                span,
+            });
+            Ok(())
         })?;
     }
 
@@ -357,7 +633,25 @@ fn parse_table_name(input: &DeriveInput) -> syn::Result<Option<String>> {
         table_name = table_alias;
     }
 
-    Ok(table_name)
+    Ok(ModelAttrs {
+        table_name,
+        relations,
+    })
+}
+
+fn to_snake_case(name: &str) -> String {
+    let mut snake = String::with_capacity(name.len() + 4);
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
 }
 
 fn is_string_type(ty: &Type) -> bool {
@@ -3,6 +3,7 @@ fn model_derive_ui() {
     let t = trybuild::TestCases::new();
     t.pass("tests/ui/pass_table_name.rs");
     t.pass("tests/ui/pass_table_alias.rs");
+    t.pass("tests/ui/pass_typed_columns.rs");
     t.compile_fail("tests/ui/fail_non_struct.rs");
     t.compile_fail("tests/ui/fail_unnamed_struct.rs");
     t.compile_fail("tests/ui/fail_missing_id.rs");
@@ -11,4 +12,7 @@ fn model_derive_ui() {
     t.compile_fail("tests/ui/fail_id_not_i64.rs");
     t.compile_fail("tests/ui/fail_deleted_at_not_option.rs");
     t.compile_fail("tests/ui/fail_table_and_table_name.rs");
+    t.compile_fail("tests/ui/fail_column_type_mismatch.rs");
+    t.compile_fail("tests/ui/fail_belongs_to_missing_field.rs");
+    t.compile_fail("tests/ui/fail_bad_relation_attr.rs");
 }
@@ -0,0 +1,7 @@
+#[derive(oxidite_macros::Model, sqlx::FromRow)]
+#[model(has_many(Post, through = "tags"))]
+struct User {
+    id: i64,
+}
+
+fn main() {}
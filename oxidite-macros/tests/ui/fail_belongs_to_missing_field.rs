@@ -0,0 +1,13 @@
+#[derive(oxidite_macros::Model, sqlx::FromRow)]
+struct User {
+    id: i64,
+}
+
+#[derive(oxidite_macros::Model, sqlx::FromRow)]
+#[model(belongs_to(User))]
+struct Post {
+    id: i64,
+    author_id: i64,
+}
+
+fn main() {}
@@ -0,0 +1,18 @@
+use oxidite_db::Model;
+use sqlx::FromRow;
+
+#[derive(oxidite_macros::Model, FromRow)]
+struct Person {
+    id: i64,
+    email: String,
+}
+
+#[derive(oxidite_macros::Model, FromRow)]
+struct Pet {
+    id: i64,
+}
+
+fn main() {
+    let _ = Person::query().filter_eq(Person::EMAIL, 5);
+    let _ = Pet::query().filter_eq(Person::ID, 1);
+}
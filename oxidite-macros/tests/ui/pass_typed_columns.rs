@@ -0,0 +1,25 @@
+use oxidite_db::{Model, SortDirection};
+use sqlx::FromRow;
+
+#[derive(oxidite_macros::Model, FromRow)]
+#[model(table_name = "people")]
+struct Person {
+    id: i64,
+    email: String,
+    age: i64,
+    nickname: Option<String>,
+}
+
+fn main() {
+    assert_eq!(Person::EMAIL.name(), "email");
+    assert_eq!(Person::ID.name(), "id");
+
+    let _query = Person::query()
+        .filter_eq(Person::EMAIL, "alice@example.com")
+        .filter_eq(Person::EMAIL, String::from("bob@example.com"))
+        .filter_gte(Person::AGE, 18)
+        .filter_in(Person::ID, [1, 2, 3])
+        .filter_like(Person::NICKNAME, "al%")
+        .filter_is_null(Person::NICKNAME)
+        .order_by(Person::AGE, SortDirection::Desc);
+}
@@ -17,7 +17,7 @@ pub use request_id::{RequestIdLayer, RequestIdMiddleware};
 pub use security_headers::{SecurityHeadersLayer, SecurityHeadersConfig, FrameOptions};
 pub use csrf::{CsrfLayer, CsrfConfig};
 pub use rate_limit::{RateLimiter, RateLimitConfig};
-pub use timeout::{TimeoutMiddleware, TimeoutError};
+pub use timeout::{Timeout, TimeoutMiddleware, TimeoutError};
 pub use server_header::add_server_header;
 pub use cache::{CacheLayer, CacheMiddleware, CacheConfig, CacheLayerBuilder};
 
@@ -2,9 +2,17 @@
 
 use std::time::Duration;
 use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
 use tokio::time::timeout;
+use tower::{Layer, Service};
+use oxidite_core::{OxiditeRequest, OxiditeResponse, Error};
 
 /// Timeout middleware
+///
+/// Also usable as a tower `Layer`, e.g. `router.route_layer(TimeoutMiddleware::seconds(5))`,
+/// in which case requests exceeding the deadline fail with `Error::ServiceUnavailable`.
+#[derive(Clone, Copy, Debug)]
 pub struct TimeoutMiddleware {
     duration: Duration,
 }
@@ -29,6 +37,48 @@ impl TimeoutMiddleware {
     }
 }
 
+impl<S> Layer<S> for TimeoutMiddleware {
+    type Service = Timeout<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Timeout {
+            inner,
+            duration: self.duration,
+        }
+    }
+}
+
+/// Service produced by layering `TimeoutMiddleware`
+#[derive(Clone)]
+pub struct Timeout<S> {
+    inner: S,
+    duration: Duration,
+}
+
+impl<S> Service<OxiditeRequest> for Timeout<S>
+where
+    S: Service<OxiditeRequest, Response = OxiditeResponse, Error = Error> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: OxiditeRequest) -> Self::Future {
+        let fut = self.inner.call(req);
+        let duration = self.duration;
+        Box::pin(async move {
+            timeout(duration, fut)
+                .await
+                .map_err(|_| Error::ServiceUnavailable(TimeoutError::Elapsed.to_string()))?
+        })
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum TimeoutError {
     #[error("Request timeout elapsed")]
@@ -445,16 +445,14 @@ impl AutoDocs for oxidite_core::Router {
         let spec_json = spec_arc.clone();
         self.get("/openapi.json", move || {
             let spec_json = spec_json.clone();
-            async move { Ok(oxidite_core::OxiditeResponse::json((*spec_json).clone())) }
+            async move { oxidite_core::OxiditeResponse::json((*spec_json).clone()) }
         });
 
         let spec_docs = spec_arc.clone();
         self.get("/api/docs", move || {
             let spec_docs = spec_docs.clone();
             async move {
-                Ok(oxidite_core::OxiditeResponse::html(generate_docs_html(
-                    &spec_docs,
-                )))
+                oxidite_core::OxiditeResponse::html(generate_docs_html(&spec_docs))
             }
         });
 
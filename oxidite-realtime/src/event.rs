@@ -18,6 +18,19 @@ pub enum EventType {
     Custom(String),
 }
 
+impl EventType {
+    /// Lowercase name, e.g. for the SSE `event:` field
+    pub fn as_str(&self) -> &str {
+        match self {
+            EventType::Message => "message",
+            EventType::Notification => "notification",
+            EventType::Update => "update",
+            EventType::System => "system",
+            EventType::Custom(name) => name,
+        }
+    }
+}
+
 /// Generic event structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Event {
@@ -8,10 +8,10 @@ pub mod pubsub;
 pub mod event;
 pub mod websocket;
 
-pub use sse::{SseEvent, SseStream, SseConfig};
+pub use sse::{LastEventId, Sse, SseEvent, SseConfig};
 pub use pubsub::{PubSub, Subscriber, Channel};
 pub use event::{Event, EventType};
-pub use websocket::{WebSocketConnection, WebSocketManager, Message as WsMessage, WebSocketError};
+pub use websocket::{WebSocket, WebSocketConnection, WebSocketManager, WebSocketUpgrade, Message as WsMessage, WebSocketError};
 
 use thiserror::Error;
 
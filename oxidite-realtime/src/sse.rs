@@ -1,7 +1,20 @@
 //! Server-Sent Events (SSE) support
 
+use crate::{Event, RealtimeError, Subscriber};
+use bytes::Bytes;
+use futures::stream::{BoxStream, Stream, StreamExt};
+use http::header::{HeaderValue, CACHE_CONTROL, CONTENT_TYPE};
+use http::request::Parts;
+use oxidite_core::{FromRequestParts, IntoResponse, OxiditeResponse};
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::convert::Infallible;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
 use std::time::Duration;
+use tokio::time::{Instant, Sleep};
 
 /// SSE event structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -79,7 +92,7 @@ impl SseEvent {
 /// SSE stream configuration
 #[derive(Debug, Clone)]
 pub struct SseConfig {
-    /// Keep-alive interval
+    /// Keep-alive interval; `Duration::ZERO` disables keep-alives
     pub keep_alive: Duration,
     /// Default retry interval for clients
     pub retry: Option<u64>,
@@ -94,20 +107,199 @@ impl Default for SseConfig {
     }
 }
 
-/// SSE stream wrapper (placeholder for actual stream implementation)
-pub struct SseStream {
+/// Server-Sent Events response
+///
+/// Turns a stream of [`SseEvent`]s into a `text/event-stream` response. A
+/// comment line is sent whenever the stream has been quiet for the configured
+/// keep-alive interval, and the configured `retry` is sent first.
+///
+/// # Example
+/// ```ignore
+/// async fn feed(LastEventId(last): LastEventId, State(pubsub): State<Arc<PubSub>>) -> Sse<BoxStream<'static, SseEvent>> {
+///     // Replay what a reconnecting client missed, then continue live
+///     Sse::from_subscriber_after(pubsub.subscribe("news").await, last, |id| {
+///         futures::stream::iter(events_after(id))
+///     })
+/// }
+/// ```
+pub struct Sse<S> {
+    events: S,
     config: SseConfig,
 }
 
-impl SseStream {
-    /// Create a new SSE stream
-    pub fn new(config: SseConfig) -> Self {
-        Self { config }
+impl<S> Sse<S>
+where
+    S: Stream<Item = SseEvent> + Send + 'static,
+{
+    /// Create an SSE response with the default config
+    pub fn new(events: S) -> Self {
+        Self {
+            events,
+            config: SseConfig::default(),
+        }
+    }
+
+    /// Replace the config
+    pub fn config(mut self, config: SseConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Set the keep-alive interval; `Duration::ZERO` disables keep-alives
+    pub fn keep_alive(mut self, interval: Duration) -> Self {
+        self.config.keep_alive = interval;
+        self
+    }
+
+    /// Get the underlying event stream
+    pub fn into_stream(self) -> S {
+        self.events
+    }
+}
+
+impl Sse<BoxStream<'static, SseEvent>> {
+    /// Stream events published to a pub/sub channel
+    ///
+    /// Events missed because the subscriber lagged are skipped; the stream
+    /// ends when the channel is closed.
+    pub fn from_subscriber(subscriber: Subscriber) -> Self {
+        let events = futures::stream::unfold(subscriber, |mut subscriber| async move {
+            loop {
+                match subscriber.recv().await {
+                    Ok(event) => return Some((SseEvent::from(event), subscriber)),
+                    Err(RealtimeError::Lagged(_)) => continue,
+                    Err(_) => return None,
+                }
+            }
+        });
+        Self::new(events.boxed())
+    }
+
+    /// Resume a pub/sub stream for a client that reconnected with
+    /// [`LastEventId`]
+    ///
+    /// `replay` gets the last id the client saw and returns the events it
+    /// missed; they are sent before the live events, and live events already
+    /// replayed (by id) are dropped. Subscribe before loading the replay so
+    /// nothing published in between is lost. Without a last id this is
+    /// [`from_subscriber`](Self::from_subscriber).
+    pub fn from_subscriber_after<F, R>(subscriber: Subscriber, last_id: Option<String>, replay: F) -> Self
+    where
+        F: FnOnce(&str) -> R,
+        R: Stream<Item = SseEvent> + Send + 'static,
+    {
+        let live = Self::from_subscriber(subscriber).into_stream();
+        let Some(last_id) = last_id else {
+            return Self::new(live);
+        };
+
+        let replayed = replay(&last_id);
+        let seen = Arc::new(Mutex::new(HashSet::from([last_id])));
+        let record = seen.clone();
+        let replayed = replayed.inspect(move |event| {
+            if let Some(id) = &event.id {
+                record.lock().unwrap().insert(id.clone());
+            }
+        });
+        let live = live.filter(move |event| {
+            let duplicate = event
+                .id
+                .as_ref()
+                .is_some_and(|id| seen.lock().unwrap().contains(id));
+            futures::future::ready(!duplicate)
+        });
+        Self::new(replayed.chain(live).boxed())
+    }
+}
+
+impl<S> IntoResponse for Sse<S>
+where
+    S: Stream<Item = SseEvent> + Send + 'static,
+{
+    fn into_response(self) -> OxiditeResponse {
+        let body = SseBody {
+            events: Box::pin(self.events),
+            retry: self.config.retry,
+            keep_alive: self.config.keep_alive,
+            timer: None,
+        };
+        let mut response = OxiditeResponse::stream(body);
+        let headers = response.headers_mut();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/event-stream"));
+        headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+        // Stop reverse proxies such as nginx from buffering the stream
+        headers.insert("x-accel-buffering", HeaderValue::from_static("no"));
+        response
+    }
+}
+
+impl From<Event> for SseEvent {
+    fn from(event: Event) -> Self {
+        SseEvent::new(event.data.to_string())
+            .id(event.id)
+            .event(event.event_type.as_str())
+    }
+}
+
+/// Encodes events and interleaves keep-alive comments
+struct SseBody<S> {
+    events: Pin<Box<S>>,
+    retry: Option<u64>,
+    keep_alive: Duration,
+    /// Created on first poll so the response can be built outside a runtime
+    timer: Option<Pin<Box<Sleep>>>,
+}
+
+impl<S: Stream<Item = SseEvent>> Stream for SseBody<S> {
+    type Item = Result<Bytes, Infallible>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        if let Some(retry) = this.retry.take() {
+            return Poll::Ready(Some(Ok(Bytes::from(format!("retry: {}\n\n", retry)))));
+        }
+
+        match this.events.as_mut().poll_next(cx) {
+            Poll::Ready(Some(event)) => {
+                if let Some(timer) = &mut this.timer {
+                    timer.as_mut().reset(Instant::now() + this.keep_alive);
+                }
+                return Poll::Ready(Some(Ok(Bytes::from(event.to_sse_string()))));
+            }
+            Poll::Ready(None) => return Poll::Ready(None),
+            Poll::Pending => {}
+        }
+
+        if this.keep_alive.is_zero() {
+            return Poll::Pending;
+        }
+        let keep_alive = this.keep_alive;
+        let timer = this
+            .timer
+            .get_or_insert_with(|| Box::pin(tokio::time::sleep(keep_alive)));
+        if timer.as_mut().poll(cx).is_ready() {
+            timer.as_mut().reset(Instant::now() + keep_alive);
+            return Poll::Ready(Some(Ok(Bytes::from_static(b": keep-alive\n\n"))));
+        }
+        Poll::Pending
     }
+}
 
-    /// Get the config
-    pub fn config(&self) -> &SseConfig {
-        &self.config
+/// The `Last-Event-ID` header sent by a reconnecting `EventSource`
+///
+/// Pass it to [`Sse::from_subscriber_after`] to replay the events the client
+/// missed before resuming the live stream.
+pub struct LastEventId(pub Option<String>);
+
+impl FromRequestParts for LastEventId {
+    async fn from_request_parts(parts: &mut Parts) -> oxidite_core::Result<Self> {
+        Ok(LastEventId(
+            parts
+                .headers
+                .get("last-event-id")
+                .and_then(|id| id.to_str().ok())
+                .map(str::to_string),
+        ))
     }
 }
 
@@ -138,4 +330,88 @@ mod tests {
         assert!(output.contains("data: Line 2\n"));
         assert!(output.contains("data: Line 3\n"));
     }
+
+    async fn body_chunks(response: OxiditeResponse, count: usize) -> Vec<String> {
+        use http_body_util::BodyExt;
+
+        let mut body = response.into_inner().into_body();
+        let mut chunks = Vec::new();
+        while chunks.len() < count {
+            let frame = body.frame().await.expect("frame").expect("body frame");
+            if let Ok(data) = frame.into_data() {
+                chunks.push(String::from_utf8(data.to_vec()).unwrap());
+            }
+        }
+        chunks
+    }
+
+    #[tokio::test]
+    async fn test_sse_response_streams_events() {
+        let events = futures::stream::iter(vec![SseEvent::new("one").id("1"), SseEvent::new("two").id("2")]);
+        let response = Sse::new(events).into_response();
+        assert_eq!(response.headers()[CONTENT_TYPE], "text/event-stream");
+        assert_eq!(response.headers()[CACHE_CONTROL], "no-cache");
+
+        let chunks = body_chunks(response, 3).await;
+        assert_eq!(chunks[0], "retry: 3000\n\n");
+        assert_eq!(chunks[1], "id: 1\ndata: one\n\n");
+        assert_eq!(chunks[2], "id: 2\ndata: two\n\n");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_sse_sends_keep_alive_comments() {
+        let response = Sse::new(futures::stream::pending())
+            .keep_alive(Duration::from_secs(15))
+            .into_response();
+        let chunks = body_chunks(response, 3).await;
+        assert_eq!(chunks[1], ": keep-alive\n\n");
+        assert_eq!(chunks[2], ": keep-alive\n\n");
+    }
+
+    #[tokio::test]
+    async fn test_sse_from_subscriber() {
+        let pubsub = crate::PubSub::new();
+        let subscriber = pubsub.subscribe("news").await;
+        let mut events = Sse::from_subscriber(subscriber).into_stream();
+
+        let event = Event::message("news", serde_json::json!({"headline": "hello"}));
+        let id = event.id.clone();
+        pubsub.publish("news", event).await.unwrap();
+
+        let sse = events.next().await.unwrap();
+        assert_eq!(sse.id.as_deref(), Some(id.as_str()));
+        assert_eq!(sse.event.as_deref(), Some("message"));
+        assert_eq!(sse.data, r#"{"headline":"hello"}"#);
+    }
+
+    #[tokio::test]
+    async fn test_sse_resumes_after_last_event_id() {
+        let pubsub = crate::PubSub::new();
+        let subscriber = pubsub.subscribe("news").await;
+        let mut events = Sse::from_subscriber_after(subscriber, Some("1".to_string()), |last| {
+            assert_eq!(last, "1");
+            futures::stream::iter(vec![SseEvent::new("two").id("2"), SseEvent::new("three").id("3")])
+        })
+        .into_stream();
+
+        for id in ["1", "3", "4"] {
+            let mut event = Event::message("news", serde_json::json!(id));
+            event.id = id.to_string();
+            pubsub.publish("news", event).await.unwrap();
+        }
+
+        let ids: Vec<_> = (&mut events).take(3).map(|event| event.id.unwrap()).collect().await;
+        assert_eq!(ids, ["2", "3", "4"]);
+    }
+
+    #[tokio::test]
+    async fn test_last_event_id_extractor() {
+        let (mut parts, _) = http::Request::builder()
+            .header("last-event-id", "42")
+            .body(())
+            .unwrap()
+            .into_parts();
+        let LastEventId(id) = LastEventId::from_request_parts(&mut parts).await.unwrap();
+        assert_eq!(id.as_deref(), Some("42"));
+    }
 }
@@ -6,8 +6,10 @@ use tokio_tungstenite::tungstenite::Message as WsMessage;
 use uuid::Uuid;
 
 pub mod rooms;
+pub mod upgrade;
 
 pub use rooms::{Room, RoomManager};
+pub use upgrade::{WebSocket, WebSocketUpgrade};
 
 /// WebSocket message types
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -190,6 +192,15 @@ pub enum WebSocketError {
 
     #[error("No active connection found for user `{0}`")]
     UserNotConnected(String),
+
+    #[error("WebSocket protocol error: {0}")]
+    Protocol(Box<tokio_tungstenite::tungstenite::Error>),
+}
+
+impl From<tokio_tungstenite::tungstenite::Error> for WebSocketError {
+    fn from(err: tokio_tungstenite::tungstenite::Error) -> Self {
+        WebSocketError::Protocol(Box::new(err))
+    }
 }
 
 pub type Result<T> = std::result::Result<T, WebSocketError>;
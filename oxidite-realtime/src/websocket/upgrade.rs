@@ -0,0 +1,456 @@
+//! HTTP/1.1 WebSocket upgrade handshake (RFC 6455).
+
+use super::{Message, WebSocketConnection, WebSocketError, WebSocketManager};
+use base64::Engine;
+use futures::{SinkExt, StreamExt};
+use http::header::{
+    HeaderValue, CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_KEY, SEC_WEBSOCKET_PROTOCOL,
+    SEC_WEBSOCKET_VERSION, UPGRADE,
+};
+use http::request::Parts;
+use http::{Method, StatusCode};
+use http_body_util::{BodyExt, Empty};
+use hyper::upgrade::{OnUpgrade, Upgraded};
+use hyper_util::rt::TokioIo;
+use oxidite_core::{Error, FromRequestParts, OxiditeResponse, ProblemDetails};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::time::{Instant, Interval};
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::{Role, WebSocketConfig};
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+use tokio_tungstenite::WebSocketStream;
+
+/// Default interval between keep-alive pings
+pub const DEFAULT_PING_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Extractor that performs the WebSocket handshake
+///
+/// Validates the upgrade request, then [`on_upgrade`](Self::on_upgrade)
+/// returns the `101 Switching Protocols` response and runs the callback with
+/// the upgraded [`WebSocket`]. If an `Arc<WebSocketManager>` is present in the
+/// request extensions (or set with [`manager`](Self::manager)), the socket is
+/// registered for the lifetime of the callback.
+///
+/// # Example
+/// ```ignore
+/// async fn chat(ws: WebSocketUpgrade) -> Response {
+///     ws.protocols(["chat.v1"])
+///         .max_message_size(64 * 1024)
+///         .on_upgrade(|mut socket| async move {
+///             while let Some(Ok(msg)) = socket.recv().await {
+///                 if socket.send(msg).await.is_err() {
+///                     break;
+///                 }
+///             }
+///         })
+/// }
+/// ```
+pub struct WebSocketUpgrade {
+    on_upgrade: OnUpgrade,
+    key: HeaderValue,
+    requested_protocols: Vec<String>,
+    protocol: Option<String>,
+    config: WebSocketConfig,
+    ping_interval: Option<Duration>,
+    manager: Option<Arc<WebSocketManager>>,
+    user_id: Option<String>,
+}
+
+impl WebSocketUpgrade {
+    /// Pick the first subprotocol requested by the client that is also in
+    /// `supported`; without a match no `Sec-WebSocket-Protocol` is sent
+    pub fn protocols<I, P>(mut self, supported: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<str>,
+    {
+        let supported: Vec<P> = supported.into_iter().collect();
+        self.protocol = self
+            .requested_protocols
+            .iter()
+            .find(|requested| supported.iter().any(|p| p.as_ref() == requested.as_str()))
+            .cloned();
+        self
+    }
+
+    /// Subprotocol that will be confirmed to the client
+    pub fn selected_protocol(&self) -> Option<&str> {
+        self.protocol.as_deref()
+    }
+
+    /// Subprotocols the client asked for, in order of preference
+    pub fn requested_protocols(&self) -> &[String] {
+        &self.requested_protocols
+    }
+
+    /// Maximum size of an incoming message (tungstenite defaults to 64 MiB)
+    pub fn max_message_size(mut self, bytes: usize) -> Self {
+        self.config.max_message_size = Some(bytes);
+        self
+    }
+
+    /// Interval between keep-alive pings; `None` disables them
+    ///
+    /// A peer that has not answered by the next ping is disconnected.
+    pub fn ping_interval(mut self, interval: Option<Duration>) -> Self {
+        self.ping_interval = interval;
+        self
+    }
+
+    /// Register the socket with this manager
+    pub fn manager(mut self, manager: Arc<WebSocketManager>) -> Self {
+        self.manager = Some(manager);
+        self
+    }
+
+    /// User the registered connection belongs to, for `send_to_user`
+    pub fn user_id(mut self, user_id: impl Into<String>) -> Self {
+        self.user_id = Some(user_id.into());
+        self
+    }
+
+    /// Accept the upgrade and run `callback` once the connection switches
+    pub fn on_upgrade<F, Fut>(self, callback: F) -> OxiditeResponse
+    where
+        F: FnOnce(WebSocket) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let mut response = http::Response::builder()
+            .status(StatusCode::SWITCHING_PROTOCOLS)
+            .header(CONNECTION, HeaderValue::from_static("upgrade"))
+            .header(UPGRADE, HeaderValue::from_static("websocket"))
+            .header(SEC_WEBSOCKET_ACCEPT, derive_accept_key(self.key.as_bytes()));
+        if let Some(protocol) = &self.protocol {
+            response = response.header(SEC_WEBSOCKET_PROTOCOL, protocol);
+        }
+        let response = response
+            .body(Empty::new().map_err(|e| match e {}).boxed())
+            .expect("valid upgrade response");
+
+        let WebSocketUpgrade {
+            on_upgrade,
+            protocol,
+            config,
+            ping_interval,
+            manager,
+            user_id,
+            ..
+        } = self;
+        tokio::spawn(async move {
+            let upgraded = match on_upgrade.await {
+                Ok(upgraded) => upgraded,
+                Err(err) => {
+                    eprintln!("WebSocket upgrade error: {}", err);
+                    return;
+                }
+            };
+            let stream =
+                WebSocketStream::from_raw_socket(TokioIo::new(upgraded), Role::Server, Some(config))
+                    .await;
+
+            let (registration, outbound) = match manager {
+                Some(manager) => {
+                    let (connection, rx) = WebSocketConnection::new(user_id);
+                    let connection = Arc::new(connection);
+                    manager.add_connection(connection.clone()).await;
+                    (Some(Registration { manager, connection }), Some(rx))
+                }
+                None => (None, None),
+            };
+
+            let socket = WebSocket {
+                stream,
+                protocol,
+                registration,
+                outbound,
+                ping: ping_interval.map(|period| tokio::time::interval_at(Instant::now() + period, period)),
+                awaiting_pong: false,
+            };
+            callback(socket).await;
+        });
+
+        OxiditeResponse::new(response)
+    }
+}
+
+impl FromRequestParts for WebSocketUpgrade {
+    async fn from_request_parts(parts: &mut Parts) -> oxidite_core::Result<Self> {
+        if parts.method != Method::GET {
+            return Err(Error::MethodNotAllowed("WebSocket upgrades must use GET".to_string()));
+        }
+        if !header_contains(parts, CONNECTION, "upgrade") || !header_contains(parts, UPGRADE, "websocket") {
+            return Err(Error::BadRequest("Expected a WebSocket upgrade request".to_string()));
+        }
+        if parts.headers.get(SEC_WEBSOCKET_VERSION).map(|v| v.as_bytes()) != Some(b"13") {
+            return Err(ProblemDetails::new(StatusCode::UPGRADE_REQUIRED)
+                .with_detail("Only WebSocket version 13 is supported")
+                .with_header(SEC_WEBSOCKET_VERSION, HeaderValue::from_static("13"))
+                .into());
+        }
+        let key = parts
+            .headers
+            .get(SEC_WEBSOCKET_KEY)
+            .filter(|key| {
+                base64::engine::general_purpose::STANDARD
+                    .decode(key.as_bytes())
+                    .is_ok_and(|nonce| nonce.len() == 16)
+            })
+            .cloned()
+            .ok_or_else(|| Error::BadRequest("Invalid Sec-WebSocket-Key".to_string()))?;
+        let on_upgrade = parts
+            .extensions
+            .remove::<OnUpgrade>()
+            .ok_or_else(|| Error::BadRequest("Connection cannot be upgraded".to_string()))?;
+
+        let requested_protocols = parts
+            .headers
+            .get_all(SEC_WEBSOCKET_PROTOCOL)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .map(|p| p.trim().to_string())
+            .filter(|p| !p.is_empty())
+            .collect();
+
+        Ok(WebSocketUpgrade {
+            on_upgrade,
+            key,
+            requested_protocols,
+            protocol: None,
+            config: WebSocketConfig::default(),
+            ping_interval: Some(DEFAULT_PING_INTERVAL),
+            manager: parts.extensions.get::<Arc<WebSocketManager>>().cloned(),
+            user_id: None,
+        })
+    }
+}
+
+/// Whether a comma-separated header contains `token`, ignoring case
+fn header_contains(parts: &Parts, name: http::header::HeaderName, token: &str) -> bool {
+    parts
+        .headers
+        .get_all(name)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|v| v.trim().eq_ignore_ascii_case(token))
+}
+
+/// Removes the connection from its manager when the socket goes away
+struct Registration {
+    manager: Arc<WebSocketManager>,
+    connection: Arc<WebSocketConnection>,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        let manager = self.manager.clone();
+        let id = self.connection.id.clone();
+        tokio::spawn(async move { manager.remove_connection(&id).await });
+    }
+}
+
+/// An upgraded WebSocket connection
+///
+/// [`recv`](Self::recv) also sends keep-alive pings and forwards messages sent
+/// to this connection through the [`WebSocketManager`], so keep calling it
+/// for as long as the connection should stay open.
+pub struct WebSocket {
+    stream: WebSocketStream<TokioIo<Upgraded>>,
+    protocol: Option<String>,
+    registration: Option<Registration>,
+    outbound: Option<broadcast::Receiver<Message>>,
+    ping: Option<Interval>,
+    awaiting_pong: bool,
+}
+
+impl WebSocket {
+    /// Negotiated subprotocol
+    pub fn protocol(&self) -> Option<&str> {
+        self.protocol.as_deref()
+    }
+
+    /// Connection registered with the manager, e.g. to join rooms by its `id`
+    pub fn connection(&self) -> Option<&Arc<WebSocketConnection>> {
+        self.registration.as_ref().map(|r| &r.connection)
+    }
+
+    /// Receive the next message, or `None` once the connection is closed
+    ///
+    /// Closes the connection if the peer misses a keep-alive ping.
+    pub async fn recv(&mut self) -> Option<Result<WsMessage, WebSocketError>> {
+        loop {
+            let outbound = async {
+                match &mut self.outbound {
+                    Some(rx) => rx.recv().await,
+                    None => std::future::pending().await,
+                }
+            };
+            let ping = async {
+                match &mut self.ping {
+                    Some(ping) => ping.tick().await,
+                    None => std::future::pending().await,
+                }
+            };
+
+            tokio::select! {
+                incoming = self.stream.next() => {
+                    self.awaiting_pong = false;
+                    return match incoming? {
+                        Ok(WsMessage::Pong(_)) => continue,
+                        Ok(msg) => Some(Ok(msg)),
+                        Err(err) => Some(Err(err.into())),
+                    };
+                }
+                message = outbound => match message {
+                    Ok(message) => {
+                        let sent = match message.to_ws_message() {
+                            Ok(msg) => self.send(msg).await,
+                            Err(err) => Err(err),
+                        };
+                        if let Err(err) = sent {
+                            return Some(Err(err));
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => self.outbound = None,
+                },
+                _ = ping => {
+                    if self.awaiting_pong {
+                        let _ = self.stream.close(None).await;
+                        return None;
+                    }
+                    self.awaiting_pong = true;
+                    if let Err(err) = self.send(WsMessage::Ping(Vec::new())).await {
+                        return Some(Err(err));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Send a message
+    pub async fn send(&mut self, message: WsMessage) -> Result<(), WebSocketError> {
+        self.stream.send(message).await.map_err(Into::into)
+    }
+
+    /// Send a close frame and flush it
+    pub async fn close(mut self) -> Result<(), WebSocketError> {
+        self.stream.close(None).await.map_err(Into::into)
+    }
+
+    /// The underlying tokio-tungstenite stream
+    ///
+    /// Keep-alive pings and manager messages are no longer handled once
+    /// the stream is taken; the manager registration ends with it.
+    pub fn into_inner(self) -> WebSocketStream<TokioIo<Upgraded>> {
+        self.stream
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oxidite_core::{Router, Server};
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+
+    async fn spawn_server(router: Router) -> std::net::SocketAddr {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = Server::from_std_listener(router, listener).unwrap();
+        tokio::spawn(server.run());
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_echo_over_upgraded_connection() {
+        let manager = Arc::new(WebSocketManager::new());
+        let registered = manager.clone();
+        let mut router = Router::new();
+        router.get("/ws", move |ws: WebSocketUpgrade| {
+            let manager = registered.clone();
+            async move {
+                ws.protocols(["chat"]).manager(manager).user_id("u1").on_upgrade(|mut socket| async move {
+                    while let Some(Ok(msg)) = socket.recv().await {
+                        if msg.is_text() && socket.send(msg).await.is_err() {
+                            break;
+                        }
+                    }
+                })
+            }
+        });
+        let addr = spawn_server(router).await;
+
+        let mut request = format!("ws://{}/ws", addr).into_client_request().unwrap();
+        request
+            .headers_mut()
+            .insert(SEC_WEBSOCKET_PROTOCOL, HeaderValue::from_static("other,chat"));
+        let (mut client, response) = tokio_tungstenite::connect_async(request).await.unwrap();
+        assert_eq!(response.headers()[SEC_WEBSOCKET_PROTOCOL], "chat");
+
+        client.send(WsMessage::Text("hello".into())).await.unwrap();
+        assert_eq!(client.next().await.unwrap().unwrap(), WsMessage::Text("hello".into()));
+
+        // Messages sent through the manager reach the socket
+        manager.send_to_user("u1", Message::text("from manager")).await.unwrap();
+        assert_eq!(client.next().await.unwrap().unwrap(), WsMessage::Text("from manager".into()));
+        assert_eq!(manager.connection_count().await, 1);
+
+        client.close(None).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while manager.connection_count().await > 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("connection unregistered");
+    }
+
+    #[tokio::test]
+    async fn test_rejects_invalid_handshakes() {
+        let (mut parts, _) = http::Request::builder()
+            .uri("/ws")
+            .header(CONNECTION, "Upgrade")
+            .header(UPGRADE, "websocket")
+            .header(SEC_WEBSOCKET_VERSION, "8")
+            .header(SEC_WEBSOCKET_KEY, "dGhlIHNhbXBsZSBub25jZQ==")
+            .body(())
+            .unwrap()
+            .into_parts();
+        let err = WebSocketUpgrade::from_request_parts(&mut parts).await.err().unwrap();
+        assert_eq!(err.status_code(), StatusCode::UPGRADE_REQUIRED);
+        let res = OxiditeResponse::from(err);
+        assert_eq!(res.status(), StatusCode::UPGRADE_REQUIRED);
+        assert_eq!(res.headers()[SEC_WEBSOCKET_VERSION], "13");
+
+        parts.headers.insert(SEC_WEBSOCKET_VERSION, HeaderValue::from_static("13"));
+        parts.headers.insert(SEC_WEBSOCKET_KEY, HeaderValue::from_static("short"));
+        let err = WebSocketUpgrade::from_request_parts(&mut parts).await.err().unwrap();
+        assert!(matches!(err, Error::BadRequest(_)));
+
+        parts.headers.remove(UPGRADE);
+        let err = WebSocketUpgrade::from_request_parts(&mut parts).await.err().unwrap();
+        assert!(matches!(err, Error::BadRequest(_)));
+    }
+
+    #[tokio::test]
+    async fn test_oversized_messages_close_the_connection() {
+        let mut router = Router::new();
+        router.get("/ws", |ws: WebSocketUpgrade| async move {
+            ws.max_message_size(8).on_upgrade(|mut socket| async move {
+                while let Some(Ok(msg)) = socket.recv().await {
+                    let _ = socket.send(msg).await;
+                }
+            })
+        });
+        let addr = spawn_server(router).await;
+
+        let (mut client, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws", addr)).await.unwrap();
+        client.send(WsMessage::Text("way more than eight bytes".into())).await.unwrap();
+        let next = tokio::time::timeout(Duration::from_secs(5), client.next()).await.unwrap();
+        assert!(!matches!(next, Some(Ok(WsMessage::Text(_)))), "{next:?}");
+    }
+}
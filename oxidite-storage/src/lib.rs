@@ -1,6 +1,8 @@
 use async_trait::async_trait;
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
+use futures_util::{Stream, StreamExt};
 use std::path::{Component, Path};
+use std::pin::Pin;
 
 pub mod local;
 pub mod validation;
@@ -19,6 +21,18 @@ pub use s3::S3Storage;
 pub trait Storage: Send + Sync {
     /// Store a file
     async fn put(&self, path: &str, data: Bytes) -> Result<StoredFile>;
+
+    /// Store a file from a stream of chunks
+    ///
+    /// Backends that cannot write incrementally buffer the stream and call
+    /// [`put`](Self::put).
+    async fn put_stream(&self, path: &str, mut data: DataStream<'_>) -> Result<StoredFile> {
+        let mut buf = BytesMut::new();
+        while let Some(chunk) = data.next().await {
+            buf.extend_from_slice(&chunk?);
+        }
+        self.put(path, buf.freeze()).await
+    }
     
     /// Retrieve a file
     async fn get(&self, path: &str) -> Result<Bytes>;
@@ -36,6 +50,9 @@ pub trait Storage: Send + Sync {
     async fn list(&self, prefix: &str) -> Result<Vec<String>>;
 }
 
+/// Chunked file contents passed to [`Storage::put_stream`]
+pub type DataStream<'a> = Pin<Box<dyn Stream<Item = Result<Bytes>> + Send + 'a>>;
+
 /// Stored file information
 #[derive(Debug, Clone)]
 pub struct StoredFile {
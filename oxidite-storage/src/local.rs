@@ -1,6 +1,7 @@
-use crate::{validate_storage_path, Storage, StoredFile, FileMetadata, Result, StorageError};
+use crate::{validate_storage_path, DataStream, Storage, StoredFile, FileMetadata, Result, StorageError};
 use async_trait::async_trait;
 use bytes::Bytes;
+use futures_util::StreamExt;
 use std::path::PathBuf;
 use tokio::fs;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
@@ -55,6 +56,44 @@ impl Storage for LocalStorage {
         })
     }
 
+    async fn put_stream(&self, path: &str, mut data: DataStream<'_>) -> Result<StoredFile> {
+        let full_path = self.resolve_path(path)?;
+
+        if let Some(parent) = full_path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        // Write chunks as they arrive, removing the partial file on failure
+        let mut file = fs::File::create(&full_path).await?;
+        let mut size = 0u64;
+        let written: Result<()> = async {
+            while let Some(chunk) = data.next().await {
+                let chunk = chunk?;
+                file.write_all(&chunk).await?;
+                size += chunk.len() as u64;
+            }
+            file.flush().await?;
+            Ok(())
+        }
+        .await;
+        if let Err(err) = written {
+            drop(file);
+            let _ = fs::remove_file(&full_path).await;
+            return Err(err);
+        }
+
+        let mime_type = mime_guess::from_path(&full_path)
+            .first_or_octet_stream()
+            .to_string();
+
+        Ok(StoredFile {
+            path: path.to_string(),
+            size,
+            mime_type,
+            url: None,
+        })
+    }
+
     async fn get(&self, path: &str) -> Result<Bytes> {
         let full_path = self.resolve_path(path)?;
         
@@ -143,6 +182,7 @@ impl Storage for LocalStorage {
 #[cfg(test)]
 mod tests {
     use super::{LocalStorage, Storage};
+    use crate::StorageError;
     use bytes::Bytes;
 
     #[tokio::test]
@@ -165,4 +205,25 @@ mod tests {
         let files = storage.list("images").await.expect("list");
         assert_eq!(files, vec!["images/logo.txt".to_string()]);
     }
+
+    #[tokio::test]
+    async fn local_storage_put_stream_writes_chunks_and_cleans_up_on_error() {
+        let root = std::env::temp_dir().join(format!("oxidite-storage-{}", uuid::Uuid::new_v4()));
+        let storage = LocalStorage::new(&root).expect("storage init");
+
+        let chunks = futures_util::stream::iter(vec![
+            Ok(Bytes::from_static(b"hello ")),
+            Ok(Bytes::from_static(b"world")),
+        ]);
+        let stored = storage.put_stream("greeting.txt", Box::pin(chunks)).await.expect("put_stream");
+        assert_eq!(stored.size, 11);
+        assert_eq!(storage.get("greeting.txt").await.unwrap(), Bytes::from_static(b"hello world"));
+
+        let failing = futures_util::stream::iter(vec![
+            Ok(Bytes::from_static(b"partial")),
+            Err(StorageError::Other("client went away".to_string())),
+        ]);
+        assert!(storage.put_stream("broken.txt", Box::pin(failing)).await.is_err());
+        assert!(!storage.exists("broken.txt").await.unwrap());
+    }
 }
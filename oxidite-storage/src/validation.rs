@@ -1,5 +1,6 @@
-use crate::{StorageError, Result};
+use crate::{Storage, StorageError, StoredFile, Result};
 use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
 
 /// File validation rules
 #[derive(Debug, Clone)]
@@ -61,15 +62,25 @@ impl FileValidator {
     }
 
     pub fn validate(&self, filename: &str, data: &Bytes) -> Result<()> {
-        // Validate file size
+        self.validate_size(data.len() as u64)?;
+        self.validate_name(filename)
+    }
+
+    /// Check a file size against `max_size`
+    pub fn validate_size(&self, size: u64) -> Result<()> {
         if let Some(max_size) = self.rules.max_size {
-            if data.len() as u64 > max_size {
+            if size > max_size {
                 return Err(StorageError::Validation(
-                    format!("File size {} exceeds maximum {}", data.len(), max_size)
+                    format!("File size {} exceeds maximum {}", size, max_size)
                 ));
             }
         }
 
+        Ok(())
+    }
+
+    /// Check a file name against the allowed extensions and MIME types
+    pub fn validate_name(&self, filename: &str) -> Result<()> {
         // Validate extension
         if let Some(allowed_extensions) = &self.rules.allowed_extensions {
             let extension = std::path::Path::new(filename)
@@ -101,6 +112,44 @@ impl FileValidator {
 
         Ok(())
     }
+
+    /// Validate an upload while streaming it into `storage`
+    ///
+    /// The file name is checked before anything is written and the size as
+    /// chunks arrive, so oversized uploads are cut off early. Works with any
+    /// stream of `Bytes`, such as an `oxidite_core::multipart::Field`.
+    ///
+    /// # Example
+    /// ```ignore
+    /// while let Some(field) = multipart.next_field().await? {
+    ///     let name = field.file_name().unwrap_or_default().to_string();
+    ///     let path = format!("uploads/{}", generate_filename(&name));
+    ///     validator.store(&storage, &path, &name, field).await?;
+    /// }
+    /// ```
+    pub async fn store<S, St, E>(
+        &self,
+        storage: &S,
+        path: &str,
+        filename: &str,
+        data: St,
+    ) -> Result<StoredFile>
+    where
+        S: Storage + ?Sized,
+        St: Stream<Item = std::result::Result<Bytes, E>> + Send,
+        E: std::fmt::Display,
+    {
+        self.validate_name(filename)?;
+
+        let mut size = 0u64;
+        let data = data.map(move |chunk| {
+            let chunk = chunk.map_err(|e| StorageError::Other(e.to_string()))?;
+            size += chunk.len() as u64;
+            self.validate_size(size)?;
+            Ok(chunk)
+        });
+        storage.put_stream(path, Box::pin(data)).await
+    }
 }
 
 /// Generate secure random filename
@@ -122,6 +171,7 @@ pub fn generate_filename(original: &str) -> String {
 #[cfg(test)]
 mod tests {
     use super::{generate_filename, FileValidator, ValidationRules};
+    use crate::{LocalStorage, Storage, StorageError};
     use bytes::Bytes;
 
     #[test]
@@ -139,4 +189,38 @@ mod tests {
         let name = generate_filename("avatar.PNG");
         assert!(name.ends_with(".png"));
     }
+
+    #[tokio::test]
+    async fn validator_store_checks_name_and_streamed_size() {
+        let root = std::env::temp_dir().join(format!("oxidite-storage-{}", uuid::Uuid::new_v4()));
+        let storage = LocalStorage::new(&root).expect("storage init");
+        let validator = FileValidator::new(
+            ValidationRules::new()
+                .max_size(8)
+                .allowed_extensions(vec!["txt".to_string()]),
+        );
+        let chunks = |parts: &[&'static [u8]]| {
+            futures_util::stream::iter(
+                parts.iter().map(|p| Ok::<_, StorageError>(Bytes::from_static(p))).collect::<Vec<_>>(),
+            )
+        };
+
+        let stored = validator
+            .store(&storage, "ok.txt", "notes.txt", chunks(&[b"abc", b"def"]))
+            .await
+            .expect("store");
+        assert_eq!(stored.size, 6);
+
+        let err = validator
+            .store(&storage, "big.txt", "notes.txt", chunks(&[b"abcdef", b"ghijkl"]))
+            .await;
+        assert!(matches!(err, Err(StorageError::Validation(_))));
+        assert!(!storage.exists("big.txt").await.unwrap());
+
+        let err = validator
+            .store(&storage, "evil.exe", "evil.exe", chunks(&[b"MZ"]))
+            .await;
+        assert!(matches!(err, Err(StorageError::Validation(_))));
+        assert!(!storage.exists("evil.exe").await.unwrap());
+    }
 }
@@ -8,11 +8,12 @@ pub mod renderer;
 pub mod filters;
 pub mod static_files;
 
-pub use parser::{Parser, TemplateNode};
+pub use parser::{Parser, TemplateNode, UrlArg};
 pub use renderer::Renderer;
 pub use filters::Filters;
 pub use static_files::{StaticFiles, serve_static, static_handler};
 use oxidite_core::types::OxiditeResponse;
+use oxidite_core::UrlFor;
 
 /// Template context for variable interpolation
 #[derive(Debug, Clone)]
@@ -65,15 +66,28 @@ impl Default for Context {
 /// Template engine to manage multiple templates
 pub struct TemplateEngine {
     templates: HashMap<String, Template>,
+    urls: Option<UrlFor>,
 }
 
 impl TemplateEngine {
     pub fn new() -> Self {
         Self {
             templates: HashMap::new(),
+            urls: None,
         }
     }
 
+    /// Enable `{{ url_for("route.name", param=value) }}` in templates
+    ///
+    /// Pass `router.url_generator()` once all named routes are registered.
+    pub fn set_url_generator(&mut self, urls: UrlFor) {
+        self.urls = Some(urls);
+    }
+
+    pub fn url_generator(&self) -> Option<&UrlFor> {
+        self.urls.as_ref()
+    }
+
     pub fn add_template(&mut self, name: impl Into<String>, source: impl Into<String>) -> Result<()> {
         let template = Template::new(source)?;
         self.templates.insert(name.into(), template);
@@ -220,4 +234,23 @@ mod tests {
         let result = tmpl.render(&ctx).unwrap();
         assert_eq!(result, "Hello Alice!");
     }
+
+    #[test]
+    fn test_url_for_uses_router_names() {
+        let mut router = oxidite_core::Router::new();
+        router
+            .get("/users/:id", || async { OxiditeResponse::ok() })
+            .name("users.show");
+
+        let mut engine = TemplateEngine::new();
+        engine.set_url_generator(router.url_generator());
+        engine
+            .add_template("link", r#"<a href="{{ url_for("users.show", id=user.id, q="a&b") }}">"#)
+            .unwrap();
+
+        let mut ctx = Context::new();
+        ctx.set("user", serde_json::json!({ "id": 5 }));
+        let result = engine.render("link", &ctx).unwrap();
+        assert_eq!(result, r#"<a href="/users/5?q=a%26b">"#);
+    }
 }
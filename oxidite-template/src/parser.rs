@@ -10,6 +10,21 @@ fn variable_regex() -> &'static Regex {
     })
 }
 
+fn url_for_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r#"^\{\{\s*url_for\(\s*"([^"]+)"((?:\s*,\s*[a-zA-Z0-9_]+\s*=\s*(?:"[^"]*"|[a-zA-Z0-9_.]+))*)\s*\)\s*\}\}"#)
+            .expect("url_for regex")
+    })
+}
+
+fn url_arg_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r#"([a-zA-Z0-9_]+)\s*=\s*(?:"([^"]*)"|([a-zA-Z0-9_.]+))"#).expect("url_for argument regex")
+    })
+}
+
 fn if_regex() -> &'static Regex {
     static RE: OnceLock<Regex> = OnceLock::new();
     RE.get_or_init(|| Regex::new(r"\{%\s*if\s+([a-zA-Z0-9_.]+)\s*%\}").expect("if regex"))
@@ -48,6 +63,16 @@ pub enum TemplateNode {
     Block { name: String, body: Vec<TemplateNode> },
     Extends(String),
     Include(String),
+    UrlFor { route: String, args: Vec<(String, UrlArg)> },
+}
+
+/// Argument passed to `url_for` in a template
+#[derive(Debug, Clone, PartialEq)]
+pub enum UrlArg {
+    /// Quoted string literal: `id="42"`
+    Literal(String),
+    /// Context variable, dotted notation allowed: `id=user.id`
+    Variable(String),
 }
 
 /// Template parser
@@ -84,6 +109,11 @@ impl Parser {
     }
 
     fn parse_tag(&self, source: &str) -> Result<Option<(TemplateNode, usize)>> {
+        // URL generation: {{ url_for("users.show", id=user.id) }}
+        if let Some(node) = self.parse_url_for(source) {
+            return Ok(Some(node));
+        }
+
         // Variable: {{ variable | filter }}
         if source.starts_with("{{") {
             return self.parse_variable(source);
@@ -124,6 +154,25 @@ impl Parser {
         Ok(None)
     }
 
+    fn parse_url_for(&self, source: &str) -> Option<(TemplateNode, usize)> {
+        let cap = url_for_regex().captures(source)?;
+        let route = cap.get(1)?.as_str().to_string();
+        let args = url_arg_regex()
+            .captures_iter(cap.get(2).map(|m| m.as_str()).unwrap_or(""))
+            .filter_map(|arg| {
+                let name = arg.get(1)?.as_str().to_string();
+                let value = match (arg.get(2), arg.get(3)) {
+                    (Some(literal), _) => UrlArg::Literal(literal.as_str().to_string()),
+                    (None, Some(var)) => UrlArg::Variable(var.as_str().to_string()),
+                    (None, None) => return None,
+                };
+                Some((name, value))
+            })
+            .collect();
+
+        Some((TemplateNode::UrlFor { route, args }, cap.get(0)?.end()))
+    }
+
     fn parse_control(&self, source: &str) -> Result<Option<(TemplateNode, usize)>> {
         // {% if condition %}
         if source.starts_with("{% if ") {
@@ -326,7 +375,7 @@ impl Parser {
 
 #[cfg(test)]
 mod tests {
-    use super::{Parser, TemplateNode};
+    use super::{Parser, TemplateNode, UrlArg};
 
     #[test]
     fn parse_variable_with_multiple_filters() {
@@ -341,4 +390,21 @@ mod tests {
             }]
         );
     }
+
+    #[test]
+    fn parse_url_for_with_literal_and_variable_args() {
+        let nodes = Parser::new(r#"<a href="{{ url_for("users.show", id=user.id, tab="posts") }}">"#)
+            .parse()
+            .expect("parser should succeed");
+        assert_eq!(
+            nodes[1],
+            TemplateNode::UrlFor {
+                route: "users.show".to_string(),
+                args: vec![
+                    ("id".to_string(), UrlArg::Variable("user.id".to_string())),
+                    ("tab".to_string(), UrlArg::Literal("posts".to_string())),
+                ],
+            }
+        );
+    }
 }
@@ -1,4 +1,5 @@
 use crate::{Context, TemplateNode, TemplateError, Result, filters::Filters, TemplateEngine, Template};
+use crate::parser::UrlArg;
 use serde_json::Value;
 use std::collections::HashMap;
 
@@ -91,6 +92,10 @@ impl<'a> Renderer<'a> {
                     // But if it does, ignore or error?
                     // Ignore for now.
                 }
+                TemplateNode::UrlFor { route, args } => {
+                    let value = self.render_url_for(route, args)?;
+                    output.push_str(&value);
+                }
                 TemplateNode::Include(template_name) => {
                     if let Some(engine) = self.engine {
                         let template = engine.get_template(template_name)
@@ -130,6 +135,29 @@ impl<'a> Renderer<'a> {
         Ok(result)
     }
 
+    fn render_url_for(&self, route: &str, args: &[(String, UrlArg)]) -> Result<String> {
+        let urls = self.engine
+            .and_then(|engine| engine.url_generator())
+            .ok_or_else(|| TemplateError::RenderError("url_for used without a URL generator on the TemplateEngine".to_string()))?;
+
+        let mut params = Vec::with_capacity(args.len());
+        for (name, arg) in args {
+            let value = match arg {
+                UrlArg::Literal(value) => value.clone(),
+                UrlArg::Variable(var) => {
+                    let value = self.context.get(var)
+                        .ok_or_else(|| TemplateError::VariableNotFound(var.to_string()))?;
+                    self.value_to_string(value)
+                }
+            };
+            params.push((name.as_str(), value));
+        }
+
+        let url = urls.url_for(route, params)
+            .map_err(|e| TemplateError::RenderError(e.to_string()))?;
+        Ok(html_escape(&url))
+    }
+
     fn render_if(&mut self, condition: &str, then_branch: &[TemplateNode], else_branch: &Option<Vec<TemplateNode>>) -> Result<String> {
         // Evaluate condition (simple truthy check)
         let is_truthy = self.evaluate_condition(condition);
@@ -194,7 +222,7 @@ impl<'a> Renderer<'a> {
 }
 
 /// HTML escape for XSS protection
-fn html_escape(s: &str) -> String {
+pub(crate) fn html_escape(s: &str) -> String {
     s.replace('&', "&amp;")
         .replace('<', "&lt;")
         .replace('>', "&gt;")
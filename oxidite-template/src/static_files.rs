@@ -1,22 +1,51 @@
-use oxidite_core::{OxiditeRequest, OxiditeResponse, Error, Result};
+use oxidite_core::headers::{EntityTag, IfModifiedSince, IfNoneMatch, Range};
+use oxidite_core::{Error, HeaderMapExt, OxiditeRequest, OxiditeResponse, Result};
 
-use std::path::Path;
-use std::sync::Arc;
+use http::header::{self, HeaderMap, HeaderValue};
+use http::{Method, StatusCode};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use std::fs::Metadata;
 use std::future::Future;
+use std::path::{Path, PathBuf};
 use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use crate::renderer::html_escape;
 
 const SERVER_HEADER: &str = concat!("Oxidite/", env!("CARGO_PKG_VERSION"));
 
+/// Precompressed siblings, in order of preference
+const ENCODINGS: [(&str, &str); 2] = [("br", "br"), ("gzip", "gz")];
+
+/// Characters escaped in directory listing links
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+
 /// Configuration for static file serving
+///
+/// Files are streamed with `ETag`, `Last-Modified` and `Cache-Control`
+/// headers, answer conditional requests with 304 and single byte ranges
+/// with 206. When the client accepts it, a `file.br` or `file.gz` sibling is
+/// sent instead of `file`.
+///
+/// # Example
+/// ```ignore
+/// let assets = StaticFiles::new("public", Some("/assets".to_string()))
+///     .cache_control("public, max-age=31536000, immutable");
+/// ```
 #[derive(Clone)]
 pub struct StaticFiles {
     root: String,
     url_prefix: Option<String>,
+    cache_control: String,
+    precompressed: bool,
+    directory_listing: bool,
 }
 
 impl StaticFiles {
     /// Create a new StaticFiles handler
-    /// 
+    ///
     /// # Arguments
     /// * `root` - The directory on the filesystem to serve files from (e.g., "public")
     /// * `url_prefix` - Optional URL prefix to strip from the request path (e.g., "/public")
@@ -24,108 +53,282 @@ impl StaticFiles {
         Self {
             root: root.into(),
             url_prefix,
+            cache_control: "no-cache".to_string(),
+            precompressed: true,
+            directory_listing: false,
         }
     }
 
+    /// `Cache-Control` sent with every file; defaults to `no-cache`, which
+    /// makes clients revalidate with the `ETag`
+    pub fn cache_control(mut self, value: impl Into<String>) -> Self {
+        self.cache_control = value.into();
+        self
+    }
+
+    /// Serve `.br`/`.gz` siblings to clients that accept them (default: on)
+    pub fn precompressed(mut self, enabled: bool) -> Self {
+        self.precompressed = enabled;
+        self
+    }
+
+    /// List the contents of directories without an `index.html` (default: off)
+    pub fn directory_listing(mut self, enabled: bool) -> Self {
+        self.directory_listing = enabled;
+        self
+    }
+
     /// Serve a static file based on the request
     pub async fn serve(&self, req: OxiditeRequest) -> Result<OxiditeResponse> {
-        use hyper::{Response, header};
-        use http_body_util::{Full, BodyExt};
-        use bytes::Bytes;
-        use http::StatusCode;
-
         let path = req.uri().path();
-        
+
         // Remove prefix if configured
-        let file_path = if let Some(prefix) = &self.url_prefix {
-            if path.starts_with(prefix) {
-                path.strip_prefix(prefix).unwrap_or(path)
-            } else {
-                path
-            }
-        } else {
-            path
+        let file_path = match &self.url_prefix {
+            Some(prefix) => path.strip_prefix(prefix.as_str()).unwrap_or(path),
+            None => path,
         };
+        let relative = relative_path(file_path).ok_or_else(|| Error::BadRequest("Invalid path".to_string()))?;
 
-        // Clean up leading slashes to make it relative
-        let file_path = file_path.trim_start_matches('/');
-        
-        // Security: prevent directory traversal
-        if file_path.contains("..") {
-            return Err(Error::BadRequest("Invalid path".to_string()));
-        }
-        
-        let full_path = Path::new(&self.root).join(file_path);
-        
-        // Check if path is a directory, if so try index.html
-        let full_path = if full_path.is_dir() {
-            full_path.join("index.html")
-        } else {
-            full_path
+        let Ok(root) = tokio::fs::canonicalize(&self.root).await else {
+            return not_found();
+        };
+        let Some((mut full_path, mut metadata)) = resolve(&root, &root.join(relative)).await else {
+            return not_found();
         };
 
-        // Read file asynchronously as bytes
-        match tokio::fs::read(&full_path).await {
-            Ok(content) => {
-                // Determine content type based on extension
-                let content_type = full_path.extension()
-                    .and_then(|ext| ext.to_str())
-                    .map(|ext| match ext.to_lowercase().as_str() {
-                        "html" | "htm" => "text/html",
-                        "css" => "text/css",
-                        "js" | "mjs" => "application/javascript",
-                        "json" => "application/json",
-                        "png" => "image/png",
-                        "jpg" | "jpeg" => "image/jpeg",
-                        "gif" => "image/gif",
-                        "svg" => "image/svg+xml",
-                        "ico" => "image/x-icon",
-                        "webp" => "image/webp",
-                        "woff" => "font/woff",
-                        "woff2" => "font/woff2",
-                        "ttf" => "font/ttf",
-                        "otf" => "font/otf",
-                        "eot" => "application/vnd.ms-fontobject",
-                        "wasm" => "application/wasm",
-                        "mp4" => "video/mp4",
-                        "webm" => "video/webm",
-                        "txt" => "text/plain",
-                        "xml" => "text/xml",
-                        _ => "application/octet-stream",
-                    })
-                    .unwrap_or("application/octet-stream");
-                
-                let res = Response::builder()
-                    .status(StatusCode::OK)
-                    .header(header::CONTENT_TYPE, content_type)
-                    .header(header::CONTENT_LENGTH, content.len())
-                    .header(header::SERVER, SERVER_HEADER)
-                    .body(Full::new(Bytes::from(content)).map_err(|e| match e {}).boxed())
-                    .map_err(|e| Error::InternalServerError(format!("Failed to build response: {}", e)))?;
-                
-                Ok(OxiditeResponse::new(res))
-            },
-            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
-                // Return 404 Not Found
-                let res = Response::builder()
-                    .status(StatusCode::NOT_FOUND)
-                    .header(header::CONTENT_TYPE, "text/plain")
-                    .header(header::SERVER, SERVER_HEADER)
-                    .body(Full::new(Bytes::from("404 Not Found")).map_err(|e| match e {}).boxed())
-                    .map_err(|e| Error::InternalServerError(format!("Failed to build response: {}", e)))?;
-                
-                Ok(OxiditeResponse::new(res))
+        // Check if path is a directory, if so try index.html
+        if metadata.is_dir() {
+            match resolve(&root, &full_path.join("index.html")).await {
+                Some((index, index_metadata)) if index_metadata.is_file() => {
+                    full_path = index;
+                    metadata = index_metadata;
+                }
+                _ if self.directory_listing => return list_directory(&full_path, path).await,
+                _ => return not_found(),
+            }
+        }
+        if !metadata.is_file() {
+            return not_found();
+        }
+
+        self.serve_file(&req, &root, full_path, metadata).await
+    }
+
+    async fn serve_file(&self, req: &OxiditeRequest, root: &Path, path: PathBuf, metadata: Metadata) -> Result<OxiditeResponse> {
+        let request_headers = req.headers();
+        let content_type = mime_guess::from_path(&path).first_or_octet_stream();
+
+        // Byte ranges always refer to the identity encoding
+        let wants_range = req.method() == Method::GET && request_headers.contains_key(header::RANGE);
+        let mut file = (path, metadata, None);
+        if self.precompressed && !wants_range {
+            for (coding, extension) in ENCODINGS {
+                if accepts_encoding(request_headers, coding) {
+                    let mut sibling = file.0.clone().into_os_string();
+                    sibling.push(".");
+                    sibling.push(extension);
+                    if let Some((sibling, metadata)) = resolve(root, Path::new(&sibling)).await.filter(|(_, m)| m.is_file()) {
+                        file = (sibling, metadata, Some(coding));
+                        break;
+                    }
+                }
+            }
+        }
+        let (path, metadata, encoding) = file;
+
+        let len = metadata.len();
+        let modified = metadata.modified().ok();
+        let etag = entity_tag(len, modified, encoding);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ETAG, header_value(&etag.to_string()));
+        if let Some(modified) = modified {
+            headers.insert(header::LAST_MODIFIED, header_value(&httpdate::fmt_http_date(modified)));
+        }
+        headers.insert(header::CACHE_CONTROL, header_value(&self.cache_control));
+        headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+        if self.precompressed {
+            headers.insert(header::VARY, HeaderValue::from_static("Accept-Encoding"));
+        }
+        headers.insert(header::SERVER, HeaderValue::from_static(SERVER_HEADER));
+
+        // If-None-Match takes precedence over If-Modified-Since
+        let not_modified = match request_headers.typed_get::<IfNoneMatch>() {
+            Some(if_none_match) => if_none_match.matches(&etag),
+            None => match (request_headers.typed_get::<IfModifiedSince>(), modified) {
+                (Some(since), Some(modified)) => !since.is_modified(modified),
+                _ => false,
             },
-            Err(e) => {
-                // Return 500 Internal Server Error for other errors
-                Err(Error::InternalServerError(format!("Failed to read file: {}", e)))
+        };
+        if not_modified && matches!(*req.method(), Method::GET | Method::HEAD) {
+            return Ok(with_headers(OxiditeResponse::no_content(), StatusCode::NOT_MODIFIED, headers));
+        }
+
+        // A malformed Range or a stale If-Range means the whole file is sent
+        let range = request_headers
+            .typed_get::<Range>()
+            .filter(|_| wants_range && if_range_matches(request_headers, &etag, modified));
+        let (status, start, count) = match range.map(|range| range.satisfiable(len)) {
+            Some(ranges) if ranges.is_empty() => {
+                headers.insert(header::CONTENT_RANGE, header_value(&format!("bytes */{}", len)));
+                let res = OxiditeResponse::text("416 Range Not Satisfiable");
+                return Ok(with_headers(res, StatusCode::RANGE_NOT_SATISFIABLE, headers));
             }
+            // Multiple ranges would need multipart/byteranges; send everything instead
+            Some(ranges) if ranges.len() == 1 => {
+                let range = &ranges[0];
+                let content_range = format!("bytes {}-{}/{}", range.start(), range.end(), len);
+                headers.insert(header::CONTENT_RANGE, header_value(&content_range));
+                (StatusCode::PARTIAL_CONTENT, *range.start(), range.end() - range.start() + 1)
+            }
+            _ => (StatusCode::OK, 0, len),
+        };
+        headers.insert(header::CONTENT_TYPE, header_value(content_type.as_ref()));
+        if let Some(encoding) = encoding {
+            headers.insert(header::CONTENT_ENCODING, HeaderValue::from_static(encoding));
         }
+        headers.insert(header::CONTENT_LENGTH, HeaderValue::from(count));
+
+        let read_error = |e: std::io::Error| Error::InternalServerError(format!("Failed to read file: {}", e));
+        let mut file = tokio::fs::File::open(&path).await.map_err(read_error)?;
+        if start > 0 {
+            file.seek(std::io::SeekFrom::Start(start)).await.map_err(read_error)?;
+        }
+        let body = OxiditeResponse::stream(tokio_util::io::ReaderStream::new(file.take(count)));
+        Ok(with_headers(body, status, headers))
+    }
+}
+
+/// Decode the request path into a path relative to the root, rejecting
+/// `..` segments and anything that could be read as an absolute path
+fn relative_path(path: &str) -> Option<PathBuf> {
+    let decoded = percent_decode_str(path).decode_utf8().ok()?;
+    let mut relative = PathBuf::new();
+    for segment in decoded.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => return None,
+            _ if segment.contains(['\\', '\0', ':']) => return None,
+            _ => relative.push(segment),
+        }
+    }
+    Some(relative)
+}
+
+/// Canonicalize `path`, following symlinks, and only return it if it is
+/// still inside `root`
+async fn resolve(root: &Path, path: &Path) -> Option<(PathBuf, Metadata)> {
+    let path = tokio::fs::canonicalize(path).await.ok()?;
+    if !path.starts_with(root) {
+        return None;
+    }
+    let metadata = tokio::fs::metadata(&path).await.ok()?;
+    Some((path, metadata))
+}
+
+/// Strong validator derived from the size and modification time, distinct
+/// per content coding
+fn entity_tag(len: u64, modified: Option<SystemTime>, encoding: Option<&str>) -> EntityTag {
+    let nanos = modified
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |since| since.as_nanos());
+    match encoding {
+        Some(encoding) => EntityTag::strong(format!("{:x}-{:x}-{}", nanos, len, encoding)),
+        None => EntityTag::strong(format!("{:x}-{:x}", nanos, len)),
     }
 }
 
+/// Whether `Accept-Encoding` allows `coding`, honouring `q=0` and `*`
+fn accepts_encoding(headers: &HeaderMap, coding: &str) -> bool {
+    let mut explicit = None;
+    let mut wildcard = None;
+    for item in headers.get_all(header::ACCEPT_ENCODING).iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+    {
+        let mut params = item.split(';');
+        let name = params.next().unwrap_or_default().trim();
+        let quality = params
+            .find_map(|param| param.trim().strip_prefix("q="))
+            .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())
+            .unwrap_or(0.0);
+        if name.eq_ignore_ascii_case(coding) {
+            explicit = Some(quality);
+        } else if name == "*" {
+            wildcard = Some(quality);
+        }
+    }
+    explicit.or(wildcard).is_some_and(|quality| quality > 0.0)
+}
+
+/// `If-Range` holds a strong ETag or the exact `Last-Modified` date; without
+/// it every range request is served
+fn if_range_matches(headers: &HeaderMap, etag: &EntityTag, modified: Option<SystemTime>) -> bool {
+    let Some(value) = headers.get(header::IF_RANGE).and_then(|value| value.to_str().ok()) else {
+        return true;
+    };
+    if let Some(tag) = EntityTag::parse(value.trim()) {
+        return tag.strong_eq(etag);
+    }
+    match (httpdate::parse_http_date(value.trim()), modified) {
+        (Ok(date), Some(modified)) => httpdate::fmt_http_date(date) == httpdate::fmt_http_date(modified),
+        _ => false,
+    }
+}
+
+async fn list_directory(dir: &Path, request_path: &str) -> Result<OxiditeResponse> {
+    let read_error = |e: std::io::Error| Error::InternalServerError(format!("Failed to read directory: {}", e));
+    let mut entries = tokio::fs::read_dir(dir).await.map_err(read_error)?;
+    let mut names = Vec::new();
+    while let Some(entry) = entries.next_entry().await.map_err(read_error)? {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') {
+            continue;
+        }
+        let is_dir = entry.file_type().await.is_ok_and(|file_type| file_type.is_dir());
+        names.push((name, is_dir));
+    }
+    names.sort();
+
+    let base = html_escape(request_path.trim_end_matches('/'));
+    let title = html_escape(&percent_decode_str(request_path).decode_utf8_lossy());
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>Index of {0}</title></head>\n<body>\n<h1>Index of {0}</h1>\n<ul>\n",
+        title
+    );
+    for (name, is_dir) in names {
+        let slash = if is_dir { "/" } else { "" };
+        html.push_str(&format!(
+            "<li><a href=\"{}/{}{}\">{}{}</a></li>\n",
+            base,
+            utf8_percent_encode(&name, PATH_SEGMENT),
+            slash,
+            html_escape(&name),
+            slash
+        ));
+    }
+    html.push_str("</ul>\n</body>\n</html>\n");
+    Ok(OxiditeResponse::html(html))
+}
+
+fn not_found() -> Result<OxiditeResponse> {
+    let mut res = OxiditeResponse::text("404 Not Found");
+    *res.status_mut() = StatusCode::NOT_FOUND;
+    Ok(res)
+}
+
+fn with_headers(mut res: OxiditeResponse, status: StatusCode, headers: HeaderMap) -> OxiditeResponse {
+    *res.status_mut() = status;
+    res.headers_mut().extend(headers);
+    res
+}
+
+fn header_value(value: &str) -> HeaderValue {
+    HeaderValue::from_str(value).unwrap_or_else(|_| HeaderValue::from_static(""))
+}
+
 /// Create a static file handler for a specific directory.
-/// 
+///
 /// # Example
 /// ```ignore
 /// use oxidite_template::static_handler;
@@ -136,7 +339,7 @@ impl StaticFiles {
 pub fn static_handler(root: impl Into<String>) -> impl Fn(OxiditeRequest) -> Pin<Box<dyn Future<Output = Result<OxiditeResponse>> + Send>> + Send + Sync + 'static {
     let root = root.into();
     let static_files = Arc::new(StaticFiles::new(root, None));
-    
+
     move |req| {
         let static_files = static_files.clone();
         Box::pin(async move {
@@ -146,10 +349,163 @@ pub fn static_handler(root: impl Into<String>) -> impl Fn(OxiditeRequest) -> Pin
 }
 
 /// Helper function to serve static files from the "public" directory.
-/// 
+///
 /// This handler serves files relative to the root of the "public" directory.
 /// For example, a request to `/style.css` will serve `public/style.css`.
 pub async fn serve_static(req: OxiditeRequest) -> Result<OxiditeResponse> {
     let static_files = StaticFiles::new("public", None);
     static_files.serve(req).await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_body_util::BodyExt;
+    use oxidite_core::types::OxiditeBody;
+
+    fn fixture(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("oxidite-static-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("public/docs")).unwrap();
+        std::fs::write(dir.join("public/app.js"), "console.log('hello world');").unwrap();
+        std::fs::write(dir.join("public/app.js.br"), "brotli").unwrap();
+        std::fs::write(dir.join("public/app.js.gz"), "gzip").unwrap();
+        std::fs::write(dir.join("public/video.mp4"), "0123456789").unwrap();
+        std::fs::write(dir.join("public/docs/a <b>.txt"), "a").unwrap();
+        std::fs::write(dir.join("secret.txt"), "secret").unwrap();
+        dir
+    }
+
+    fn get(uri: &str, headers: &[(&str, &str)]) -> OxiditeRequest {
+        let mut req = http::Request::get(uri);
+        for (name, value) in headers {
+            req = req.header(*name, *value);
+        }
+        req.body(OxiditeBody::default()).unwrap()
+    }
+
+    async fn body(res: OxiditeResponse) -> String {
+        let bytes = res.into_inner().into_body().collect().await.unwrap().to_bytes();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_conditional_requests() {
+        let dir = fixture("conditional");
+        let files = StaticFiles::new(dir.join("public").to_string_lossy(), None).precompressed(false);
+
+        let res = files.serve(get("/video.mp4", &[])).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()["content-type"], "video/mp4");
+        assert_eq!(res.headers()["content-length"], "10");
+        assert_eq!(res.headers()["cache-control"], "no-cache");
+        let etag = res.headers()["etag"].to_str().unwrap().to_string();
+        let last_modified = res.headers()["last-modified"].to_str().unwrap().to_string();
+        assert!(etag.starts_with('"'), "{etag}");
+        assert_eq!(body(res).await, "0123456789");
+
+        let res = files.serve(get("/video.mp4", &[("if-none-match", &format!("\"other\", {}", etag))])).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(res.headers()["etag"], etag.as_str());
+        assert_eq!(body(res).await, "");
+
+        let res = files.serve(get("/video.mp4", &[("if-modified-since", &last_modified)])).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+        // If-None-Match wins over If-Modified-Since
+        let headers = [("if-none-match", "\"other\""), ("if-modified-since", last_modified.as_str())];
+        assert_eq!(files.serve(get("/video.mp4", &headers)).await.unwrap().status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_byte_ranges() {
+        let dir = fixture("range");
+        let files = StaticFiles::new(dir.join("public").to_string_lossy(), None);
+
+        let res = files.serve(get("/video.mp4", &[("range", "bytes=2-5"), ("accept-encoding", "br")])).await.unwrap();
+        assert_eq!(res.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(res.headers()["content-range"], "bytes 2-5/10");
+        assert_eq!(res.headers()["content-length"], "4");
+        let etag = res.headers()["etag"].to_str().unwrap().to_string();
+        assert_eq!(body(res).await, "2345");
+
+        let res = files.serve(get("/video.mp4", &[("range", "bytes=-3"), ("if-range", &etag)])).await.unwrap();
+        assert_eq!(res.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(body(res).await, "789");
+
+        let res = files.serve(get("/video.mp4", &[("range", "bytes=20-")])).await.unwrap();
+        assert_eq!(res.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(res.headers()["content-range"], "bytes */10");
+
+        for headers in [
+            [("range", "bytes=0-1, 4-5"), ("if-range", etag.as_str())],
+            [("range", "bytes=0-1"), ("if-range", "\"stale\"")],
+            [("range", "lines=1-2"), ("if-range", etag.as_str())],
+        ] {
+            let res = files.serve(get("/video.mp4", &headers)).await.unwrap();
+            assert_eq!(res.status(), StatusCode::OK, "{headers:?}");
+            assert_eq!(body(res).await, "0123456789");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_precompressed_variants() {
+        let dir = fixture("precompressed");
+        let files = StaticFiles::new(dir.join("public").to_string_lossy(), Some("/assets".to_string()));
+
+        let serve = |accept: &'static str| files.serve(get("/assets/app.js", &[("accept-encoding", accept)]));
+        let res = serve("gzip, deflate, br").await.unwrap();
+        assert_eq!(res.headers()["content-encoding"], "br");
+        assert_eq!(res.headers()["content-type"], "text/javascript");
+        assert_eq!(res.headers()["vary"], "Accept-Encoding");
+        let br_etag = res.headers()["etag"].clone();
+        assert_eq!(body(res).await, "brotli");
+
+        let res = serve("br;q=0, *").await.unwrap();
+        assert_eq!(res.headers()["content-encoding"], "gzip");
+        assert_ne!(res.headers()["etag"], br_etag);
+        assert_eq!(body(res).await, "gzip");
+
+        let res = serve("identity").await.unwrap();
+        assert!(res.headers().get("content-encoding").is_none());
+        assert_eq!(body(res).await, "console.log('hello world');");
+    }
+
+    #[tokio::test]
+    async fn test_path_traversal_is_rejected() {
+        let dir = fixture("traversal");
+        let files = StaticFiles::new(dir.join("public").to_string_lossy(), None);
+
+        for uri in ["/../secret.txt", "/docs/%2e%2e/%2E%2E/secret.txt", "/..%5csecret.txt"] {
+            let err = files.serve(get(uri, &[])).await.err().expect(uri);
+            assert!(matches!(err, Error::BadRequest(_)), "{uri}");
+        }
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(dir.join("secret.txt"), dir.join("public/link.txt")).unwrap();
+            let res = files.serve(get("/link.txt", &[])).await.unwrap();
+            assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_directory_listing() {
+        let dir = fixture("listing");
+        let root = dir.join("public").to_string_lossy().into_owned();
+
+        let res = StaticFiles::new(root.clone(), None).serve(get("/docs/", &[])).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        let res = StaticFiles::new(root, None).directory_listing(true).serve(get("/docs", &[])).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let html = body(res).await;
+        assert!(html.contains("<a href=\"/docs/a%20%3Cb%3E.txt\">a &lt;b&gt;.txt</a>"), "{html}");
+
+        std::fs::create_dir_all(dir.join("public/q&a'x")).unwrap();
+        std::fs::write(dir.join("public/q&a'x/faq.txt"), "faq").unwrap();
+        let root = dir.join("public").to_string_lossy().into_owned();
+        let res = StaticFiles::new(root, None).directory_listing(true).serve(get("/q&a'x", &[])).await.unwrap();
+        let html = body(res).await;
+        assert!(html.contains("<a href=\"/q&amp;a&#x27;x/faq.txt\">"), "{html}");
+    }
+}
@@ -84,7 +84,10 @@ pub mod prelude {
         Router, Server, Handler,
         Error, Result,
         Request, Response,
-        extract::{Json, Path, Query, State, FromRequest, Form, Cookies, Body},
+        IntoResponse, Html, Redirect,
+        extract::{Json, Path, Query, State, FromRequest, FromRequestParts, Form, Cookies, Body},
+        ClientIp, TypedHeader, DefaultBodyLimit,
+        CookieJar, SignedCookieJar, PrivateCookieJar,
     };
     
     pub use oxidite_middleware::{
@@ -107,7 +110,7 @@ pub mod prelude {
     pub use oxidite_cache::Cache;
     
     #[cfg(feature = "realtime")]
-    pub use oxidite_realtime::WebSocketManager;
+    pub use oxidite_realtime::{Sse, SseEvent, WebSocketManager, WebSocketUpgrade};
     
     #[cfg(feature = "graphql")]
     pub use oxidite_graphql::{GraphQLHandler, GraphQLSchema};